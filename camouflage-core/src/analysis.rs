@@ -0,0 +1,518 @@
+//! Offline spectral analysis of generated or mixed audio
+
+use crate::wav::read_wav;
+use anyhow::{Context, Result};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::Path;
+
+/// Upper edge of the audible band in Hz
+pub const AUDIBLE_LIMIT_HZ: f32 = 20000.0;
+
+/// Largest audible-band energy fraction still considered inaudible (0.1%)
+pub const MAX_AUDIBLE_ENERGY_FRACTION: f64 = 0.001;
+
+/// Audible-band level taken as the threshold of hearing, in dBFS
+///
+/// Assumes full scale plays at about 100 dB SPL at the listener, which puts
+/// the 0 dB SPL hearing threshold at -100 dBFS.
+pub const HEARING_THRESHOLD_DBFS: f64 = -100.0;
+
+/// Headroom below the hearing threshold needed to rate a signal safe, in dB
+pub const SAFE_HEADROOM_DB: f64 = 10.0;
+
+/// Number of spectral peaks reported
+const NUM_PEAKS: usize = 5;
+
+/// Peaks more than 40 dB below the strongest one are ignored
+const PEAK_FLOOR: f64 = 1e-4;
+
+/// Oversampling factor for true-peak measurement
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+/// Half-width of the true-peak interpolation kernel in input samples
+///
+/// Wide enough to stay flat up to ~0.48 of the sample rate, where
+/// ultrasonic carriers sit on 48 kHz devices.
+const TRUE_PEAK_HALF_WIDTH: usize = 64;
+
+/// True-peak level at or above which reconstruction may clip, in dBTP
+pub const TRUE_PEAK_WARN_DBTP: f32 = -1.0;
+
+/// Highest true peak, as a fraction of full scale, that leaves headroom
+/// for the output chain
+pub const MAX_SAFE_PEAK: f32 = 0.95;
+
+/// Level summary of an audio signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalStats {
+    /// Largest absolute sample value
+    pub sample_peak: f32,
+    /// Largest absolute value of the 4x oversampled signal
+    pub true_peak: f32,
+    /// Root mean square level
+    pub rms: f32,
+}
+
+impl SignalStats {
+    /// Measure peak and RMS levels of mono samples
+    ///
+    /// The true peak interpolates between samples with a Hann-windowed sinc,
+    /// catching inter-sample peaks that the DAC reconstructs but the sample
+    /// values miss. Near the edges only the sample peak is used.
+    pub fn measure(samples: &[f32]) -> Self {
+        let sample_peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        let rms = if samples.is_empty() {
+            0.0
+        } else {
+            let sum: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+            (sum / samples.len() as f64).sqrt() as f32
+        };
+
+        let half = TRUE_PEAK_HALF_WIDTH;
+        let kernels: Vec<Vec<f64>> = (1..TRUE_PEAK_OVERSAMPLING)
+            .map(|phase| {
+                let offset = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+                (0..2 * half)
+                    .map(|k| windowed_sinc(k as f64 - (half - 1) as f64 - offset, half))
+                    .collect()
+            })
+            .collect();
+
+        let mut true_peak = sample_peak;
+        // Interpolate between samples i and i + 1 where the kernel fits
+        for i in (half - 1)..samples.len().saturating_sub(half) {
+            let window = &samples[i + 1 - half..=i + half];
+            for kernel in &kernels {
+                let value: f64 = window.iter().zip(kernel).map(|(&x, &h)| x as f64 * h).sum();
+                true_peak = true_peak.max(value.abs() as f32);
+            }
+        }
+
+        Self {
+            sample_peak,
+            true_peak,
+            rms,
+        }
+    }
+
+    /// True peak in dB relative to full scale (dBTP)
+    pub fn true_peak_dbtp(&self) -> f32 {
+        20.0 * self.true_peak.log10()
+    }
+
+    /// Whether the true peak is close enough to full scale to risk clipping
+    pub fn near_full_scale(&self) -> bool {
+        self.true_peak_dbtp() >= TRUE_PEAK_WARN_DBTP
+    }
+
+    /// Whether the true peak is above [`MAX_SAFE_PEAK`]
+    pub fn exceeds_safe_peak(&self) -> bool {
+        self.true_peak > MAX_SAFE_PEAK
+    }
+}
+
+fn windowed_sinc(x: f64, half_width: usize) -> f64 {
+    use std::f64::consts::PI;
+
+    let sinc = if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    };
+    sinc * (0.5 + 0.5 * (PI * x / half_width as f64).cos())
+}
+
+/// Spectral summary of an audio signal
+#[derive(Debug, Clone)]
+pub struct SpectrumReport {
+    /// Sample rate of the analyzed signal in Hz
+    pub sample_rate: u32,
+    /// Fraction of total energy below 20 kHz (0.0-1.0)
+    pub audible_energy_fraction: f64,
+    /// Energy-weighted mean frequency in Hz
+    pub spectral_centroid: f32,
+    /// Frequencies of the strongest spectral peaks (within 40 dB), loudest first
+    pub peak_frequencies: Vec<f32>,
+    /// Strongest audible-band bin relative to the strongest bin overall, in dB
+    pub audible_peak_db: f64,
+    /// Sine amplitude of the strongest bin, in dBFS
+    pub peak_dbfs: f64,
+}
+
+impl SpectrumReport {
+    /// Whether all significant energy lies above 20 kHz
+    pub fn is_ultrasonic(&self) -> bool {
+        self.audible_energy_fraction < MAX_AUDIBLE_ENERGY_FRACTION
+    }
+
+    /// Strongest audible-band component as a sine amplitude, in dBFS
+    pub fn audible_peak_dbfs(&self) -> f64 {
+        self.peak_dbfs + self.audible_peak_db
+    }
+}
+
+/// How likely a signal is to be heard in a quiet room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudibilityRisk {
+    /// Audible-band leakage sits well below the hearing threshold
+    Safe,
+    /// Leakage is close to the hearing threshold
+    Caution,
+    /// Leakage exceeds the hearing threshold
+    Audible,
+}
+
+impl fmt::Display for AudibilityRisk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Safe => write!(f, "safe"),
+            Self::Caution => write!(f, "caution"),
+            Self::Audible => write!(f, "audible"),
+        }
+    }
+}
+
+/// Audible-band leakage of a signal compared with the hearing threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudibilityReport {
+    /// Fraction of total energy below 20 kHz (0.0-1.0)
+    pub audible_energy_fraction: f64,
+    /// Strongest audible-band component, in dBFS
+    pub audible_peak_dbfs: f64,
+    /// How far that component sits below [`HEARING_THRESHOLD_DBFS`], in dB
+    pub headroom_db: f64,
+    pub risk: AudibilityRisk,
+}
+
+impl From<&SpectrumReport> for AudibilityReport {
+    fn from(report: &SpectrumReport) -> Self {
+        let audible_peak_dbfs = report.audible_peak_dbfs();
+        let headroom_db = HEARING_THRESHOLD_DBFS - audible_peak_dbfs;
+        let risk = if !report.is_ultrasonic() || headroom_db < 0.0 {
+            AudibilityRisk::Audible
+        } else if headroom_db < SAFE_HEADROOM_DB {
+            AudibilityRisk::Caution
+        } else {
+            AudibilityRisk::Safe
+        };
+
+        Self {
+            audible_energy_fraction: report.audible_energy_fraction,
+            audible_peak_dbfs,
+            headroom_db,
+            risk,
+        }
+    }
+}
+
+/// Level of a frequency band against the rest of a spectrum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BandLevel {
+    /// Strongest bin inside the band, as a sine amplitude in dBFS
+    pub band_dbfs: f64,
+    /// Median bin outside the band, as a sine amplitude in dBFS
+    pub floor_dbfs: f64,
+}
+
+impl BandLevel {
+    /// How far the band stands above the floor, in dB
+    pub fn snr_db(&self) -> f64 {
+        self.band_dbfs - self.floor_dbfs
+    }
+}
+
+/// Measure the strongest component between `low_hz` and `high_hz`
+///
+/// The floor is the median of every other bin, so broadband noise and a
+/// few loud room sounds do not skew it.
+pub fn band_level(
+    samples: &[f32],
+    sample_rate: u32,
+    low_hz: f32,
+    high_hz: f32,
+) -> Result<BandLevel> {
+    let spectrum = PowerSpectrum::hann(samples, sample_rate)?;
+    let (mut band_peak, mut outside) = (0.0f64, Vec::new());
+    for (i, &power) in spectrum.power.iter().enumerate().skip(1) {
+        if (low_hz..=high_hz).contains(&spectrum.frequency(i)) {
+            band_peak = band_peak.max(power);
+        } else {
+            outside.push(power);
+        }
+    }
+    outside.sort_by(f64::total_cmp);
+    let floor = outside.get(outside.len() / 2).copied().unwrap_or(0.0);
+
+    Ok(BandLevel {
+        band_dbfs: spectrum.dbfs(band_peak),
+        floor_dbfs: spectrum.dbfs(floor),
+    })
+}
+
+/// Power of each FFT bin from DC to Nyquist
+pub(crate) struct PowerSpectrum {
+    /// Squared magnitude of each bin
+    pub power: Vec<f64>,
+    /// Width of a bin in Hz
+    pub bin_hz: f32,
+    /// Sum of the window, which scales power back to sine amplitude
+    window_sum: f64,
+}
+
+impl PowerSpectrum {
+    /// Spectrum of `samples` under a Hann window
+    pub fn hann(samples: &[f32], sample_rate: u32) -> Result<Self> {
+        let len = samples.len();
+        Self::compute(samples, sample_rate, |i| {
+            0.5 - 0.5 * (2.0 * PI * i as f32 / (len - 1) as f32).cos()
+        })
+    }
+
+    /// Spectrum of `samples` without a window, for recordings that are
+    /// quiet at both ends already
+    pub fn unwindowed(samples: &[f32], sample_rate: u32) -> Result<Self> {
+        Self::compute(samples, sample_rate, |_| 1.0)
+    }
+
+    fn compute(samples: &[f32], sample_rate: u32, window: impl Fn(usize) -> f32) -> Result<Self> {
+        if samples.len() < 2 {
+            anyhow::bail!("Not enough samples to analyze: {}", samples.len());
+        }
+
+        let len = samples.len();
+        let mut window_sum = 0.0f64;
+        let mut buffer: Vec<Complex<f32>> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let window = window(i);
+                window_sum += window as f64;
+                Complex::new(x * window, 0.0)
+            })
+            .collect();
+        FftPlanner::new().plan_fft_forward(len).process(&mut buffer);
+
+        Ok(Self {
+            power: buffer[..len / 2 + 1]
+                .iter()
+                .map(|c| c.norm_sqr() as f64)
+                .collect(),
+            bin_hz: sample_rate as f32 / len as f32,
+            window_sum,
+        })
+    }
+
+    /// Center frequency of bin `i` in Hz
+    pub fn frequency(&self, i: usize) -> f32 {
+        i as f32 * self.bin_hz
+    }
+
+    /// A bin's power as the amplitude of a sine in dBFS
+    ///
+    /// A sine of amplitude A peaks at A * sum(window) / 2.
+    pub fn dbfs(&self, power: f64) -> f64 {
+        20.0 * (2.0 * power.sqrt() / self.window_sum).log10()
+    }
+
+    /// Mean of `weight(frequency) * power` over the bins from `low_hz` to
+    /// `high_hz`, in dB
+    pub fn band_power_db(&self, low_hz: f32, high_hz: f32, weight: impl Fn(f32) -> f64) -> f32 {
+        let first = (low_hz / self.bin_hz).ceil() as usize;
+        let last = ((high_hz / self.bin_hz).floor() as usize).min(self.power.len() - 1);
+        let bins = first..=last;
+        let count = bins.clone().count().max(1);
+        let total: f64 = bins
+            .map(|i| self.power[i] * weight(self.frequency(i)))
+            .sum();
+        10.0 * (total / count as f64).max(f64::MIN_POSITIVE).log10() as f32
+    }
+}
+
+/// Read a WAV file as its sample rate and channels averaged to mono
+pub fn read_mono_wav(path: &Path) -> Result<(u32, Vec<f32>)> {
+    let (spec, samples) = read_wav(path)?;
+    let channels = spec.channels.max(1) as usize;
+
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok((spec.sample_rate, mono))
+}
+
+/// Analyze the spectrum of a WAV file
+///
+/// Channels are averaged to mono before analysis.
+pub fn analyze_wav(path: &Path) -> Result<SpectrumReport> {
+    let (sample_rate, mono) = read_mono_wav(path)?;
+    analyze_samples(&mono, sample_rate)
+        .with_context(|| format!("Failed to analyze {}", path.display()))
+}
+
+/// Level of every FFT bin from DC to Nyquist as `(frequency, dBFS)`
+///
+/// Levels are sine amplitudes on the same scale as
+/// [`SpectrumReport::peak_dbfs`].
+pub fn spectrum_dbfs(samples: &[f32], sample_rate: u32) -> Result<Vec<(f32, f64)>> {
+    let spectrum = PowerSpectrum::hann(samples, sample_rate)?;
+    Ok(spectrum
+        .power
+        .iter()
+        .enumerate()
+        .map(|(i, &power)| (spectrum.frequency(i), spectrum.dbfs(power)))
+        .collect())
+}
+
+/// Analyze the spectrum of mono samples
+///
+/// A Hann window is applied over the whole signal before a single FFT,
+/// giving a deterministic check of where the signal energy lies.
+pub fn analyze_samples(samples: &[f32], sample_rate: u32) -> Result<SpectrumReport> {
+    let spectrum = PowerSpectrum::hann(samples, sample_rate)?;
+    let (power, bin_hz) = (&spectrum.power, spectrum.bin_hz);
+
+    let total: f64 = power.iter().sum();
+    let audible: f64 = power
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| (*i as f32 * bin_hz) < AUDIBLE_LIMIT_HZ)
+        .map(|(_, p)| p)
+        .sum();
+    let audible_peak = power
+        .iter()
+        .enumerate()
+        .skip(1) // DC
+        .filter(|(i, _)| (*i as f32 * bin_hz) < AUDIBLE_LIMIT_HZ)
+        .map(|(_, &p)| p)
+        .fold(0.0, f64::max);
+    let overall_peak = power.iter().copied().fold(0.0, f64::max);
+    let weighted: f64 = power
+        .iter()
+        .enumerate()
+        .map(|(i, p)| i as f64 * bin_hz as f64 * p)
+        .sum();
+
+    let mut peaks: Vec<(usize, f64)> = (1..power.len().saturating_sub(1))
+        .filter(|&i| power[i] > power[i - 1] && power[i] >= power[i + 1])
+        .map(|i| (i, power[i]))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let floor = peaks.first().map_or(0.0, |p| p.1 * PEAK_FLOOR);
+
+    Ok(SpectrumReport {
+        sample_rate,
+        audible_energy_fraction: if total > 0.0 { audible / total } else { 0.0 },
+        spectral_centroid: if total > 0.0 {
+            (weighted / total) as f32
+        } else {
+            0.0
+        },
+        peak_frequencies: peaks
+            .into_iter()
+            .take_while(|p| p.1 >= floor)
+            .take(NUM_PEAKS)
+            .map(|(i, _)| i as f32 * bin_hz)
+            .collect(),
+        audible_peak_db: if audible_peak > 0.0 && overall_peak > 0.0 {
+            10.0 * (audible_peak / overall_peak).log10()
+        } else {
+            f64::NEG_INFINITY
+        },
+        peak_dbfs: spectrum.dbfs(overall_peak),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{SignalConfig, SignalGenerator};
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn test_generated_signal_is_ultrasonic() {
+        let config = SignalConfig::default();
+        let mut generator = SignalGenerator::new(config.clone());
+        let mut buffer = vec![0.0; config.sample_rate as usize];
+        generator.generate_buffer(&mut buffer);
+
+        let report = analyze_samples(&buffer, config.sample_rate).unwrap();
+        assert!(report.is_ultrasonic());
+        assert!((report.peak_frequencies[0] - 23000.0).abs() < 400.0);
+    }
+
+    #[test]
+    fn test_band_level_finds_tone_above_noise() {
+        let sample_rate = 48000;
+        let mut seed = 7u32;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                let tone = 0.01 * (2.0 * PI * 22000.0 * i as f32 / sample_rate as f32).sin();
+                tone + 0.01 * noise
+            })
+            .collect();
+
+        let level = band_level(&samples, sample_rate, 21800.0, 22200.0).unwrap();
+        assert!((level.band_dbfs - -40.0).abs() < 1.0, "{}", level.band_dbfs);
+        assert!(level.snr_db() > 30.0, "{}", level.snr_db());
+
+        // Nothing but noise in another band
+        let empty = band_level(&samples, sample_rate, 20500.0, 21000.0).unwrap();
+        assert!(empty.snr_db() < 15.0, "{}", empty.snr_db());
+    }
+
+    #[test]
+    fn test_audible_tone_fails_check() {
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| 0.5 * (2.0 * PI * 1000.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let report = analyze_samples(&samples, sample_rate).unwrap();
+        assert!(!report.is_ultrasonic());
+        assert!((report.peak_frequencies[0] - 1000.0).abs() < 2.0);
+        assert!(
+            (report.peak_dbfs - -6.02).abs() < 0.1,
+            "{}",
+            report.peak_dbfs
+        );
+        assert_eq!(
+            AudibilityReport::from(&report).risk,
+            AudibilityRisk::Audible
+        );
+    }
+
+    #[test]
+    fn test_true_peak_catches_inter_sample_peak() {
+        // A full-scale sine at a quarter of the sample rate, sampled 45
+        // degrees off its crests: every sample is at 0.707 but the
+        // reconstructed waveform reaches 1.0 between them
+        let samples: Vec<f32> = (0..4800)
+            .map(|i| (PI / 2.0 * i as f32 + PI / 4.0).sin())
+            .collect();
+
+        let stats = SignalStats::measure(&samples);
+        assert!((stats.sample_peak - FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!((stats.true_peak - 1.0).abs() < 0.01, "{}", stats.true_peak);
+        assert!((stats.rms - FRAC_1_SQRT_2).abs() < 1e-3);
+        assert!(stats.near_full_scale());
+    }
+
+    #[test]
+    fn test_true_peak_of_ultrasonic_tone() {
+        // 23 kHz at 48 kHz: samples often miss the crest by a wide margin
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| 0.5 * (2.0 * PI * 23000.0 * i as f32 / sample_rate as f32 + 0.3).sin())
+            .collect();
+
+        let stats = SignalStats::measure(&samples);
+        assert!((stats.true_peak - 0.5).abs() < 0.01, "{}", stats.true_peak);
+        assert!(stats.sample_peak <= stats.true_peak);
+        assert!(!stats.near_full_scale());
+    }
+}
@@ -0,0 +1,94 @@
+//! Steering a multi-speaker array toward a target
+//!
+//! Each output channel drives one speaker. Delaying the channels so their
+//! wavefronts line up in one direction puts the strongest signal there and
+//! less elsewhere, so a microphone at a known spot can be jammed without
+//! flooding the whole room. The target is assumed to be far away compared
+//! to the size of the array, so the wavefronts arriving from it are flat.
+
+use serde::{Deserialize, Serialize};
+
+/// Speed of sound in air at room temperature, in m/s
+pub const SPEED_OF_SOUND_M_S: f32 = 343.0;
+
+/// Speaker layout and the direction to aim at
+///
+/// Positions are `[x, y]` in meters, one per output channel in channel
+/// order, with the array facing along `y`. Channels past the listed
+/// speakers follow the first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Beamform {
+    /// Position of each channel's speaker in meters
+    pub speaker_positions: Vec<[f32; 2]>,
+    /// Direction of the target in degrees from straight ahead (`y`),
+    /// positive toward `x`
+    pub target_angle_deg: f32,
+}
+
+impl Beamform {
+    /// Delay of each channel in seconds, the earliest at zero
+    pub fn channel_delays(&self) -> Vec<f32> {
+        let mut delays = Vec::with_capacity(self.speaker_positions.len());
+        self.write_delays(&mut delays);
+        delays
+    }
+
+    /// Replace `delays` with [`Self::channel_delays`], reusing its allocation
+    pub(crate) fn write_delays(&self, delays: &mut Vec<f32>) {
+        let (sin, cos) = self.target_angle_deg.to_radians().sin_cos();
+        // How far along the target direction each speaker sits; the one
+        // nearest the target waits for the others' waves to catch up
+        let lead = |&[x, y]: &[f32; 2]| x * sin + y * cos;
+        let last = self
+            .speaker_positions
+            .iter()
+            .map(lead)
+            .fold(f32::INFINITY, f32::min);
+        delays.clear();
+        delays.extend(
+            self.speaker_positions
+                .iter()
+                .map(|position| (lead(position) - last) / SPEED_OF_SOUND_M_S),
+        );
+    }
+
+    /// Copy `other` into `this`, reusing the position list's allocation
+    pub(crate) fn assign(this: &mut Option<Beamform>, other: &Option<Beamform>) {
+        match (this, other) {
+            (Some(this), Some(other)) => {
+                this.speaker_positions.clone_from(&other.speaker_positions);
+                this.target_angle_deg = other.target_angle_deg;
+            }
+            (this, other) => *this = other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_speaker_delays() {
+        // 20 cm apart, side by side
+        let mut beam = Beamform {
+            speaker_positions: vec![[-0.1, 0.0], [0.1, 0.0]],
+            target_angle_deg: 30.0,
+        };
+        // The right speaker is 0.2 * sin(30°) = 10 cm nearer the target
+        let expected = 0.1 / SPEED_OF_SOUND_M_S;
+        let delays = beam.channel_delays();
+        assert_eq!(delays[0], 0.0);
+        assert!((delays[1] - expected).abs() < 1e-8, "{:?}", delays);
+
+        // Mirrored target, mirrored delays
+        beam.target_angle_deg = -30.0;
+        let delays = beam.channel_delays();
+        assert!((delays[0] - expected).abs() < 1e-8, "{:?}", delays);
+        assert_eq!(delays[1], 0.0);
+
+        // Straight ahead, both already line up
+        beam.target_angle_deg = 0.0;
+        assert_eq!(beam.channel_delays(), vec![0.0, 0.0]);
+    }
+}
@@ -1,14 +1,28 @@
 use anyhow::{Context, Result};
-use std::fs;
-use std::path::PathBuf;
+use serde::Serialize;
+use std::fmt;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tracing::{info, warn};
 
+/// Environment variable that replaces the per-user config directory
+pub const CONFIG_DIR_ENV: &str = "CAMOUFLAGE_CONFIG_DIR";
+
+/// How long a stopping daemon gets to exit before it is killed outright
+pub const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a stopping daemon is checked for
+const STOP_POLL: Duration = Duration::from_millis(50);
+
 /// Daemon configuration and control
 pub struct DaemonConfig {
     pub mode: String,
     pub amplitude: f32,
     pub frequency: f32,
     pub auto_start: bool,
+    /// Where the daemon writes its log
+    pub log_file: PathBuf,
 }
 
 impl Default for DaemonConfig {
@@ -18,15 +32,23 @@ impl Default for DaemonConfig {
             amplitude: 0.25,
             frequency: 23000.0,
             auto_start: true,
+            log_file: get_log_file()
+                .unwrap_or_else(|_| std::env::temp_dir().join("camouflage.log")),
         }
     }
 }
 
 /// Get the daemon configuration directory
+///
+/// `CAMOUFLAGE_CONFIG_DIR` if set, otherwise `camouflage` in the user's
+/// config directory. It is created if missing.
 pub fn get_config_dir() -> Result<PathBuf> {
-    let config_dir = dirs::config_dir()
-        .context("Failed to get config directory")?
-        .join("camouflage");
+    let config_dir = match std::env::var_os(CONFIG_DIR_ENV) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::config_dir()
+            .context("Failed to get config directory")?
+            .join("camouflage"),
+    };
 
     fs::create_dir_all(&config_dir).context("Failed to create config directory")?;
 
@@ -38,76 +60,175 @@ pub fn get_pid_file() -> Result<PathBuf> {
     Ok(get_config_dir()?.join("camouflage.pid"))
 }
 
+/// Get the path where the daemon keeps its generator state
+pub fn get_state_file() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("generator-state.json"))
+}
+
+/// Get the default daemon log file
+pub fn get_log_file() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("daemon.log"))
+}
+
+/// Exclusive claim on starting the daemon
+///
+/// Held for the daemon's whole life and released by the OS when it exits,
+/// even after a crash. On Unix the lock is inherited across `fork`, so it can
+/// be taken before daemonizing. Dropping it in one process does not release
+/// it while a forked copy is still alive.
+#[derive(Debug)]
+pub struct DaemonLock {
+    _file: File,
+}
+
+/// Take the daemon lock, or `None` if another daemon already holds it
+///
+/// Unlike [`is_running`], this cannot race with a concurrent start.
+pub fn lock_daemon() -> Result<Option<DaemonLock>> {
+    lock_file(&get_config_dir()?.join("daemon.lock"))
+}
+
+fn lock_file(path: &Path) -> Result<Option<DaemonLock>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(DaemonLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => {
+            Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+        }
+    }
+}
+
 /// Check if daemon is running
 pub fn is_running() -> bool {
-    if let Ok(pid_file) = get_pid_file() {
-        if let Ok(pid_str) = fs::read_to_string(&pid_file) {
-            if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                // Check if process exists
-                #[cfg(unix)]
-                {
-                    use std::process::Command;
-                    return Command::new("kill")
-                        .args(["-0", &pid.to_string()])
-                        .output()
-                        .map(|o| o.status.success())
-                        .unwrap_or(false);
-                }
-
-                #[cfg(windows)]
-                {
-                    use std::process::Command;
-                    return Command::new("tasklist")
-                        .args(["/FI", &format!("PID eq {}", pid)])
-                        .output()
-                        .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
-                        .unwrap_or(false);
-                }
-            }
-        }
+    get_pid_file().is_ok_and(|pid_file| running_pid(&pid_file).is_some())
+}
+
+/// PID in `pid_file`, if the file names a live process
+fn running_pid(pid_file: &Path) -> Option<u32> {
+    let pid = read_pid(pid_file).ok()?;
+    process_exists(pid).then_some(pid)
+}
+
+fn read_pid(pid_file: &Path) -> Result<u32> {
+    let pid_str = fs::read_to_string(pid_file).context("Failed to read PID file")?;
+    pid_str.trim().parse().context("Invalid PID in file")
+}
+
+fn process_exists(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        use std::process::Command;
+        Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    #[cfg(windows)]
+    {
+        use std::process::Command;
+        Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid)])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).contains(&pid.to_string()))
+            .unwrap_or(false)
     }
-    false
 }
 
 /// Save daemon PID
 pub fn save_pid() -> Result<()> {
-    let pid_file = get_pid_file()?;
+    save_pid_to(&get_pid_file()?)
+}
+
+fn save_pid_to(pid_file: &Path) -> Result<()> {
     let pid = std::process::id();
-    fs::write(&pid_file, pid.to_string()).context("Failed to write PID file")?;
+    fs::write(pid_file, pid.to_string()).context("Failed to write PID file")?;
     info!("Saved daemon PID: {}", pid);
     Ok(())
 }
 
 /// Remove daemon PID file
 pub fn remove_pid() -> Result<()> {
-    let pid_file = get_pid_file()?;
+    remove_pid_file(&get_pid_file()?)
+}
+
+fn remove_pid_file(pid_file: &Path) -> Result<()> {
     if pid_file.exists() {
-        fs::remove_file(&pid_file).context("Failed to remove PID file")?;
+        fs::remove_file(pid_file).context("Failed to remove PID file")?;
     }
     Ok(())
 }
 
-/// Stop running daemon
+/// Stop the running daemon and wait until its process is gone
+///
+/// A daemon still alive after [`STOP_TIMEOUT`] is killed outright. Once
+/// this returns, the daemon lock is free for a new start.
 pub fn stop_daemon() -> Result<()> {
-    let pid_file = get_pid_file()?;
+    stop_daemon_at(&get_pid_file()?, STOP_TIMEOUT)
+}
 
+fn stop_daemon_at(pid_file: &Path, timeout: Duration) -> Result<()> {
     if !pid_file.exists() {
         info!("No daemon running");
         return Ok(());
     }
 
-    let pid_str = fs::read_to_string(&pid_file).context("Failed to read PID file")?;
-    let pid = pid_str
-        .trim()
-        .parse::<i32>()
-        .context("Invalid PID in file")?;
+    let pid = read_pid(pid_file)?;
+    if !process_exists(pid) {
+        info!("Daemon (PID: {}) had already exited", pid);
+        return remove_pid_file(pid_file);
+    }
 
     info!("Stopping daemon (PID: {})...", pid);
+    signal_stop(pid, false)?;
+    if !wait_for_exit(pid, timeout) {
+        warn!(
+            "Daemon (PID: {}) still running after {:.1}s, killing it",
+            pid,
+            timeout.as_secs_f32()
+        );
+        signal_stop(pid, true)?;
+        if !wait_for_exit(pid, timeout) {
+            anyhow::bail!("Daemon (PID: {}) did not exit", pid);
+        }
+    }
+
+    remove_pid_file(pid_file)?;
+    info!("✓ Daemon stopped");
+    Ok(())
+}
+
+/// Poll until `pid` is gone; false if it outlives `timeout`
+fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while process_exists(pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(STOP_POLL);
+    }
+    true
+}
 
+/// Ask `pid` to exit, or with `force` kill it outright
+///
+/// Windows has no graceful signal for a detached process, so both kill.
+fn signal_stop(pid: u32, force: bool) -> Result<()> {
     #[cfg(unix)]
     {
         use std::process::Command;
-        let output = Command::new("kill")
+        let mut command = Command::new("kill");
+        if force {
+            command.arg("-KILL");
+        }
+        let output = command
             .arg(pid.to_string())
             .output()
             .context("Failed to kill process")?;
@@ -123,6 +244,7 @@ pub fn stop_daemon() -> Result<()> {
     #[cfg(windows)]
     {
         use std::process::Command;
+        let _ = force;
         let output = Command::new("taskkill")
             .args(["/PID", &pid.to_string(), "/F"])
             .output()
@@ -136,21 +258,187 @@ pub fn stop_daemon() -> Result<()> {
         }
     }
 
-    remove_pid()?;
-    info!("✓ Daemon stopped");
     Ok(())
 }
 
+/// Snapshot of the daemon's state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DaemonStatus {
+    pub running: bool,
+    /// PID from the PID file, if the daemon is running
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for DaemonStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.running, self.pid) {
+            (true, Some(pid)) => write!(f, "Running (PID: {})", pid),
+            (true, None) => write!(f, "Running"),
+            (false, _) => write!(f, "Stopped"),
+        }
+    }
+}
+
+/// Get structured daemon status
+pub fn get_daemon_status() -> DaemonStatus {
+    match get_pid_file() {
+        Ok(pid_file) => daemon_status_at(&pid_file),
+        Err(_) => DaemonStatus {
+            running: false,
+            pid: None,
+        },
+    }
+}
+
+fn daemon_status_at(pid_file: &Path) -> DaemonStatus {
+    let pid = running_pid(pid_file);
+    DaemonStatus {
+        running: pid.is_some(),
+        pid,
+    }
+}
+
 /// Get daemon status
 pub fn get_status() -> String {
-    if is_running() {
-        if let Ok(pid_file) = get_pid_file() {
-            if let Ok(pid_str) = fs::read_to_string(&pid_file) {
-                return format!("Running (PID: {})", pid_str.trim());
+    get_daemon_status().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn test_only_one_concurrent_start_takes_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("daemon.lock");
+        let barrier = Arc::new(Barrier::new(2));
+
+        let starts: Vec<_> = (0..2)
+            .map(|_| {
+                let path = path.clone();
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let lock = lock_file(&path).unwrap();
+                    // Keep the winner's lock until both have tried
+                    barrier.wait();
+                    lock
+                })
+            })
+            .collect();
+        let results: Vec<_> = starts.into_iter().map(|t| t.join().unwrap()).collect();
+
+        assert_eq!(results.iter().filter(|lock| lock.is_some()).count(), 1);
+
+        // Released once the winner goes away
+        drop(results);
+        assert!(lock_file(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_pid_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("camouflage.pid");
+        assert_eq!(daemon_status_at(&pid_file).to_string(), "Stopped");
+
+        // This test process stands in for the daemon
+        save_pid_to(&pid_file).unwrap();
+        assert_eq!(
+            daemon_status_at(&pid_file),
+            DaemonStatus {
+                running: true,
+                pid: Some(std::process::id()),
             }
-        }
-        "Running".to_string()
-    } else {
-        "Stopped".to_string()
+        );
+
+        remove_pid_file(&pid_file).unwrap();
+        assert!(!pid_file.exists());
+        assert_eq!(running_pid(&pid_file), None);
+        // Removing again is not an error
+        remove_pid_file(&pid_file).unwrap();
+    }
+
+    #[test]
+    fn test_garbage_pid_file_is_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("camouflage.pid");
+        fs::write(&pid_file, "not a pid").unwrap();
+
+        assert!(!daemon_status_at(&pid_file).running);
+        assert!(stop_daemon_at(&pid_file, STOP_TIMEOUT).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stale_pid_is_not_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("camouflage.pid");
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        fs::write(&pid_file, exited.id().to_string()).unwrap();
+
+        assert_eq!(running_pid(&pid_file), None);
+        assert!(!daemon_status_at(&pid_file).running);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_daemon_kills_and_removes_pid() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("camouflage.pid");
+        stop_daemon_at(&pid_file, STOP_TIMEOUT).unwrap();
+
+        // Reaped as soon as it exits, as a detached daemon would be
+        let spawn = |script: &str| {
+            let mut daemon = std::process::Command::new("sh")
+                .args(["-c", script])
+                .spawn()
+                .unwrap();
+            fs::write(&pid_file, daemon.id().to_string()).unwrap();
+            std::thread::spawn(move || daemon.wait().unwrap().signal())
+        };
+
+        let exited = spawn("exec sleep 30");
+        stop_daemon_at(&pid_file, STOP_TIMEOUT).unwrap();
+        // `kill` sends SIGTERM
+        assert_eq!(exited.join().unwrap(), Some(15));
+        assert!(!pid_file.exists());
+
+        let stubborn = spawn("trap '' TERM; while :; do sleep 0.1; done");
+        std::thread::sleep(Duration::from_millis(200));
+        stop_daemon_at(&pid_file, Duration::from_millis(300)).unwrap();
+        assert_eq!(stubborn.join().unwrap(), Some(9));
+        assert!(!pid_file.exists());
+
+        // A PID file left by a crash is just cleared
+        fs::write(&pid_file, "4194304").unwrap();
+        stop_daemon_at(&pid_file, STOP_TIMEOUT).unwrap();
+        assert!(!pid_file.exists());
+    }
+
+    #[test]
+    fn test_status_display_and_json() {
+        let status = DaemonStatus {
+            running: true,
+            pid: Some(4242),
+        };
+        assert_eq!(status.to_string(), "Running (PID: 4242)");
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"running":true,"pid":4242}"#
+        );
+
+        let stopped = DaemonStatus {
+            running: false,
+            pid: None,
+        };
+        assert_eq!(stopped.to_string(), "Stopped");
+        assert_eq!(
+            serde_json::to_string(&stopped).unwrap(),
+            r#"{"running":false,"pid":null}"#
+        );
     }
 }
@@ -0,0 +1,194 @@
+//! Error types for the core library
+
+use thiserror::Error;
+
+/// Why a [`SignalConfig`](crate::SignalConfig) was rejected
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum SignalConfigError {
+    #[error("Frequency must be positive, got {0}")]
+    InvalidFrequency(f32),
+
+    #[error("Amplitude must be between 0.0 and 1.0, got {0}")]
+    InvalidAmplitude(f32),
+
+    #[error("Number of tones must be at least 1")]
+    NoTones,
+
+    #[error("Frequency spread must not be negative, got {0}")]
+    InvalidSpread(f32),
+
+    #[error("Sample rate must be positive")]
+    InvalidSampleRate,
+
+    #[error("Crossfade must not be negative, got {0} ms")]
+    InvalidCrossfade(f32),
+
+    #[error("Lowest tone is {lowest:.0} Hz, below the {limit:.0} Hz audible limit")]
+    AudibleTone { lowest: f32, limit: f32 },
+
+    #[error("Highest tone is {highest:.0} Hz, at or above the Nyquist limit for {sample_rate} Hz")]
+    AboveNyquist { highest: f32, sample_rate: u32 },
+
+    #[error("Hop interval must be positive, got {0} ms")]
+    InvalidHopInterval(f32),
+
+    #[error("Channel phase offset must be finite, got {0}")]
+    InvalidPhaseOffset(f32),
+
+    #[error("Beamforming needs at least one speaker position")]
+    NoSpeakers,
+
+    #[error("Speaker position must be finite, got ({x}, {y}) m")]
+    InvalidSpeakerPosition { x: f32, y: f32 },
+
+    #[error("Beam target angle must be finite, got {0} degrees")]
+    InvalidBeamAngle(f32),
+
+    #[error("Custom tone distribution has {offsets} offsets for {tones} tones")]
+    ToneCountMismatch { offsets: usize, tones: usize },
+
+    #[error("Tone offset must be finite, got {0} Hz")]
+    InvalidToneOffset(f32),
+
+    #[error("Spread dither depth must be between 0.0 and 1.0, got {0}")]
+    InvalidSpreadDither(f32),
+
+    #[error("Spread dither rate must be positive, got {0} Hz")]
+    InvalidSpreadDitherRate(f32),
+
+    #[error("Nyquist guard band must be between 0 Hz and the Nyquist limit, got {0} Hz")]
+    InvalidNyquistGuard(f32),
+
+    #[error("Highest tone is {highest:.0} Hz, above the {limit:.0} Hz limit set by the Nyquist guard band")]
+    InGuardBand { highest: f32, limit: f32 },
+
+    #[error("Master gain must be between 0.0 and 1.0, got {0}")]
+    InvalidMasterGain(f32),
+
+    #[error("Frequency tilt must be finite, got {0} dB/octave")]
+    InvalidTilt(f32),
+
+    #[error(
+        "Pilot tone reaches {highest:.0} Hz, too close to the Nyquist limit for {sample_rate} Hz"
+    )]
+    PilotAboveNyquist { highest: f32, sample_rate: u32 },
+
+    #[error("A tone at {tone:.0} Hz is too close to the {pilot:.0} Hz pilot tone")]
+    PilotClash { tone: f32, pilot: f32 },
+}
+
+/// Why [`SignalController::retune`](crate::SignalController::retune) failed
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RetuneError {
+    #[error("Invalid signal configuration")]
+    InvalidConfig(#[from] SignalConfigError),
+
+    #[error("Retune queue is full; the audio thread has not caught up")]
+    QueueFull,
+}
+
+/// Errors from creating or running a jammer
+#[derive(Debug, Error)]
+pub enum CamouflageError {
+    #[error("No output device available")]
+    NoOutputDevice,
+
+    #[error("Failed to read output device name")]
+    DeviceName(#[from] cpal::DeviceNameError),
+
+    #[error("Failed to query default output config")]
+    DefaultStreamConfig(#[from] cpal::DefaultStreamConfigError),
+
+    #[error(
+        "Output device has no usable stream config: the signal needs {needed} Hz or more \
+         in f32, i32, i16 or u16; the device supports {supported}"
+    )]
+    NoUsableOutputConfig { needed: u32, supported: String },
+
+    #[error(
+        "Output device cannot play the loop at its {sample_rate} Hz sample rate; \
+         the device supports {supported}"
+    )]
+    UnsupportedLoopRate { sample_rate: u32, supported: String },
+
+    #[error("Failed to load the loop")]
+    LoopFile(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("The jammer is playing a loop, not generating a signal")]
+    NotGenerating,
+
+    #[error("Unsupported output sample format: {0}")]
+    UnsupportedSampleFormat(cpal::SampleFormat),
+
+    #[error("Invalid signal configuration")]
+    InvalidConfig(#[from] SignalConfigError),
+
+    #[error("Failed to build output stream")]
+    StreamBuild(#[from] cpal::BuildStreamError),
+
+    #[error("Failed to start output stream")]
+    StreamPlay(#[from] cpal::PlayStreamError),
+
+    #[error("Failed to retune the signal")]
+    Retune(#[from] RetuneError),
+
+    #[error("Failed to start the null output")]
+    NullOutput(#[source] std::io::Error),
+
+    #[error("Failed to open the output in WASAPI exclusive mode")]
+    ExclusiveOutput(#[source] std::io::Error),
+
+    #[error("Failed to start the CPU profiler")]
+    Profiler(#[source] std::io::Error),
+
+    /// `channel` is zero-based; the message counts from one
+    #[error("Output has {available} channels, there is no channel {}", .channel + 1)]
+    InvalidChannel { channel: usize, available: u16 },
+
+    #[error("Failed to save or restore generator state")]
+    State(#[from] std::io::Error),
+
+    #[error("System audio error")]
+    SystemAudio(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Failed to start the jammer thread")]
+    JammerThread(#[source] std::io::Error),
+
+    #[error("Jammer thread has exited")]
+    JammerExited,
+}
+
+impl From<anyhow::Error> for CamouflageError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::SystemAudio(err.into())
+    }
+}
+
+/// Result type for jammer operations
+pub type Result<T> = std::result::Result<T, CamouflageError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_invalid_config_keeps_source() {
+        let err = CamouflageError::from(SignalConfigError::NoTones);
+        assert!(matches!(
+            err,
+            CamouflageError::InvalidConfig(SignalConfigError::NoTones)
+        ));
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            "Number of tones must be at least 1"
+        );
+
+        // Converts into anyhow for the CLI with the chain intact
+        let err = anyhow::Error::from(err);
+        assert_eq!(
+            format!("{:#}", err),
+            "Invalid signal configuration: Number of tones must be at least 1"
+        );
+    }
+}
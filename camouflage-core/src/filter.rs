@@ -0,0 +1,170 @@
+//! Biquad filters used to shape the generated signal
+
+use std::f64::consts::PI;
+
+/// Number of biquad sections in the safety high-pass (8th order)
+const HIGH_PASS_SECTIONS: usize = 4;
+
+/// Second-order IIR section (RBJ cookbook, direct form I)
+#[derive(Debug, Clone)]
+pub(crate) struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    x1: f64,
+    x2: f64,
+    y1: f64,
+    y2: f64,
+}
+
+impl Biquad {
+    fn from_coefficients(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    /// Band-pass with 0 dB peak gain
+    pub(crate) fn band_pass(center: f32, bandwidth: f32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f64;
+        let center = (center as f64).min(sample_rate / 2.0 - 1.0);
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() / (2.0 * center / bandwidth as f64);
+
+        Self::from_coefficients(
+            [alpha, 0.0, -alpha],
+            [1.0 + alpha, -2.0 * w0.cos(), 1.0 - alpha],
+        )
+    }
+
+    /// High-pass with the given quality factor
+    pub(crate) fn high_pass(cutoff: f32, q: f64, sample_rate: u32) -> Self {
+        let w0 = 2.0 * PI * cutoff as f64 / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos = w0.cos();
+
+        Self::from_coefficients(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    /// Clear the filter's memory, keeping its coefficients
+    pub(crate) fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    pub(crate) fn process(&mut self, x: f32) -> f32 {
+        self.process_f64(x as f64) as f32
+    }
+
+    pub(crate) fn process_f64(&mut self, x: f64) -> f64 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Steep Butterworth high-pass built from cascaded biquads
+#[derive(Debug, Clone)]
+pub(crate) struct HighPass {
+    sections: Vec<Biquad>,
+}
+
+impl HighPass {
+    /// 8th-order Butterworth high-pass at `cutoff` Hz
+    ///
+    /// Returns `None` if the cutoff is not below the Nyquist limit.
+    pub(crate) fn butterworth(cutoff: f32, sample_rate: u32) -> Option<Self> {
+        if cutoff <= 0.0 || cutoff >= sample_rate as f32 / 2.0 {
+            return None;
+        }
+
+        let order = (2 * HIGH_PASS_SECTIONS) as f64;
+        let sections = (1..=HIGH_PASS_SECTIONS)
+            .map(|k| {
+                let q = 1.0 / (2.0 * ((2 * k - 1) as f64 * PI / (2.0 * order)).cos());
+                Biquad::high_pass(cutoff, q, sample_rate)
+            })
+            .collect();
+
+        Some(Self { sections })
+    }
+
+    /// Clear the memory of every section
+    pub(crate) fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Biquad::reset);
+    }
+
+    pub(crate) fn process(&mut self, x: f32) -> f32 {
+        self.sections
+            .iter_mut()
+            .fold(x, |sample, section| section.process(sample))
+    }
+
+    pub(crate) fn process_f64(&mut self, x: f64) -> f64 {
+        self.sections
+            .iter_mut()
+            .fold(x, |sample, section| section.process_f64(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze_samples;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_high_pass_removes_audible_leakage() {
+        let sample_rate = 48000;
+        // DC offset and a 1 kHz product riding on a 23 kHz tone
+        let input: Vec<f32> = (0..sample_rate)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                0.05 + 0.05 * (2.0 * PI * 1000.0 * t).sin() + 0.25 * (2.0 * PI * 23000.0 * t).sin()
+            })
+            .collect();
+        assert!(!analyze_samples(&input, sample_rate)
+            .unwrap()
+            .is_ultrasonic());
+
+        let mut filter = HighPass::butterworth(19000.0, sample_rate).unwrap();
+        let output: Vec<f32> = input.iter().map(|&x| filter.process(x)).collect();
+
+        let report = analyze_samples(&output, sample_rate).unwrap();
+        assert!(report.is_ultrasonic());
+        assert!(report.audible_peak_db < -75.0, "{}", report.audible_peak_db);
+        assert!((report.peak_frequencies[0] - 23000.0).abs() < 2.0);
+
+        // The ultrasonic tone passes almost unattenuated
+        let peak = output[sample_rate as usize / 2..]
+            .iter()
+            .fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!((peak - 0.25).abs() < 0.03, "{}", peak);
+    }
+
+    #[test]
+    fn test_high_pass_needs_cutoff_below_nyquist() {
+        assert!(HighPass::butterworth(19000.0, 44100).is_some());
+        assert!(HighPass::butterworth(19000.0, 32000).is_none());
+    }
+}
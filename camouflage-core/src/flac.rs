@@ -0,0 +1,375 @@
+//! Minimal lossless FLAC encoder
+//!
+//! Each channel of each block is coded with the best fixed polynomial
+//! predictor (order 0-4) and a single Rice partition, falling back to
+//! verbatim samples when prediction does not pay off.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Inter-channel samples per frame
+const BLOCK_SIZE: usize = 4096;
+
+/// Highest fixed predictor order defined by the format
+const MAX_FIXED_ORDER: usize = 4;
+
+/// Largest parameter of the 5-bit Rice coding method
+const MAX_RICE_PARAM: u32 = 30;
+
+/// MSB-first bit writer
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    /// Write the low `count` bits of `value` (at most 32)
+    fn write(&mut self, value: u64, count: u32) {
+        debug_assert!(count <= 32);
+        if count == 0 {
+            return;
+        }
+        self.acc = (self.acc << count) | (value & ((1u64 << count) - 1));
+        self.bits += count;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.bytes.push((self.acc >> self.bits) as u8);
+        }
+        self.acc &= (1u64 << self.bits) - 1;
+    }
+
+    fn write_signed(&mut self, value: i64, count: u32) {
+        self.write(value as u64, count);
+    }
+
+    fn write_unary(&mut self, zeros: u64) {
+        let mut remaining = zeros;
+        while remaining >= 32 {
+            self.write(0, 32);
+            remaining -= 32;
+        }
+        self.write(1, remaining as u32 + 1);
+    }
+
+    /// Pad with zero bits to the next byte boundary
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Frame number in FLAC's extended UTF-8 coding
+fn write_utf8(writer: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        writer.write(value, 8);
+        return;
+    }
+
+    // Lead byte has `len` one bits, then 7 - len payload bits
+    let mut len = 2;
+    while len < 7 && value >> ((7 - len) + 6 * (len - 1)) != 0 {
+        len += 1;
+    }
+    let continuation = len - 1;
+    let marker = (0xFF00u64 >> len) & 0xFF;
+    writer.write(marker | (value >> (6 * continuation)), 8);
+    for i in (0..continuation).rev() {
+        writer.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+/// Frame header code for a sample size
+fn sample_size_code(bits_per_sample: u16) -> Option<u64> {
+    match bits_per_sample {
+        8 => Some(0b001),
+        12 => Some(0b010),
+        16 => Some(0b100),
+        20 => Some(0b101),
+        24 => Some(0b110),
+        _ => None,
+    }
+}
+
+/// Residuals of the fixed predictor of `order`
+fn fixed_residuals(samples: &[i64], order: usize) -> Vec<i64> {
+    samples
+        .iter()
+        .enumerate()
+        .skip(order)
+        .map(|(i, &x)| {
+            let s = |k: usize| samples[i - k];
+            match order {
+                0 => x,
+                1 => x - s(1),
+                2 => x - 2 * s(1) + s(2),
+                3 => x - 3 * s(1) + 3 * s(2) - s(3),
+                _ => x - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }
+        })
+        .collect()
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Best Rice parameter for `residuals` and the resulting size in bits
+fn rice_cost(residuals: &[i64]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAM)
+        .map(|k| {
+            let bits: u64 = residuals
+                .iter()
+                .map(|&r| (zigzag(r) >> k) + 1 + k as u64)
+                .sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+fn write_subframe(writer: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    let verbatim_bits = samples.len() as u64 * bits_per_sample as u64;
+
+    let best = (0..=MAX_FIXED_ORDER.min(samples.len().saturating_sub(1)))
+        .map(|order| {
+            let residuals = fixed_residuals(samples, order);
+            let (param, bits) = rice_cost(&residuals);
+            let total = order as u64 * bits_per_sample as u64 + 2 + 4 + 5 + bits;
+            (order, residuals, param, total)
+        })
+        .min_by_key(|candidate| candidate.3);
+
+    match best {
+        Some((order, residuals, param, total)) if total < verbatim_bits => {
+            // Zero pad bit, FIXED type 0b001xxx, no wasted bits
+            writer.write(0, 1);
+            writer.write(0b001000 | order as u64, 6);
+            writer.write(0, 1);
+            for &warmup in &samples[..order] {
+                writer.write_signed(warmup, bits_per_sample);
+            }
+            // 5-bit Rice parameters, a single partition
+            writer.write(0b01, 2);
+            writer.write(0, 4);
+            writer.write(param as u64, 5);
+            for residual in residuals {
+                let value = zigzag(residual);
+                writer.write_unary(value >> param);
+                writer.write(value, param);
+            }
+        }
+        _ => {
+            writer.write(0, 1);
+            writer.write(0b000001, 6);
+            writer.write(0, 1);
+            for &sample in samples {
+                writer.write_signed(sample, bits_per_sample);
+            }
+        }
+    }
+}
+
+/// Encode interleaved integer samples as a FLAC file
+pub(crate) fn write_flac(
+    path: &Path,
+    samples: &[i32],
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+) -> Result<()> {
+    if !(1..=8).contains(&channels) {
+        anyhow::bail!("FLAC supports 1-8 channels, got {}", channels);
+    }
+    let Some(sample_size_code) = sample_size_code(bits_per_sample) else {
+        anyhow::bail!(
+            "FLAC output supports 8, 12, 16, 20 or 24 bits per sample, got {}",
+            bits_per_sample
+        );
+    };
+    if sample_rate == 0 || sample_rate >= 1 << 20 {
+        anyhow::bail!("Sample rate not representable in FLAC: {}", sample_rate);
+    }
+
+    let channels = channels as usize;
+    let bits = bits_per_sample as u32;
+    let total_frames = (samples.len() / channels) as u64;
+
+    let mut header = BitWriter::new();
+    header.write(u64::from(u32::from_be_bytes(*b"fLaC")), 32);
+    // Last metadata block, STREAMINFO, 34 bytes
+    header.write(1, 1);
+    header.write(0, 7);
+    header.write(34, 24);
+    header.write(BLOCK_SIZE as u64, 16);
+    header.write(BLOCK_SIZE as u64, 16);
+    header.write(0, 24); // Frame sizes unknown
+    header.write(0, 24);
+    header.write(sample_rate as u64, 20);
+    header.write(channels as u64 - 1, 3);
+    header.write(bits as u64 - 1, 5);
+    header.write(total_frames >> 32, 4);
+    header.write(total_frames & 0xFFFF_FFFF, 32);
+    for _ in 0..4 {
+        header.write(0, 32); // MD5 left unset
+    }
+    let mut output = header.into_bytes();
+
+    for (frame_number, block) in samples.chunks(BLOCK_SIZE * channels).enumerate() {
+        let block_len = block.len() / channels;
+        if block_len == 0 {
+            break;
+        }
+
+        let mut frame = BitWriter::new();
+        frame.write(0b11111111111110, 14);
+        frame.write(0, 1); // Reserved
+        frame.write(0, 1); // Fixed block size
+        frame.write(0b0111, 4); // 16-bit block size at end of header
+        frame.write(0b0000, 4); // Sample rate from STREAMINFO
+        frame.write(channels as u64 - 1, 4); // Independent channels
+        frame.write(sample_size_code, 3);
+        frame.write(0, 1);
+        write_utf8(&mut frame, frame_number as u64);
+        frame.write(block_len as u64 - 1, 16);
+        let mut bytes = frame.into_bytes();
+        bytes.push(crc8(&bytes));
+
+        let mut body = BitWriter::new();
+        for channel in 0..channels {
+            let channel_samples: Vec<i64> = block
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .map(|&s| s as i64)
+                .collect();
+            write_subframe(&mut body, &channel_samples, bits);
+        }
+        bytes.extend(body.into_bytes());
+
+        let crc = crc16(&bytes);
+        bytes.extend(crc.to_be_bytes());
+        output.extend(bytes);
+    }
+
+    fs::write(path, output).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn decode(path: &Path) -> (claxon::metadata::StreamInfo, Vec<i32>) {
+        let mut reader = claxon::FlacReader::open(path).unwrap();
+        let info = reader.streaminfo();
+        let samples = reader.samples().map(|s| s.unwrap()).collect();
+        (info, samples)
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut seed = 1u64;
+
+        for (channels, bits) in [(1u16, 16u16), (2, 24), (3, 8)] {
+            let path = temp_dir
+                .path()
+                .join(format!("{}ch_{}bit.flac", channels, bits));
+            let full_scale = (1i64 << (bits - 1)) - 1;
+
+            // Not a whole number of blocks; a tone plus noise on each channel
+            let frames = BLOCK_SIZE * 2 + 123;
+            let samples: Vec<i32> = (0..frames * channels as usize)
+                .map(|i| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    let noise = (seed % 64) as f64 - 32.0;
+                    let tone = (i as f64 * 0.3).sin() * full_scale as f64 * 0.7;
+                    (tone + noise).clamp(-(full_scale as f64), full_scale as f64) as i32
+                })
+                .collect();
+
+            write_flac(&path, &samples, channels, 48000, bits).unwrap();
+            let (info, decoded) = decode(&path);
+
+            assert_eq!(info.channels, channels as u32);
+            assert_eq!(info.bits_per_sample, bits as u32);
+            assert_eq!(info.samples, Some(frames as u64));
+            assert_eq!(decoded, samples);
+        }
+    }
+
+    #[test]
+    fn test_predictable_signal_compresses() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("tone.flac");
+        let samples: Vec<i32> = (0..48000)
+            .map(|i| {
+                ((i as f64 * 2.0 * std::f64::consts::PI * 440.0 / 48000.0).sin() * 10000.0) as i32
+            })
+            .collect();
+
+        write_flac(&path, &samples, 1, 48000, 16).unwrap();
+        assert_eq!(decode(&path).1, samples);
+        assert!(fs::metadata(&path).unwrap().len() < 48000 * 2 / 2);
+    }
+
+    #[test]
+    fn test_utf8_frame_numbers() {
+        for (value, expected) in [
+            (0x7Fu64, vec![0x7F]),
+            (0x80, vec![0xC2, 0x80]),
+            (0x7FF, vec![0xDF, 0xBF]),
+            (0x800, vec![0xE0, 0xA0, 0x80]),
+        ] {
+            let mut writer = BitWriter::new();
+            write_utf8(&mut writer, value);
+            assert_eq!(writer.into_bytes(), expected, "{:#x}", value);
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! LAN discovery and remote control of several daemons
+//!
+//! Each daemon advertises itself over mDNS as [`SERVICE_TYPE`] and accepts
+//! one JSON request per TCP connection, answered with one JSON line. Every
+//! request carries a token shared by the fleet; others are refused.
+
+use crate::signal::SignalConfig;
+use anyhow::{Context, Result};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// mDNS service type the daemons advertise
+pub const SERVICE_TYPE: &str = "_camouflage._tcp.local.";
+
+/// How long either side waits on a connection before giving up, and how
+/// long a member gives a peer to send its whole request
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request a member reads; a `set` with a config is far shorter
+const MAX_REQUEST_BYTES: u64 = 64 * 1024;
+
+/// Connections a member serves at once; more are dropped unanswered
+const MAX_CONNECTIONS: usize = 16;
+
+/// How long a connection waits for the daemon to act on its command
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something a fleet member can be told to do
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Report the running configuration
+    Status,
+    /// Crossfade to a new signal; the sample rate is the member's own
+    Set { config: SignalConfig },
+    /// Stop jamming and exit
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    token: String,
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// What a fleet member is doing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemberStatus {
+    /// Name the member advertises under, usually its host name
+    pub name: String,
+    /// Jammer mode, "speaker" or "system"
+    pub mode: String,
+    pub config: SignalConfig,
+}
+
+/// Answer to a [`Command`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Status after the command, if it succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MemberStatus>,
+}
+
+impl Response {
+    pub fn ok(status: MemberStatus) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+        }
+    }
+}
+
+/// A command from the network, waiting for the daemon to act on it
+pub struct Incoming {
+    pub command: Command,
+    reply: Sender<Response>,
+}
+
+impl Incoming {
+    /// Send the outcome back to the member that asked
+    pub fn reply(self, response: Response) {
+        // The connection may have timed out in the meantime
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Advertises this daemon and receives commands from the fleet
+///
+/// Commands are queued for [`recv_timeout`](Self::recv_timeout), so they
+/// are handled on the daemon's own thread. Dropping the server withdraws
+/// the advertisement; the listener thread lives as long as the process.
+pub struct FleetServer {
+    incoming: Receiver<Incoming>,
+    port: u16,
+    mdns: Option<(ServiceDaemon, String)>,
+}
+
+impl FleetServer {
+    /// Listen on `port` (0 picks a free one) and advertise as `name`
+    ///
+    /// Failing to advertise is logged and leaves the server reachable by
+    /// address only.
+    pub fn start(name: &str, token: String, port: u16) -> Result<Self> {
+        if token.is_empty() {
+            anyhow::bail!("The fleet token must not be empty");
+        }
+        let listener = TcpListener::bind(("0.0.0.0", port))
+            .with_context(|| format!("Failed to listen on port {}", port))?;
+        let port = listener.local_addr()?.port();
+
+        let (sender, incoming) = mpsc::channel();
+        let token: Arc<str> = token.into();
+        thread::Builder::new()
+            .name("camouflage-fleet".into())
+            .spawn(move || {
+                // Each connection gets its own thread, so a slow peer
+                // cannot hold up the others
+                let open = Arc::new(AtomicUsize::new(0));
+                for stream in listener.incoming().flatten() {
+                    if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                        open.fetch_sub(1, Ordering::SeqCst);
+                        debug!("Too many fleet connections, dropping one");
+                        continue;
+                    }
+                    let (token, sender, open) =
+                        (Arc::clone(&token), sender.clone(), Arc::clone(&open));
+                    let spawned = thread::Builder::new()
+                        .name("camouflage-fleet-conn".into())
+                        .spawn(move || {
+                            if let Err(e) = handle_connection(stream, &token, &sender) {
+                                debug!("Fleet connection failed: {:#}", e);
+                            }
+                            open.fetch_sub(1, Ordering::SeqCst);
+                        });
+                    if let Err(e) = spawned {
+                        warn!("Failed to handle a fleet connection: {}", e);
+                    }
+                }
+            })
+            .context("Failed to start the fleet listener")?;
+
+        let mdns = match advertise(name, port) {
+            Ok(mdns) => Some(mdns),
+            Err(e) => {
+                warn!("Not advertising over mDNS: {:#}", e);
+                None
+            }
+        };
+
+        Ok(Self {
+            incoming,
+            port,
+            mdns,
+        })
+    }
+
+    /// Port the server listens on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Wait up to `timeout` for the next command
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Incoming> {
+        self.incoming.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for FleetServer {
+    fn drop(&mut self) {
+        if let Some((mdns, fullname)) = self.mdns.take() {
+            let _ = mdns.unregister(&fullname);
+            let _ = mdns.shutdown();
+        }
+    }
+}
+
+/// Register the service; returns the daemon and the registered full name
+fn advertise(name: &str, port: u16) -> Result<(ServiceDaemon, String)> {
+    let label = dns_label(name);
+    let mdns = ServiceDaemon::new().context("Failed to start mDNS")?;
+    let info = ServiceInfo::new(
+        SERVICE_TYPE,
+        &label,
+        &format!("{}.local.", label),
+        "",
+        port,
+        None,
+    )?
+    .enable_addr_auto();
+    let fullname = info.get_fullname().to_string();
+    mdns.register(info)
+        .context("Failed to register the mDNS service")?;
+    Ok((mdns, fullname))
+}
+
+/// `name` reduced to letters, digits and hyphens, as DNS labels allow
+fn dns_label(name: &str) -> String {
+    let label: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .take(63)
+        .collect();
+    match label.trim_matches('-') {
+        "" => "camouflage".to_string(),
+        label => label.to_string(),
+    }
+}
+
+/// Reads from a connection until a deadline for the whole request,
+/// however slowly the peer trickles it in
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request took too long",
+            ));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+fn handle_connection(stream: TcpStream, token: &str, sender: &Sender<Incoming>) -> Result<()> {
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let reader = DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + IO_TIMEOUT,
+    };
+    let mut line = String::new();
+    BufReader::new(reader.take(MAX_REQUEST_BYTES)).read_line(&mut line)?;
+
+    let response = match serde_json::from_str::<Request>(&line) {
+        _ if line.len() as u64 >= MAX_REQUEST_BYTES && !line.ends_with('\n') => {
+            Response::error(format!("Request is over {} bytes", MAX_REQUEST_BYTES))
+        }
+        Err(e) => Response::error(format!("Malformed request: {}", e)),
+        Ok(request) if !tokens_match(&request.token, token) => Response::error("Wrong token"),
+        Ok(request) => {
+            let (reply, response) = mpsc::channel();
+            sender
+                .send(Incoming {
+                    command: request.command,
+                    reply,
+                })
+                .context("Daemon is no longer listening")?;
+            response
+                .recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| Response::error("Daemon did not answer in time"))
+        }
+    };
+
+    let mut stream = &stream;
+    writeln!(stream, "{}", serde_json::to_string(&response)?)?;
+    Ok(())
+}
+
+/// Compare without stopping at the first difference, so response timing
+/// does not reveal how much of a guessed token was right
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A daemon found on the network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Member {
+    /// Advertised instance name
+    pub name: String,
+    pub addr: SocketAddr,
+}
+
+/// Browse the network for daemons for `duration`
+///
+/// Each member is listed once, at an IPv4 address since that is all
+/// [`FleetServer`] listens on.
+pub fn discover(duration: Duration) -> Result<Vec<Member>> {
+    let mdns = ServiceDaemon::new().context("Failed to start mDNS")?;
+    let events = mdns.browse(SERVICE_TYPE).context("Failed to browse mDNS")?;
+
+    let deadline = Instant::now() + duration;
+    let mut members: Vec<Member> = Vec::new();
+    while let Ok(event) = events.recv_deadline(deadline) {
+        let ServiceEvent::ServiceResolved(info) = event else {
+            continue;
+        };
+        // A member may resolve without its IPv4 address at first
+        let Some(ip) = info.get_addresses().iter().find(|ip| ip.is_ipv4()).copied() else {
+            continue;
+        };
+
+        let name = info
+            .get_fullname()
+            .trim_end_matches(SERVICE_TYPE)
+            .trim_end_matches('.')
+            .to_string();
+        if !members.iter().any(|m| m.name == name) {
+            members.push(Member {
+                name,
+                addr: SocketAddr::new(ip, info.get_port()),
+            });
+        }
+    }
+
+    let _ = mdns.shutdown();
+    members.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(members)
+}
+
+/// Send one command to the member at `addr` and wait for its answer
+pub fn send(addr: SocketAddr, token: &str, command: Command) -> Result<Response> {
+    let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}", addr))?;
+    // Retuning waits for the daemon's loop, so allow for the reply timeout
+    stream.set_read_timeout(Some(IO_TIMEOUT + REPLY_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let request = Request {
+        token: token.to_string(),
+        command,
+    };
+    let mut writer = &stream;
+    writeln!(writer, "{}", serde_json::to_string(&request)?)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .with_context(|| format!("No answer from {}", addr))?;
+    serde_json::from_str(&line).with_context(|| format!("Malformed answer from {}", addr))
+}
+
+/// Address of the loopback interface, for reaching a server on this machine
+pub fn localhost(port: u16) -> SocketAddr {
+    SocketAddr::new(IpAddr::from([127, 0, 0, 1]), port)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_round_trip_with_token() {
+        let server = FleetServer::start("test host", "secret".into(), 0).unwrap();
+        let addr = localhost(server.port());
+
+        let client = thread::spawn(move || {
+            let status = send(addr, "secret", Command::Status).unwrap();
+            let wrong = send(addr, "guess!", Command::Stop).unwrap();
+            (status, wrong)
+        });
+
+        // Only the request with the right token reaches the daemon
+        let incoming = server.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(incoming.command, Command::Status);
+        let status = MemberStatus {
+            name: "test-host".into(),
+            mode: "speaker".into(),
+            config: SignalConfig::default(),
+        };
+        incoming.reply(Response::ok(status.clone()));
+
+        let (answer, refused) = client.join().unwrap();
+        assert_eq!(answer, Response::ok(status));
+        assert_eq!(refused, Response::error("Wrong token"));
+        assert!(server.recv_timeout(Duration::from_millis(100)).is_none());
+
+        assert_eq!(dns_label("Bob's Mac.local"), "Bob-s-Mac-local");
+    }
+
+    #[test]
+    fn test_slow_and_oversized_requests_do_not_block_others() {
+        let server = FleetServer::start("test host", "secret".into(), 0).unwrap();
+        let addr = localhost(server.port());
+
+        // A peer that starts a request and never finishes it
+        let mut slow = TcpStream::connect(addr).unwrap();
+        slow.write_all(br#"{"token":"sec"#).unwrap();
+
+        // One that never stops sending gets cut off with an error
+        let flood = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let chunk = [b' '; 4096];
+            let mut sent = 0;
+            while sent <= MAX_REQUEST_BYTES && stream.write_all(&chunk).is_ok() {
+                sent += chunk.len() as u64;
+            }
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            serde_json::from_str::<Response>(&line).unwrap()
+        });
+
+        let client = thread::spawn(move || send(addr, "secret", Command::Stop).unwrap());
+        let incoming = server.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert_eq!(incoming.command, Command::Stop);
+        incoming.reply(Response::error("stopping"));
+        assert_eq!(client.join().unwrap(), Response::error("stopping"));
+        assert_eq!(
+            flood.join().unwrap(),
+            Response::error(format!("Request is over {} bytes", MAX_REQUEST_BYTES))
+        );
+        drop(slow);
+        assert_eq!(
+            serde_json::to_string(&Command::Set {
+                config: SignalConfig::default()
+            })
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap(),
+            r#"{"command":"set""#
+        );
+    }
+}
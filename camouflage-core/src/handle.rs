@@ -0,0 +1,217 @@
+//! Async control of a speaker jammer from a tokio runtime
+//!
+//! cpal streams cannot move between threads, so the jammer lives on its own
+//! OS thread for its whole life. A [`JammerHandle`] sends that thread
+//! commands and awaits the replies, so no task ever blocks on audio.
+
+use crate::error::{CamouflageError, Result};
+use crate::jammer::SpeakerJammer;
+use crate::signal::SignalConfig;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{oneshot, watch};
+
+/// How often the jammer thread runs the watchdog while idle
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What the jammer thread is doing
+#[derive(Debug, Clone, PartialEq)]
+pub enum JammerStatus {
+    /// Building the jammer and opening its stream
+    Starting,
+    /// Playing; holds the most recently applied configuration
+    Running(SignalConfig),
+    /// Stopped by [`JammerHandle::stop`] or by dropping the handle
+    Stopped,
+    /// The jammer could not be built or started
+    Failed(String),
+}
+
+enum Request {
+    SetConfig(SignalConfig, oneshot::Sender<Result<()>>),
+    Stop(oneshot::Sender<()>),
+}
+
+/// Async handle to a jammer running on its own thread
+///
+/// Dropping the handle stops the jammer without waiting for it.
+pub struct JammerHandle {
+    requests: mpsc::Sender<Request>,
+    status: watch::Receiver<JammerStatus>,
+}
+
+impl JammerHandle {
+    /// Build a jammer with `build` on a new thread and start it there
+    ///
+    /// Returns at once, before the stream is open; [`status`](Self::status)
+    /// reports whether it started. `build` is where to enable the watchdog
+    /// or pick output channels.
+    pub fn spawn<F>(build: F) -> Result<Self>
+    where
+        F: FnOnce() -> Result<SpeakerJammer> + Send + 'static,
+    {
+        let (requests, incoming) = mpsc::channel();
+        let (status_tx, status) = watch::channel(JammerStatus::Starting);
+        thread::Builder::new()
+            .name("camouflage-jammer".into())
+            .spawn(move || run_jammer(build, incoming, status_tx))
+            .map_err(CamouflageError::JammerThread)?;
+
+        Ok(Self { requests, status })
+    }
+
+    /// Crossfade to a new configuration; see [`SpeakerJammer::update_config`]
+    ///
+    /// Sent while the jammer is starting, the change is applied once it runs.
+    pub async fn set_config(&self, signal_config: SignalConfig) -> Result<()> {
+        let (reply, response) = oneshot::channel();
+        self.requests
+            .send(Request::SetConfig(signal_config, reply))
+            .map_err(|_| CamouflageError::JammerExited)?;
+        response.await.map_err(|_| CamouflageError::JammerExited)?
+    }
+
+    /// Stop the jammer and wait until its stream is closed
+    ///
+    /// Does nothing if the jammer has already stopped or failed.
+    pub async fn stop(&self) {
+        let (reply, response) = oneshot::channel();
+        if self.requests.send(Request::Stop(reply)).is_ok() {
+            let _ = response.await;
+        }
+    }
+
+    /// Receiver that sees every status change
+    pub fn status(&self) -> watch::Receiver<JammerStatus> {
+        self.status.clone()
+    }
+}
+
+impl SpeakerJammer {
+    /// Start a jammer on the default device, controlled through the handle
+    ///
+    /// See [`JammerHandle::spawn`].
+    pub fn spawn(signal_config: SignalConfig) -> Result<JammerHandle> {
+        JammerHandle::spawn(move || SpeakerJammer::new(signal_config))
+    }
+}
+
+/// Body of the jammer thread: start, then serve requests until told to stop
+fn run_jammer<F>(build: F, requests: mpsc::Receiver<Request>, status: watch::Sender<JammerStatus>)
+where
+    F: FnOnce() -> Result<SpeakerJammer>,
+{
+    let started = build().and_then(|mut jammer| jammer.start().map(|()| jammer));
+    let mut jammer = match started {
+        Ok(jammer) => jammer,
+        Err(e) => {
+            status.send_replace(JammerStatus::Failed(format!(
+                "{:#}",
+                anyhow::Error::from(e)
+            )));
+            return;
+        }
+    };
+    status.send_replace(JammerStatus::Running(jammer.signal_config()));
+
+    loop {
+        match requests.recv_timeout(POLL_INTERVAL) {
+            Ok(Request::SetConfig(signal_config, reply)) => {
+                let result = jammer.update_config(signal_config);
+                if result.is_ok() {
+                    status.send_replace(JammerStatus::Running(jammer.signal_config()));
+                }
+                let _ = reply.send(result);
+            }
+            Ok(Request::Stop(reply)) => {
+                jammer.stop();
+                status.send_replace(JammerStatus::Stopped);
+                let _ = reply.send(());
+                return;
+            }
+            Err(RecvTimeoutError::Timeout) => jammer.poll_watchdog(),
+            Err(RecvTimeoutError::Disconnected) => {
+                jammer.stop();
+                status.send_replace(JammerStatus::Stopped);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_handle_controls_jammer() {
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let handle = JammerHandle::spawn({
+            let config = config.clone();
+            move || SpeakerJammer::null(config)
+        })
+        .unwrap();
+        let mut status = handle.status();
+        status
+            .wait_for(|s| matches!(s, JammerStatus::Running(_)))
+            .await
+            .unwrap();
+
+        handle
+            .set_config(SignalConfig {
+                amplitude: 0.2,
+                ..config.clone()
+            })
+            .await
+            .unwrap();
+        assert!(
+            matches!(&*status.borrow(), JammerStatus::Running(c) if c.amplitude == 0.2),
+            "{:?}",
+            *status.borrow()
+        );
+
+        let err = handle
+            .set_config(SignalConfig {
+                num_tones: 0,
+                ..config.clone()
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CamouflageError::Retune(_)), "{:?}", err);
+
+        handle.stop().await;
+        assert_eq!(*status.borrow(), JammerStatus::Stopped);
+        assert!(matches!(
+            handle.set_config(config).await,
+            Err(CamouflageError::JammerExited)
+        ));
+        handle.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_start_is_reported() {
+        let handle = JammerHandle::spawn(|| {
+            SpeakerJammer::null(SignalConfig {
+                num_tones: 0,
+                ..Default::default()
+            })
+        })
+        .unwrap();
+        let mut status = handle.status();
+        status
+            .wait_for(|s| !matches!(s, JammerStatus::Starting))
+            .await
+            .unwrap();
+
+        assert!(
+            matches!(&*status.borrow(), JammerStatus::Failed(e) if e.contains("at least 1")),
+            "{:?}",
+            *status.borrow()
+        );
+        assert!(handle.set_config(SignalConfig::default()).await.is_err());
+    }
+}
@@ -1,83 +1,1414 @@
 //! Jammer implementations for different modes
 
-use crate::signal::{SignalConfig, SignalGenerator};
-use anyhow::{Context, Result};
+use crate::analysis::{analyze_samples, read_mono_wav, AUDIBLE_LIMIT_HZ};
+use crate::error::{CamouflageError, Result};
+use crate::mix::MixCurve;
+use crate::pilot::{PILOT_DEVIATION_HZ, PILOT_FREQUENCY_HZ};
+#[cfg(windows)]
+use crate::platform::wasapi::{ExclusiveBuffer, ExclusiveFormat, ExclusiveStream};
+use crate::platform::{self, priority, SystemAudio, VirtualDevice};
+use crate::signal::{SignalConfig, SignalGenerator, WaveformType};
+use crate::source::{SignalController, SignalSource};
+use anyhow::Context;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Stream, StreamConfig};
+use cpal::{
+    BuildStreamError, Device, FromSample, Sample, SampleFormat, SampleRate, SizedSample, Stream,
+    StreamConfig, SupportedStreamConfig, SupportedStreamConfigRange,
+};
+use crossbeam_queue::ArrayQueue;
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
-use tracing::{debug, info};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// How often a waiting jammer checks its stop flag
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Frames per simulated callback when rendering without a device
+const RENDER_BLOCK_FRAMES: usize = 512;
+
+/// How long `start` waits for the callback to report its priority
+const PROMOTION_WAIT: Duration = Duration::from_millis(500);
+
+/// Stream format of the null output used when there is no device
+const NULL_SAMPLE_RATE: u32 = 48000;
+const NULL_CHANNELS: u16 = 2;
+
+/// Sample formats an output stream can be built with, preferred first
+const STREAM_FORMATS: [SampleFormat; 4] = [
+    SampleFormat::F32,
+    SampleFormat::I32,
+    SampleFormat::I16,
+    SampleFormat::U16,
+];
+
+/// Common device sample rates, ascending
+const STANDARD_SAMPLE_RATES: [u32; 6] = [44100, 48000, 88200, 96000, 176400, 192000];
+
+/// Output RMS below which the watchdog counts a buffer as silent (-100 dBFS)
+const WATCHDOG_SILENCE_RMS: f32 = 1e-5;
+
+/// Output channels that carry the signal; the others are written silent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChannelMask(u64);
+
+impl ChannelMask {
+    const ALL: Self = Self(u64::MAX);
+
+    /// Mask of the zero-based `channels`, or every channel if empty
+    ///
+    /// Channels above 63 cannot be selected and are ignored.
+    fn from_channels(channels: &[usize]) -> Self {
+        if channels.is_empty() {
+            return Self::ALL;
+        }
+        Self(
+            channels
+                .iter()
+                .filter(|&&channel| channel < 64)
+                .fold(0, |mask, &channel| mask | 1 << channel),
+        )
+    }
+
+    fn contains(self, channel: usize) -> bool {
+        self == Self::ALL || (channel < 64 && self.0 & 1 << channel != 0)
+    }
+}
+
+/// Produces the output one interleaved frame at a time
+trait FrameSource {
+    /// Fill `frame`, the first samples of a `channels`-channel frame
+    fn write_frame(&mut self, frame: &mut [f32], channels: usize);
+}
+
+impl FrameSource for SignalGenerator {
+    fn write_frame(&mut self, frame: &mut [f32], channels: usize) {
+        self.generate_interleaved(frame, channels);
+    }
+}
+
+/// Mono samples played on repeat, the same on every channel
+struct SampleLoop {
+    samples: Arc<[f32]>,
+    position: usize,
+}
+
+impl SampleLoop {
+    /// Start at the beginning of `samples`, which must not be empty
+    fn new(samples: Arc<[f32]>) -> Self {
+        Self {
+            samples,
+            position: 0,
+        }
+    }
+}
+
+impl FrameSource for SampleLoop {
+    fn write_frame(&mut self, frame: &mut [f32], _channels: usize) {
+        frame.fill(self.samples[self.position]);
+        self.position = (self.position + 1) % self.samples.len();
+    }
+}
+
+/// Load a WAV file to loop, as its sample rate and mono samples
+///
+/// Warns if more than a trace of its energy is in the audible band.
+fn load_loop(path: &Path) -> anyhow::Result<(u32, Arc<[f32]>)> {
+    let (sample_rate, samples) =
+        read_mono_wav(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if samples.is_empty() {
+        anyhow::bail!("{} has no samples", path.display());
+    }
+
+    match analyze_samples(&samples, sample_rate) {
+        Ok(report) if !report.is_ultrasonic() => warn!(
+            "{} has {:.2}% of its energy below {:.0} kHz and may be audible",
+            path.display(),
+            report.audible_energy_fraction * 100.0,
+            AUDIBLE_LIMIT_HZ / 1000.0
+        ),
+        Ok(_) => {}
+        Err(e) => warn!("Could not check {} is ultrasonic: {}", path.display(), e),
+    }
+    Ok((sample_rate, samples.into()))
+}
+
+/// Fill an interleaved buffer from `source`, one frame at a time
+///
+/// `frame` is scratch space with one sample per channel. Channels outside
+/// `mask` are silent. Returns the RMS level of what was written.
+fn fill_interleaved<S, T>(
+    source: &mut S,
+    data: &mut [T],
+    frame: &mut [f32],
+    mask: ChannelMask,
+) -> f32
+where
+    S: FrameSource,
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = frame.len();
+    let mut sum_squares = 0.0f64;
+    for out in data.chunks_mut(channels) {
+        let frame = &mut frame[..out.len()];
+        source.write_frame(frame, channels);
+        for (channel, (out, &sample)) in out.iter_mut().zip(frame.iter()).enumerate() {
+            let sample = if mask.contains(channel) { sample } else { 0.0 };
+            *out = T::from_sample(sample);
+            sum_squares += (sample as f64).powi(2);
+        }
+    }
+
+    if data.is_empty() {
+        0.0
+    } else {
+        (sum_squares / data.len() as f64).sqrt() as f32
+    }
+}
+
+/// Render `frames` frames in device-sized blocks, converting through `T`
+fn render_frames<T>(generator: &mut SignalGenerator, frames: usize, channels: usize) -> Vec<f32>
+where
+    T: SizedSample + FromSample<f32>,
+    f32: FromSample<T>,
+{
+    let mut output = Vec::with_capacity(frames * channels);
+    let mut block = vec![T::EQUILIBRIUM; RENDER_BLOCK_FRAMES * channels];
+    let mut frame = vec![0.0; channels];
+    let mut remaining = frames;
+
+    while remaining > 0 {
+        let block_frames = remaining.min(RENDER_BLOCK_FRAMES);
+        let block = &mut block[..block_frames * channels];
+        fill_interleaved(generator, block, &mut frame, ChannelMask::ALL);
+        output.extend(block.iter().map(|&s| f32::from_sample(s)));
+        remaining -= block_frames;
+    }
+
+    output
+}
+
+/// Block until `duration` has elapsed (if given) or `stop` is set
+pub fn wait_until_stopped(duration: Option<Duration>, stop: &AtomicBool) {
+    wait_polling(duration, stop, || {});
+}
+
+/// [`wait_until_stopped`], calling `poll` between checks of the stop flag
+fn wait_polling(duration: Option<Duration>, stop: &AtomicBool, mut poll: impl FnMut()) {
+    let deadline = duration.map(|d| Instant::now() + d);
+
+    while !stop.load(Ordering::SeqCst) {
+        poll();
+        let remaining = match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => remaining,
+                _ => break,
+            },
+            None => STOP_POLL_INTERVAL,
+        };
+        std::thread::sleep(remaining.min(STOP_POLL_INTERVAL));
+    }
+}
+
+/// Lowest and highest frequency `config` puts out in Hz, across every hop
+/// carrier, the spread dither's swing and the pilot
+fn emitted_band(config: &SignalConfig) -> (f32, f32) {
+    let (lowest, highest) = config.occupied_band();
+    match config.pilot_id {
+        Some(_) => (
+            lowest.min(PILOT_FREQUENCY_HZ - PILOT_DEVIATION_HZ),
+            highest.max(PILOT_FREQUENCY_HZ + PILOT_DEVIATION_HZ),
+        ),
+        None => (lowest, highest),
+    }
+}
+
+/// Log what `config` emits at the negotiated sample rate
+fn log_emitted_frequencies(config: &SignalConfig) {
+    let sample_rate = config.sample_rate;
+    let nyquist = sample_rate as f32 / 2.0;
+    let (lowest, highest) = emitted_band(config);
+    if config.waveform != WaveformType::BandNoise {
+        let tones: Vec<String> = config
+            .tone_frequencies()
+            .iter()
+            .map(|f| format!("{:.0}", f))
+            .collect();
+        info!("Emitting tones at {} Hz", tones.join(", "));
+    }
+    info!(
+        "Emitted band {:.0}-{:.0} Hz at {} Hz (Nyquist limit {:.0} Hz)",
+        lowest, highest, sample_rate, nyquist
+    );
+}
+
+/// Explain where `config` would alias at its sample rate, if anything it
+/// emits reaches the Nyquist limit
+///
+/// The device may run at a lower rate than the signal needs when it
+/// supports nothing faster, so a config that was fine as requested is
+/// rejected at the rate the device gave it.
+fn alias_warning(config: &SignalConfig) -> Option<String> {
+    let sample_rate = config.sample_rate;
+    let nyquist = sample_rate as f32 / 2.0;
+    let (_, highest) = emitted_band(config);
+    if highest < nyquist {
+        return None;
+    }
+    let alias = sample_rate as f32 - highest;
+    Some(format!(
+        "Signal reaches {:.0} Hz, past the {:.0} Hz Nyquist limit at the device's \
+         {} Hz sample rate; it would alias down to {:.0} Hz{}",
+        highest,
+        nyquist,
+        sample_rate,
+        alias,
+        if alias < AUDIBLE_LIMIT_HZ {
+            ", which is audible"
+        } else {
+            ""
+        }
+    ))
+}
+
+/// Pick a stream config that can carry a signal needing `min_sample_rate`
+///
+/// The device default is kept when it fits. Otherwise the format earliest
+/// in [`STREAM_FORMATS`] wins, then the default's channel count, then the
+/// rate closest to the default's, or to the lowest standard rate that is
+/// high enough if the default's is not.
+fn choose_output_config(
+    default: Option<&SupportedStreamConfig>,
+    supported: &[SupportedStreamConfigRange],
+    min_sample_rate: u32,
+) -> Option<SupportedStreamConfig> {
+    if let Some(default) = default {
+        if STREAM_FORMATS.contains(&default.sample_format())
+            && default.sample_rate().0 >= min_sample_rate
+        {
+            return Some(default.clone());
+        }
+    }
+
+    let default_rate = default.map_or(NULL_SAMPLE_RATE, |d| d.sample_rate().0);
+    let target = if default_rate >= min_sample_rate {
+        default_rate
+    } else {
+        STANDARD_SAMPLE_RATES
+            .into_iter()
+            .find(|&rate| rate >= min_sample_rate)
+            .unwrap_or(min_sample_rate)
+    };
+    let channels = default.map_or(NULL_CHANNELS, |d| d.channels());
+    supported
+        .iter()
+        .filter(|range| range.max_sample_rate().0 >= min_sample_rate)
+        .filter_map(|range| {
+            let format = STREAM_FORMATS
+                .iter()
+                .position(|&f| f == range.sample_format())?;
+            let rate = target.clamp(range.min_sample_rate().0, range.max_sample_rate().0);
+            let config = range.with_sample_rate(SampleRate(rate));
+            Some((
+                (format, range.channels() != channels, rate.abs_diff(target)),
+                config,
+            ))
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, config)| config)
+}
+
+/// Pick a stream config at exactly `sample_rate`, for playing a loop as is
+///
+/// The device default is kept when it fits, otherwise ranked as in
+/// [`choose_output_config`].
+fn choose_loop_config(
+    default: Option<&SupportedStreamConfig>,
+    supported: &[SupportedStreamConfigRange],
+    sample_rate: u32,
+) -> Option<SupportedStreamConfig> {
+    if let Some(default) = default {
+        if STREAM_FORMATS.contains(&default.sample_format())
+            && default.sample_rate().0 == sample_rate
+        {
+            return Some(default.clone());
+        }
+    }
+
+    let channels = default.map_or(NULL_CHANNELS, |d| d.channels());
+    supported
+        .iter()
+        .filter(|range| {
+            (range.min_sample_rate().0..=range.max_sample_rate().0).contains(&sample_rate)
+        })
+        .filter_map(|range| {
+            let format = STREAM_FORMATS
+                .iter()
+                .position(|&f| f == range.sample_format())?;
+            Some((
+                (format, range.channels() != channels),
+                range.with_sample_rate(SampleRate(sample_rate)),
+            ))
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, config)| config)
+}
+
+/// One line per supported config range, for error messages
+fn describe_configs(supported: &[SupportedStreamConfigRange]) -> String {
+    if supported.is_empty() {
+        return "none reported".to_string();
+    }
+    supported
+        .iter()
+        .map(|range| {
+            format!(
+                "{}ch {} {}-{} Hz",
+                range.channels(),
+                range.sample_format(),
+                range.min_sample_rate().0,
+                range.max_sample_rate().0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Output stream parameters negotiated with the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+}
+
+impl StreamInfo {
+    /// Highest frequency the stream can carry
+    pub fn nyquist(&self) -> f32 {
+        self.sample_rate as f32 / 2.0
+    }
+}
+
+impl fmt::Display for StreamInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} kHz, {}ch, {}",
+            self.sample_rate as f32 / 1000.0,
+            self.channels,
+            self.sample_format
+        )
+    }
+}
+
+/// Generator CPU use, accumulated since the jammer was created
+///
+/// Each stream callback is timed against its budget: how long its buffer
+/// takes to play. A load near 1 risks underruns.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CpuStats {
+    /// Stream callbacks rendered
+    pub callbacks: u64,
+    /// Time spent rendering
+    pub busy: Duration,
+    /// Playback time of the rendered buffers
+    pub budget: Duration,
+    /// Highest fraction of its budget a single callback used
+    pub peak_load: f32,
+    /// Callbacks that took longer than their buffer lasts
+    pub overruns: u64,
+}
+
+impl CpuStats {
+    /// Fraction of the budget spent rendering
+    pub fn load(&self) -> f32 {
+        if self.budget.is_zero() {
+            0.0
+        } else {
+            self.busy.as_secs_f32() / self.budget.as_secs_f32()
+        }
+    }
+
+    /// What was accumulated after `earlier`; the peak is kept as is
+    pub fn since(&self, earlier: &CpuStats) -> CpuStats {
+        CpuStats {
+            callbacks: self.callbacks.saturating_sub(earlier.callbacks),
+            busy: self.busy.saturating_sub(earlier.busy),
+            budget: self.budget.saturating_sub(earlier.budget),
+            peak_load: self.peak_load,
+            overruns: self.overruns.saturating_sub(earlier.overruns),
+        }
+    }
+}
+
+impl fmt::Display for CpuStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}% of callback budget (peak {:.1}%, {} overruns)",
+            self.load() * 100.0,
+            self.peak_load * 100.0,
+            self.overruns
+        )
+    }
+}
+
+/// Running totals behind [`CpuStats`], updated by the stream callback
+struct CallbackLoad {
+    sample_rate: u32,
+    callbacks: AtomicU64,
+    busy_ns: AtomicU64,
+    budget_ns: AtomicU64,
+    /// Highest single-callback load, in millionths of its budget
+    peak_ppm: AtomicU64,
+    overruns: AtomicU64,
+}
+
+impl CallbackLoad {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            callbacks: AtomicU64::new(0),
+            busy_ns: AtomicU64::new(0),
+            budget_ns: AtomicU64::new(0),
+            peak_ppm: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a callback that took `busy` to render `frames` frames
+    fn record(&self, frames: usize, busy: Duration) {
+        let budget_ns = frames as u64 * 1_000_000_000 / self.sample_rate as u64;
+        let busy_ns = busy.as_nanos() as u64;
+        self.callbacks.fetch_add(1, Ordering::Relaxed);
+        self.busy_ns.fetch_add(busy_ns, Ordering::Relaxed);
+        self.budget_ns.fetch_add(budget_ns, Ordering::Relaxed);
+        if let Some(ppm) = busy_ns.saturating_mul(1_000_000).checked_div(budget_ns) {
+            self.peak_ppm.fetch_max(ppm, Ordering::Relaxed);
+        }
+        if busy_ns > budget_ns {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn stats(&self) -> CpuStats {
+        CpuStats {
+            callbacks: self.callbacks.load(Ordering::Relaxed),
+            busy: Duration::from_nanos(self.busy_ns.load(Ordering::Relaxed)),
+            budget: Duration::from_nanos(self.budget_ns.load(Ordering::Relaxed)),
+            peak_load: self.peak_ppm.load(Ordering::Relaxed) as f32 / 1e6,
+            overruns: self.overruns.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// When the stream callback last wrote an audible buffer, and how long
+/// its buffers take to render
+///
+/// A callback that stops running leaves the time unchanged, so a stalled
+/// stream looks the same as one writing silence.
+struct OutputMeter {
+    epoch: Instant,
+    last_audible_ms: AtomicU64,
+    load: CallbackLoad,
+}
+
+impl OutputMeter {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_audible_ms: AtomicU64::new(0),
+            load: CallbackLoad::new(sample_rate),
+        }
+    }
+
+    /// Record the RMS level of a buffer written by the callback
+    fn record(&self, rms: f32) {
+        if rms >= WATCHDOG_SILENCE_RMS {
+            self.mark_audible();
+        }
+    }
+
+    fn mark_audible(&self) {
+        let elapsed = self.epoch.elapsed().as_millis() as u64;
+        self.last_audible_ms.store(elapsed, Ordering::Relaxed);
+    }
+
+    /// Time from the last audible buffer until `now`
+    fn silent_for(&self, now: Instant) -> Duration {
+        let last_audible =
+            self.epoch + Duration::from_millis(self.last_audible_ms.load(Ordering::Relaxed));
+        now.saturating_duration_since(last_audible)
+    }
+}
+
+/// What an output stream plays
+// Only ever moved between the jammer and a callback, never copied in bulk
+#[allow(clippy::large_enum_variant)]
+enum Playback {
+    /// The generator's signal, retuned through the jammer's controller
+    Signal(SignalSource),
+    /// A loaded waveform on repeat
+    Loop(SampleLoop),
+}
+
+/// Where a jammer's output comes from, kept to replace lost playback
+enum Content {
+    Signal(SignalController),
+    Loop(Arc<[f32]>),
+}
+
+/// The playback while a stream callback owns it
+///
+/// Dropping the callback, and with it the stream, parks the playback
+/// again, so the next stream carries on from the same phase.
+struct CallbackSource {
+    playback: Option<Playback>,
+    home: Arc<ArrayQueue<Playback>>,
+}
+
+impl Drop for CallbackSource {
+    fn drop(&mut self) {
+        if let Some(playback) = self.playback.take() {
+            // The slot is empty while a callback holds the playback
+            let _ = self.home.push(playback);
+        }
+    }
+}
+
+/// Stand-in for a device stream that renders in real time and discards
+///
+/// Keeps the signal, its saved state and the watchdog moving on machines
+/// with no audio output. Stops when dropped.
+struct NullStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NullStream {
+    fn spawn(
+        source: CallbackSource,
+        meter: Arc<OutputMeter>,
+        config: &StreamConfig,
+        mask: ChannelMask,
+    ) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let channels = config.channels.max(1) as usize;
+        let block = Duration::from_secs_f64(
+            RENDER_BLOCK_FRAMES as f64 / config.sample_rate.0.max(1) as f64,
+        );
+
+        let thread = thread::Builder::new()
+            .name("camouflage-null-output".into())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    let mut owned = source;
+                    let mut data = vec![0.0f32; RENDER_BLOCK_FRAMES * channels];
+                    let mut frame = vec![0.0; channels];
+                    let mut deadline = Instant::now();
+                    while !stop.load(Ordering::Relaxed) {
+                        if let Some(playback) = owned.playback.as_mut() {
+                            render_callback(playback, &mut data, &mut frame, mask, &meter);
+                        }
+                        deadline += block;
+                        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for NullStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // Joining parks the source before the jammer looks for it
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A playing output, on a device or the null output
+///
+/// Only ever held; dropping it stops the output.
+#[allow(dead_code)]
+enum OutputStream {
+    Device(Stream),
+    Null(NullStream),
+    #[cfg(windows)]
+    Exclusive(ExclusiveStream),
+}
+
+/// Body of the stream callback: render `data`, report its level and time it
+fn render_callback<T>(
+    playback: &mut Playback,
+    data: &mut [T],
+    frame: &mut [f32],
+    mask: ChannelMask,
+    meter: &OutputMeter,
+) where
+    T: SizedSample + FromSample<f32>,
+{
+    let started = Instant::now();
+    let rms = match playback {
+        Playback::Signal(source) => {
+            source.render(|generator| fill_interleaved(generator, data, frame, mask))
+        }
+        Playback::Loop(sample_loop) => fill_interleaved(sample_loop, data, frame, mask),
+    };
+    meter.record(rms);
+    meter
+        .load
+        .record(data.len() / frame.len().max(1), started.elapsed());
+}
+
+/// Raise the calling stream callback thread to realtime scheduling
+///
+/// The outcome goes to `outcome` for a normal thread to log; logging here
+/// could block the callback.
+fn promote_callback_thread(
+    frames: usize,
+    sample_rate: u32,
+    outcome: &ArrayQueue<std::io::Result<()>>,
+) {
+    let period = Duration::from_secs_f64(frames as f64 / sample_rate.max(1) as f64);
+    let _ = outcome.push(priority::promote_current_thread(period));
+}
+
+/// Logs the generator's CPU use at an interval until dropped
+struct Profiler {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Profiler {
+    fn spawn(meter: Arc<OutputMeter>, interval: Duration) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name("camouflage-profiler".into())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    let mut last = meter.load.stats();
+                    let mut next = Instant::now() + interval;
+                    while !stop.load(Ordering::Relaxed) {
+                        thread::sleep(STOP_POLL_INTERVAL);
+                        if Instant::now() < next {
+                            continue;
+                        }
+                        next += interval;
+                        let stats = meter.load.stats();
+                        let window = stats.since(&last);
+                        if window.callbacks > 0 {
+                            info!("Generator using {}", window);
+                        }
+                        last = stats;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
 
 /// Speaker jammer - outputs ultrasonic signal through speakers
 ///
 /// This mode outputs ultrasonic signals directly to speakers, which then
 /// interfere with nearby microphones through air conduction.
 pub struct SpeakerJammer {
-    generator: Arc<Mutex<SignalGenerator>>,
-    stream: Option<Stream>,
-    device: Device,
+    /// Holds the playback while no stream is running
+    parked: Arc<ArrayQueue<Playback>>,
+    content: Content,
+    stream: Option<OutputStream>,
+    /// `None` for the null output
+    device: Option<Device>,
     config: StreamConfig,
+    sample_format: SampleFormat,
+    /// Channels the signal is written to
+    channel_mask: ChannelMask,
+    meter: Arc<OutputMeter>,
+    watchdog: Option<Duration>,
+    profiler: Option<Profiler>,
+    amplitude_ramp: Option<Duration>,
+    /// Raise each device stream's callback thread to realtime scheduling
+    realtime_priority: bool,
+    /// How the last promotion went, until [`Self::report_promotion`] logs it
+    promotion: Arc<ArrayQueue<std::io::Result<()>>>,
+    /// Open the device in WASAPI exclusive mode with this format
+    #[cfg(windows)]
+    exclusive: Option<ExclusiveFormat>,
 }
 
 impl SpeakerJammer {
     /// Create a new speaker jammer
-    pub fn new(mut signal_config: SignalConfig) -> Result<Self> {
+    ///
+    /// Uses the device's default config if a stream can be built with it
+    /// at a rate high enough for the signal, and otherwise the best match
+    /// among the configs the device supports. The configuration is
+    /// validated against the chosen sample rate.
+    pub fn new(signal_config: SignalConfig) -> Result<Self> {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
-            .context("No output device available")?;
+            .ok_or(CamouflageError::NoOutputDevice)?;
+
+        info!("Using audio device: {}", device.name()?);
+
+        let default = device.default_output_config();
+        match &default {
+            Ok(config) => info!("Default output config: {:?}", config),
+            Err(err) => warn!("No default output config: {}", err),
+        }
+        // Some backends cannot enumerate configs; only the default is tried
+        let supported: Vec<SupportedStreamConfigRange> = match device.supported_output_configs() {
+            Ok(configs) => configs.collect(),
+            Err(err) => {
+                warn!("Could not query supported output configs: {}", err);
+                Vec::new()
+            }
+        };
+
+        let needed = signal_config.required_sample_rate();
+        let config = match choose_output_config(default.as_ref().ok(), &supported, needed) {
+            Some(config) if default.as_ref().ok() == Some(&config) => config,
+            Some(config) => {
+                info!("Using output config: {:?}", config);
+                config
+            }
+            // Nothing to choose from; validation reports a rate problem
+            None if supported.is_empty() => default?,
+            None => {
+                return Err(CamouflageError::NoUsableOutputConfig {
+                    needed,
+                    supported: describe_configs(&supported),
+                })
+            }
+        };
+
+        Self::with_output(
+            signal_config,
+            Some(device),
+            config.sample_format(),
+            config.into(),
+        )
+    }
 
+    /// Create a speaker jammer that opens the default output in WASAPI
+    /// exclusive mode
+    ///
+    /// Shared mode plays at the Windows mixer's rate, usually 48 kHz.
+    /// Exclusive mode bypasses the mixer for a rate the hardware supports,
+    /// 96 kHz where it can, giving the signal room above the audible band.
+    /// If the device refuses, for example because another application holds
+    /// it or exclusive use is turned off in its properties, a warning is
+    /// logged and this falls back to [`new`](Self::new), as it always does
+    /// on other platforms.
+    pub fn new_exclusive(signal_config: SignalConfig) -> Result<Self> {
+        #[cfg(windows)]
+        match platform::wasapi::probe(signal_config.required_sample_rate()) {
+            Ok(format) => {
+                let device = cpal::default_host()
+                    .default_output_device()
+                    .ok_or(CamouflageError::NoOutputDevice)?;
+                info!("Using audio device in exclusive mode: {}", device.name()?);
+                info!("Exclusive output format: {:?}", format);
+                let config = StreamConfig {
+                    channels: format.channels,
+                    sample_rate: cpal::SampleRate(format.sample_rate),
+                    buffer_size: cpal::BufferSize::Default,
+                };
+                let mut jammer =
+                    Self::with_output(signal_config, Some(device), format.sample_format, config)?;
+                jammer.exclusive = Some(format);
+                return Ok(jammer);
+            }
+            Err(e) => warn!(
+                "Exclusive mode unavailable, falling back to shared mode: {}",
+                e
+            ),
+        }
+        #[cfg(not(windows))]
+        warn!("Exclusive mode is only available on Windows, using shared mode");
+        Self::new(signal_config)
+    }
+
+    /// Create a speaker jammer that loops a WAV file instead of generating
+    ///
+    /// For waveforms prepared elsewhere. The file is loaded into memory,
+    /// averaged to mono and played on every channel at its own sample
+    /// rate, which the device must support; nothing is resampled. A
+    /// warning is logged if the file has audible energy. The jammer cannot
+    /// be retuned and has no generator state to save.
+    pub fn from_wav_loop(path: &Path) -> Result<Self> {
+        let (sample_rate, samples) =
+            load_loop(path).map_err(|e| CamouflageError::LoopFile(e.into()))?;
+
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or(CamouflageError::NoOutputDevice)?;
         info!("Using audio device: {}", device.name()?);
 
-        let config = device.default_output_config()?;
-        info!("Default output config: {:?}", config);
+        let default = device.default_output_config().ok();
+        let supported: Vec<SupportedStreamConfigRange> = match device.supported_output_configs() {
+            Ok(configs) => configs.collect(),
+            Err(err) => {
+                warn!("Could not query supported output configs: {}", err);
+                Vec::new()
+            }
+        };
+        let config =
+            choose_loop_config(default.as_ref(), &supported, sample_rate).ok_or_else(|| {
+                CamouflageError::UnsupportedLoopRate {
+                    sample_rate,
+                    supported: describe_configs(&supported),
+                }
+            })?;
+        info!(
+            "Looping {} ({:.1} s) with output config: {:?}",
+            path.display(),
+            samples.len() as f32 / sample_rate as f32,
+            config
+        );
+
+        let playback = Playback::Loop(SampleLoop::new(Arc::clone(&samples)));
+        Ok(Self::with_content(
+            Content::Loop(samples),
+            playback,
+            Some(device),
+            config.sample_format(),
+            config.into(),
+        ))
+    }
+
+    /// Create a speaker jammer, or one on the null output if there is no device
+    ///
+    /// The null output renders the signal in real time and discards it, so
+    /// a daemon on a headless machine keeps running, saving state and
+    /// accepting changes. Missing or unusable defaults count as no device;
+    /// other errors are returned as from [`new`](Self::new).
+    pub fn new_or_null(signal_config: SignalConfig) -> Result<Self> {
+        match Self::new(signal_config.clone()) {
+            Err(CamouflageError::NoOutputDevice | CamouflageError::DefaultStreamConfig(_)) => {
+                warn!("No usable output device, rendering to the null output");
+                Self::null(signal_config)
+            }
+            result => result,
+        }
+    }
+
+    /// Create a jammer on the null output
+    pub fn null(signal_config: SignalConfig) -> Result<Self> {
+        let config = StreamConfig {
+            channels: NULL_CHANNELS,
+            sample_rate: cpal::SampleRate(NULL_SAMPLE_RATE),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        Self::with_output(signal_config, None, SampleFormat::F32, config)
+    }
 
+    fn with_output(
+        mut signal_config: SignalConfig,
+        device: Option<Device>,
+        sample_format: SampleFormat,
+        config: StreamConfig,
+    ) -> Result<Self> {
         // Update signal config with actual sample rate
-        signal_config.sample_rate = config.sample_rate().0;
+        signal_config.sample_rate = config.sample_rate.0;
+        if let Some(warning) = alias_warning(&signal_config) {
+            warn!("⚠️  {}", warning);
+        }
+        signal_config.validate()?;
 
-        let generator = Arc::new(Mutex::new(SignalGenerator::new(signal_config)));
+        let (source, controller) =
+            SignalSource::with_channels(signal_config, config.channels as usize)?;
+        Ok(Self::with_content(
+            Content::Signal(controller),
+            Playback::Signal(source),
+            device,
+            sample_format,
+            config,
+        ))
+    }
 
-        Ok(Self {
-            generator,
+    /// A stopped jammer playing `content`, with `playback` parked
+    fn with_content(
+        content: Content,
+        playback: Playback,
+        device: Option<Device>,
+        sample_format: SampleFormat,
+        config: StreamConfig,
+    ) -> Self {
+        let parked = Arc::new(ArrayQueue::new(1));
+        let _ = parked.push(playback);
+        let meter = Arc::new(OutputMeter::new(config.sample_rate.0));
+
+        Self {
+            parked,
+            content,
             stream: None,
             device,
-            config: config.into(),
-        })
+            config,
+            sample_format,
+            channel_mask: ChannelMask::ALL,
+            meter,
+            watchdog: None,
+            profiler: None,
+            amplitude_ramp: None,
+            realtime_priority: false,
+            promotion: Arc::new(ArrayQueue::new(1)),
+            #[cfg(windows)]
+            exclusive: None,
+        }
+    }
+
+    /// Whether this jammer renders to the null output instead of a device
+    pub fn is_null(&self) -> bool {
+        self.device.is_none()
     }
 
     /// Start jamming
+    ///
+    /// Does nothing if the jammer is already running, so the existing
+    /// stream is never replaced and leaked.
     pub fn start(&mut self) -> Result<()> {
-        let generator = Arc::clone(&self.generator);
-        let channels = self.config.channels as usize;
+        if self.is_running() {
+            debug!("Speaker jammer already running");
+            return Ok(());
+        }
+
+        let mut playback = self.take_playback()?;
+        if let Playback::Signal(source) = &playback {
+            log_emitted_frequencies(source.config());
+        }
+        if let (Some(ramp), Playback::Signal(source)) = (self.amplitude_ramp, &mut playback) {
+            info!("Ramping amplitude up over {:.1} s", ramp.as_secs_f32());
+            source.generator_mut().ramp_amplitude(ramp);
+        }
+        self.open_stream(playback)?;
+
+        info!("Speaker jammer started");
+        if self.realtime_priority && !self.is_null() {
+            // The callback promotes itself on its first buffer
+            let deadline = Instant::now() + PROMOTION_WAIT;
+            while self.promotion.is_empty() && Instant::now() < deadline {
+                thread::sleep(STOP_POLL_INTERVAL);
+            }
+            self.report_promotion();
+        }
+        Ok(())
+    }
+
+    /// Log how raising the callback to realtime priority went, if a
+    /// stream has tried since the last report
+    fn report_promotion(&self) {
+        match self.promotion.pop() {
+            Some(Ok(())) => info!("Audio callback running at realtime priority"),
+            Some(Err(e)) => warn!(
+                "Could not raise the audio callback to realtime priority, running at normal priority: {}",
+                e
+            ),
+            None => {}
+        }
+    }
+
+    /// Fade the signal in over `duration` each time the jammer starts
+    pub fn set_amplitude_ramp(&mut self, duration: Duration) {
+        self.amplitude_ramp = Some(duration);
+    }
+
+    /// Run the audio callback at realtime priority, so other load on the
+    /// machine does not make it miss buffers
+    ///
+    /// The callback thread is promoted the first time it runs: `SCHED_FIFO`
+    /// on Linux, a time-constraint policy on macOS and MMCSS "Pro Audio" on
+    /// Windows. Linux needs `CAP_SYS_NICE` or an `rtprio` limit; without it
+    /// a warning is logged and the stream runs at normal priority. The null
+    /// output is not promoted.
+    pub fn with_realtime_priority(mut self) -> Self {
+        self.realtime_priority = true;
+        self
+    }
+
+    /// Play the signal only on these zero-based channels, leaving the rest
+    /// silent; an empty list restores every channel
+    ///
+    /// Useful when only some of a device's speakers can reproduce
+    /// ultrasound. Takes effect the next time the stream opens.
+    pub fn set_output_channels(&mut self, channels: &[usize]) -> Result<()> {
+        let available = self.config.channels;
+        if let Some(&channel) = channels.iter().find(|&&c| c >= available as usize) {
+            return Err(CamouflageError::InvalidChannel { channel, available });
+        }
+        self.channel_mask = ChannelMask::from_channels(channels);
+        Ok(())
+    }
 
-        let stream = self.device.build_output_stream(
+    /// Take the parked playback, for a new stream or a change while stopped
+    ///
+    /// Only fails if a previous stream never released it, in which case a
+    /// fresh source starts from the latest configuration, or the loop from
+    /// its beginning.
+    fn take_playback(&mut self) -> Result<Playback> {
+        if let Some(playback) = self.parked.pop() {
+            return Ok(playback);
+        }
+
+        warn!("Previous stream did not release its playback, starting a new one");
+        match &mut self.content {
+            Content::Signal(controller) => {
+                let (source, new_controller) = SignalSource::with_channels(
+                    controller.config(),
+                    self.config.channels as usize,
+                )?;
+                *controller = new_controller;
+                Ok(Playback::Signal(source))
+            }
+            Content::Loop(samples) => Ok(Playback::Loop(SampleLoop::new(Arc::clone(samples)))),
+        }
+    }
+
+    /// The controller retuning the generator, unless a loop is playing
+    fn controller(&self) -> Result<&SignalController> {
+        match &self.content {
+            Content::Signal(controller) => Ok(controller),
+            Content::Loop(_) => Err(CamouflageError::NotGenerating),
+        }
+    }
+
+    /// Build and play an output stream that owns `playback`
+    fn open_stream(&mut self, playback: Playback) -> Result<()> {
+        // A new stream gets a full timeout before the watchdog may fire
+        self.meter.mark_audible();
+
+        let Some(device) = &self.device else {
+            let owned = CallbackSource {
+                playback: Some(playback),
+                home: Arc::clone(&self.parked),
+            };
+            let stream = NullStream::spawn(
+                owned,
+                Arc::clone(&self.meter),
+                &self.config,
+                self.channel_mask,
+            )
+            .map_err(CamouflageError::NullOutput)?;
+            self.stream = Some(OutputStream::Null(stream));
+            return Ok(());
+        };
+
+        #[cfg(windows)]
+        if let Some(format) = self.exclusive {
+            let stream = self
+                .spawn_exclusive(format, playback)
+                .map_err(CamouflageError::ExclusiveOutput)?;
+            self.stream = Some(OutputStream::Exclusive(stream));
+            return Ok(());
+        }
+
+        let stream = match self.sample_format {
+            SampleFormat::F32 => self.build_stream::<f32>(device, playback)?,
+            SampleFormat::I16 => self.build_stream::<i16>(device, playback)?,
+            SampleFormat::U16 => self.build_stream::<u16>(device, playback)?,
+            SampleFormat::I32 => self.build_stream::<i32>(device, playback)?,
+            format => {
+                let _ = self.parked.push(playback);
+                return Err(CamouflageError::UnsupportedSampleFormat(format));
+            }
+        };
+
+        stream.play()?;
+        self.stream = Some(OutputStream::Device(stream));
+        Ok(())
+    }
+
+    /// Run the jammer pipeline without an output device
+    ///
+    /// Validates the configuration against `stream`, then generates
+    /// `duration` of interleaved samples exactly as the stream callback
+    /// would, including conversion to the stream's sample format.
+    pub fn render_to_vec(
+        mut signal_config: SignalConfig,
+        stream: StreamInfo,
+        duration: Duration,
+    ) -> Result<Vec<f32>> {
+        signal_config.sample_rate = stream.sample_rate;
+        signal_config.validate()?;
+
+        let channels = stream.channels.max(1) as usize;
+        let mut generator = SignalGenerator::with_channels(signal_config, channels);
+        let frames = (duration.as_secs_f64() * stream.sample_rate as f64).round() as usize;
+
+        Ok(match stream.sample_format {
+            SampleFormat::F32 => render_frames::<f32>(&mut generator, frames, channels),
+            SampleFormat::I16 => render_frames::<i16>(&mut generator, frames, channels),
+            SampleFormat::U16 => render_frames::<u16>(&mut generator, frames, channels),
+            SampleFormat::I32 => render_frames::<i32>(&mut generator, frames, channels),
+            format => return Err(CamouflageError::UnsupportedSampleFormat(format)),
+        })
+    }
+
+    /// Whether an output stream is open and playing
+    pub fn is_running(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Sample rate, channel count and sample format of the output stream
+    pub fn stream_info(&self) -> StreamInfo {
+        StreamInfo {
+            sample_rate: self.config.sample_rate.0,
+            channels: self.config.channels,
+            sample_format: self.sample_format,
+        }
+    }
+
+    /// Most recently requested signal configuration
+    ///
+    /// A jammer playing a loop reports the default configuration at the
+    /// loop's sample rate.
+    pub fn signal_config(&self) -> SignalConfig {
+        match &self.content {
+            Content::Signal(controller) => controller.config(),
+            Content::Loop(_) => SignalConfig {
+                sample_rate: self.config.sample_rate.0,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Crossfade to a new configuration, from any thread
+    ///
+    /// The sample rate is taken from the stream. The change is queued for
+    /// the audio thread, which picks it up without blocking.
+    pub fn update_config(&self, mut signal_config: SignalConfig) -> Result<()> {
+        signal_config.sample_rate = self.config.sample_rate.0;
+        self.controller()?.retune(signal_config)?;
+        Ok(())
+    }
+
+    /// Save the generator's phase and hop position to `path`
+    ///
+    /// While running, the snapshot comes from the audio thread.
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        let controller = self.controller()?;
+        if self.is_running() {
+            return Ok(controller.save_state(path)?);
+        }
+        match self.parked.pop() {
+            Some(playback) => {
+                let saved = match &playback {
+                    Playback::Signal(source) => source.generator().save_state(path),
+                    Playback::Loop(_) => unreachable!("a signal jammer parks a signal source"),
+                };
+                let _ = self.parked.push(playback);
+                Ok(saved?)
+            }
+            None => Ok(controller.save_state(path)?),
+        }
+    }
+
+    /// Resume the generator from `path`; see [`SignalGenerator::restore_state`]
+    ///
+    /// Only possible before the jammer starts; returns `false` while running.
+    pub fn restore_state(&mut self, path: &Path) -> Result<bool> {
+        self.controller()?;
+        if self.is_running() {
+            warn!("Cannot restore signal state while the jammer is running");
+            return Ok(false);
+        }
+        let mut playback = self.take_playback()?;
+        let restored = match &mut playback {
+            Playback::Signal(source) => source.generator_mut().restore_state(path),
+            Playback::Loop(_) => unreachable!("a signal jammer parks a signal source"),
+        };
+        let _ = self.parked.push(playback);
+        Ok(restored?)
+    }
+
+    /// Build an output stream writing the playback as samples of type `T`
+    ///
+    /// The callback owns `playback` and parks it again when the stream, or
+    /// a failed build, drops the callback.
+    fn build_stream<T>(
+        &self,
+        device: &Device,
+        playback: Playback,
+    ) -> std::result::Result<Stream, BuildStreamError>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        let mut owned = CallbackSource {
+            playback: Some(playback),
+            home: Arc::clone(&self.parked),
+        };
+        let meter = Arc::clone(&self.meter);
+        let mask = self.channel_mask;
+        let mut frame = vec![0.0; self.config.channels.max(1) as usize];
+        let mut promote = self.realtime_priority;
+        let promotion = Arc::clone(&self.promotion);
+        let sample_rate = self.config.sample_rate.0;
+
+        device.build_output_stream(
             &self.config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                let mut gen = generator.lock().unwrap();
-                for frame in data.chunks_mut(channels) {
-                    let sample = gen.next_sample();
-                    for channel in frame.iter_mut() {
-                        *channel = sample;
-                    }
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                if promote {
+                    promote = false;
+                    promote_callback_thread(data.len() / frame.len(), sample_rate, &promotion);
+                }
+                if let Some(playback) = owned.playback.as_mut() {
+                    render_callback(playback, data, &mut frame, mask, &meter);
                 }
             },
             |err| {
                 eprintln!("Audio stream error: {}", err);
             },
             None,
-        )?;
+        )
+    }
 
-        stream.play()?;
-        self.stream = Some(stream);
+    /// Start an exclusive-mode stream rendering the playback, like
+    /// [`build_stream`](Self::build_stream) does for a cpal stream
+    #[cfg(windows)]
+    fn spawn_exclusive(
+        &self,
+        format: ExclusiveFormat,
+        playback: Playback,
+    ) -> std::io::Result<ExclusiveStream> {
+        let mut owned = CallbackSource {
+            playback: Some(playback),
+            home: Arc::clone(&self.parked),
+        };
+        let meter = Arc::clone(&self.meter);
+        let mask = self.channel_mask;
+        let mut frame = vec![0.0; format.channels.max(1) as usize];
+        let mut promote = self.realtime_priority;
+        let promotion = Arc::clone(&self.promotion);
 
-        info!("Speaker jammer started");
+        ExclusiveStream::spawn(format, move |buffer| {
+            let Some(playback) = owned.playback.as_mut() else {
+                return;
+            };
+            let samples = match &buffer {
+                ExclusiveBuffer::F32(data) => data.len(),
+                ExclusiveBuffer::I32(data) => data.len(),
+                ExclusiveBuffer::I16(data) => data.len(),
+            };
+            if promote {
+                promote = false;
+                promote_callback_thread(samples / frame.len(), format.sample_rate, &promotion);
+            }
+            match buffer {
+                ExclusiveBuffer::F32(data) => {
+                    render_callback(playback, data, &mut frame, mask, &meter)
+                }
+                ExclusiveBuffer::I32(data) => {
+                    render_callback(playback, data, &mut frame, mask, &meter)
+                }
+                ExclusiveBuffer::I16(data) => {
+                    render_callback(playback, data, &mut frame, mask, &meter)
+                }
+            }
+        })
+    }
+
+    /// Jam for `duration`, or until `stop` is set, then stop
+    ///
+    /// Runs the watchdog while waiting, if enabled.
+    pub fn start_for(&mut self, duration: Duration, stop: &AtomicBool) -> Result<()> {
+        self.start()?;
+        wait_polling(Some(duration), stop, || self.poll_watchdog());
+        self.stop();
+        Ok(())
+    }
+
+    /// Rebuild the stream when its output stays silent for `timeout`
+    ///
+    /// Covers drivers that report the stream as playing while nothing
+    /// reaches the device. Checks happen in [`check_watchdog`](Self::check_watchdog),
+    /// which [`start_for`](Self::start_for) calls while it waits.
+    pub fn enable_watchdog(&mut self, timeout: Duration) {
+        self.watchdog = Some(timeout);
+    }
+
+    /// Generator CPU use against the stream callback's time budget
+    ///
+    /// Covers every stream the jammer has run, including the null output.
+    pub fn cpu_stats(&self) -> CpuStats {
+        self.meter.load.stats()
+    }
+
+    /// Log [`cpu_stats`](Self::cpu_stats) for each `interval` from a
+    /// background thread, e.g. "Generator using 4.0% of callback budget"
+    ///
+    /// Helps pick a tone count that weak hardware can render in time.
+    /// Replaces an earlier interval; logging stops with the jammer.
+    pub fn enable_profiling(&mut self, interval: Duration) -> Result<()> {
+        self.profiler = None;
+        let profiler = Profiler::spawn(Arc::clone(&self.meter), interval)
+            .map_err(CamouflageError::Profiler)?;
+        self.profiler = Some(profiler);
         Ok(())
     }
 
-    /// Stop jamming
+    /// Rebuild the stream if the watchdog finds it silent
+    ///
+    /// Returns whether the stream was rebuilt. After a failed rebuild the
+    /// next attempt waits for another full timeout.
+    pub fn check_watchdog(&mut self) -> Result<bool> {
+        // A rebuilt stream's callback reports here, off the audio thread
+        self.report_promotion();
+        let (Some(timeout), Some(_)) = (self.watchdog, &self.stream) else {
+            return Ok(false);
+        };
+        let silent = self.meter.silent_for(Instant::now());
+        if silent < timeout {
+            return Ok(false);
+        }
+
+        warn!(
+            "Output silent for {:.1} s, rebuilding the stream",
+            silent.as_secs_f32()
+        );
+        self.meter.mark_audible();
+        self.stream = None;
+        let playback = self.take_playback()?;
+        self.open_stream(playback)?;
+        Ok(true)
+    }
+
+    /// [`check_watchdog`](Self::check_watchdog), logging failures
+    pub(crate) fn poll_watchdog(&mut self) {
+        if let Err(e) = self.check_watchdog() {
+            warn!("Watchdog failed to rebuild the stream: {}", e);
+        }
+    }
+
+    /// Stop jamming; does nothing if already stopped
     pub fn stop(&mut self) {
         if let Some(stream) = self.stream.take() {
             drop(stream);
+            self.report_promotion();
             info!("Speaker jammer stopped");
         }
     }
@@ -97,10 +1428,17 @@ impl Drop for SpeakerJammer {
 /// 3. Mixing it with ultrasonic jamming signal
 /// 4. Routing the mixed signal to speakers
 ///
-/// Current implementation uses SpeakerJammer as a fallback.
+/// Current implementation uses SpeakerJammer as a fallback, with the
+/// virtual device and platform capture set up and torn down alongside it.
 pub struct SystemJammer {
     speaker_jammer: SpeakerJammer,
+    system_audio: Box<dyn SystemAudio>,
+    /// Removes the virtual device when dropped
+    device: Option<VirtualDevice>,
+    capturing: bool,
     mix_ratio: f32,
+    mix_curve: MixCurve,
+    normalize_input: bool,
 }
 
 impl SystemJammer {
@@ -116,22 +1454,179 @@ impl SystemJammer {
         // 4. Route to speakers
 
         let speaker_jammer = SpeakerJammer::new(signal_config)?;
+        Ok(Self::with_speaker(speaker_jammer, mix_ratio))
+    }
 
-        Ok(Self {
+    /// Create a system jammer, on the null output if there is no device
+    ///
+    /// See [`SpeakerJammer::new_or_null`]. Capture of system audio is set
+    /// up as usual.
+    pub fn new_or_null(signal_config: SignalConfig, mix_ratio: f32) -> Result<Self> {
+        debug!("Creating system jammer with mix ratio: {}", mix_ratio);
+        let speaker_jammer = SpeakerJammer::new_or_null(signal_config)?;
+        Ok(Self::with_speaker(speaker_jammer, mix_ratio))
+    }
+
+    fn with_speaker(speaker_jammer: SpeakerJammer, mix_ratio: f32) -> Self {
+        Self {
             speaker_jammer,
+            system_audio: platform::get_system_audio(),
+            device: None,
+            capturing: false,
             mix_ratio,
-        })
+            mix_curve: MixCurve::default(),
+            normalize_input: false,
+        }
     }
 
-    /// Start jamming
+    /// Start jamming; does nothing if already running
     pub fn start(&mut self) -> Result<()> {
-        info!("Starting system jammer (mix ratio: {})", self.mix_ratio);
+        if self.is_running() {
+            debug!("System jammer already running");
+            return Ok(());
+        }
+
+        let (original_gain, signal_gain) = self.mix_gains();
+        info!(
+            "Starting system jammer (mix ratio: {}, gains {:.3} original / {:.3} signal{})",
+            self.mix_ratio,
+            original_gain,
+            signal_gain,
+            if self.normalize_input {
+                ", input normalized"
+            } else {
+                ""
+            }
+        );
+        if !self.capturing {
+            match self.system_audio.open_virtual_device() {
+                Ok(device) => self.device = Some(device),
+                Err(e) => warn!(
+                    "No virtual audio device, jamming through speakers only: {:#}",
+                    e
+                ),
+            }
+            self.system_audio.start_capture()?;
+            self.capturing = true;
+        }
         self.speaker_jammer.start()
     }
 
-    /// Stop jamming
+    /// Whether the output stream is playing
+    pub fn is_running(&self) -> bool {
+        self.speaker_jammer.is_running()
+    }
+
+    /// Jam for `duration`, or until `stop` is set, then stop
+    pub fn start_for(&mut self, duration: Duration, stop: &AtomicBool) -> Result<()> {
+        self.start()?;
+        wait_polling(Some(duration), stop, || self.speaker_jammer.poll_watchdog());
+        self.stop();
+        Ok(())
+    }
+
+    /// Sample rate, channel count and sample format of the output stream
+    pub fn stream_info(&self) -> StreamInfo {
+        self.speaker_jammer.stream_info()
+    }
+
+    /// Choose how the mix ratio splits gain; linear unless set
+    pub fn set_mix_curve(&mut self, curve: MixCurve) {
+        self.mix_curve = curve;
+    }
+
+    /// Bring captured audio to a fixed loudness before mixing; off unless set
+    pub fn set_normalize_input(&mut self, normalize: bool) {
+        self.normalize_input = normalize;
+    }
+
+    /// Whether captured audio is normalized before mixing
+    pub fn normalize_input(&self) -> bool {
+        self.normalize_input
+    }
+
+    /// Gains for the captured audio and for the signal
+    pub fn mix_gains(&self) -> (f32, f32) {
+        self.mix_curve.gains(self.mix_ratio)
+    }
+
+    /// Names of the inputs system audio can be captured from
+    pub fn capture_sources(&self) -> Result<Vec<String>> {
+        Ok(self.system_audio.list_capture_sources()?)
+    }
+
+    /// Capture system audio from `name`, one of [`capture_sources`](Self::capture_sources)
+    pub fn set_capture_source(&mut self, name: &str) -> Result<()> {
+        Ok(self.system_audio.select_capture_source(name)?)
+    }
+
+    /// Source system audio is captured from, if the platform has one
+    pub fn capture_source(&self) -> Option<String> {
+        self.system_audio.capture_source()
+    }
+
+    /// See [`SpeakerJammer::set_amplitude_ramp`]
+    pub fn set_amplitude_ramp(&mut self, duration: Duration) {
+        self.speaker_jammer.set_amplitude_ramp(duration);
+    }
+
+    /// See [`SpeakerJammer::enable_watchdog`]
+    pub fn enable_watchdog(&mut self, timeout: Duration) {
+        self.speaker_jammer.enable_watchdog(timeout);
+    }
+
+    /// See [`SpeakerJammer::check_watchdog`]
+    pub fn check_watchdog(&mut self) -> Result<bool> {
+        self.speaker_jammer.check_watchdog()
+    }
+
+    /// See [`SpeakerJammer::cpu_stats`]
+    pub fn cpu_stats(&self) -> CpuStats {
+        self.speaker_jammer.cpu_stats()
+    }
+
+    /// See [`SpeakerJammer::enable_profiling`]
+    pub fn enable_profiling(&mut self, interval: Duration) -> Result<()> {
+        self.speaker_jammer.enable_profiling(interval)
+    }
+
+    /// See [`SpeakerJammer::with_realtime_priority`]
+    pub fn with_realtime_priority(mut self) -> Self {
+        self.speaker_jammer.realtime_priority = true;
+        self
+    }
+
+    /// See [`SpeakerJammer::signal_config`]
+    pub fn signal_config(&self) -> SignalConfig {
+        self.speaker_jammer.signal_config()
+    }
+
+    /// Crossfade to a new configuration, from any thread
+    pub fn update_config(&self, signal_config: SignalConfig) -> Result<()> {
+        self.speaker_jammer.update_config(signal_config)
+    }
+
+    /// Save the generator's phase and hop position to `path`
+    pub fn save_state(&self, path: &Path) -> Result<()> {
+        self.speaker_jammer.save_state(path)
+    }
+
+    /// Resume the generator from `path`; see [`SignalGenerator::restore_state`]
+    pub fn restore_state(&mut self, path: &Path) -> Result<bool> {
+        self.speaker_jammer.restore_state(path)
+    }
+
+    /// Stop jamming and tear down the platform capture and virtual device
+    ///
+    /// On Linux this unloads the PulseAudio modules. Dropping the jammer
+    /// stops it too. Stopping twice is harmless.
     pub fn stop(&mut self) {
         self.speaker_jammer.stop();
+        if self.capturing {
+            self.system_audio.stop_capture();
+            self.capturing = false;
+        }
+        self.device = None;
     }
 }
 
@@ -144,6 +1639,197 @@ impl Drop for SystemJammer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cpal::SupportedBufferSize;
+
+    fn config_range(
+        channels: u16,
+        format: SampleFormat,
+        min_rate: u32,
+        max_rate: u32,
+    ) -> SupportedStreamConfigRange {
+        SupportedStreamConfigRange::new(
+            channels,
+            SampleRate(min_rate),
+            SampleRate(max_rate),
+            SupportedBufferSize::Unknown,
+            format,
+        )
+    }
+
+    #[test]
+    fn test_choose_output_config() {
+        let supported = [
+            config_range(2, SampleFormat::I16, 44100, 44100),
+            config_range(2, SampleFormat::U8, 8000, 192000),
+            config_range(6, SampleFormat::F32, 44100, 96000),
+            config_range(2, SampleFormat::F32, 44100, 96000),
+        ];
+        let needed = SignalConfig::default().required_sample_rate();
+        assert!((46001..=48000).contains(&needed), "{}", needed);
+
+        // A usable default is kept
+        let default = supported[3].with_sample_rate(SampleRate(48000));
+        assert_eq!(
+            choose_output_config(Some(&default), &supported, needed),
+            Some(default)
+        );
+
+        // Too slow: stereo f32 at the next standard rate
+        let default = supported[0].with_sample_rate(SampleRate(44100));
+        let chosen = choose_output_config(Some(&default), &supported, needed).unwrap();
+        assert_eq!(chosen.sample_format(), SampleFormat::F32);
+        assert_eq!(chosen.channels(), 2);
+        assert_eq!(chosen.sample_rate().0, 48000);
+
+        // A format no stream can be built with
+        let default = supported[1].with_sample_rate(SampleRate(96000));
+        let chosen = choose_output_config(Some(&default), &supported, needed).unwrap();
+        assert_eq!(chosen.sample_format(), SampleFormat::F32);
+        assert_eq!(chosen.sample_rate().0, 96000);
+
+        // Only unusable formats and slow rates
+        let unusable = &supported[..2];
+        assert_eq!(choose_output_config(None, unusable, needed), None);
+        assert_eq!(
+            describe_configs(unusable),
+            "2ch i16 44100-44100 Hz, 2ch u8 8000-192000 Hz"
+        );
+
+        // A loop needs its own rate exactly, even below the default's
+        let default = supported[3].with_sample_rate(SampleRate(96000));
+        let chosen = choose_loop_config(Some(&default), &supported, 88200).unwrap();
+        assert_eq!(chosen.sample_format(), SampleFormat::F32);
+        assert_eq!(chosen.channels(), 2);
+        assert_eq!(chosen.sample_rate().0, 88200);
+        assert_eq!(choose_loop_config(Some(&default), &supported, 192000), None);
+    }
+
+    #[test]
+    fn test_emitted_band() {
+        let config = SignalConfig {
+            hop_set: vec![22000.0, 23500.0],
+            ..Default::default()
+        };
+        assert_eq!(emitted_band(&config), (21700.0, 23800.0));
+
+        // The pilot sits below every tone here
+        let config = SignalConfig {
+            pilot_id: Some(1),
+            ..config
+        };
+        assert_eq!(emitted_band(&config), (20900.0, 23800.0));
+    }
+
+    #[test]
+    fn test_alias_warning_at_a_low_device_rate() {
+        let config = SignalConfig::default();
+        assert_eq!(alias_warning(&config), None);
+
+        // Valid as requested at 48 kHz, not at the 44.1 kHz a device gave it
+        let config = SignalConfig {
+            sample_rate: 44100,
+            ..config
+        };
+        let warning = alias_warning(&config).unwrap();
+        assert!(warning.contains("alias down to 20800 Hz"), "{}", warning);
+        assert!(!warning.contains("audible"), "{}", warning);
+
+        let config = SignalConfig {
+            frequency: 21500.0,
+            sample_rate: 32000,
+            ..config
+        };
+        let warning = alias_warning(&config).unwrap();
+        assert!(
+            warning.ends_with("10200 Hz, which is audible"),
+            "{}",
+            warning
+        );
+        assert!(matches!(
+            SpeakerJammer::with_output(
+                config,
+                None,
+                SampleFormat::F32,
+                StreamConfig {
+                    channels: NULL_CHANNELS,
+                    sample_rate: SampleRate(32000),
+                    buffer_size: cpal::BufferSize::Default,
+                }
+            ),
+            Err(CamouflageError::InvalidConfig(
+                crate::SignalConfigError::AboveNyquist { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_wav_loop_plays_on_repeat() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let samples: Vec<f32> = (0..4800)
+            .map(|i| 0.2 * (2.0 * std::f32::consts::PI * 22000.0 * i as f32 / 48000.0).sin())
+            .collect();
+        let path = temp_dir.path().join("loop.wav");
+        crate::wav::write_audio(&path, spec, &samples, None).unwrap();
+
+        let (sample_rate, loaded) = load_loop(&path).unwrap();
+        assert_eq!(sample_rate, 48000);
+        assert_eq!(&loaded[..], &samples[..]);
+
+        // Wraps around, with the same sample on every channel
+        let mut sample_loop = SampleLoop::new(Arc::clone(&loaded));
+        let mut data = vec![0.0f32; 6000 * 2];
+        fill_interleaved(&mut sample_loop, &mut data, &mut [0.0; 2], ChannelMask::ALL);
+        for (i, frame) in data.chunks(2).enumerate() {
+            assert_eq!(frame, [samples[i % 4800]; 2]);
+        }
+
+        // Plays on the null output but has nothing to retune or save
+        let config = StreamConfig {
+            channels: 2,
+            sample_rate: SampleRate(sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        let playback = Playback::Loop(SampleLoop::new(Arc::clone(&loaded)));
+        let mut jammer = SpeakerJammer::with_content(
+            Content::Loop(loaded),
+            playback,
+            None,
+            SampleFormat::F32,
+            config,
+        );
+        jammer.start().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(jammer.meter.silent_for(Instant::now()) < Duration::from_secs(5));
+        assert!(matches!(
+            jammer.update_config(SignalConfig::default()),
+            Err(CamouflageError::NotGenerating)
+        ));
+        assert!(matches!(
+            jammer.save_state(&temp_dir.path().join("state.json")),
+            Err(CamouflageError::NotGenerating)
+        ));
+        jammer.stop();
+        assert!(jammer.parked.pop().is_some());
+
+        // Audible content only warns; an empty file cannot loop
+        let audible: Vec<f32> = (0..4800)
+            .map(|i| 0.2 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 48000.0).sin())
+            .collect();
+        crate::wav::write_audio(&path, spec, &audible, None).unwrap();
+        assert!(load_loop(&path).is_ok());
+        crate::wav::write_audio(&path, spec, &[], None).unwrap();
+        assert!(load_loop(&path).is_err());
+        assert!(matches!(
+            SpeakerJammer::from_wav_loop(&path),
+            Err(CamouflageError::LoopFile(_))
+        ));
+    }
 
     #[test]
     fn test_speaker_jammer_creation() {
@@ -151,11 +1837,38 @@ mod tests {
 
         // This might fail in CI without audio devices
         if let Ok(mut jammer) = SpeakerJammer::new(config) {
+            assert!(jammer.stream_info().sample_rate > 0);
             assert!(jammer.start().is_ok());
             jammer.stop();
         }
     }
 
+    #[test]
+    fn test_start_and_stop_are_idempotent() {
+        let mut jammer = SpeakerJammer::null(SignalConfig::default()).unwrap();
+        assert!(!jammer.is_running());
+        jammer.start().unwrap();
+        jammer.start().unwrap();
+        // One stream, which took the only playback
+        assert!(matches!(jammer.stream, Some(OutputStream::Null(_))));
+        assert!(jammer.parked.is_empty());
+        jammer.stop();
+        jammer.stop();
+        assert!(jammer.stream.is_none());
+        assert_eq!(jammer.parked.len(), 1);
+
+        // This might fail in CI without audio devices
+        if let Ok(mut jammer) = SystemJammer::new(SignalConfig::default(), 0.5) {
+            if jammer.start().is_ok() {
+                assert!(jammer.start().is_ok());
+                assert!(jammer.is_running());
+                jammer.stop();
+                jammer.stop();
+                assert!(!jammer.is_running() && !jammer.capturing);
+            }
+        }
+    }
+
     #[test]
     fn test_system_jammer_creation() {
         let config = SignalConfig::default();
@@ -166,4 +1879,373 @@ mod tests {
             jammer.stop();
         }
     }
+
+    #[test]
+    fn test_render_to_vec_matches_generator() {
+        let stream = StreamInfo {
+            sample_rate: 48000,
+            channels: 2,
+            sample_format: SampleFormat::F32,
+        };
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let samples =
+            SpeakerJammer::render_to_vec(config.clone(), stream, Duration::from_millis(100))
+                .unwrap();
+        assert_eq!(samples.len(), 4800 * 2);
+
+        let mut generator = SignalGenerator::new(config);
+        for frame in samples.chunks(2) {
+            let expected = generator.next_sample();
+            assert_eq!(frame, [expected, expected]);
+        }
+    }
+
+    #[test]
+    fn test_render_to_vec_applies_channel_phase() {
+        let stream = StreamInfo {
+            sample_rate: 48000,
+            channels: 2,
+            sample_format: SampleFormat::F32,
+        };
+        let config = SignalConfig {
+            channel_phase_offsets: vec![0.0, 90.0],
+            seed: Some(1),
+            ..Default::default()
+        };
+        let samples =
+            SpeakerJammer::render_to_vec(config.clone(), stream, Duration::from_millis(10))
+                .unwrap();
+
+        let mut generator = SignalGenerator::new(config);
+        for out in samples.chunks(2) {
+            let mut frame = [0.0; 2];
+            generator.next_frame(&mut frame);
+            assert_eq!(out, frame);
+        }
+        assert!(samples.chunks(2).any(|frame| frame[0] != frame[1]));
+    }
+
+    #[test]
+    fn test_output_channels() {
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(config.clone());
+        let mut data = vec![0.0f32; 480 * 4];
+        let mut frame = [0.0; 4];
+        let mask = ChannelMask::from_channels(&[0, 2]);
+        fill_interleaved(&mut generator, &mut data, &mut frame, mask);
+
+        let mut expected = SignalGenerator::new(config);
+        for out in data.chunks(4) {
+            let sample = expected.next_sample();
+            assert_eq!(out, [sample, 0.0, sample, 0.0]);
+        }
+
+        // Channels are checked against the output's count
+        let mut jammer = SpeakerJammer::null(SignalConfig::default()).unwrap();
+        assert!(jammer.set_output_channels(&[1]).is_ok());
+        assert_eq!(jammer.channel_mask, ChannelMask(0b10));
+        assert!(matches!(
+            jammer.set_output_channels(&[0, 2]),
+            Err(CamouflageError::InvalidChannel {
+                channel: 2,
+                available: 2
+            })
+        ));
+        assert!(jammer.set_output_channels(&[]).is_ok());
+        assert_eq!(jammer.channel_mask, ChannelMask::ALL);
+    }
+
+    #[test]
+    fn test_render_to_vec_converts_and_validates() {
+        let stream = StreamInfo {
+            sample_rate: 44100,
+            channels: 1,
+            sample_format: SampleFormat::I16,
+        };
+        // Tones must fit below the 22.05 kHz Nyquist limit
+        let config = SignalConfig {
+            frequency: 21000.0,
+            ..Default::default()
+        };
+        let samples =
+            SpeakerJammer::render_to_vec(config, stream, Duration::from_millis(50)).unwrap();
+
+        // Quantized to 16-bit steps
+        assert_eq!(samples.len(), 2205);
+        for s in &samples {
+            let steps = s * 32768.0;
+            assert_eq!(steps, steps.round());
+        }
+
+        let audible = SignalConfig {
+            frequency: 15000.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            SpeakerJammer::render_to_vec(audible, stream, Duration::from_millis(50)),
+            Err(CamouflageError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_meter_tracks_silence() {
+        let meter = OutputMeter::new(48000);
+        let now = Instant::now();
+        meter.record(0.1);
+        assert!(meter.silent_for(now + Duration::from_millis(1)) < Duration::from_millis(500));
+
+        // Silent buffers do not count, so the gap keeps growing
+        meter.record(0.0);
+        meter.record(WATCHDOG_SILENCE_RMS / 2.0);
+        assert!(meter.silent_for(now + Duration::from_secs(3)) > Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_promotion_is_left_for_a_normal_thread() {
+        let outcome = Arc::new(ArrayQueue::new(1));
+        let sink = Arc::clone(&outcome);
+        // Its own thread, so a granted promotion does not outlive the test
+        thread::spawn(move || promote_callback_thread(512, 48000, &sink))
+            .join()
+            .unwrap();
+        assert!(outcome.pop().is_some());
+        assert!(outcome.is_empty());
+    }
+
+    #[test]
+    fn test_callback_load() {
+        let load = CallbackLoad::new(48000);
+        assert_eq!(load.stats().load(), 0.0);
+
+        // 480 frames last 10 ms at 48 kHz
+        load.record(480, Duration::from_millis(1));
+        load.record(480, Duration::from_millis(3));
+        let first = load.stats();
+        assert_eq!(first.callbacks, 2);
+        assert_eq!(first.budget, Duration::from_millis(20));
+        assert!((first.load() - 0.2).abs() < 1e-6);
+        assert!((first.peak_load - 0.3).abs() < 1e-6);
+        assert_eq!(first.overruns, 0);
+
+        load.record(480, Duration::from_millis(12));
+        let window = load.stats().since(&first);
+        assert_eq!(window.callbacks, 1);
+        assert!((window.load() - 1.2).abs() < 1e-6);
+        assert_eq!(window.overruns, 1);
+        assert_eq!(
+            window.to_string(),
+            "120.0% of callback budget (peak 120.0%, 1 overruns)"
+        );
+    }
+
+    #[test]
+    fn test_fill_interleaved_reports_rms() {
+        let config = SignalConfig {
+            num_tones: 1,
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(config.clone());
+        let mut data = vec![0.0f32; 4800 * 2];
+        let rms = fill_interleaved(&mut generator, &mut data, &mut [0.0; 2], ChannelMask::ALL);
+        assert!(
+            (rms - config.amplitude / 2f32.sqrt()).abs() < 1e-3,
+            "{}",
+            rms
+        );
+
+        let silent = SignalConfig {
+            amplitude: 0.0,
+            ..config
+        };
+        let mut generator = SignalGenerator::new(silent);
+        assert_eq!(
+            fill_interleaved(&mut generator, &mut data, &mut [0.0; 2], ChannelMask::ALL),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_callback_survives_concurrent_retunes() {
+        let (source, controller) = SignalSource::new(SignalConfig::default()).unwrap();
+        let meter = Arc::new(OutputMeter::new(48000));
+        let done = Arc::new(AtomicBool::new(false));
+        let mut playback = Playback::Signal(source);
+
+        // Stands in for the stream callback, switching sample formats midway
+        let callback = {
+            let meter = Arc::clone(&meter);
+            let done = Arc::clone(&done);
+            std::thread::spawn(move || {
+                let mut frame = [0.0; 2];
+                let mut float = [0.0f32; 512];
+                let mut int = [0i16; 512];
+                let mut buffers = 0;
+                while !done.load(Ordering::Relaxed) || buffers < 100 {
+                    if buffers % 2 == 0 {
+                        render_callback(
+                            &mut playback,
+                            &mut float,
+                            &mut frame,
+                            ChannelMask::ALL,
+                            &meter,
+                        );
+                        assert!(float.iter().all(|s| s.is_finite() && s.abs() <= 1.0));
+                    } else {
+                        render_callback(
+                            &mut playback,
+                            &mut int,
+                            &mut frame,
+                            ChannelMask::ALL,
+                            &meter,
+                        );
+                    }
+                    buffers += 1;
+                }
+                playback
+            })
+        };
+
+        for i in 0..2000 {
+            let config = SignalConfig {
+                frequency: 21000.0 + (i % 20) as f32 * 100.0,
+                amplitude: 0.1 + (i % 5) as f32 * 0.1,
+                ..Default::default()
+            };
+            // The callback may fall behind; dropping a retune is fine
+            let _ = controller.retune(config);
+        }
+        done.store(true, Ordering::Relaxed);
+        let Playback::Signal(mut source) = callback.join().unwrap() else {
+            unreachable!();
+        };
+
+        // The last retune that got through is what ends up playing
+        source.fill(&mut [0.0; 16]);
+        assert_eq!(source.config(), &controller.config());
+        assert!(meter.silent_for(Instant::now()) < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_dropped_callback_parks_source() {
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let (source, _controller) = SignalSource::new(config.clone()).unwrap();
+        let parked = Arc::new(ArrayQueue::new(1));
+        let callback = CallbackSource {
+            playback: Some(Playback::Signal(source)),
+            home: Arc::clone(&parked),
+        };
+        assert!(parked.is_empty());
+        drop(callback);
+        let Some(Playback::Signal(source)) = parked.pop() else {
+            panic!("source was not parked");
+        };
+        assert_eq!(source.config(), &config);
+    }
+
+    #[test]
+    fn test_update_config_while_running() {
+        // This might fail in CI without audio devices
+        if let Ok(mut jammer) = SpeakerJammer::new(SignalConfig::default()) {
+            jammer.start().unwrap();
+            let retuned = SignalConfig {
+                frequency: 22000.0,
+                ..Default::default()
+            };
+            jammer.update_config(retuned).unwrap();
+            jammer.stop();
+            assert_eq!(jammer.signal_config().frequency, 22000.0);
+            assert!(jammer.parked.pop().is_some());
+        }
+    }
+
+    #[test]
+    fn test_null_output_keeps_running() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("state.json");
+        let mut jammer = SpeakerJammer::null(SignalConfig::default()).unwrap();
+        assert!(jammer.is_null());
+        assert_eq!(jammer.stream_info().sample_rate, NULL_SAMPLE_RATE);
+
+        jammer.start().unwrap();
+        assert!(jammer.is_running());
+        jammer
+            .update_config(SignalConfig {
+                frequency: 22000.0,
+                ..Default::default()
+            })
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+
+        // The snapshot comes from the null output's render thread
+        jammer.save_state(&path).unwrap();
+        jammer.stop();
+        assert!(!jammer.is_running());
+
+        let mut restored = SpeakerJammer::null(SignalConfig {
+            frequency: 22000.0,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(restored.restore_state(&path).unwrap());
+        assert!(jammer.meter.silent_for(Instant::now()) < Duration::from_secs(5));
+
+        // The render thread is timed like a device callback
+        let stats = jammer.cpu_stats();
+        assert!(stats.callbacks > 0);
+        assert!(stats.load() > 0.0 && stats.load() < 1.0, "{}", stats);
+    }
+
+    #[test]
+    fn test_new_or_null_always_has_an_output() {
+        let mut jammer = SpeakerJammer::new_or_null(SignalConfig::default()).unwrap();
+        if jammer.is_null() {
+            jammer.start().unwrap();
+            jammer.stop();
+        }
+
+        let audible = SignalConfig {
+            frequency: 15000.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            SpeakerJammer::new_or_null(audible),
+            Err(CamouflageError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_stream_info_display() {
+        let info = StreamInfo {
+            sample_rate: 96000,
+            channels: 2,
+            sample_format: SampleFormat::F32,
+        };
+        assert_eq!(info.to_string(), "96 kHz, 2ch, f32");
+        assert_eq!(info.nyquist(), 48000.0);
+    }
+
+    #[test]
+    fn test_wait_until_stopped() {
+        // Stops at the deadline
+        let stop = AtomicBool::new(false);
+        let start = Instant::now();
+        wait_until_stopped(Some(Duration::from_millis(100)), &stop);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // Returns immediately once the flag is set, even without a deadline
+        stop.store(true, Ordering::SeqCst);
+        let start = Instant::now();
+        wait_until_stopped(None, &stop);
+        wait_until_stopped(Some(Duration::from_secs(60)), &stop);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
 }
@@ -2,12 +2,40 @@
 //!
 //! Core functionality for ultrasonic audio jamming.
 
+pub mod analysis;
+pub mod beamform;
 pub mod daemon;
+mod error;
+mod filter;
+mod flac;
+#[cfg(feature = "fleet")]
+pub mod fleet;
+#[cfg(feature = "async")]
+mod handle;
 mod jammer;
+mod mix;
+pub mod pilot;
 pub mod platform;
+#[cfg(feature = "plot")]
+pub mod plot;
+pub mod selftest;
 mod signal;
+mod source;
+pub mod wav;
 
-pub use daemon::{get_status, is_running, remove_pid, save_pid, stop_daemon, DaemonConfig};
-pub use jammer::{SpeakerJammer, SystemJammer};
-pub use platform::SystemAudio;
-pub use signal::{SignalConfig, SignalGenerator};
+pub use beamform::Beamform;
+pub use daemon::{
+    get_daemon_status, get_log_file, get_state_file, get_status, is_running, lock_daemon,
+    remove_pid, save_pid, stop_daemon, DaemonConfig, DaemonLock, DaemonStatus,
+};
+pub use error::{CamouflageError, RetuneError, SignalConfigError};
+#[cfg(feature = "async")]
+pub use handle::{JammerHandle, JammerStatus};
+pub use jammer::{wait_until_stopped, CpuStats, SpeakerJammer, StreamInfo, SystemJammer};
+pub use mix::MixCurve;
+pub use platform::{SystemAudio, VirtualDevice};
+pub use signal::{
+    SignalConfig, SignalGenerator, ToneDistribution, WaveformType, MIN_ULTRASONIC_FREQUENCY,
+    SAFETY_HIGHPASS_HZ,
+};
+pub use source::{SignalController, SignalSource};
@@ -0,0 +1,111 @@
+//! Gain curves for mixing the signal into other audio
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// RMS level that [`normalization_gain`] brings input to, in dBFS
+pub const NORMALIZE_TARGET_DBFS: f32 = -20.0;
+
+/// How a mix ratio splits gain between the original audio and the signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MixCurve {
+    /// `original * (1 - ratio) + signal * ratio`; perceived loudness dips
+    /// by 3 dB around a ratio of 0.5
+    #[default]
+    Linear,
+    /// Quarter-cycle cosine and sine gains, whose squares always sum to
+    /// one, so the loudness of the original stays even across ratios
+    EqualPower,
+}
+
+impl MixCurve {
+    /// Gains for the original audio and for the signal at `ratio` (0.0-1.0)
+    pub fn gains(self, ratio: f32) -> (f32, f32) {
+        let ratio = ratio.clamp(0.0, 1.0);
+        match self {
+            MixCurve::Linear => (1.0 - ratio, ratio),
+            MixCurve::EqualPower => {
+                let angle = ratio * FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            }
+        }
+    }
+}
+
+/// Gain that brings `samples` to [`NORMALIZE_TARGET_DBFS`] RMS
+///
+/// The gain is capped so the peak stays at full scale, and silence is
+/// left as it is.
+pub fn normalization_gain(samples: &[f32]) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    if samples.is_empty() || peak < 1e-6 {
+        return 1.0;
+    }
+
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    let target = 10f32.powf(NORMALIZE_TARGET_DBFS / 20.0);
+    (target / rms).min(1.0 / peak)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain_profiles() {
+        for curve in [MixCurve::Linear, MixCurve::EqualPower] {
+            // Both curves run from all original to all signal
+            let (original, signal) = curve.gains(0.0);
+            assert!((original - 1.0).abs() < 1e-6 && signal.abs() < 1e-6);
+            let (original, signal) = curve.gains(1.0);
+            assert!(original.abs() < 1e-6 && (signal - 1.0).abs() < 1e-6);
+        }
+
+        for step in 0..=10 {
+            let ratio = step as f32 / 10.0;
+
+            let (original, signal) = MixCurve::Linear.gains(ratio);
+            assert!((original + signal - 1.0).abs() < 1e-6);
+
+            let (original, signal) = MixCurve::EqualPower.gains(ratio);
+            assert!((original * original + signal * signal - 1.0).abs() < 1e-6);
+        }
+
+        // Linear loses 3 dB of power at the midpoint; equal power does not
+        let power = |(original, signal): (f32, f32)| original * original + signal * signal;
+        let linear_db = 10.0 * power(MixCurve::Linear.gains(0.5)).log10();
+        assert!((linear_db + 3.01).abs() < 0.01, "{}", linear_db);
+        assert!(power(MixCurve::EqualPower.gains(0.5)) > 0.999);
+        assert_eq!(MixCurve::default(), MixCurve::Linear);
+    }
+
+    #[test]
+    fn test_normalization_gain() {
+        let sine = |amplitude: f32| -> Vec<f32> {
+            (0..4800)
+                .map(|i| amplitude * (i as f32 * 0.0576).sin())
+                .collect()
+        };
+        let rms_dbfs = |samples: &[f32], gain: f32| {
+            let mean_square =
+                samples.iter().map(|s| (s * gain).powi(2)).sum::<f32>() / samples.len() as f32;
+            10.0 * mean_square.log10()
+        };
+
+        // Quiet and loud inputs land on the same level
+        for amplitude in [0.01, 0.1, 0.5] {
+            let samples = sine(amplitude);
+            let level = rms_dbfs(&samples, normalization_gain(&samples));
+            assert!((level - NORMALIZE_TARGET_DBFS).abs() < 0.1, "{}", level);
+        }
+
+        // A lone click cannot reach the target without clipping
+        let mut click = vec![0.0; 4800];
+        click[100] = 0.25;
+        assert!((normalization_gain(&click) - 4.0).abs() < 1e-4);
+
+        assert_eq!(normalization_gain(&[0.0; 16]), 1.0);
+        assert_eq!(normalization_gain(&[]), 1.0);
+    }
+}
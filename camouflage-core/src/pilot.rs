@@ -0,0 +1,137 @@
+//! Pilot tone that identifies a jammer to monitoring devices
+//!
+//! The pilot is a quiet, continuous-phase FSK signal around
+//! [`PILOT_FREQUENCY_HZ`]. It repeats a 32-bit frame: an 8-bit sync word,
+//! the 16-bit ID and a CRC-8 of the ID, most significant bit first. A `1`
+//! is sent [`PILOT_DEVIATION_HZ`] above the center and a `0` as far below.
+
+use std::f32::consts::TAU;
+
+/// Center frequency of the pilot in Hz
+pub const PILOT_FREQUENCY_HZ: f32 = 21000.0;
+
+/// Shift of each bit's tone from the center in Hz
+pub const PILOT_DEVIATION_HZ: f32 = 100.0;
+
+/// Tones must keep at least this far from the pilot's two frequencies
+pub const PILOT_GUARD_HZ: f32 = 200.0;
+
+/// Peak level of the pilot before the master gain
+pub const PILOT_AMPLITUDE: f32 = 0.02;
+
+/// Length of one bit in seconds
+pub const PILOT_BIT_SECS: f32 = 0.02;
+
+/// Bits in one frame
+pub const PILOT_FRAME_BITS: usize = 32;
+
+/// Marks the start of a frame
+pub const PILOT_SYNC: u8 = 0xE4;
+
+/// Lowest and highest frequency the pilot and its guard occupy, in Hz
+pub fn pilot_band() -> (f32, f32) {
+    let reach = PILOT_DEVIATION_HZ + PILOT_GUARD_HZ;
+    (PILOT_FREQUENCY_HZ - reach, PILOT_FREQUENCY_HZ + reach)
+}
+
+/// Samples per bit at `sample_rate`
+pub fn bit_samples(sample_rate: u32) -> usize {
+    ((sample_rate as f32 * PILOT_BIT_SECS).round() as usize).max(1)
+}
+
+/// CRC-8 (polynomial 0x07) of `id`, high byte first
+fn crc8(id: u16) -> u8 {
+    id.to_be_bytes().iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// The frame the pilot repeats for `id`, first bit in the top bit
+pub fn encode_frame(id: u16) -> u32 {
+    (PILOT_SYNC as u32) << 24 | (id as u32) << 8 | crc8(id) as u32
+}
+
+/// The ID in `frame`, if its sync word and checksum are intact
+pub fn decode_frame(frame: u32) -> Option<u16> {
+    let id = (frame >> 8) as u16;
+    ((frame >> 24) as u8 == PILOT_SYNC && frame as u8 == crc8(id)).then_some(id)
+}
+
+/// Oscillator sending one ID's frame on repeat
+///
+/// Holds no heap memory, so it can be rebuilt on the audio thread.
+pub(crate) struct PilotTone {
+    id: u16,
+    frame: u32,
+    bit: usize,
+    samples_left: usize,
+    bit_samples: usize,
+    phase: f32,
+    sample_rate: f32,
+}
+
+impl PilotTone {
+    pub(crate) fn new(id: u16, sample_rate: u32) -> Self {
+        let bit_samples = bit_samples(sample_rate);
+        Self {
+            id,
+            frame: encode_frame(id),
+            bit: 0,
+            samples_left: bit_samples,
+            bit_samples,
+            phase: 0.0,
+            sample_rate: sample_rate as f32,
+        }
+    }
+
+    /// Whether this oscillator already sends `id` at `sample_rate`
+    pub(crate) fn matches(&self, id: u16, sample_rate: u32) -> bool {
+        self.id == id && self.sample_rate == sample_rate as f32
+    }
+
+    /// Next sample at unit amplitude
+    pub(crate) fn next(&mut self) -> f32 {
+        let one = self.frame >> (PILOT_FRAME_BITS - 1 - self.bit) & 1 == 1;
+        let frequency = if one {
+            PILOT_FREQUENCY_HZ + PILOT_DEVIATION_HZ
+        } else {
+            PILOT_FREQUENCY_HZ - PILOT_DEVIATION_HZ
+        };
+        let sample = (TAU * self.phase).sin();
+        // The phase carries over between bits, so switching does not click
+        self.phase = (self.phase + frequency / self.sample_rate).fract();
+
+        self.samples_left -= 1;
+        if self.samples_left == 0 {
+            self.samples_left = self.bit_samples;
+            self.bit = (self.bit + 1) % PILOT_FRAME_BITS;
+        }
+        sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_round_trips() {
+        for id in [0, 1, 0x1234, u16::MAX] {
+            let frame = encode_frame(id);
+            assert_eq!(frame >> 24, PILOT_SYNC as u32);
+            assert_eq!(decode_frame(frame), Some(id));
+            // Any single flipped bit is caught
+            for bit in 0..PILOT_FRAME_BITS {
+                assert_eq!(decode_frame(frame ^ 1 << bit), None, "{} {}", id, bit);
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Amplitude calibration against a level measured at a microphone
+
+use super::capture::Capture;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Device, Stream};
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Time for the tone to ramp to a new amplitude, avoiding audible clicks
+const RAMP_SECS: f32 = 0.02;
+
+/// What amplitude calibration aims for
+#[derive(Debug, Clone)]
+pub struct CalibrationTarget {
+    /// Frequency of the calibration tone in Hz
+    pub frequency: f32,
+    /// Tone level to reach at the microphone in dBFS
+    pub level_dbfs: f32,
+    /// Amplitude of the first step
+    pub start_amplitude: f32,
+    /// Amplitude the steps never exceed
+    pub max_amplitude: f32,
+    /// Increase between steps in dB
+    pub step_db: f32,
+    /// How long each step plays
+    pub step_duration: Duration,
+}
+
+impl Default for CalibrationTarget {
+    fn default() -> Self {
+        Self {
+            frequency: 23000.0,
+            level_dbfs: -40.0,
+            start_amplitude: 0.01,
+            max_amplitude: 0.5,
+            step_db: 2.0,
+            step_duration: Duration::from_millis(400),
+        }
+    }
+}
+
+impl CalibrationTarget {
+    /// Amplitudes to try, rising by `step_db` and ending at `max_amplitude`
+    pub fn amplitude_steps(&self) -> Vec<f32> {
+        let factor = 10f32.powf(self.step_db.max(0.1) / 20.0);
+        let mut steps = Vec::new();
+        let mut amplitude = self.start_amplitude.max(1e-4);
+        while amplitude < self.max_amplitude {
+            steps.push(amplitude);
+            amplitude *= factor;
+        }
+        steps.push(self.max_amplitude);
+        steps
+    }
+}
+
+/// Result of [`calibrate_amplitude`]
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Lowest tested amplitude that reached the target, or the maximum
+    pub amplitude: f32,
+    /// Tone level measured at that amplitude in dBFS
+    pub level_dbfs: f32,
+    /// Whether the target level was reached at all
+    pub reached_target: bool,
+}
+
+/// Level of a single frequency in dBFS, measured with a Hann-windowed Goertzel filter
+pub fn tone_level_dbfs(samples: &[f32], frequency: f32, sample_rate: u32) -> f32 {
+    if samples.len() < 2 {
+        return f32::NEG_INFINITY;
+    }
+
+    let len = samples.len();
+    let w = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+    let coeff = 2.0 * w.cos();
+    let (mut s1, mut s2, mut window_sum) = (0.0f64, 0.0f64, 0.0f64);
+    for (i, &x) in samples.iter().enumerate() {
+        let window = 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / (len - 1) as f64).cos();
+        window_sum += window;
+        let s = x as f64 * window + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+
+    let power = s1 * s1 + s2 * s2 - coeff * s1 * s2;
+    let amplitude = 2.0 * power.max(0.0).sqrt() / window_sum;
+    20.0 * (amplitude as f32).log10()
+}
+
+/// Play a sine tone whose amplitude can be changed while it plays
+fn build_tone_stream(device: &Device, frequency: f32, amplitude: Arc<AtomicU32>) -> Result<Stream> {
+    let config = device
+        .default_output_config()
+        .context("Failed to query output config")?;
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+    if frequency >= sample_rate / 2.0 {
+        anyhow::bail!(
+            "Output runs at {} Hz and cannot play {:.0} Hz",
+            sample_rate,
+            frequency
+        );
+    }
+
+    let smoothing = 1.0 / (RAMP_SECS * sample_rate);
+    let mut current = 0.0f32;
+    let mut phase = 0.0f32;
+    let stream = device.build_output_stream(
+        &config.into(),
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            let target = f32::from_bits(amplitude.load(Ordering::Relaxed));
+            for frame in data.chunks_mut(channels) {
+                current += (target - current) * smoothing;
+                let sample = current * (2.0 * PI * phase).sin();
+                phase = (phase + frequency / sample_rate).fract();
+                for channel in frame.iter_mut() {
+                    *channel = sample;
+                }
+            }
+        },
+        |err| {
+            eprintln!("Audio stream error: {}", err);
+        },
+        None,
+    )?;
+
+    stream.play()?;
+    Ok(stream)
+}
+
+/// Play an ultrasonic tone at a fixed amplitude, fading in and out
+pub fn play_tone(
+    device: &Device,
+    frequency: f32,
+    amplitude: f32,
+    duration: Duration,
+) -> Result<()> {
+    let level = Arc::new(AtomicU32::new(amplitude.to_bits()));
+    let stream = build_tone_stream(device, frequency, Arc::clone(&level))?;
+
+    std::thread::sleep(duration);
+    level.store(0f32.to_bits(), Ordering::Relaxed);
+    std::thread::sleep(Duration::from_secs_f32(RAMP_SECS * 5.0));
+    drop(stream);
+
+    Ok(())
+}
+
+/// Raise a tone's amplitude step by step until the microphone hears the target level
+///
+/// Each step plays for `target.step_duration`; the first quarter lets the
+/// level settle and the rest is measured on the input's first channel.
+pub fn calibrate_amplitude(
+    device_in: &Device,
+    device_out: &Device,
+    target: &CalibrationTarget,
+) -> Result<Calibration> {
+    let capture = Capture::start(device_in)?;
+    let input_rate = capture.sample_rate;
+    if target.frequency >= input_rate as f32 / 2.0 {
+        anyhow::bail!(
+            "Input runs at {} Hz and cannot capture {:.0} Hz",
+            input_rate,
+            target.frequency
+        );
+    }
+
+    let amplitude = Arc::new(AtomicU32::new(0f32.to_bits()));
+    let output = build_tone_stream(device_out, target.frequency, Arc::clone(&amplitude))?;
+
+    let settle = target.step_duration / 4;
+    let mut last = Calibration {
+        amplitude: 0.0,
+        level_dbfs: f32::NEG_INFINITY,
+        reached_target: false,
+    };
+    for step in target.amplitude_steps() {
+        amplitude.store(step.to_bits(), Ordering::Relaxed);
+        std::thread::sleep(settle);
+        capture.take();
+        std::thread::sleep(target.step_duration - settle);
+
+        let samples = capture.take();
+        let level_dbfs = tone_level_dbfs(&samples, target.frequency, input_rate);
+        info!(
+            "Amplitude {:.3}: {:.1} dBFS at the microphone",
+            step, level_dbfs
+        );
+
+        last = Calibration {
+            amplitude: step,
+            level_dbfs,
+            reached_target: level_dbfs >= target.level_dbfs,
+        };
+        if last.reached_target {
+            break;
+        }
+    }
+
+    amplitude.store(0f32.to_bits(), Ordering::Relaxed);
+    std::thread::sleep(Duration::from_secs_f32(RAMP_SECS * 5.0));
+    drop(output);
+    drop(capture);
+
+    if !last.reached_target {
+        warn!(
+            "Target {:.1} dBFS not reached at maximum amplitude {:.2}",
+            target.level_dbfs, last.amplitude
+        );
+    }
+    Ok(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tone_level_dbfs() {
+        let sample_rate = 96000;
+        let samples: Vec<f32> = (0..9600)
+            .map(|i| 0.5 * (2.0 * PI * 23000.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let level = tone_level_dbfs(&samples, 23000.0, sample_rate);
+        assert!((level - -6.02).abs() < 0.1, "{}", level);
+
+        // Far from the tone there is next to nothing
+        assert!(tone_level_dbfs(&samples, 21000.0, sample_rate) < -60.0);
+    }
+
+    #[test]
+    fn test_amplitude_steps() {
+        let target = CalibrationTarget {
+            start_amplitude: 0.1,
+            max_amplitude: 0.3,
+            step_db: 6.0,
+            ..Default::default()
+        };
+        let steps = target.amplitude_steps();
+        assert_eq!(steps.len(), 3);
+        assert!((steps[1] - 0.1995).abs() < 1e-3);
+        assert_eq!(*steps.last().unwrap(), 0.3);
+    }
+}
@@ -0,0 +1,61 @@
+//! Recording from a microphone while something else plays
+
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{Device, Stream};
+use std::sync::{Arc, Mutex};
+
+/// Input stream collecting the first channel of a device
+pub(crate) struct Capture {
+    samples: Arc<Mutex<Vec<f32>>>,
+    stream: Stream,
+    /// Sample rate of the recording in Hz
+    pub sample_rate: u32,
+}
+
+impl Capture {
+    /// Start recording from `device` at its default input config
+    pub fn start(device: &Device) -> Result<Self> {
+        let config = device
+            .default_input_config()
+            .context("Failed to query input config")?;
+        let sample_rate = config.sample_rate().0;
+        let channels = config.channels().max(1) as usize;
+
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&samples);
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let mut sink = sink.lock().unwrap();
+                sink.extend(data.iter().step_by(channels));
+            },
+            |err| {
+                eprintln!("Audio input error: {}", err);
+            },
+            None,
+        )?;
+        stream.play()?;
+
+        Ok(Self {
+            samples,
+            stream,
+            sample_rate,
+        })
+    }
+
+    /// Take what has been recorded so far, leaving the recording empty
+    pub fn take(&self) -> Vec<f32> {
+        std::mem::take(&mut *self.samples.lock().unwrap())
+    }
+
+    /// Stop recording and return everything not yet taken
+    pub fn finish(self) -> Result<Vec<f32>> {
+        drop(self.stream);
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        if samples.is_empty() {
+            anyhow::bail!("The input device delivered no audio");
+        }
+        Ok(samples)
+    }
+}
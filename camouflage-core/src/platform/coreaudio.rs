@@ -0,0 +1,357 @@
+//! The few CoreAudio calls needed to set up system mode without Audio MIDI Setup
+
+use anyhow::Result;
+use core_foundation_sys::array::{
+    kCFTypeArrayCallBacks, CFArrayCreate, CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef,
+};
+use core_foundation_sys::base::{kCFAllocatorDefault, CFIndex, CFRelease, CFTypeRef};
+use core_foundation_sys::dictionary::{
+    kCFTypeDictionaryKeyCallBacks, kCFTypeDictionaryValueCallBacks, CFDictionaryCreate,
+    CFDictionaryRef,
+};
+use core_foundation_sys::number::{kCFNumberSInt32Type, CFNumberCreate};
+use core_foundation_sys::string::{
+    kCFStringEncodingUTF8, CFStringCreateWithBytes, CFStringGetCString, CFStringGetLength,
+    CFStringGetMaximumSizeForEncoding, CFStringRef,
+};
+use std::ffi::c_void;
+use std::{mem, ptr};
+
+type AudioObjectId = u32;
+type OsStatus = i32;
+
+#[repr(C)]
+struct PropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+const fn four_cc(code: &[u8; 4]) -> u32 {
+    u32::from_be_bytes(*code)
+}
+
+const SYSTEM_OBJECT: AudioObjectId = 1;
+const SCOPE_GLOBAL: u32 = four_cc(b"glob");
+const SCOPE_OUTPUT: u32 = four_cc(b"outp");
+const ELEMENT_MAIN: u32 = 0;
+const HARDWARE_DEVICES: u32 = four_cc(b"dev#");
+const HARDWARE_DEFAULT_OUTPUT: u32 = four_cc(b"dOut");
+const DEVICE_UID: u32 = four_cc(b"uid ");
+const DEVICE_STREAMS: u32 = four_cc(b"stm#");
+const OBJECT_NAME: u32 = four_cc(b"lnam");
+const AGGREGATE_SUB_DEVICES: u32 = four_cc(b"grup");
+
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyDataSize(
+        object: AudioObjectId,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+    ) -> OsStatus;
+
+    fn AudioObjectGetPropertyData(
+        object: AudioObjectId,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: *mut u32,
+        data: *mut c_void,
+    ) -> OsStatus;
+
+    fn AudioObjectSetPropertyData(
+        object: AudioObjectId,
+        address: *const PropertyAddress,
+        qualifier_size: u32,
+        qualifier: *const c_void,
+        size: u32,
+        data: *const c_void,
+    ) -> OsStatus;
+
+    fn AudioHardwareCreateAggregateDevice(
+        description: CFDictionaryRef,
+        device: *mut AudioObjectId,
+    ) -> OsStatus;
+}
+
+/// CoreFoundation object released on drop
+struct Owned(CFTypeRef);
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { CFRelease(self.0) };
+        }
+    }
+}
+
+fn check(status: OsStatus, what: &str) -> Result<()> {
+    if status != 0 {
+        anyhow::bail!("{} failed (OSStatus {})", what, status);
+    }
+    Ok(())
+}
+
+fn address(selector: u32, scope: u32) -> PropertyAddress {
+    PropertyAddress {
+        selector,
+        scope,
+        element: ELEMENT_MAIN,
+    }
+}
+
+fn property_size(object: AudioObjectId, address: &PropertyAddress) -> Result<u32> {
+    let mut size = 0u32;
+    let status =
+        unsafe { AudioObjectGetPropertyDataSize(object, address, 0, ptr::null(), &mut size) };
+    check(status, "Reading a CoreAudio property size")?;
+    Ok(size)
+}
+
+fn device_ids() -> Result<Vec<AudioObjectId>> {
+    let address = address(HARDWARE_DEVICES, SCOPE_GLOBAL);
+    let mut size = property_size(SYSTEM_OBJECT, &address)?;
+    let mut ids = vec![0 as AudioObjectId; size as usize / mem::size_of::<AudioObjectId>()];
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            SYSTEM_OBJECT,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            ids.as_mut_ptr().cast(),
+        )
+    };
+    check(status, "Listing audio devices")?;
+    ids.truncate(size as usize / mem::size_of::<AudioObjectId>());
+    Ok(ids)
+}
+
+fn string_property(object: AudioObjectId, selector: u32) -> Result<String> {
+    let address = address(selector, SCOPE_GLOBAL);
+    let mut value: CFStringRef = ptr::null();
+    let mut size = mem::size_of::<CFStringRef>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            (&mut value as *mut CFStringRef).cast(),
+        )
+    };
+    check(status, "Reading a CoreAudio device property")?;
+    let value = Owned(value.cast());
+    Ok(to_string(value.0.cast()))
+}
+
+/// UIDs in an array-of-strings property, e.g. an aggregate's sub-devices
+fn string_array_property(object: AudioObjectId, selector: u32) -> Result<Vec<String>> {
+    let address = address(selector, SCOPE_GLOBAL);
+    let mut value: CFArrayRef = ptr::null();
+    let mut size = mem::size_of::<CFArrayRef>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            object,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            (&mut value as *mut CFArrayRef).cast(),
+        )
+    };
+    check(status, "Reading a CoreAudio device property")?;
+    if value.is_null() {
+        return Ok(Vec::new());
+    }
+    let value = Owned(value.cast());
+    let array: CFArrayRef = value.0.cast();
+    let count = unsafe { CFArrayGetCount(array) };
+    Ok((0..count)
+        .map(|i| to_string(unsafe { CFArrayGetValueAtIndex(array, i) }.cast()))
+        .collect())
+}
+
+fn has_output_streams(device: AudioObjectId) -> bool {
+    property_size(device, &address(DEVICE_STREAMS, SCOPE_OUTPUT)).is_ok_and(|size| size > 0)
+}
+
+fn to_string(value: CFStringRef) -> String {
+    if value.is_null() {
+        return String::new();
+    }
+    let mut buffer = unsafe {
+        let len = CFStringGetLength(value);
+        vec![0u8; CFStringGetMaximumSizeForEncoding(len, kCFStringEncodingUTF8) as usize + 1]
+    };
+    let ok = unsafe {
+        CFStringGetCString(
+            value,
+            buffer.as_mut_ptr().cast(),
+            buffer.len() as CFIndex,
+            kCFStringEncodingUTF8,
+        )
+    };
+    if ok == 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
+
+fn cf_string(value: &str) -> Owned {
+    Owned(
+        unsafe {
+            CFStringCreateWithBytes(
+                kCFAllocatorDefault,
+                value.as_ptr(),
+                value.len() as CFIndex,
+                kCFStringEncodingUTF8,
+                0,
+            )
+        }
+        .cast(),
+    )
+}
+
+fn cf_number(value: i32) -> Owned {
+    Owned(
+        unsafe {
+            CFNumberCreate(
+                kCFAllocatorDefault,
+                kCFNumberSInt32Type,
+                (&value as *const i32).cast(),
+            )
+        }
+        .cast(),
+    )
+}
+
+fn cf_array(values: &[Owned]) -> Owned {
+    let values: Vec<CFTypeRef> = values.iter().map(|v| v.0).collect();
+    Owned(
+        unsafe {
+            CFArrayCreate(
+                kCFAllocatorDefault,
+                values.as_ptr(),
+                values.len() as CFIndex,
+                &kCFTypeArrayCallBacks,
+            )
+        }
+        .cast(),
+    )
+}
+
+fn cf_dictionary(entries: &[(&str, Owned)]) -> Owned {
+    let keys: Vec<Owned> = entries.iter().map(|(key, _)| cf_string(key)).collect();
+    let key_refs: Vec<CFTypeRef> = keys.iter().map(|k| k.0).collect();
+    let value_refs: Vec<CFTypeRef> = entries.iter().map(|(_, value)| value.0).collect();
+    Owned(
+        unsafe {
+            CFDictionaryCreate(
+                kCFAllocatorDefault,
+                key_refs.as_ptr(),
+                value_refs.as_ptr(),
+                entries.len() as CFIndex,
+                &kCFTypeDictionaryKeyCallBacks,
+                &kCFTypeDictionaryValueCallBacks,
+            )
+        }
+        .cast(),
+    )
+}
+
+/// UID of the current default output device
+pub fn default_output_uid() -> Result<String> {
+    let address = address(HARDWARE_DEFAULT_OUTPUT, SCOPE_GLOBAL);
+    let mut device: AudioObjectId = 0;
+    let mut size = mem::size_of::<AudioObjectId>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            SYSTEM_OBJECT,
+            &address,
+            0,
+            ptr::null(),
+            &mut size,
+            (&mut device as *mut AudioObjectId).cast(),
+        )
+    };
+    check(status, "Reading the default output device")?;
+    string_property(device, DEVICE_UID)
+}
+
+/// Name of an aggregate output device that includes `sub_device_uid`
+///
+/// Finds Multi-Output Devices made here or in Audio MIDI Setup alike.
+pub fn find_aggregate_output(sub_device_uid: &str) -> Result<Option<String>> {
+    for device in device_ids()? {
+        if !has_output_streams(device) {
+            continue;
+        }
+        // Only aggregate devices have a sub-device list
+        let Ok(sub_device_uids) = string_array_property(device, AGGREGATE_SUB_DEVICES) else {
+            continue;
+        };
+        if sub_device_uids.iter().any(|uid| uid == sub_device_uid) {
+            return string_property(device, OBJECT_NAME).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Create a Multi-Output Device that plays to every device in `sub_device_uids`
+///
+/// The first device is the clock source; drift correction is enabled on
+/// the others. The device is public and persists like one made in Audio
+/// MIDI Setup.
+pub fn create_multi_output_device(name: &str, uid: &str, sub_device_uids: &[&str]) -> Result<()> {
+    let Some((&clock, _)) = sub_device_uids.split_first() else {
+        anyhow::bail!("A Multi-Output Device needs at least one device");
+    };
+    let sub_devices: Vec<Owned> = sub_device_uids
+        .iter()
+        .map(|&sub_uid| {
+            cf_dictionary(&[
+                ("uid", cf_string(sub_uid)),
+                ("drift", cf_number((sub_uid != clock) as i32)),
+            ])
+        })
+        .collect();
+    let description = cf_dictionary(&[
+        ("name", cf_string(name)),
+        ("uid", cf_string(uid)),
+        ("subdevices", cf_array(&sub_devices)),
+        ("master", cf_string(clock)),
+        ("stacked", cf_number(1)),
+        ("private", cf_number(0)),
+    ]);
+
+    let mut device: AudioObjectId = 0;
+    let status = unsafe { AudioHardwareCreateAggregateDevice(description.0.cast(), &mut device) };
+    check(status, "Creating the Multi-Output Device")
+}
+
+/// Make the output device called `name` the system default
+pub fn set_default_output(name: &str) -> Result<()> {
+    let device = device_ids()?
+        .into_iter()
+        .filter(|&id| has_output_streams(id))
+        .find(|&id| string_property(id, OBJECT_NAME).is_ok_and(|n| n == name))
+        .ok_or_else(|| anyhow::anyhow!("Output device not found: {}", name))?;
+
+    let address = address(HARDWARE_DEFAULT_OUTPUT, SCOPE_GLOBAL);
+    let status = unsafe {
+        AudioObjectSetPropertyData(
+            SYSTEM_OBJECT,
+            &address,
+            0,
+            ptr::null(),
+            mem::size_of::<AudioObjectId>() as u32,
+            (&device as *const AudioObjectId).cast(),
+        )
+    };
+    check(status, "Setting the default output device")
+}
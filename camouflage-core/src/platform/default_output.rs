@@ -0,0 +1,116 @@
+//! Pointing the system default output at the virtual device and back
+//!
+//! The output that was default before the switch is saved in the config
+//! directory, so it can be restored by a later process, e.g. on uninstall.
+
+use super::SystemAudio;
+use crate::daemon::get_config_dir;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+pub(super) fn previous_default_file() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join("previous-default-output"))
+}
+
+/// Output that was default before [`switch_default_output`], if saved
+pub fn previous_default_output() -> Option<String> {
+    load_previous(&previous_default_file().ok()?)
+}
+
+/// Make the virtual device the default output, saving the current default
+///
+/// Returns the previous default, or `None` if the virtual device already
+/// was the default; an earlier saved default is kept in that case.
+pub fn switch_default_output(audio: &dyn SystemAudio) -> Result<Option<String>> {
+    switch_with(audio, &previous_default_file()?)
+}
+
+/// Put back the default output saved by [`switch_default_output`]
+///
+/// The default is only changed if it is still the virtual device, so a
+/// choice the user made since is kept. Returns the saved output, or `None`
+/// if nothing was saved.
+pub fn restore_default_output(audio: &dyn SystemAudio) -> Result<Option<String>> {
+    restore_with(audio, &previous_default_file()?)
+}
+
+pub(super) fn switch_with(audio: &dyn SystemAudio, path: &Path) -> Result<Option<String>> {
+    let virtual_output = audio.virtual_output_name();
+    let current = audio.default_output()?;
+    if current == virtual_output {
+        return Ok(None);
+    }
+
+    fs::write(path, &current).with_context(|| format!("Failed to write {}", path.display()))?;
+    audio.set_default_output(&virtual_output)?;
+    info!(
+        "Default output switched from {} to {}",
+        current, virtual_output
+    );
+    Ok(Some(current))
+}
+
+fn restore_with(audio: &dyn SystemAudio, path: &Path) -> Result<Option<String>> {
+    let Some(previous) = load_previous(path) else {
+        return Ok(None);
+    };
+    if audio.default_output()? == audio.virtual_output_name() {
+        audio.set_default_output(&previous)?;
+        info!("Default output restored to {}", previous);
+    }
+
+    fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    Ok(Some(previous))
+}
+
+fn load_previous(path: &Path) -> Option<String> {
+    let name = fs::read_to_string(path).ok()?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::fake::FakeAudio;
+
+    #[test]
+    fn test_switch_and_restore_default_output() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("previous-default-output");
+        let audio = FakeAudio::with_default("speakers");
+
+        assert_eq!(
+            switch_with(&audio, &path).unwrap(),
+            Some("speakers".to_string())
+        );
+        assert_eq!(audio.default_output().unwrap(), "virtual");
+
+        // Switching again keeps the original default
+        assert_eq!(switch_with(&audio, &path).unwrap(), None);
+        assert_eq!(load_previous(&path), Some("speakers".to_string()));
+
+        assert_eq!(
+            restore_with(&audio, &path).unwrap(),
+            Some("speakers".to_string())
+        );
+        assert_eq!(audio.default_output().unwrap(), "speakers");
+        assert!(!path.exists());
+        assert_eq!(restore_with(&audio, &path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_restore_keeps_a_newer_choice() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("previous-default-output");
+        let audio = FakeAudio::with_default("speakers");
+
+        switch_with(&audio, &path).unwrap();
+        audio.set_default_output("headphones").unwrap();
+
+        restore_with(&audio, &path).unwrap();
+        assert_eq!(audio.default_output().unwrap(), "headphones");
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::Device;
+use std::f32::consts::PI;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Lowest sample rate whose Nyquist limit can carry the default 23 kHz signal
+pub const ULTRASONIC_MIN_SAMPLE_RATE: u32 = 48000;
+
+/// A range of stream configurations supported by an output device
+#[derive(Debug, Clone)]
+pub struct SupportedOutputConfig {
+    pub channels: u16,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+}
+
+/// Description of an audio output device
+#[derive(Debug, Clone)]
+pub struct OutputDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<SupportedOutputConfig>,
+}
+
+impl OutputDeviceInfo {
+    /// Highest sample rate the device supports
+    pub fn max_sample_rate(&self) -> u32 {
+        self.configs
+            .iter()
+            .map(|c| c.max_sample_rate)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Distinct channel counts the device supports, ascending
+    pub fn channel_counts(&self) -> Vec<u16> {
+        let mut channels: Vec<u16> = self.configs.iter().map(|c| c.channels).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+
+    /// Whether the device can output frequencies above 20 kHz
+    pub fn supports_ultrasonic(&self) -> bool {
+        self.max_sample_rate() >= ULTRASONIC_MIN_SAMPLE_RATE
+    }
+}
+
+/// List all audio output devices on the default host
+pub fn list_output_devices() -> Result<Vec<OutputDeviceInfo>> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+
+    let devices = host
+        .output_devices()
+        .context("Failed to enumerate output devices")?;
+
+    let mut infos = Vec::new();
+    for device in devices {
+        let Ok(name) = device.name() else {
+            continue;
+        };
+
+        let configs = match device.supported_output_configs() {
+            Ok(configs) => configs
+                .map(|c| SupportedOutputConfig {
+                    channels: c.channels(),
+                    min_sample_rate: c.min_sample_rate().0,
+                    max_sample_rate: c.max_sample_rate().0,
+                })
+                .collect(),
+            Err(err) => {
+                warn!("Could not query configs for '{}': {}", name, err);
+                Vec::new()
+            }
+        };
+
+        infos.push(OutputDeviceInfo {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+            configs,
+        });
+    }
+
+    Ok(infos)
+}
+
+/// Names of all audio input devices on the default host
+pub fn list_input_devices() -> Result<Vec<String>> {
+    Ok(cpal::default_host()
+        .input_devices()
+        .context("Failed to enumerate input devices")?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// Name of the default output device as the audio host reports it
+pub fn default_output_name() -> Result<String> {
+    cpal::default_host()
+        .default_output_device()
+        .context("No output device available")?
+        .name()
+        .context("Failed to read the default output device's name")
+}
+
+/// Find an output device by exact name
+pub fn find_output_device(name: &str) -> Result<Device> {
+    let host = cpal::default_host();
+    host.output_devices()
+        .context("Failed to enumerate output devices")?
+        .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        .with_context(|| format!("Output device not found: {}", name))
+}
+
+/// The named output device, or the default one
+pub fn output_device(name: Option<&str>) -> Result<Device> {
+    match name {
+        Some(name) => find_output_device(name),
+        None => cpal::default_host()
+            .default_output_device()
+            .context("No output device available"),
+    }
+}
+
+/// The named input device, or the default one if there is any
+pub fn input_device(name: Option<&str>) -> Result<Option<Device>> {
+    match name {
+        Some(name) => find_input_device(name).map(Some),
+        None => Ok(cpal::default_host().default_input_device()),
+    }
+}
+
+/// Find an input device by exact name
+pub fn find_input_device(name: &str) -> Result<Device> {
+    let host = cpal::default_host();
+    host.input_devices()
+        .context("Failed to enumerate input devices")?
+        .find(|d| d.name().map(|n| n == name).unwrap_or(false))
+        .with_context(|| format!("Input device not found: {}", name))
+}
+
+/// Play a short, quiet logarithmic sweep on the named device
+///
+/// The sweep starts in the audible range so the user can hear that the
+/// device works, and rises towards the device's Nyquist limit.
+pub fn play_test_sweep(name: &str, duration: Duration, amplitude: f32) -> Result<()> {
+    let device = find_output_device(name)?;
+    let config = device.default_output_config()?;
+    let sample_rate = config.sample_rate().0 as f32;
+    let channels = config.channels() as usize;
+
+    let start_freq = 500.0f32;
+    let end_freq = (sample_rate / 2.0 - 1000.0).max(start_freq);
+    let total_samples = (duration.as_secs_f32() * sample_rate).max(1.0);
+
+    info!(
+        "Playing test sweep on '{}': {:.0} Hz → {:.0} Hz over {:.1}s",
+        name,
+        start_freq,
+        end_freq,
+        duration.as_secs_f32()
+    );
+
+    let mut n = 0.0f32;
+    let mut phase = 0.0f32;
+    let stream = device.build_output_stream(
+        &config.into(),
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(channels) {
+                let progress = (n / total_samples).min(1.0);
+                let freq = start_freq * (end_freq / start_freq).powf(progress);
+                let sample = if n < total_samples {
+                    amplitude * (2.0 * PI * phase).sin()
+                } else {
+                    0.0
+                };
+
+                phase = (phase + freq / sample_rate).fract();
+                n += 1.0;
+
+                for channel in frame.iter_mut() {
+                    *channel = sample;
+                }
+            }
+        },
+        |err| {
+            eprintln!("Audio stream error: {}", err);
+        },
+        None,
+    )?;
+
+    stream.play()?;
+    std::thread::sleep(duration);
+    drop(stream);
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! System audio stand-in for tests
+
+use super::{SystemAudio, VirtualDevice};
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// System audio that only tracks the default output
+pub struct FakeAudio {
+    default: Mutex<String>,
+    /// Whether the virtual device can be created
+    pub available: bool,
+    /// Calls to `create_virtual_device`
+    pub created: AtomicUsize,
+}
+
+impl FakeAudio {
+    pub fn with_default(name: &str) -> Self {
+        Self {
+            default: Mutex::new(name.to_string()),
+            available: true,
+            created: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl SystemAudio for FakeAudio {
+    fn create_virtual_device(&self) -> Result<VirtualDevice> {
+        self.created.fetch_add(1, Ordering::SeqCst);
+        if !self.available {
+            anyhow::bail!("virtual device driver not installed");
+        }
+        Ok(VirtualDevice::unmanaged())
+    }
+    fn start_capture(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn stop_capture(&mut self) {}
+    fn virtual_device_exists(&self) -> bool {
+        self.available
+    }
+    fn remove_virtual_device(&self) -> Result<()> {
+        Ok(())
+    }
+    fn list_capture_sources(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+    fn set_capture_source(&mut self, _: String) {}
+    fn capture_source(&self) -> Option<String> {
+        None
+    }
+    fn virtual_output_name(&self) -> String {
+        "virtual".to_string()
+    }
+    fn default_output(&self) -> Result<String> {
+        Ok(self.default.lock().unwrap().clone())
+    }
+    fn set_default_output(&self, name: &str) -> Result<()> {
+        *self.default.lock().unwrap() = name.to_string();
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
-use super::SystemAudio;
+use super::{previous_default_output, SystemAudio, VirtualDevice};
 use anyhow::{Context, Result};
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
 use tracing::{info, warn};
 
 /// Linux system audio implementation using PulseAudio/PipeWire
 pub struct LinuxSystemAudio {
     sink_name: String,
+    capture_source: Option<String>,
 }
 
 impl Default for LinuxSystemAudio {
@@ -18,6 +21,7 @@ impl LinuxSystemAudio {
     pub fn new() -> Self {
         Self {
             sink_name: "camouflage_sink".to_string(),
+            capture_source: None,
         }
     }
 
@@ -37,57 +41,225 @@ impl LinuxSystemAudio {
             .unwrap_or(false)
     }
 
+    /// Create the null sink and route its monitor to the default output
+    ///
+    /// Each step is checked before the next one, and a failure unloads
+    /// whatever was already loaded, so a sink is never left with no route.
+    /// Modules left over from a crashed run are unloaded first, so repeated
+    /// runs never stack extra loopbacks.
+    ///
+    /// The loopback plays to the current default output, or to the one
+    /// saved before the sink was made the default, and never to the sink
+    /// itself. If the sink was the default it is made the default again.
     fn create_pulseaudio_loopback(&self) -> Result<()> {
+        let was_default = self
+            .default_output()
+            .is_ok_and(|default| default == self.sink_name);
+        let playback = if was_default {
+            previous_default_output()
+        } else {
+            self.default_output().ok()
+        };
+
+        if self.virtual_device_exists() {
+            let removed = self.remove_stray_modules()?;
+            warn!(
+                "Removed {} leftover camouflage module(s) from a previous run",
+                removed
+            );
+        }
+
         info!("Creating PulseAudio null sink and loopback...");
 
-        // Create null sink
-        let output = Command::new("pactl")
-            .args([
-                "load-module",
-                "module-null-sink",
-                &format!("sink_name={}", self.sink_name),
-                "sink_properties=device.description=Camouflage_Virtual_Output",
-            ])
-            .output()
-            .context("Failed to create null sink")?;
+        let sink = load_module(&[
+            "module-null-sink",
+            &format!("sink_name={}", self.sink_name),
+            "sink_properties=device.description=Camouflage_Virtual_Output",
+        ])
+        .context("Failed to create null sink")?;
+        if !self.virtual_device_exists() {
+            unload_module(sink);
+            anyhow::bail!(
+                "Null sink '{}' did not appear after loading",
+                self.sink_name
+            );
+        }
 
-        if !output.status.success() {
+        // The monitor source can lag behind the sink under PipeWire
+        let source = format!("source={}.monitor", self.sink_name);
+        let playback = playback.map(|sink| format!("sink={}", sink));
+        let mut args = vec!["module-loopback", &source, "latency_msec=1"];
+        args.extend(playback.as_deref());
+        let mut attempt = 1;
+        let loopback = loop {
+            match load_module(&args) {
+                Ok(index) => break index,
+                Err(e) if attempt < LOOPBACK_ATTEMPTS => {
+                    warn!("Loopback attempt {} failed, retrying: {:#}", attempt, e);
+                    attempt += 1;
+                    thread::sleep(LOOPBACK_RETRY_DELAY);
+                }
+                Err(e) => {
+                    unload_module(sink);
+                    return Err(e.context("Failed to create loopback; removed the null sink"));
+                }
+            }
+        };
+        if !module_loaded(loopback) {
+            unload_module(loopback);
+            unload_module(sink);
             anyhow::bail!(
-                "Failed to create null sink: {}",
-                String::from_utf8_lossy(&output.stderr)
+                "Loopback module {} is not loaded; removed the null sink",
+                loopback
             );
         }
 
-        // Create loopback from null sink to default output
+        info!("✓ PulseAudio virtual device created");
+        if was_default {
+            if let Err(e) = self.set_default_output(&self.sink_name) {
+                warn!(
+                    "Could not make '{}' the default output again: {:#}",
+                    self.sink_name, e
+                );
+            }
+        } else {
+            info!(
+                "  Set '{}' as default output, or run `camouflage install` to do it for you",
+                self.sink_name
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Unload every null sink and loopback left behind for this sink
+    ///
+    /// Other modules, including other null sinks, are left alone. Returns
+    /// how many modules were unloaded.
+    pub fn remove_stray_modules(&self) -> Result<usize> {
         let output = Command::new("pactl")
-            .args([
-                "load-module",
-                "module-loopback",
-                &format!("source={}.monitor", self.sink_name),
-                "latency_msec=1",
-            ])
+            .args(["list", "modules", "short"])
             .output()
-            .context("Failed to create loopback")?;
-
+            .context("Failed to run pactl")?;
         if !output.status.success() {
-            warn!(
-                "Loopback creation warning: {}",
-                String::from_utf8_lossy(&output.stderr)
+            anyhow::bail!(
+                "Failed to list PulseAudio modules: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
             );
         }
 
-        info!("✓ PulseAudio virtual device created");
-        info!(
-            "  Set '{}' as default output in your system settings",
-            self.sink_name
-        );
+        let modules =
+            parse_camouflage_modules(&String::from_utf8_lossy(&output.stdout), &self.sink_name);
+        // Loopbacks were loaded after their sink, so unload newest first
+        for &index in modules.iter().rev() {
+            unload_module(index);
+        }
+        Ok(modules.len())
+    }
+}
 
-        Ok(())
+/// Times to try loading the loopback before giving up
+const LOOPBACK_ATTEMPTS: u32 = 3;
+
+/// Wait between loopback attempts
+const LOOPBACK_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Load a PulseAudio module and return its index
+fn load_module(args: &[&str]) -> Result<u32> {
+    let output = Command::new("pactl")
+        .arg("load-module")
+        .args(args)
+        .output()
+        .context("Failed to run pactl")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
     }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    parse_module_index(&stdout).with_context(|| {
+        format!(
+            "Unexpected output from pactl load-module: {}",
+            stdout.trim()
+        )
+    })
+}
+
+/// Unload a module by index, logging rather than failing
+fn unload_module(index: u32) {
+    let unloaded = Command::new("pactl")
+        .args(["unload-module", &index.to_string()])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    if !unloaded {
+        warn!("Failed to unload PulseAudio module {}", index);
+    }
+}
+
+/// Whether `pactl list modules short` includes the module `index`
+fn module_loaded(index: u32) -> bool {
+    Command::new("pactl")
+        .args(["list", "modules", "short"])
+        .output()
+        .map(|o| lists_module(&String::from_utf8_lossy(&o.stdout), index))
+        .unwrap_or(false)
+}
+
+/// Module index printed by `pactl load-module`
+fn parse_module_index(stdout: &str) -> Option<u32> {
+    stdout.trim().parse().ok()
+}
+
+/// Source names in a `pactl list sources short` listing
+fn parse_source_names(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .filter_map(|line| line.split('\t').nth(1))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Default sink from `pactl info` output
+fn parse_default_sink(info: &str) -> Option<String> {
+    info.lines()
+        .find_map(|line| line.strip_prefix("Default Sink:"))
+        .map(|sink| sink.trim().to_string())
+        .filter(|sink| !sink.is_empty())
+}
+
+/// Indices of the null sinks and loopbacks for `sink_name` in a
+/// `pactl list modules short` listing, in listing order
+fn parse_camouflage_modules(listing: &str, sink_name: &str) -> Vec<u32> {
+    let sink_arg = format!("sink_name={}", sink_name);
+    let source_arg = format!("source={}.monitor", sink_name);
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let index = fields.next()?.trim().parse().ok()?;
+            let name = fields.next()?;
+            let mut args = fields.next().unwrap_or("").split_whitespace();
+            let ours = match name {
+                "module-null-sink" => args.any(|arg| arg == sink_arg),
+                "module-loopback" => args.any(|arg| arg == source_arg),
+                _ => false,
+            };
+            ours.then_some(index)
+        })
+        .collect()
+}
+
+/// Whether a `pactl list modules short` listing has a module `index`
+fn lists_module(listing: &str, index: u32) -> bool {
+    listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .any(|field| field.parse() == Ok(index))
 }
 
 impl SystemAudio for LinuxSystemAudio {
-    fn create_virtual_device(&self) -> Result<()> {
+    /// The guard unloads the sink and loopback, leaving other modules alone
+    fn create_virtual_device(&self) -> Result<VirtualDevice> {
         if self.check_pulseaudio() {
             self.create_pulseaudio_loopback()?;
         } else if self.check_pipewire() {
@@ -102,25 +274,22 @@ impl SystemAudio for LinuxSystemAudio {
             );
         }
 
-        Ok(())
+        let owner = LinuxSystemAudio {
+            sink_name: self.sink_name.clone(),
+            capture_source: None,
+        };
+        Ok(VirtualDevice::new(move || owner.remove_virtual_device()))
     }
 
     fn start_capture(&mut self) -> Result<()> {
-        info!("System audio capture active on Linux");
+        if let Some(source) = self.capture_source() {
+            info!("Capturing system audio from {}", source);
+        }
         Ok(())
     }
 
     fn stop_capture(&mut self) {
         info!("Stopping Linux system audio capture");
-
-        // Remove null sink and loopback
-        let _ = Command::new("pactl")
-            .args(["unload-module", "module-null-sink"])
-            .output();
-
-        let _ = Command::new("pactl")
-            .args(["unload-module", "module-loopback"])
-            .output();
     }
 
     fn virtual_device_exists(&self) -> bool {
@@ -132,16 +301,149 @@ impl SystemAudio for LinuxSystemAudio {
     }
 
     fn remove_virtual_device(&self) -> Result<()> {
-        // Remove null sink and loopback
-        let _ = Command::new("pactl")
-            .args(["unload-module", "module-null-sink"])
-            .output();
+        self.remove_stray_modules()?;
+        info!("✓ Virtual audio device removed");
+        Ok(())
+    }
 
-        let _ = Command::new("pactl")
-            .args(["unload-module", "module-loopback"])
-            .output();
+    fn list_capture_sources(&self) -> Result<Vec<String>> {
+        let output = Command::new("pactl")
+            .args(["list", "sources", "short"])
+            .output()
+            .context("Failed to run pactl")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to list PulseAudio sources: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(parse_source_names(&String::from_utf8_lossy(&output.stdout)))
+    }
 
-        info!("✓ Virtual audio device removed");
+    fn set_capture_source(&mut self, source: String) {
+        self.capture_source = Some(source);
+    }
+
+    /// Defaults to the monitor of the null sink
+    fn capture_source(&self) -> Option<String> {
+        Some(
+            self.capture_source
+                .clone()
+                .unwrap_or_else(|| format!("{}.monitor", self.sink_name)),
+        )
+    }
+
+    fn virtual_output_name(&self) -> String {
+        self.sink_name.clone()
+    }
+
+    fn default_output(&self) -> Result<String> {
+        let output = Command::new("pactl")
+            .arg("info")
+            .output()
+            .context("Failed to run pactl")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to query PulseAudio: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        parse_default_sink(&String::from_utf8_lossy(&output.stdout))
+            .context("PulseAudio reports no default sink")
+    }
+
+    fn set_default_output(&self, name: &str) -> Result<()> {
+        let output = Command::new("pactl")
+            .args(["set-default-sink", name])
+            .output()
+            .context("Failed to run pactl")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to set default sink to {}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::check_capture_source;
+
+    #[test]
+    fn test_pactl_module_parsing() {
+        assert_eq!(parse_module_index("536870913\n"), Some(536870913));
+        assert_eq!(
+            parse_module_index("Failure: Module initialization failed"),
+            None
+        );
+
+        let listing = "0\tmodule-device-restore\t\t\n\
+                       25\tmodule-null-sink\tsink_name=camouflage_sink\t\n\
+                       26\tmodule-loopback\tsource=camouflage_sink.monitor latency_msec=1\t\n";
+        assert!(lists_module(listing, 26));
+        assert!(!lists_module(listing, 2));
+        assert!(!lists_module("", 0));
+
+        let sources = parse_source_names(
+            "0\talsa_output.pci.analog-stereo.monitor\tmodule-alsa-card.c\ts16le 2ch 48000Hz\tSUSPENDED\n\
+             1\tcamouflage_sink.monitor\tmodule-null-sink.c\tfloat32le 2ch 48000Hz\tIDLE\n",
+        );
+        assert_eq!(
+            sources,
+            [
+                "alsa_output.pci.analog-stereo.monitor",
+                "camouflage_sink.monitor"
+            ]
+        );
+        assert!(check_capture_source("camouflage_sink.monitor", &sources).is_ok());
+        assert!(check_capture_source("camouflage_sink", &sources).is_err());
+
+        // Two crashed runs' worth of modules, next to a user's own null sink
+        let stray = "0\tmodule-device-restore\t\t\n\
+                     25\tmodule-null-sink\tsink_name=camouflage_sink sink_properties=device.description=Camouflage_Virtual_Output\t\n\
+                     26\tmodule-loopback\tsource=camouflage_sink.monitor latency_msec=1\t\n\
+                     27\tmodule-null-sink\tsink_name=recording_sink\t\n\
+                     28\tmodule-loopback\tsource=recording_sink.monitor\t\n\
+                     31\tmodule-loopback\tsource=camouflage_sink.monitor latency_msec=1\t\n";
+        assert_eq!(
+            parse_camouflage_modules(stray, "camouflage_sink"),
+            [25, 26, 31]
+        );
+        assert!(parse_camouflage_modules(listing, "other_sink").is_empty());
+    }
+
+    #[test]
+    fn test_parse_default_sink() {
+        let info = "Server Name: PulseAudio (on PipeWire 1.0.5)\n\
+                    Default Sink: alsa_output.pci-0000_00_1f.3.analog-stereo\n\
+                    Default Source: alsa_input.pci-0000_00_1f.3.analog-stereo\n";
+        assert_eq!(
+            parse_default_sink(info).as_deref(),
+            Some("alsa_output.pci-0000_00_1f.3.analog-stereo")
+        );
+        assert_eq!(parse_default_sink("Default Sink: \n"), None);
+        assert_eq!(parse_default_sink("Server Name: pulseaudio\n"), None);
+    }
+
+    #[test]
+    fn test_virtual_device_removed_on_drop() {
+        let audio = LinuxSystemAudio {
+            sink_name: "camouflage_test_sink".to_string(),
+            capture_source: None,
+        };
+
+        // Needs a PulseAudio or PipeWire server, which CI may not have
+        let Ok(device) = audio.create_virtual_device() else {
+            return;
+        };
+        assert!(audio.virtual_device_exists());
+
+        drop(device);
+        assert!(!audio.virtual_device_exists());
+        assert!(audio.remove_stray_modules().unwrap() == 0);
+    }
+}
@@ -1,12 +1,22 @@
-use super::SystemAudio;
+#[cfg(target_os = "macos")]
+use super::coreaudio;
+use super::{devices, SystemAudio, VirtualDevice};
 use anyhow::{Context, Result};
 use std::process::Command;
 use tracing::{info, warn};
 
+/// CoreAudio UID of the BlackHole 2ch driver
+#[cfg(target_os = "macos")]
+const BLACKHOLE_UID: &str = "BlackHole2ch_UID";
+
+/// UID given to the Multi-Output Device created by an unattended install
+#[cfg(target_os = "macos")]
+const MULTI_OUTPUT_UID: &str = "so.nomi.camouflage.multi-output";
+
 /// macOS system audio implementation using BlackHole
-#[allow(dead_code)]
 pub struct MacOSSystemAudio {
     device_name: String,
+    capture_source: Option<String>,
 }
 
 impl Default for MacOSSystemAudio {
@@ -19,6 +29,7 @@ impl MacOSSystemAudio {
     pub fn new() -> Self {
         Self {
             device_name: "BlackHole 2ch".to_string(),
+            capture_source: None,
         }
     }
 
@@ -83,18 +94,102 @@ impl MacOSSystemAudio {
     }
 }
 
+/// Create a Multi-Output Device feeding BlackHole and the current default output
+#[cfg(target_os = "macos")]
+fn create_aggregate_device(name: &str) -> Result<()> {
+    let speakers = coreaudio::default_output_uid()?;
+    if speakers == BLACKHOLE_UID {
+        anyhow::bail!(
+            "BlackHole is the default output. Select your speakers under \
+             System Settings → Sound → Output and run this again"
+        );
+    }
+    coreaudio::create_multi_output_device(name, MULTI_OUTPUT_UID, &[&speakers, BLACKHOLE_UID])
+}
+
+#[cfg(not(target_os = "macos"))]
+fn create_aggregate_device(_name: &str) -> Result<()> {
+    anyhow::bail!("CoreAudio is only available on macOS")
+}
+
+/// Name of a Multi-Output Device that already feeds BlackHole, if any
+#[cfg(target_os = "macos")]
+fn existing_multi_output() -> Option<String> {
+    coreaudio::find_aggregate_output(BLACKHOLE_UID).unwrap_or_else(|e| {
+        warn!(
+            "Could not look for an existing Multi-Output Device: {:#}",
+            e
+        );
+        None
+    })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn existing_multi_output() -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn set_default_device(name: &str) -> Result<()> {
+    coreaudio::set_default_output(name)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_default_device(_name: &str) -> Result<()> {
+    anyhow::bail!("CoreAudio is only available on macOS")
+}
+
 impl SystemAudio for MacOSSystemAudio {
-    fn create_virtual_device(&self) -> Result<()> {
+    /// BlackHole stays installed; the guard removes nothing
+    ///
+    /// Setup instructions are only shown if no Multi-Output Device feeds
+    /// BlackHole yet.
+    fn create_virtual_device(&self) -> Result<VirtualDevice> {
         if !self.check_blackhole_installed() {
             self.install_blackhole()?;
         }
 
-        self.create_multi_output_device()?;
-        Ok(())
+        match existing_multi_output() {
+            Some(name) => info!("✓ Already configured: {} plays to BlackHole", name),
+            None => self.create_multi_output_device()?,
+        }
+        Ok(VirtualDevice::unmanaged())
+    }
+
+    /// Uses BlackHole if it is installed, and otherwise points to
+    /// `camouflage install` rather than installing it
+    fn open_virtual_device(&self) -> Result<VirtualDevice> {
+        if !self.check_blackhole_installed() {
+            anyhow::bail!("BlackHole is not installed. Run `camouflage install` to set it up");
+        }
+        if existing_multi_output().is_none() {
+            self.create_multi_output_device()?;
+        }
+        Ok(VirtualDevice::unmanaged())
+    }
+
+    /// Installs BlackHole if needed, then creates the Multi-Output Device
+    /// through CoreAudio unless one exists
+    fn create_virtual_device_unattended(&self) -> Result<VirtualDevice> {
+        if !self.check_blackhole_installed() {
+            self.install_blackhole()?;
+        }
+
+        let name = self.virtual_output_name();
+        if devices::find_output_device(&name).is_ok() {
+            info!("{} already exists", name);
+        } else {
+            create_aggregate_device(&name)?;
+            info!("✓ Created {} with BlackHole and the current output", name);
+        }
+        Ok(VirtualDevice::unmanaged())
     }
 
     fn start_capture(&mut self) -> Result<()> {
         info!("Starting system audio capture on macOS");
+        if let Some(source) = self.capture_source() {
+            info!("Capturing system audio from {}", source);
+        }
         // In a full implementation, this would:
         // 1. Create audio tap on BlackHole
         // 2. Set up audio routing
@@ -117,4 +212,41 @@ impl SystemAudio for MacOSSystemAudio {
         info!("To remove BlackHole: brew uninstall blackhole-2ch");
         Ok(())
     }
+
+    fn list_capture_sources(&self) -> Result<Vec<String>> {
+        devices::list_input_devices()
+    }
+
+    fn set_capture_source(&mut self, source: String) {
+        self.capture_source = Some(source);
+    }
+
+    /// Defaults to the BlackHole input
+    fn capture_source(&self) -> Option<String> {
+        Some(
+            self.capture_source
+                .clone()
+                .unwrap_or_else(|| self.device_name.clone()),
+        )
+    }
+
+    /// The Multi-Output Device that feeds both BlackHole and the speakers
+    ///
+    /// One set up by hand under another name is used if there is one.
+    fn virtual_output_name(&self) -> String {
+        existing_multi_output().unwrap_or_else(|| "Multi-Output Device".to_string())
+    }
+
+    fn default_output(&self) -> Result<String> {
+        devices::default_output_name()
+    }
+
+    fn set_default_output(&self, name: &str) -> Result<()> {
+        set_default_device(name).with_context(|| {
+            format!(
+                "Failed to set the default output. Pick '{}' under System Settings → Sound → Output",
+                name
+            )
+        })
+    }
 }
@@ -1,13 +1,104 @@
+pub mod calibrate;
+mod capture;
+#[cfg(target_os = "macos")]
+mod coreaudio;
+pub mod default_output;
+pub mod devices;
+#[cfg(test)]
+mod fake;
 pub mod linux;
 pub mod macos;
+#[cfg(windows)]
+mod policy_config;
+pub(crate) mod priority;
+pub mod response;
+pub mod setup;
+pub mod verify;
+#[cfg(windows)]
+pub(crate) mod wasapi;
 pub mod windows;
 
 use anyhow::Result;
+use tracing::warn;
+
+pub use cpal::Device;
+
+pub use calibrate::{
+    calibrate_amplitude, play_tone, tone_level_dbfs, Calibration, CalibrationTarget,
+};
+pub use default_output::{previous_default_output, restore_default_output, switch_default_output};
+pub use devices::{
+    default_output_name, find_input_device, find_output_device, input_device, list_input_devices,
+    list_output_devices, output_device, play_test_sweep, OutputDeviceInfo, SupportedOutputConfig,
+};
+pub use response::{measure_hf_response, FrequencyResponse, ResponsePoint};
+pub use setup::{install_unattended, SetupStep};
+pub use verify::{check_emission, verify_emission, Emission, MIN_EMISSION_SNR_DB};
+
+/// Virtual audio device that is removed when the guard drops
+///
+/// Returned by [`SystemAudio::create_virtual_device`]. On platforms where
+/// the device is set up by hand, the guard removes nothing.
+#[must_use = "dropping the guard removes the device"]
+pub struct VirtualDevice {
+    remove: Option<Box<dyn FnOnce() -> Result<()> + Send>>,
+}
+
+impl VirtualDevice {
+    fn new(remove: impl FnOnce() -> Result<()> + Send + 'static) -> Self {
+        Self {
+            remove: Some(Box::new(remove)),
+        }
+    }
+
+    /// Guard for a device this process does not manage
+    fn unmanaged() -> Self {
+        Self { remove: None }
+    }
+
+    /// Leave the device in place after the guard is gone
+    pub fn keep(mut self) {
+        self.remove = None;
+    }
+}
+
+impl Drop for VirtualDevice {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove.take() {
+            if let Err(e) = remove() {
+                warn!("Failed to remove virtual audio device: {:#}", e);
+            }
+        }
+    }
+}
 
 /// Platform-specific system audio implementation
 pub trait SystemAudio {
-    /// Create a virtual audio device
-    fn create_virtual_device(&self) -> Result<()>;
+    /// Create a virtual audio device, removed again when the guard drops
+    fn create_virtual_device(&self) -> Result<VirtualDevice>;
+
+    /// Create the virtual device without manual steps, or fail saying
+    /// what is missing
+    ///
+    /// Used by `install --non-interactive`. Defaults to
+    /// [`create_virtual_device`](Self::create_virtual_device).
+    fn create_virtual_device_unattended(&self) -> Result<VirtualDevice> {
+        self.create_virtual_device()
+    }
+
+    /// Virtual device for one run of system mode
+    ///
+    /// A device that already exists, such as one kept by `camouflage
+    /// install`, is reused and left in place when the guard drops; only a
+    /// device created here is removed. Never installs software. Defaults
+    /// to [`create_virtual_device`](Self::create_virtual_device) when there
+    /// is no device yet.
+    fn open_virtual_device(&self) -> Result<VirtualDevice> {
+        if self.virtual_device_exists() {
+            return Ok(VirtualDevice::unmanaged());
+        }
+        self.create_virtual_device()
+    }
 
     /// Start capturing system audio
     fn start_capture(&mut self) -> Result<()>;
@@ -20,6 +111,58 @@ pub trait SystemAudio {
 
     /// Remove virtual audio device
     fn remove_virtual_device(&self) -> Result<()>;
+
+    /// Names of the inputs system audio can be captured from
+    fn list_capture_sources(&self) -> Result<Vec<String>>;
+
+    /// Capture from `name`, one of [`list_capture_sources`](Self::list_capture_sources)
+    fn select_capture_source(&mut self, name: &str) -> Result<()> {
+        let sources = self.list_capture_sources()?;
+        let source = check_capture_source(name, &sources)?;
+        self.set_capture_source(source);
+        Ok(())
+    }
+
+    /// Remember a source already checked by
+    /// [`select_capture_source`](Self::select_capture_source)
+    fn set_capture_source(&mut self, source: String);
+
+    /// Source capture will use: the selected one, else the platform's usual one
+    fn capture_source(&self) -> Option<String>;
+
+    /// Output device applications should play to for system mode to hear them
+    fn virtual_output_name(&self) -> String;
+
+    /// Name of the system default output device
+    fn default_output(&self) -> Result<String>;
+
+    /// Make `name` the system default output device
+    fn set_default_output(&self, name: &str) -> Result<()>;
+}
+
+/// `name` if it is among `sources`, otherwise an error listing them
+fn check_capture_source(name: &str, sources: &[String]) -> Result<String> {
+    if sources.iter().any(|source| source == name) {
+        return Ok(name.to_string());
+    }
+    if sources.is_empty() {
+        anyhow::bail!("Capture source not found: {} (none available)", name);
+    }
+    anyhow::bail!(
+        "Capture source not found: {}\n   Available: {}",
+        name,
+        sources.join(", ")
+    )
+}
+
+/// Name of the system default output device
+pub fn get_default_output() -> Result<String> {
+    get_system_audio().default_output()
+}
+
+/// Make `name` the system default output device
+pub fn set_default_output(name: &str) -> Result<()> {
+    get_system_audio().set_default_output(name)
 }
 
 /// Get the platform-specific system audio implementation
@@ -37,3 +180,25 @@ pub fn get_system_audio() -> Box<dyn SystemAudio> {
 pub fn get_system_audio() -> Box<dyn SystemAudio> {
     Box::new(windows::WindowsSystemAudio::new())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::fake::FakeAudio;
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_open_virtual_device_reuses_an_existing_one() {
+        // An existing device gets a guard that removes nothing
+        let audio = FakeAudio::with_default("speakers");
+        let device = audio.open_virtual_device().unwrap();
+        assert!(device.remove.is_none());
+        assert_eq!(audio.created.load(Ordering::SeqCst), 0);
+
+        // Only a missing one is created
+        let mut audio = FakeAudio::with_default("speakers");
+        audio.available = false;
+        assert!(audio.open_virtual_device().is_err());
+        assert_eq!(audio.created.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,93 @@
+//! Changing the default output on Windows
+//!
+//! Windows has no documented API for this. The Sound control panel goes
+//! through the undocumented IPolicyConfig COM interface, whose layout has
+//! not changed since Windows 7 and which every output switcher relies on.
+
+use super::wasapi::Com;
+use anyhow::{Context, Result};
+use std::ffi::c_void;
+use std::thread;
+use windows::core::{IUnknown, IUnknown_Vtbl, Interface, GUID, HRESULT, PCWSTR};
+use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+use windows::Win32::Media::Audio::{
+    eCommunications, eConsole, eMultimedia, eRender, ERole, IMMDevice, IMMDeviceEnumerator,
+    MMDeviceEnumerator, DEVICE_STATE_ACTIVE,
+};
+use windows::Win32::System::Com::{CoCreateInstance, CoTaskMemFree, CLSCTX_ALL, STGM_READ};
+
+/// The Sound control panel's implementation of IPolicyConfig
+const CLSID_POLICY_CONFIG_CLIENT: GUID = GUID::from_u128(0x870af99c_171d_4f9e_af0d_e63df40c2bc9);
+
+#[repr(transparent)]
+#[derive(Clone)]
+struct IPolicyConfig(IUnknown);
+
+#[repr(C)]
+struct IPolicyConfigVtbl {
+    _base: IUnknown_Vtbl,
+    /// GetMixFormat through SetPropertyValue, which are not called
+    _unused: [usize; 10],
+    set_default_endpoint: unsafe extern "system" fn(*mut c_void, PCWSTR, ERole) -> HRESULT,
+}
+
+// SAFETY: the vtable matches IPolicyConfig's up to SetDefaultEndpoint
+unsafe impl Interface for IPolicyConfig {
+    type Vtable = IPolicyConfigVtbl;
+    const IID: GUID = GUID::from_u128(0xf8679f50_850a_41cf_9c72_430f290290c8);
+}
+
+/// Make the output called `name`, as the audio host names it, the default
+/// for media, games and calls alike
+pub(crate) fn set_default_output(name: &str) -> Result<()> {
+    // COM stays out of the caller's thread, which cpal may have set up
+    thread::scope(|scope| {
+        scope
+            .spawn(|| -> Result<()> {
+                let _com = Com::init()?;
+                let device = find_output(name)?
+                    .with_context(|| format!("Output device not found: {}", name))?;
+                // SAFETY: COM is initialized on this thread, and the ID is
+                // freed once after the calls that read it
+                unsafe {
+                    let policy: IPolicyConfig =
+                        CoCreateInstance(&CLSID_POLICY_CONFIG_CLIENT, None, CLSCTX_ALL)
+                            .context("IPolicyConfig is not available")?;
+                    let id = device.GetId()?;
+                    let set = [eConsole, eMultimedia, eCommunications]
+                        .into_iter()
+                        .try_for_each(|role| {
+                            (policy.vtable().set_default_endpoint)(
+                                policy.as_raw(),
+                                PCWSTR(id.0),
+                                role,
+                            )
+                            .ok()
+                        });
+                    CoTaskMemFree(Some(id.0 as *const _));
+                    set.context("Setting the default output device")
+                }
+            })
+            .join()
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("setting the default output panicked")))
+    })
+}
+
+/// Active render endpoint whose friendly name, which cpal reports as the
+/// device name, is `name`
+fn find_output(name: &str) -> windows::core::Result<Option<IMMDevice>> {
+    // SAFETY: COM is initialized on the calling thread
+    unsafe {
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let endpoints = enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        for i in 0..endpoints.GetCount()? {
+            let device = endpoints.Item(i)?;
+            let store = device.OpenPropertyStore(STGM_READ)?;
+            if store.GetValue(&PKEY_Device_FriendlyName)?.to_string() == name {
+                return Ok(Some(device));
+            }
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,110 @@
+//! Realtime scheduling for the audio callback thread
+//!
+//! Each platform has its own mechanism and its own permission rules:
+//!
+//! - **Linux**: `SCHED_FIFO`. Needs `CAP_SYS_NICE` or an `rtprio` limit, for
+//!   example `@audio - rtprio 95` in `/etc/security/limits.conf` with the
+//!   user in the `audio` group.
+//! - **macOS**: a Mach time-constraint policy sized to the buffer period.
+//!   No special permission.
+//! - **Windows**: the MMCSS "Pro Audio" task. No special permission.
+
+use std::io;
+use std::time::Duration;
+
+/// `SCHED_FIFO` priority requested on Linux, capped at the system maximum
+#[cfg(all(unix, not(target_os = "macos")))]
+const FIFO_PRIORITY: libc::c_int = 70;
+
+/// Share of each buffer period the callback may compute for on macOS
+#[cfg(target_os = "macos")]
+const COMPUTATION_SHARE: f64 = 0.5;
+
+/// Raise the calling thread to realtime scheduling
+///
+/// `period` is how often the thread has to deliver a buffer.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub(crate) fn promote_current_thread(_period: Duration) -> io::Result<()> {
+    // SAFETY: plain libc calls on the current thread with a valid param
+    unsafe {
+        let max = libc::sched_get_priority_max(libc::SCHED_FIFO);
+        let param = libc::sched_param {
+            sched_priority: FIFO_PRIORITY.min(max),
+        };
+        match libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) {
+            0 => Ok(()),
+            code => Err(io::Error::from_raw_os_error(code)),
+        }
+    }
+}
+
+/// Raise the calling thread to realtime scheduling
+///
+/// `period` is how often the thread has to deliver a buffer.
+#[cfg(target_os = "macos")]
+pub(crate) fn promote_current_thread(period: Duration) -> io::Result<()> {
+    use mach2::mach_time::mach_timebase_info;
+    use mach2::thread_policy::{
+        thread_policy_set, thread_time_constraint_policy, THREAD_TIME_CONSTRAINT_POLICY,
+        THREAD_TIME_CONSTRAINT_POLICY_COUNT,
+    };
+
+    // SAFETY: plain Mach calls on the current thread; the policy struct
+    // matches the flavor and count passed with it. The port from
+    // pthread_mach_thread_np is not a new right, so nothing leaks.
+    unsafe {
+        let mut timebase = mach_timebase_info { numer: 0, denom: 0 };
+        mach_timebase_info(&mut timebase);
+        let ticks = |duration: f64| {
+            (duration * timebase.denom as f64 / timebase.numer.max(1) as f64) as u32
+        };
+        let period_ns = period.as_nanos() as f64;
+        let mut policy = thread_time_constraint_policy {
+            period: ticks(period_ns),
+            computation: ticks(period_ns * COMPUTATION_SHARE),
+            constraint: ticks(period_ns),
+            preemptible: 1,
+        };
+        match thread_policy_set(
+            libc::pthread_mach_thread_np(libc::pthread_self()),
+            THREAD_TIME_CONSTRAINT_POLICY,
+            &mut policy as *mut _ as libc::thread_policy_t,
+            THREAD_TIME_CONSTRAINT_POLICY_COUNT,
+        ) {
+            0 => Ok(()),
+            code => Err(io::Error::other(format!(
+                "thread_policy_set failed with {}",
+                code
+            ))),
+        }
+    }
+}
+
+/// Raise the calling thread to realtime scheduling
+///
+/// `period` is how often the thread has to deliver a buffer.
+#[cfg(windows)]
+pub(crate) fn promote_current_thread(_period: Duration) -> io::Result<()> {
+    use windows_sys::Win32::System::Threading::AvSetMmThreadCharacteristicsW;
+
+    let task: Vec<u16> = "Pro Audio".encode_utf16().chain([0]).collect();
+    let mut index = 0;
+    // SAFETY: `task` is NUL-terminated and outlives the call. The handle is
+    // kept, so the thread stays registered for as long as it runs.
+    let handle = unsafe { AvSetMmThreadCharacteristicsW(task.as_ptr(), &mut index) };
+    if handle == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Raise the calling thread to realtime scheduling
+///
+/// `period` is how often the thread has to deliver a buffer.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn promote_current_thread(_period: Duration) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "realtime scheduling is not supported on this platform",
+    ))
+}
@@ -0,0 +1,259 @@
+//! High-frequency response of a speaker, measured with a microphone
+//!
+//! A quiet logarithmic sweep is played across the top of the audible band
+//! and into the ultrasonic range while the input records. The recording's
+//! spectrum, corrected for the sweep's falling energy per Hz, gives the
+//! level at each frequency relative to the bottom of the sweep.
+
+use super::capture::Capture;
+use crate::analysis::PowerSpectrum;
+use anyhow::{Context, Result};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::Device;
+use std::f32::consts::PI;
+use std::time::Duration;
+use tracing::info;
+
+/// Sweep range in Hz; the top is lowered to fit the devices' Nyquist limits
+pub const SWEEP_START_HZ: f32 = 15000.0;
+pub const SWEEP_END_HZ: f32 = 25000.0;
+
+/// Sweep level, low enough to be gentle on tweeters
+const SWEEP_AMPLITUDE: f32 = 0.05;
+
+const SWEEP_DURATION: Duration = Duration::from_secs(4);
+
+/// Recording before and after the sweep, covering device latency
+const CAPTURE_MARGIN: Duration = Duration::from_millis(500);
+
+/// Fade at each end of the sweep, keeping its edges out of the spectrum
+const FADE_SECS: f32 = 0.01;
+
+/// Width of each reported band in Hz
+const BAND_WIDTH_HZ: f32 = 500.0;
+
+/// Drop from the reference level that counts as rolled off, in dB
+pub const ROLLOFF_DB: f32 = 10.0;
+
+/// How far the reference band must stand above the noise below the
+/// sweep for the measurement to count, in dB
+const MIN_SWEEP_SNR_DB: f32 = 10.0;
+
+/// Keep the sweep this far below the lower Nyquist limit
+const NYQUIST_MARGIN_HZ: f32 = 500.0;
+
+/// Level of one band of the sweep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponsePoint {
+    /// Center of the band in Hz
+    pub frequency: f32,
+    /// Level relative to the first band in dB
+    pub level_db: f32,
+}
+
+/// Result of [`measure_hf_response`]
+#[derive(Debug, Clone)]
+pub struct FrequencyResponse {
+    /// One point per band, from the bottom of the sweep up
+    pub points: Vec<ResponsePoint>,
+    /// First band whose level is [`ROLLOFF_DB`] or more below the first
+    /// band's, or `None` if the response holds up to the top of the sweep
+    pub rolloff_hz: Option<f32>,
+    /// Sample rate of the capture in Hz
+    pub sample_rate: u32,
+}
+
+/// Logarithmic sweep from `start` to `end` Hz with faded ends
+fn render_sweep(start: f32, end: f32, sample_rate: u32, duration: Duration) -> Vec<f32> {
+    let total = (duration.as_secs_f32() * sample_rate as f32) as usize;
+    let fade = (FADE_SECS * sample_rate as f32) as usize;
+    let mut phase = 0.0f32;
+    (0..total)
+        .map(|n| {
+            let progress = n as f32 / total as f32;
+            let frequency = start * (end / start).powf(progress);
+            let edge = n.min(total - 1 - n);
+            let gain = (edge as f32 / fade.max(1) as f32).min(1.0);
+            let sample = SWEEP_AMPLITUDE * gain * (2.0 * PI * phase).sin();
+            phase = (phase + frequency / sample_rate as f32).fract();
+            sample
+        })
+        .collect()
+}
+
+/// Band levels of a recording of a sweep from `start` to `end` Hz
+///
+/// The recording may start and end with silence; any latency between
+/// output and input does not matter.
+pub fn analyze_sweep(
+    captured: &[f32],
+    sample_rate: u32,
+    start: f32,
+    end: f32,
+) -> Result<FrequencyResponse> {
+    // The sweep fades in and out between stretches of silence, so the
+    // recording needs no window
+    let spectrum = PowerSpectrum::unwindowed(captured, sample_rate)?;
+    // A log sweep spends time, and so energy, in proportion to 1/f per Hz
+    let band_power = |low: f32, high: f32, flatten: bool| {
+        spectrum.band_power_db(low, high, |f| if flatten { f as f64 } else { 1.0 })
+    };
+
+    let bands = ((end - start) / BAND_WIDTH_HZ).floor() as usize;
+    if bands == 0 {
+        anyhow::bail!(
+            "Sweep from {:.0} to {:.0} Hz is narrower than one band",
+            start,
+            end
+        );
+    }
+    let levels: Vec<(f32, f32)> = (0..bands)
+        .map(|i| {
+            let low = start + i as f32 * BAND_WIDTH_HZ;
+            (
+                low + BAND_WIDTH_HZ / 2.0,
+                band_power(low, low + BAND_WIDTH_HZ, true),
+            )
+        })
+        .collect();
+
+    // Both unflattened, so the comparison is like for like
+    let reference = band_power(start, start + BAND_WIDTH_HZ, false);
+    let below = band_power(start - 4000.0, start - 1000.0, false);
+    if reference - below < MIN_SWEEP_SNR_DB {
+        anyhow::bail!(
+            "The microphone did not pick up the sweep ({:.1} dB above the noise below it)",
+            reference - below
+        );
+    }
+
+    let reference = levels[0].1;
+    let points: Vec<ResponsePoint> = levels
+        .into_iter()
+        .map(|(frequency, level)| ResponsePoint {
+            frequency,
+            level_db: level - reference,
+        })
+        .collect();
+    let rolloff_hz = points
+        .iter()
+        .find(|point| point.level_db <= -ROLLOFF_DB)
+        .map(|point| point.frequency);
+
+    Ok(FrequencyResponse {
+        points,
+        rolloff_hz,
+        sample_rate,
+    })
+}
+
+/// Sweep `device_out` from 15 to 25 kHz and measure it with `device_in`
+///
+/// The top of the sweep is lowered to fit both devices' sample rates. The
+/// result covers the speaker, the microphone and the room together, so a
+/// microphone that rolls off early limits what can be measured. The
+/// input's first channel is analyzed.
+pub fn measure_hf_response(device_out: &Device, device_in: &Device) -> Result<FrequencyResponse> {
+    let output_config = device_out
+        .default_output_config()
+        .context("Failed to query output config")?;
+    let output_rate = output_config.sample_rate().0;
+    let output_channels = output_config.channels().max(1) as usize;
+    let capture = Capture::start(device_in)?;
+    let input_rate = capture.sample_rate;
+
+    let nyquist = output_rate.min(input_rate) as f32 / 2.0;
+    let end = SWEEP_END_HZ.min(nyquist - NYQUIST_MARGIN_HZ);
+    if end < SWEEP_START_HZ + 2.0 * BAND_WIDTH_HZ {
+        anyhow::bail!(
+            "Output ({} Hz) and input ({} Hz) sample rates are too low to sweep above {:.0} Hz",
+            output_rate,
+            input_rate,
+            SWEEP_START_HZ
+        );
+    }
+
+    std::thread::sleep(CAPTURE_MARGIN);
+
+    info!(
+        "Sweeping {:.0} Hz → {:.0} Hz over {:.1}s",
+        SWEEP_START_HZ,
+        end,
+        SWEEP_DURATION.as_secs_f32()
+    );
+    let sweep = render_sweep(SWEEP_START_HZ, end, output_rate, SWEEP_DURATION);
+    let mut position = 0;
+    let output = device_out.build_output_stream(
+        &output_config.into(),
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            for frame in data.chunks_mut(output_channels) {
+                frame.fill(sweep.get(position).copied().unwrap_or(0.0));
+                position += 1;
+            }
+        },
+        |err| {
+            eprintln!("Audio stream error: {}", err);
+        },
+        None,
+    )?;
+    output.play()?;
+    std::thread::sleep(SWEEP_DURATION + CAPTURE_MARGIN);
+    drop(output);
+
+    let samples = capture.finish()?;
+    analyze_sweep(&samples, input_rate, SWEEP_START_HZ, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sweep as a speaker with `gain_db` at each frequency would play it,
+    /// between stretches of quiet noise
+    fn simulated_capture(end: f32, gain_db: impl Fn(f32) -> f32) -> Vec<f32> {
+        let sample_rate = 48000;
+        let sweep = render_sweep(SWEEP_START_HZ, end, sample_rate, SWEEP_DURATION);
+        let total = sweep.len() as f32;
+        let mut seed = 7u32;
+        let mut noise = move || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            ((seed >> 8) as f32 / (1 << 24) as f32 - 0.5) * 1e-4
+        };
+
+        let silence = sample_rate as usize / 4;
+        let mut captured: Vec<f32> = (0..silence).map(|_| noise()).collect();
+        captured.extend(sweep.iter().enumerate().map(|(n, &s)| {
+            let frequency = SWEEP_START_HZ * (end / SWEEP_START_HZ).powf(n as f32 / total);
+            s * 10f32.powf(gain_db(frequency) / 20.0) + noise()
+        }));
+        captured.extend((0..silence).map(|_| noise()));
+        captured
+    }
+
+    #[test]
+    fn test_analyze_sweep_finds_rolloff() {
+        let end = 23500.0;
+
+        // A flat speaker holds its level to the top of the sweep
+        let flat =
+            analyze_sweep(&simulated_capture(end, |_| 0.0), 48000, SWEEP_START_HZ, end).unwrap();
+        assert_eq!(flat.points.len(), 17);
+        assert!(flat.rolloff_hz.is_none(), "{:?}", flat.points);
+        assert!(
+            flat.points.iter().all(|p| p.level_db.abs() < 1.0),
+            "{:?}",
+            flat.points
+        );
+
+        // 12 dB per kHz above 20 kHz passes -10 dB near 20.8 kHz
+        let falling = |f: f32| -12.0 * ((f - 20000.0) / 1000.0).max(0.0);
+        let response =
+            analyze_sweep(&simulated_capture(end, falling), 48000, SWEEP_START_HZ, end).unwrap();
+        let rolloff = response.rolloff_hz.unwrap();
+        assert!((20500.0..=21500.0).contains(&rolloff), "{}", rolloff);
+
+        // Noise alone is not a measurement
+        let silence = vec![0.0; 48000];
+        assert!(analyze_sweep(&silence, 48000, SWEEP_START_HZ, end).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Unattended system mode install, reported step by step
+
+use super::default_output::{previous_default_file, switch_with};
+use super::SystemAudio;
+use anyhow::Result;
+use std::path::Path;
+
+/// Outcome of one install step
+#[derive(Debug)]
+pub struct SetupStep {
+    pub name: &'static str,
+    /// What the step did, or why it failed
+    pub result: Result<String>,
+}
+
+impl SetupStep {
+    fn new(name: &'static str, result: Result<String>) -> Self {
+        Self { name, result }
+    }
+}
+
+/// Create the virtual device and, with `switch_default`, make it the
+/// default output and check that it took
+///
+/// Nothing is prompted for. Steps run in order up to the first failure and
+/// every step that ran is returned, so callers can report the real state.
+pub fn install_unattended(audio: &dyn SystemAudio, switch_default: bool) -> Vec<SetupStep> {
+    match previous_default_file() {
+        Ok(path) => install_with(audio, switch_default, &path),
+        Err(e) => vec![SetupStep::new("Open the config directory", Err(e))],
+    }
+}
+
+fn install_with(audio: &dyn SystemAudio, switch_default: bool, path: &Path) -> Vec<SetupStep> {
+    let virtual_output = audio.virtual_output_name();
+    let mut steps = vec![SetupStep::new(
+        "Create the virtual device",
+        audio.create_virtual_device_unattended().map(|device| {
+            device.keep();
+            format!("{} is available", virtual_output)
+        }),
+    )];
+    if !switch_default || steps[0].result.is_err() {
+        return steps;
+    }
+
+    let switched = switch_with(audio, path).map(|previous| match previous {
+        Some(previous) => format!("was {}", previous),
+        None => "already the default".to_string(),
+    });
+    let failed = switched.is_err();
+    steps.push(SetupStep::new("Make it the default output", switched));
+    if failed {
+        return steps;
+    }
+
+    let current = audio.default_output().and_then(|current| {
+        if current != virtual_output {
+            anyhow::bail!("default output is still {}", current);
+        }
+        Ok(format!("{} is the default output", current))
+    });
+    steps.push(SetupStep::new("Check the default output", current));
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::fake::FakeAudio;
+
+    #[test]
+    fn test_install_reports_each_step() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("previous-default-output");
+        let audio = FakeAudio::with_default("speakers");
+
+        let steps = install_with(&audio, true, &path);
+        let outcomes: Vec<_> = steps
+            .iter()
+            .map(|step| step.result.as_ref().unwrap().as_str())
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                "virtual is available",
+                "was speakers",
+                "virtual is the default output"
+            ]
+        );
+
+        // Only the device without switching
+        let steps = install_with(&audio, false, &path);
+        assert_eq!(steps.len(), 1);
+    }
+
+    #[test]
+    fn test_install_stops_at_first_failure() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("previous-default-output");
+        let mut audio = FakeAudio::with_default("speakers");
+        audio.available = false;
+
+        let steps = install_with(&audio, true, &path);
+        assert_eq!(steps.len(), 1);
+        assert!(steps[0].result.is_err());
+        assert_eq!(audio.default_output().unwrap(), "speakers");
+    }
+}
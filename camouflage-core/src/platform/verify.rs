@@ -0,0 +1,110 @@
+//! Check with a microphone that the jamming signal reaches the air
+
+use super::capture::Capture;
+use crate::analysis::{self, BandLevel};
+use crate::signal::SignalConfig;
+use anyhow::Result;
+use cpal::Device;
+use std::time::Duration;
+
+/// Band edges are widened by this much to allow for window leakage
+const BAND_MARGIN_HZ: f32 = 200.0;
+
+/// How far the signal band must stand above the noise floor to pass, in dB
+///
+/// The loudest of a few hundred noise-only bins already sits about 10 dB
+/// above their median, so the margin has to be well clear of that.
+pub const MIN_EMISSION_SNR_DB: f64 = 20.0;
+
+/// Result of [`verify_emission`]
+#[derive(Debug, Clone, Copy)]
+pub struct Emission {
+    /// Frequency range searched for the signal in Hz
+    pub band: (f32, f32),
+    /// Measured level of that band and of the rest of the spectrum
+    pub level: BandLevel,
+    /// Sample rate of the capture in Hz
+    pub sample_rate: u32,
+}
+
+impl Emission {
+    /// Whether the signal was clearly picked up
+    pub fn passed(&self) -> bool {
+        self.level.snr_db() >= MIN_EMISSION_SNR_DB
+    }
+}
+
+/// Look for the band occupied by `config` in mono `samples`
+pub fn check_emission(
+    samples: &[f32],
+    sample_rate: u32,
+    config: &SignalConfig,
+) -> Result<Emission> {
+    let (lowest, highest) = config.occupied_band();
+    let band = (lowest - BAND_MARGIN_HZ, highest + BAND_MARGIN_HZ);
+    let level = analysis::band_level(samples, sample_rate, band.0, band.1)?;
+    Ok(Emission {
+        band,
+        level,
+        sample_rate,
+    })
+}
+
+/// Record `duration` from `device` and check it for the signal of `config`
+///
+/// Only measures; whatever is jamming must already be playing. The
+/// input's first channel is analyzed.
+pub fn verify_emission(
+    device: &Device,
+    config: &SignalConfig,
+    duration: Duration,
+) -> Result<Emission> {
+    let capture = Capture::start(device)?;
+    let sample_rate = capture.sample_rate;
+    let (_, highest) = config.occupied_band();
+    if highest >= sample_rate as f32 / 2.0 {
+        anyhow::bail!(
+            "Input runs at {} Hz and cannot capture the signal up to {:.0} Hz",
+            sample_rate,
+            highest
+        );
+    }
+
+    std::thread::sleep(duration);
+    let samples = capture.finish()?;
+    check_emission(&samples, sample_rate, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::SignalGenerator;
+
+    #[test]
+    fn test_check_emission() {
+        let config = SignalConfig {
+            hop_set: vec![21000.0, 23000.0],
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(SignalConfig {
+            amplitude: 0.01,
+            ..config.clone()
+        });
+        let mut captured = vec![0.0; 48000];
+        generator.generate_buffer(&mut captured);
+
+        let emission = check_emission(&captured, 48000, &config).unwrap();
+        assert!(emission.passed(), "{:?}", emission);
+        assert!(emission.band.0 < 21000.0 && emission.band.1 > 23000.0);
+
+        // Silence and room noise alone do not pass
+        let mut seed = 3u32;
+        let noise: Vec<f32> = (0..48000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                ((seed >> 8) as f32 / (1 << 24) as f32 - 0.5) * 0.01
+            })
+            .collect();
+        assert!(!check_emission(&noise, 48000, &config).unwrap().passed());
+    }
+}
@@ -0,0 +1,363 @@
+//! WASAPI exclusive-mode output
+//!
+//! Shared mode plays through the Windows mixer at its rate, usually
+//! 48 kHz, which leaves little room above the audible band. Exclusive mode
+//! bypasses the mixer and opens the device at a rate its hardware supports,
+//! such as 96 or 192 kHz. cpal only opens shared-mode streams, so this
+//! drives the default render endpoint directly.
+
+use cpal::SampleFormat;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use windows::core::PCSTR;
+use windows::Win32::Foundation::{CloseHandle, HANDLE, S_OK, WAIT_OBJECT_0};
+use windows::Win32::Media::Audio::{
+    eConsole, eRender, IAudioClient, IAudioRenderClient, IMMDevice, IMMDeviceEnumerator,
+    MMDeviceEnumerator, AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED,
+    AUDCLNT_SHAREMODE_EXCLUSIVE, AUDCLNT_STREAMFLAGS_EVENTCALLBACK, WAVEFORMATEX,
+    WAVEFORMATEXTENSIBLE, WAVEFORMATEXTENSIBLE_0,
+};
+use windows::Win32::Media::KernelStreaming::{KSDATAFORMAT_SUBTYPE_PCM, WAVE_FORMAT_EXTENSIBLE};
+use windows::Win32::Media::Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL,
+    COINIT_MULTITHREADED,
+};
+use windows::Win32::System::Threading::{CreateEventA, WaitForSingleObject};
+
+/// Rates tried in order; 96 kHz leaves ample room without the cost of 192
+const RATES: [u32; 6] = [96000, 192000, 88200, 176400, 48000, 44100];
+
+/// Sample formats tried in order, with the bits the device reads of each;
+/// many devices take 24 bits padded to 32 but not a full 32
+const SAMPLE_FORMATS: [(SampleFormat, u16); 4] = [
+    (SampleFormat::F32, 32),
+    (SampleFormat::I32, 32),
+    (SampleFormat::I32, 24),
+    (SampleFormat::I16, 16),
+];
+
+/// How long the render thread waits for the device before checking for stop
+const EVENT_TIMEOUT_MS: u32 = 500;
+
+/// Format an exclusive-mode stream is opened with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ExclusiveFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+    /// Bits the device reads from each sample's container
+    valid_bits: u16,
+    /// Speaker positions of the channels, from the mixer's format
+    channel_mask: u32,
+}
+
+impl ExclusiveFormat {
+    fn wave_format(&self) -> WAVEFORMATEXTENSIBLE {
+        let bits = self.sample_format.sample_size() as u16 * 8;
+        let block_align = self.channels * bits / 8;
+        WAVEFORMATEXTENSIBLE {
+            Format: WAVEFORMATEX {
+                wFormatTag: WAVE_FORMAT_EXTENSIBLE as u16,
+                nChannels: self.channels,
+                nSamplesPerSec: self.sample_rate,
+                nAvgBytesPerSec: self.sample_rate * block_align as u32,
+                nBlockAlign: block_align,
+                wBitsPerSample: bits,
+                cbSize: (std::mem::size_of::<WAVEFORMATEXTENSIBLE>()
+                    - std::mem::size_of::<WAVEFORMATEX>()) as u16,
+            },
+            Samples: WAVEFORMATEXTENSIBLE_0 {
+                wValidBitsPerSample: self.valid_bits,
+            },
+            dwChannelMask: self.channel_mask,
+            SubFormat: if self.sample_format == SampleFormat::F32 {
+                KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+            } else {
+                KSDATAFORMAT_SUBTYPE_PCM
+            },
+        }
+    }
+}
+
+/// One device buffer, typed by the stream's sample format
+pub(crate) enum ExclusiveBuffer<'a> {
+    F32(&'a mut [f32]),
+    I32(&'a mut [i32]),
+    I16(&'a mut [i16]),
+}
+
+/// COM initialized for the current thread until dropped
+pub(super) struct Com;
+
+impl Com {
+    pub(super) fn init() -> windows::core::Result<Self> {
+        // SAFETY: balanced by CoUninitialize when the guard drops
+        unsafe { CoInitializeEx(None, COINIT_MULTITHREADED).ok()? };
+        Ok(Self)
+    }
+}
+
+impl Drop for Com {
+    fn drop(&mut self) {
+        // SAFETY: COM was initialized on this thread by `init`
+        unsafe { CoUninitialize() };
+    }
+}
+
+/// Event handle closed when dropped
+struct Event(HANDLE);
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from CreateEventA and is closed once
+        unsafe {
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+fn default_device() -> windows::core::Result<IMMDevice> {
+    // SAFETY: COM is initialized on the calling thread
+    unsafe {
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        enumerator.GetDefaultAudioEndpoint(eRender, eConsole)
+    }
+}
+
+/// First of [`RATES`] at or above `needed` Hz and of [`SAMPLE_FORMATS`]
+/// that `client` takes in exclusive mode, with the mixer's channels
+fn negotiate(client: &IAudioClient, needed: u32) -> io::Result<ExclusiveFormat> {
+    // SAFETY: the mix format is read while valid and freed once
+    let (channels, channel_mask) = unsafe {
+        let mix = client.GetMixFormat()?;
+        let channels = (*mix).nChannels;
+        let mask = if (*mix).wFormatTag as u32 == WAVE_FORMAT_EXTENSIBLE {
+            (*mix.cast::<WAVEFORMATEXTENSIBLE>()).dwChannelMask
+        } else {
+            0
+        };
+        CoTaskMemFree(Some(mix as *const _));
+        (channels, mask)
+    };
+
+    let candidates = RATES
+        .iter()
+        .filter(|&&rate| rate >= needed)
+        .flat_map(|&rate| {
+            SAMPLE_FORMATS
+                .iter()
+                .map(move |&(sample_format, valid_bits)| ExclusiveFormat {
+                    sample_rate: rate,
+                    channels,
+                    sample_format,
+                    valid_bits,
+                    channel_mask,
+                })
+        });
+    for format in candidates {
+        let wave = format.wave_format();
+        // SAFETY: `wave` outlives the call and starts with a WAVEFORMATEX
+        let result = unsafe {
+            client.IsFormatSupported(
+                AUDCLNT_SHAREMODE_EXCLUSIVE,
+                (&wave as *const WAVEFORMATEXTENSIBLE).cast(),
+                None,
+            )
+        };
+        if result == S_OK {
+            return Ok(format);
+        }
+    }
+    Err(io::Error::other(format!(
+        "the device takes none of {:?} Hz at or above {} Hz in exclusive mode",
+        RATES, needed
+    )))
+}
+
+/// Open `device` in exclusive mode with `format`, returning the client and
+/// its buffer size in frames
+fn initialize(
+    device: &IMMDevice,
+    format: &ExclusiveFormat,
+) -> windows::core::Result<(IAudioClient, u32)> {
+    let wave = format.wave_format();
+    let wave_ptr: *const WAVEFORMATEX = (&wave as *const WAVEFORMATEXTENSIBLE).cast();
+    // SAFETY: COM is initialized on the calling thread and `wave` outlives
+    // every call that reads it
+    unsafe {
+        let mut client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+        let mut period = 0i64;
+        client.GetDevicePeriod(Some(&mut period), None)?;
+        let init = |client: &IAudioClient, period: i64| {
+            client.Initialize(
+                AUDCLNT_SHAREMODE_EXCLUSIVE,
+                AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+                period,
+                period,
+                wave_ptr,
+                None,
+            )
+        };
+        match init(&client, period) {
+            // The period has to match a buffer size the hardware can use;
+            // retry with the one the device suggests, on a fresh client
+            Err(e) if e.code() == AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED => {
+                let frames = client.GetBufferSize()?;
+                period = (10_000_000.0 * frames as f64 / format.sample_rate as f64).round() as i64;
+                client = device.Activate(CLSCTX_ALL, None)?;
+                init(&client, period)?;
+            }
+            result => result?,
+        }
+        let frames = client.GetBufferSize()?;
+        Ok((client, frames))
+    }
+}
+
+/// Pick an exclusive-mode format for a signal needing `needed` Hz and
+/// check that the default output grants exclusive access
+///
+/// Fails if another application holds the device exclusively, exclusive
+/// use is disabled in the device's properties, or no rate fits.
+pub(crate) fn probe(needed: u32) -> io::Result<ExclusiveFormat> {
+    // COM stays out of the caller's thread, which cpal may have set up
+    thread::scope(|scope| {
+        scope
+            .spawn(|| -> io::Result<ExclusiveFormat> {
+                let _com = Com::init()?;
+                let device = default_device()?;
+                // SAFETY: COM is initialized on this thread
+                let client: IAudioClient = unsafe { device.Activate(CLSCTX_ALL, None)? };
+                let format = negotiate(&client, needed)?;
+                drop(client);
+                initialize(&device, &format)?;
+                Ok(format)
+            })
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("exclusive mode probe panicked")))
+    })
+}
+
+/// Exclusive-mode stream on the default output, rendered on its own thread
+///
+/// Stops when dropped.
+pub(crate) struct ExclusiveStream {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ExclusiveStream {
+    /// Open the default output with `format` and call `render` for each
+    /// buffer the device asks for
+    ///
+    /// Returns once the stream is playing, or with the error that kept it
+    /// from starting.
+    pub fn spawn<F>(format: ExclusiveFormat, mut render: F) -> io::Result<Self>
+    where
+        F: FnMut(ExclusiveBuffer<'_>) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (ready, opened) = mpsc::sync_channel(1);
+        let thread = thread::Builder::new()
+            .name("camouflage-wasapi".into())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    let mut ready = Some(ready);
+                    if let Err(e) = play(&format, &stop, &mut ready, &mut render) {
+                        match ready {
+                            Some(ready) => {
+                                let _ = ready.send(Err(e.into()));
+                            }
+                            None => eprintln!("Audio stream error: {}", e),
+                        }
+                    }
+                }
+            })?;
+
+        match opened.recv() {
+            Ok(Ok(())) => Ok(Self {
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(e)
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(io::Error::other("exclusive output thread exited"))
+            }
+        }
+    }
+}
+
+impl Drop for ExclusiveStream {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // Joining drops `render`, and with it what it owns
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Body of the render thread; reports on `ready` once playing
+fn play<F>(
+    format: &ExclusiveFormat,
+    stop: &AtomicBool,
+    ready: &mut Option<SyncSender<io::Result<()>>>,
+    render: &mut F,
+) -> windows::core::Result<()>
+where
+    F: FnMut(ExclusiveBuffer<'_>),
+{
+    let _com = Com::init()?;
+    let (client, frames) = initialize(&default_device()?, format)?;
+    let samples = frames as usize * format.channels as usize;
+
+    // SAFETY: COM is initialized on this thread; each buffer is written
+    // within its GetBuffer/ReleaseBuffer pair and holds `samples` samples
+    // of the negotiated format
+    unsafe {
+        let event = Event(CreateEventA(None, false, false, PCSTR::null())?);
+        client.SetEventHandle(event.0)?;
+        let render_client: IAudioRenderClient = client.GetService()?;
+
+        render_client.GetBuffer(frames)?;
+        render_client.ReleaseBuffer(frames, AUDCLNT_BUFFERFLAGS_SILENT.0 as u32)?;
+        client.Start()?;
+        if let Some(ready) = ready.take() {
+            let _ = ready.send(Ok(()));
+        }
+
+        loop {
+            let signaled = WaitForSingleObject(event.0, EVENT_TIMEOUT_MS) == WAIT_OBJECT_0;
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            if !signaled {
+                continue;
+            }
+
+            let data = render_client.GetBuffer(frames)?;
+            render(match format.sample_format {
+                SampleFormat::F32 => {
+                    ExclusiveBuffer::F32(std::slice::from_raw_parts_mut(data.cast(), samples))
+                }
+                SampleFormat::I32 => {
+                    ExclusiveBuffer::I32(std::slice::from_raw_parts_mut(data.cast(), samples))
+                }
+                _ => ExclusiveBuffer::I16(std::slice::from_raw_parts_mut(data.cast(), samples)),
+            });
+            render_client.ReleaseBuffer(frames, 0)?;
+        }
+        client.Stop()?;
+    }
+    Ok(())
+}
@@ -1,11 +1,13 @@
-use super::SystemAudio;
+use super::{devices, SystemAudio, VirtualDevice};
 use anyhow::Result;
+use cpal::traits::{DeviceTrait, HostTrait};
 use tracing::{info, warn};
 
 /// Windows system audio implementation
 #[allow(dead_code)]
 pub struct WindowsSystemAudio {
     device_name: String,
+    capture_source: Option<String>,
 }
 
 impl Default for WindowsSystemAudio {
@@ -18,12 +20,14 @@ impl WindowsSystemAudio {
     pub fn new() -> Self {
         Self {
             device_name: "Camouflage Virtual Audio".to_string(),
+            capture_source: None,
         }
     }
 }
 
 impl SystemAudio for WindowsSystemAudio {
-    fn create_virtual_device(&self) -> Result<()> {
+    /// Only prints setup instructions; the guard removes nothing
+    fn create_virtual_device(&self) -> Result<VirtualDevice> {
         warn!("⚠️  Windows system mode requires manual setup:");
         warn!("");
         warn!("   Option 1: VB-Audio Virtual Cable (Recommended)");
@@ -38,11 +42,27 @@ impl SystemAudio for WindowsSystemAudio {
         warn!("");
         warn!("   After setup, run: camouflage system");
 
-        Ok(())
+        Ok(VirtualDevice::unmanaged())
+    }
+
+    /// Checks for VB-Audio Virtual Cable, which has to be installed by hand
+    fn create_virtual_device_unattended(&self) -> Result<VirtualDevice> {
+        if !self.virtual_device_exists() {
+            anyhow::bail!(
+                "VB-Audio Virtual Cable is not installed.\n   \
+                 Download it from https://vb-audio.com/Cable/, run its installer as \
+                 administrator, reboot and run this again"
+            );
+        }
+        info!("Found {}", self.virtual_output_name());
+        Ok(VirtualDevice::unmanaged())
     }
 
     fn start_capture(&mut self) -> Result<()> {
         info!("System audio capture mode on Windows");
+        if let Some(source) = self.capture_source() {
+            info!("Capturing system audio from {}", source);
+        }
         warn!("Ensure virtual audio cable is installed and configured");
         Ok(())
     }
@@ -51,14 +71,62 @@ impl SystemAudio for WindowsSystemAudio {
         info!("Stopping Windows system audio capture");
     }
 
+    fn virtual_output_name(&self) -> String {
+        "CABLE Input (VB-Audio Virtual Cable)".to_string()
+    }
+
+    fn default_output(&self) -> Result<String> {
+        devices::default_output_name()
+    }
+
+    /// Sets every role, so calls follow the same output as media
+    #[cfg(windows)]
+    fn set_default_output(&self, name: &str) -> Result<()> {
+        super::policy_config::set_default_output(name)
+    }
+
+    #[cfg(not(windows))]
+    fn set_default_output(&self, _name: &str) -> Result<()> {
+        anyhow::bail!("The Windows default output can only be changed on Windows")
+    }
+
+    /// Whether VB-Audio Virtual Cable's output is present
     fn virtual_device_exists(&self) -> bool {
-        // Check for VB-Cable or VoiceMeeter
-        // This is a simplified check
-        false
+        let cable = self.virtual_output_name();
+        devices::list_output_devices()
+            .is_ok_and(|outputs| outputs.iter().any(|output| output.name == cable))
     }
 
     fn remove_virtual_device(&self) -> Result<()> {
         info!("To remove virtual audio: Uninstall VB-Audio Cable from Control Panel");
         Ok(())
     }
+
+    /// WASAPI can capture any output as a loopback, so outputs are listed
+    /// first, followed by inputs such as "CABLE Output"
+    fn list_capture_sources(&self) -> Result<Vec<String>> {
+        let mut sources: Vec<String> = devices::list_output_devices()?
+            .into_iter()
+            .map(|device| device.name)
+            .collect();
+        for input in devices::list_input_devices()? {
+            if !sources.contains(&input) {
+                sources.push(input);
+            }
+        }
+        Ok(sources)
+    }
+
+    fn set_capture_source(&mut self, source: String) {
+        self.capture_source = Some(source);
+    }
+
+    /// Defaults to the loopback of the default output
+    fn capture_source(&self) -> Option<String> {
+        self.capture_source.clone().or_else(|| {
+            cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.name().ok())
+        })
+    }
 }
@@ -0,0 +1,127 @@
+//! SVG charts of a signal's spectrum
+
+use crate::analysis::{analyze_samples, spectrum_dbfs, AUDIBLE_LIMIT_HZ};
+use anyhow::{anyhow, Result};
+use plotters::prelude::*;
+use std::path::Path;
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 600;
+
+/// Bottom of the level axis; quieter bins are drawn at this level
+const FLOOR_DBFS: f64 = -140.0;
+
+/// Bins are merged, keeping the loudest, down to about this many points
+const MAX_POINTS: usize = 2000;
+
+/// Draw the magnitude spectrum of mono `samples` to an SVG file at `path`
+///
+/// The 20 kHz audible boundary is drawn as a vertical line and the
+/// strongest peaks found by [`analyze_samples`] are marked and labelled.
+pub fn plot_spectrum(samples: &[f32], sample_rate: u32, path: &Path) -> Result<()> {
+    if !path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+    {
+        anyhow::bail!(
+            "Unsupported plot format: {} (only .svg is supported)",
+            path.display()
+        );
+    }
+
+    let report = analyze_samples(samples, sample_rate)?;
+    let bins = spectrum_dbfs(samples, sample_rate)?;
+    let points: Vec<(f32, f64)> = bins
+        .chunks(bins.len().div_ceil(MAX_POINTS))
+        .map(|chunk| {
+            let loudest = chunk.iter().map(|b| b.1).fold(f64::NEG_INFINITY, f64::max);
+            (chunk[0].0 / 1000.0, loudest.max(FLOOR_DBFS))
+        })
+        .collect();
+    let level_at = |frequency: f32| {
+        let bin_hz = sample_rate as f32 / samples.len() as f32;
+        let index = ((frequency / bin_hz).round() as usize).min(bins.len() - 1);
+        bins[index].1.max(FLOOR_DBFS)
+    };
+
+    let nyquist_khz = sample_rate as f32 / 2000.0;
+    let boundary_khz = AUDIBLE_LIMIT_HZ / 1000.0;
+    let draw_error = |e| anyhow!("Failed to draw {}: {}", path.display(), e);
+
+    let root = SVGBackend::new(path, (WIDTH, HEIGHT)).into_drawing_area();
+    root.fill(&WHITE).map_err(draw_error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!("Spectrum ({} Hz sample rate)", sample_rate),
+            ("sans-serif", 22),
+        )
+        .margin(15)
+        .x_label_area_size(45)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0.0f32..nyquist_khz, FLOOR_DBFS..0.0)
+        .map_err(draw_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("Frequency (kHz)")
+        .y_desc("Level (dBFS)")
+        .draw()
+        .map_err(draw_error)?;
+
+    chart
+        .draw_series(LineSeries::new(points, &BLUE))
+        .map_err(draw_error)?;
+
+    chart
+        .draw_series(std::iter::once(PathElement::new(
+            vec![(boundary_khz, FLOOR_DBFS), (boundary_khz, 0.0)],
+            RED.stroke_width(2),
+        )))
+        .map_err(draw_error)?;
+    chart
+        .draw_series(std::iter::once(Text::new(
+            "20 kHz audible limit",
+            (boundary_khz, -5.0),
+            ("sans-serif", 14).into_font().color(&RED),
+        )))
+        .map_err(draw_error)?;
+
+    for &frequency in &report.peak_frequencies {
+        let peak = (frequency / 1000.0, level_at(frequency));
+        chart
+            .draw_series(std::iter::once(
+                EmptyElement::at(peak)
+                    + Circle::new((0, 0), 4, BLACK.filled())
+                    + Text::new(format!("{:.0} Hz", frequency), (6, -14), ("sans-serif", 12)),
+            ))
+            .map_err(draw_error)?;
+    }
+
+    root.present().map_err(draw_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plot_spectrum_marks_boundary_and_peaks() {
+        let sample_rate = 48000;
+        let samples: Vec<f32> = (0..sample_rate)
+            .map(|i| 0.3 * (2.0 * PI * 21000.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("spectrum.svg");
+        plot_spectrum(&samples, sample_rate, &path).unwrap();
+
+        let svg = std::fs::read_to_string(&path).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("20 kHz audible limit"));
+        assert!(svg.contains("21000 Hz"));
+
+        let png = temp_dir.path().join("spectrum.png");
+        assert!(plot_spectrum(&samples, sample_rate, &png).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Offline checks that the signal code works on this machine
+//!
+//! Needs no audio hardware or network. Every preset is rendered with a
+//! fixed seed, so the results are the same on every run.
+
+use crate::analysis::{analyze_samples, MAX_AUDIBLE_ENERGY_FRACTION};
+use crate::signal::{SignalConfig, SignalGenerator, WaveformType};
+
+/// Length of audio rendered for each preset, in seconds
+const RENDER_SECS: f32 = 1.0;
+
+/// Audible energy allowed for band noise, whose single band-pass filter
+/// has wide skirts
+const BAND_NOISE_MAX_AUDIBLE_FRACTION: f64 = 0.05;
+
+/// Outcome of one check on one preset
+#[derive(Debug, Clone)]
+pub struct SelfTestCheck {
+    pub preset: &'static str,
+    /// Short name of what was checked
+    pub check: &'static str,
+    pub passed: bool,
+    /// Measured value or error, for the report
+    pub detail: String,
+}
+
+/// Validate, render and analyze every built-in preset
+///
+/// A preset that fails validation is not rendered, so it has a single
+/// check in the result.
+pub fn run_self_test() -> Vec<SelfTestCheck> {
+    let mut checks = Vec::new();
+    for (preset, config, _) in SignalConfig::presets() {
+        let mut check = |check, passed, detail: String| {
+            checks.push(SelfTestCheck {
+                preset,
+                check,
+                passed,
+                detail,
+            })
+        };
+
+        if let Err(e) = config.validate() {
+            check("config valid", false, e.to_string());
+            continue;
+        }
+        check("config valid", true, String::new());
+
+        let config = SignalConfig {
+            seed: Some(0),
+            ..config.clone()
+        };
+        let mut buffer = vec![0.0; (config.sample_rate as f32 * RENDER_SECS) as usize];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut buffer);
+
+        let peak = buffer.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        check("peak below full scale", peak < 1.0, format!("{:.3}", peak));
+
+        let spectrum = match analyze_samples(&buffer, config.sample_rate) {
+            Ok(spectrum) => spectrum,
+            Err(e) => {
+                check("spectrum", false, format!("{:#}", e));
+                continue;
+            }
+        };
+        let limit = if config.waveform == WaveformType::BandNoise {
+            BAND_NOISE_MAX_AUDIBLE_FRACTION
+        } else {
+            MAX_AUDIBLE_ENERGY_FRACTION
+        };
+        check(
+            "audible-band energy",
+            spectrum.audible_energy_fraction < limit,
+            format!(
+                "{:.4}% (limit {}%)",
+                spectrum.audible_energy_fraction * 100.0,
+                limit * 100.0
+            ),
+        );
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_pass_self_test() {
+        let checks = run_self_test();
+        assert!(checks.len() >= SignalConfig::presets().len() * 3);
+        for check in &checks {
+            assert!(check.passed, "{:?}", check);
+        }
+    }
+}
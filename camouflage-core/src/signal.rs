@@ -1,20 +1,320 @@
 //! Signal generation module for ultrasonic audio
 
+use crate::analysis::{analyze_samples, AudibilityReport, SignalStats};
+use crate::beamform::Beamform;
+use crate::error::SignalConfigError;
+use crate::filter::{Biquad, HighPass};
+use crate::pilot::{self, PilotTone, PILOT_AMPLITUDE};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::f32::consts::PI;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub};
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::info;
+
+/// Lowest frequency in Hz considered inaudible
+pub const MIN_ULTRASONIC_FREQUENCY: f32 = 20000.0;
+
+/// Cutoff in Hz of the optional safety high-pass filter
+pub const SAFETY_HIGHPASS_HZ: f32 = 19000.0;
+
+/// Margin in Hz kept above [`MIN_ULTRASONIC_FREQUENCY`] when auto-adjusting
+const ADJUST_MARGIN_HZ: f32 = 500.0;
+
+/// Minimum bandwidth in Hz of the band-limited noise waveform
+const MIN_NOISE_BANDWIDTH_HZ: f32 = 1000.0;
+
+/// Length of the buffer rendered by [`SignalConfig::audibility_report`]
+const AUDIBILITY_RENDER_SECS: f32 = 0.5;
+
+/// Halvings of the amplitude range in [`SignalConfig::fit_to_audibility_budget`]
+const BUDGET_FIT_STEPS: usize = 12;
+
+/// Waveform used for each tone
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WaveformType {
+    #[default]
+    Sine,
+    Square,
+    Sawtooth,
+    Triangle,
+    /// Noise band-passed around the base frequency, spanning all tones
+    BandNoise,
+}
+
+impl WaveformType {
+    /// Whether the waveform is built from harmonics of the tone frequency
+    pub fn is_harmonic_rich(&self) -> bool {
+        matches!(self, Self::Square | Self::Sawtooth | Self::Triangle)
+    }
+
+    /// Band-limited value at `phase` (0.0-1.0) for a tone of `freq` Hz
+    ///
+    /// Harmonic-rich waveforms are summed only up to the Nyquist limit, so
+    /// nothing aliases back into the audible range. At 48 kHz an ultrasonic
+    /// tone has no harmonics below Nyquist and these reduce to a sine.
+    fn sample<S: Sample>(&self, phase: S, freq: f32, sample_rate: u32) -> S {
+        let nyquist = sample_rate as f32 / 2.0;
+        let harmonics = ((nyquist / freq) as usize).max(1);
+        let int = |k: usize| S::from_f32(k as f32);
+        let partial = |k: usize| (S::from_f32(2.0) * S::PI * int(k) * phase).sin();
+
+        match self {
+            Self::Sine | Self::BandNoise => partial(1),
+            Self::Square => {
+                S::from_f32(4.0) / S::PI
+                    * (1..=harmonics)
+                        .step_by(2)
+                        .map(|k| partial(k) / int(k))
+                        .sum::<S>()
+            }
+            Self::Sawtooth => {
+                S::from_f32(2.0) / S::PI
+                    * (1..=harmonics)
+                        .map(|k| {
+                            let sign = S::from_f32(if k % 2 == 1 { 1.0 } else { -1.0 });
+                            sign * partial(k) / int(k)
+                        })
+                        .sum::<S>()
+            }
+            Self::Triangle => {
+                S::from_f32(8.0) / (S::PI * S::PI)
+                    * (1..=harmonics)
+                        .step_by(2)
+                        .map(|k| {
+                            let sign = S::from_f32(if (k / 2) % 2 == 0 { 1.0 } else { -1.0 });
+                            sign * partial(k) / int(k * k)
+                        })
+                        .sum::<S>()
+            }
+        }
+    }
+}
+
+/// Sample type the generator renders: `f32` in realtime, `f64` offline
+///
+/// Oscillator phases are stored as `f64` but stepped at the sample type's
+/// precision, so the `f32` path is unchanged by the wider storage.
+trait Sample:
+    Copy
+    + Default
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + AddAssign
+    + MulAssign
+    + Sum
+{
+    const PI: Self;
+
+    fn from_f32(x: f32) -> Self;
+
+    /// `numerator / denominator`, for fade and ramp gains
+    fn ratio(numerator: usize, denominator: usize) -> Self;
+
+    fn sin(self) -> Self;
+
+    /// Stored `phase` plus `offset` cycles, wrapped to 0.0-1.0
+    fn shifted(phase: f64, offset: f32) -> Self;
+
+    /// Step `phase` on by one sample of a `freq` Hz tone
+    fn advance(phase: &mut f64, freq: f32, sample_rate: u32);
+
+    fn high_pass(self, filter: &mut HighPass) -> Self;
+
+    fn clamp_unit(self) -> Self;
+}
+
+impl Sample for f32 {
+    const PI: Self = PI;
+
+    fn from_f32(x: f32) -> Self {
+        x
+    }
+
+    fn ratio(numerator: usize, denominator: usize) -> Self {
+        numerator as f32 / denominator as f32
+    }
+
+    fn sin(self) -> Self {
+        f32::sin(self)
+    }
+
+    fn shifted(phase: f64, offset: f32) -> Self {
+        (phase as f32 + offset).rem_euclid(1.0)
+    }
+
+    fn advance(phase: &mut f64, freq: f32, sample_rate: u32) {
+        let mut next = *phase as f32 + freq / sample_rate as f32;
+        if next >= 1.0 {
+            next -= 1.0;
+        }
+        *phase = next as f64;
+    }
+
+    fn high_pass(self, filter: &mut HighPass) -> Self {
+        filter.process(self)
+    }
+
+    fn clamp_unit(self) -> Self {
+        self.clamp(-1.0, 1.0)
+    }
+}
+
+impl Sample for f64 {
+    const PI: Self = std::f64::consts::PI;
+
+    fn from_f32(x: f32) -> Self {
+        x as f64
+    }
+
+    fn ratio(numerator: usize, denominator: usize) -> Self {
+        numerator as f64 / denominator as f64
+    }
+
+    fn sin(self) -> Self {
+        f64::sin(self)
+    }
+
+    fn shifted(phase: f64, offset: f32) -> Self {
+        (phase + offset as f64).rem_euclid(1.0)
+    }
+
+    fn advance(phase: &mut f64, freq: f32, sample_rate: u32) {
+        *phase += freq as f64 / sample_rate as f64;
+        if *phase >= 1.0 {
+            *phase -= 1.0;
+        }
+    }
+
+    fn high_pass(self, filter: &mut HighPass) -> Self {
+        filter.process_f64(self)
+    }
+
+    fn clamp_unit(self) -> Self {
+        self.clamp(-1.0, 1.0)
+    }
+}
+
+/// How the tones of a multi-tone signal are spaced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneDistribution {
+    /// Evenly spaced by `frequency_spread`, centered on the base frequency
+    #[default]
+    Even,
+    /// Same span as `Even`, with spacing growing by a constant ratio so
+    /// tones are densest at the low end
+    Geometric,
+    /// Offsets in Hz from the base frequency, one per tone
+    Custom(Vec<f32>),
+}
+
+impl ToneDistribution {
+    /// Copy `other` into `self`, reusing a custom list's allocation
+    fn assign_from(&mut self, other: &ToneDistribution) {
+        match (self, other) {
+            (Self::Custom(offsets), Self::Custom(other)) => offsets.clone_from(other),
+            (this, other) => *this = other.clone(),
+        }
+    }
+}
 
 /// Configuration for ultrasonic signal generation
-#[derive(Debug, Clone)]
+///
+/// Missing fields fall back to [`SignalConfig::default`] when deserializing,
+/// so config files only need to list the values they change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SignalConfig {
     /// Base frequency in Hz (typically 20000-24000 for ultrasonic)
     pub frequency: f32,
     /// Sample rate in Hz (typically 48000)
     pub sample_rate: u32,
-    /// Signal amplitude (0.0-1.0)
+    /// Signal amplitude (0.0-1.0), shared out evenly between the tones
     pub amplitude: f32,
+    /// Gain on the summed output (0.0-1.0), applied after `amplitude`
+    ///
+    /// Scales every tone alike, so the output peaks at up to
+    /// `amplitude * master_gain` with the balance between tones kept.
+    /// Lowering it makes room for a tilt or more tones without touching
+    /// `amplitude`.
+    pub master_gain: f32,
     /// Number of tones for multi-tone jamming
     pub num_tones: usize,
     /// Frequency spread between tones in Hz
     pub frequency_spread: f32,
+    /// Placement of the tones around the base frequency
+    pub tone_distribution: ToneDistribution,
+    /// How far the tone spacing wanders from `frequency_spread`, as a
+    /// fraction (0.05 for ±5%); 0.0 keeps it fixed
+    ///
+    /// A moving spacing keeps a comb filter matched to the tones from
+    /// staying matched. Applies to multi-tone signals other than band noise.
+    pub spread_dither_depth: f32,
+    /// How often the spacing dither heads for a new random target, in Hz
+    pub spread_dither_rate_hz: f32,
+    /// Waveform used for each tone
+    pub waveform: WaveformType,
+    /// Gain slope across the tones in dB per octave, 0 dB at 20 kHz
+    ///
+    /// A positive tilt boosts the higher tones to make up for a tweeter's
+    /// roll-off. Applies to tones, not band noise.
+    pub frequency_tilt_db_per_octave: f32,
+    /// Keep every tone at least this many Hz below the Nyquist limit
+    ///
+    /// Output filters roll off before Nyquist, and tones right under it can
+    /// alias back down on devices with gentle anti-imaging filters. 0.0
+    /// allows anything below Nyquist.
+    pub nyquist_guard_hz: f32,
+    /// Run the output through a steep high-pass at 19 kHz, removing any DC
+    /// offset or audible-band leakage
+    pub safety_highpass: bool,
+    /// Crossfade length in milliseconds when the generator is retuned
+    pub crossfade_ms: f32,
+    /// Carrier frequencies to hop between in Hz; empty disables hopping
+    pub hop_set: Vec<f32>,
+    /// Time between carrier hops in milliseconds
+    pub hop_interval_ms: f32,
+    /// Seed for the pseudo-random hop schedule
+    pub hop_seed: u64,
+    /// Phase offset of each output channel in degrees; channels past the
+    /// end of the list are in phase with the first
+    pub channel_phase_offsets: Vec<f32>,
+    /// Delay each output channel to aim the signal from a speaker array
+    /// toward a target, on top of `channel_phase_offsets`
+    ///
+    /// See [`crate::beamform`]. Band noise and the pilot are the same on
+    /// every channel and are not steered.
+    pub beamform: Option<Beamform>,
+    /// Start multi-tone signals at pseudo-random phases instead of zero
+    ///
+    /// Tones that start in phase add up to their combined amplitude right
+    /// away; spread phases lower the crest factor and keep peaks off full scale.
+    pub random_phases: bool,
+    /// Seed for the initial tone phases
+    pub phase_seed: u64,
+    /// Seed for every random part of the signal: tone phases, spacing
+    /// dither, hop schedule and band noise
+    ///
+    /// `None` draws one from entropy when the generator is created, and
+    /// [`SignalGenerator::config`] reports what was drawn. `hop_seed` and
+    /// `phase_seed` are combined with it.
+    pub seed: Option<u64>,
+    /// Add a quiet pilot tone sending this ID, so monitoring devices can
+    /// tell the jammer is on and which one it is
+    ///
+    /// See [`crate::pilot`] for the encoding. Tones must keep clear of the
+    /// pilot's band around 21 kHz.
+    pub pilot_id: Option<u16>,
 }
 
 impl Default for SignalConfig {
@@ -23,112 +323,2494 @@ impl Default for SignalConfig {
             frequency: 23000.0,
             sample_rate: 48000,
             amplitude: 0.25, // Optimized for inaudibility while maintaining effectiveness
+            master_gain: 1.0,
             num_tones: 3,
             frequency_spread: 300.0,
+            tone_distribution: ToneDistribution::Even,
+            spread_dither_depth: 0.0,
+            spread_dither_rate_hz: 0.5,
+            waveform: WaveformType::Sine,
+            frequency_tilt_db_per_octave: 0.0,
+            nyquist_guard_hz: 0.0,
+            safety_highpass: false,
+            crossfade_ms: 50.0,
+            hop_set: Vec::new(),
+            hop_interval_ms: 500.0,
+            hop_seed: 0,
+            channel_phase_offsets: Vec::new(),
+            beamform: None,
+            random_phases: true,
+            phase_seed: 0,
+            seed: None,
+            pilot_id: None,
         }
     }
 }
 
-/// Generator for ultrasonic signals
-pub struct SignalGenerator {
-    config: SignalConfig,
-    phase: f32,
-    tone_phases: Vec<f32>,
-}
+/// Named starting points for common situations, in display order
+///
+/// Each entry is (name, config, description). Sample rates are the usual
+/// 48 kHz placeholder and get replaced by the output device's rate.
+static PRESETS: LazyLock<Vec<(&'static str, SignalConfig, &'static str)>> = LazyLock::new(|| {
+    vec![
+        (
+            "balanced",
+            SignalConfig::default(),
+            "The defaults: three tones around 23 kHz at a quarter of full scale",
+        ),
+        (
+            "stealth",
+            SignalConfig {
+                amplitude: 0.1,
+                safety_highpass: true,
+                ..Default::default()
+            },
+            "Quiet and high-passed, for quiet rooms and listeners sensitive to high frequencies",
+        ),
+        (
+            "aggressive",
+            SignalConfig {
+                frequency: 22000.0,
+                amplitude: 0.5,
+                num_tones: 5,
+                frequency_spread: 500.0,
+                ..Default::default()
+            },
+            "Loud and wide (21-23 kHz) for noisy rooms; may be audible to children and pets",
+        ),
+        (
+            "hopping",
+            SignalConfig {
+                hop_set: vec![21500.0, 22500.0, 23500.0],
+                hop_interval_ms: 250.0,
+                ..Default::default()
+            },
+            "Hops the carrier every 250 ms so a fixed notch filter cannot remove it",
+        ),
+        (
+            "noise",
+            SignalConfig {
+                frequency: 22500.0,
+                num_tones: 5,
+                frequency_spread: 500.0,
+                waveform: WaveformType::BandNoise,
+                ..Default::default()
+            },
+            "Band-limited noise across 21.5-23.5 kHz instead of discrete tones",
+        ),
+    ]
+});
 
-impl SignalGenerator {
-    /// Create a new signal generator with the given configuration
-    pub fn new(config: SignalConfig) -> Self {
-        let tone_phases = vec![0.0; config.num_tones];
-        Self {
-            config,
-            phase: 0.0,
-            tone_phases,
+impl SignalConfig {
+    /// Built-in presets as (name, config, description)
+    pub fn presets() -> &'static [(&'static str, SignalConfig, &'static str)] {
+        &PRESETS
+    }
+
+    /// The preset called `name`, if there is one
+    pub fn preset(name: &str) -> Option<SignalConfig> {
+        Self::presets()
+            .iter()
+            .find(|(preset, _, _)| *preset == name)
+            .map(|(_, config, _)| config.clone())
+    }
+
+    /// Offset in Hz of the outermost tones from the base frequency
+    fn max_tone_offset(&self) -> f32 {
+        (self.num_tones.max(1) as f32 - 1.0) / 2.0 * self.frequency_spread
+    }
+
+    /// Lowest and highest tone frequencies in Hz, at the widest spacing
+    /// the spread dither reaches
+    pub fn tone_range(&self) -> (f32, f32) {
+        self.tone_range_at(self.frequency)
+    }
+
+    /// Frequency of each tone in Hz
+    pub fn tone_frequencies(&self) -> Vec<f32> {
+        self.tone_frequencies_at(self.frequency).collect()
+    }
+
+    /// Frequency of each tone in Hz around `base`
+    fn tone_frequencies_at(&self, base: f32) -> impl Iterator<Item = f32> + '_ {
+        let tones = self.num_tones.max(1);
+        let (lowest, highest) = self.nominal_tone_range_at(base);
+        (0..tones).map(move |i| {
+            let position = if tones == 1 {
+                0.5
+            } else {
+                i as f32 / (tones - 1) as f32
+            };
+            match &self.tone_distribution {
+                ToneDistribution::Geometric if lowest > 0.0 && tones > 1 => {
+                    lowest * (highest / lowest).powf(position)
+                }
+                ToneDistribution::Custom(offsets) => base + offsets.get(i).copied().unwrap_or(0.0),
+                _ => lowest + (highest - lowest) * position,
+            }
+        })
+    }
+
+    /// Gain of a tone at `frequency` from the frequency tilt
+    pub fn tilt_gain(&self, frequency: f32) -> f32 {
+        if self.frequency_tilt_db_per_octave == 0.0 || frequency <= 0.0 {
+            return 1.0;
         }
+        let octaves = (frequency / MIN_ULTRASONIC_FREQUENCY).log2();
+        10f32.powf(self.frequency_tilt_db_per_octave * octaves / 20.0)
     }
 
-    /// Generate the next sample
-    pub fn next_sample(&mut self) -> f32 {
-        if self.config.num_tones == 1 {
-            // Single tone generation
-            let sample = self.config.amplitude * (2.0 * PI * self.phase).sin();
-            self.phase += self.config.frequency / self.config.sample_rate as f32;
-            if self.phase >= 1.0 {
-                self.phase -= 1.0;
-            }
-            sample
+    /// Sum of the tilted tone amplitudes around the loudest hop carrier,
+    /// after the master gain, plus the pilot if there is one
+    ///
+    /// The most the tones can add up to in one sample, reached when their
+    /// phases line up.
+    pub fn summed_tone_peak(&self) -> f32 {
+        let pilot = match self.pilot_id {
+            Some(_) => PILOT_AMPLITUDE * self.master_gain,
+            None => 0.0,
+        };
+        if self.waveform == WaveformType::BandNoise {
+            return self.amplitude * self.master_gain + pilot;
+        }
+        let per_tone = self.amplitude * self.master_gain / self.num_tones.max(1) as f32;
+        let tones = std::iter::once(self.frequency)
+            .chain(self.hop_set.iter().copied())
+            .map(|base| {
+                self.tone_frequencies_at(base)
+                    .map(|f| per_tone * self.tilt_gain(f))
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+        tones + pilot
+    }
+
+    /// A tone frequency around `base` that comes too close to the pilot
+    ///
+    /// Band noise counts as one tone spread over its band, and dithered
+    /// tones over their full swing.
+    fn pilot_clash_at(&self, base: f32) -> Option<f32> {
+        let (pilot_low, pilot_high) = pilot::pilot_band();
+        let overlaps = |low: f32, high: f32| low <= pilot_high && high >= pilot_low;
+
+        if self.waveform == WaveformType::BandNoise {
+            let (lowest, highest) = self.tone_range_at(base);
+            let center = (lowest + highest) / 2.0;
+            let half = (highest - lowest).max(MIN_NOISE_BANDWIDTH_HZ) / 2.0;
+            return overlaps(center - half, center + half).then_some(center);
+        }
+
+        let depth = if self.has_spread_dither() {
+            self.spread_dither_depth
         } else {
-            // Multi-tone generation
-            let mut sample = 0.0;
-            let amplitude_per_tone = self.config.amplitude / self.config.num_tones as f32;
-
-            for (i, phase) in self.tone_phases.iter_mut().enumerate() {
-                let offset = (i as f32 - (self.config.num_tones as f32 - 1.0) / 2.0)
-                    * self.config.frequency_spread;
-                let freq = self.config.frequency + offset;
-
-                sample += amplitude_per_tone * (2.0 * PI * *phase).sin();
-                *phase += freq / self.config.sample_rate as f32;
-                if *phase >= 1.0 {
-                    *phase -= 1.0;
-                }
+            0.0
+        };
+        self.tone_frequencies_at(base).find(|&tone| {
+            let (a, b) = (
+                base + (tone - base) * (1.0 - depth),
+                base + (tone - base) * (1.0 + depth),
+            );
+            overlaps(a.min(b), a.max(b))
+        })
+    }
+
+    /// Lowest and highest frequency any tone reaches, across every hop carrier
+    pub fn occupied_band(&self) -> (f32, f32) {
+        std::iter::once(self.frequency)
+            .chain(self.hop_set.iter().copied())
+            .map(|base| self.tone_range_at(base))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(low, high), (l, h)| {
+                (low.min(l), high.max(h))
+            })
+    }
+
+    /// Lowest sample rate whose Nyquist limit clears every tone, the pilot
+    /// and the guard band
+    pub fn required_sample_rate(&self) -> u32 {
+        let (_, mut highest) = self.occupied_band();
+        if self.pilot_id.is_some() {
+            highest = highest.max(pilot::pilot_band().1);
+        }
+        (2.0 * (highest + self.nyquist_guard_hz)).floor() as u32 + 1
+    }
+
+    /// Whether the tone spacing is dithered while playing
+    fn has_spread_dither(&self) -> bool {
+        self.spread_dither_depth > 0.0
+            && self.num_tones > 1
+            && self.waveform != WaveformType::BandNoise
+    }
+
+    /// Lowest and highest frequencies in Hz the tones around `base` reach,
+    /// scaling their offsets by the dither's full swing either way
+    fn tone_range_at(&self, base: f32) -> (f32, f32) {
+        let (lowest, highest) = self.nominal_tone_range_at(base);
+        if !self.has_spread_dither() {
+            return (lowest, highest);
+        }
+
+        let depth = self.spread_dither_depth;
+        let swing = |offset: f32| [offset * (1.0 - depth), offset * (1.0 + depth)];
+        let (low, high) = (swing(lowest - base), swing(highest - base));
+        (base + low[0].min(low[1]), base + high[0].max(high[1]))
+    }
+
+    /// Lowest and highest tone frequencies in Hz around `base`, undithered
+    fn nominal_tone_range_at(&self, base: f32) -> (f32, f32) {
+        match &self.tone_distribution {
+            ToneDistribution::Custom(offsets) if !offsets.is_empty() => {
+                let lowest = offsets.iter().copied().fold(f32::INFINITY, f32::min);
+                let highest = offsets.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                (base + lowest, base + highest)
+            }
+            _ => {
+                let offset = self.max_tone_offset();
+                (base - offset, base + offset)
             }
-            sample
         }
     }
 
-    /// Generate a buffer of samples
-    pub fn generate_buffer(&mut self, buffer: &mut [f32]) {
-        for sample in buffer.iter_mut() {
-            *sample = self.next_sample();
+    /// Check that every tone around `base` is below Nyquist and, unless
+    /// `allow_audible`, ultrasonic
+    fn check_base_frequency(
+        &self,
+        base: f32,
+        allow_audible: bool,
+    ) -> Result<(), SignalConfigError> {
+        if !base.is_finite() || base <= 0.0 {
+            return Err(SignalConfigError::InvalidFrequency(base));
+        }
+
+        let (lowest, highest) = self.tone_range_at(base);
+        if lowest < MIN_ULTRASONIC_FREQUENCY && !allow_audible {
+            return Err(SignalConfigError::AudibleTone {
+                lowest,
+                limit: MIN_ULTRASONIC_FREQUENCY,
+            });
+        }
+
+        let nyquist = self.sample_rate as f32 / 2.0;
+        if highest >= nyquist {
+            return Err(SignalConfigError::AboveNyquist {
+                highest,
+                sample_rate: self.sample_rate,
+            });
+        }
+        if self.nyquist_guard_hz > 0.0 && highest > nyquist - self.nyquist_guard_hz {
+            return Err(SignalConfigError::InGuardBand {
+                highest,
+                limit: nyquist - self.nyquist_guard_hz,
+            });
         }
+
+        Ok(())
     }
 
-    /// Get the current configuration
-    pub fn config(&self) -> &SignalConfig {
-        &self.config
+    /// Check that the configuration is usable and every tone, including
+    /// every hop carrier, is ultrasonic and below the Nyquist limit
+    pub fn validate(&self) -> Result<(), SignalConfigError> {
+        self.validate_with(false)
     }
 
-    /// Update the configuration
-    pub fn update_config(&mut self, config: SignalConfig) {
-        self.config = config;
-        self.tone_phases = vec![0.0; self.config.num_tones];
-        self.phase = 0.0;
+    /// Like [`validate`](Self::validate), but lets tones into the audible band
+    ///
+    /// For research sweeps that map where jamming stops working below
+    /// 20 kHz. Tones must still be below Nyquist, so nothing aliases. Never
+    /// play such a config through a jammer.
+    pub fn validate_allowing_audible(&self) -> Result<(), SignalConfigError> {
+        self.validate_with(true)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    fn validate_with(&self, allow_audible: bool) -> Result<(), SignalConfigError> {
+        if !(0.0..=1.0).contains(&self.amplitude) {
+            return Err(SignalConfigError::InvalidAmplitude(self.amplitude));
+        }
+        if !(0.0..=1.0).contains(&self.master_gain) {
+            return Err(SignalConfigError::InvalidMasterGain(self.master_gain));
+        }
+        if self.num_tones == 0 {
+            return Err(SignalConfigError::NoTones);
+        }
+        if !self.frequency_spread.is_finite() || self.frequency_spread < 0.0 {
+            return Err(SignalConfigError::InvalidSpread(self.frequency_spread));
+        }
+        if self.sample_rate == 0 {
+            return Err(SignalConfigError::InvalidSampleRate);
+        }
+        if !self.crossfade_ms.is_finite() || self.crossfade_ms < 0.0 {
+            return Err(SignalConfigError::InvalidCrossfade(self.crossfade_ms));
+        }
+        if !(0.0..=1.0).contains(&self.spread_dither_depth) {
+            return Err(SignalConfigError::InvalidSpreadDither(
+                self.spread_dither_depth,
+            ));
+        }
+        if self.spread_dither_depth > 0.0
+            && !(self.spread_dither_rate_hz.is_finite() && self.spread_dither_rate_hz > 0.0)
+        {
+            return Err(SignalConfigError::InvalidSpreadDitherRate(
+                self.spread_dither_rate_hz,
+            ));
+        }
 
-    #[test]
-    fn test_signal_generation() {
-        let config = SignalConfig::default();
-        let mut generator = SignalGenerator::new(config);
+        if !(0.0..self.sample_rate as f32 / 2.0).contains(&self.nyquist_guard_hz) {
+            return Err(SignalConfigError::InvalidNyquistGuard(
+                self.nyquist_guard_hz,
+            ));
+        }
+        if !self.frequency_tilt_db_per_octave.is_finite() {
+            return Err(SignalConfigError::InvalidTilt(
+                self.frequency_tilt_db_per_octave,
+            ));
+        }
+        if let Some(&offset) = self.channel_phase_offsets.iter().find(|o| !o.is_finite()) {
+            return Err(SignalConfigError::InvalidPhaseOffset(offset));
+        }
+        if let Some(beam) = &self.beamform {
+            if beam.speaker_positions.is_empty() {
+                return Err(SignalConfigError::NoSpeakers);
+            }
+            if let Some(&[x, y]) = beam
+                .speaker_positions
+                .iter()
+                .find(|p| !p.iter().all(|c| c.is_finite()))
+            {
+                return Err(SignalConfigError::InvalidSpeakerPosition { x, y });
+            }
+            if !beam.target_angle_deg.is_finite() {
+                return Err(SignalConfigError::InvalidBeamAngle(beam.target_angle_deg));
+            }
+        }
+        if let ToneDistribution::Custom(offsets) = &self.tone_distribution {
+            if offsets.len() != self.num_tones {
+                return Err(SignalConfigError::ToneCountMismatch {
+                    offsets: offsets.len(),
+                    tones: self.num_tones,
+                });
+            }
+            if let Some(&offset) = offsets.iter().find(|o| !o.is_finite()) {
+                return Err(SignalConfigError::InvalidToneOffset(offset));
+            }
+        }
 
-        let mut buffer = vec![0.0; 1000];
-        generator.generate_buffer(&mut buffer);
+        self.check_base_frequency(self.frequency, allow_audible)?;
 
-        // Verify samples are within expected range
-        for sample in &buffer {
-            assert!(sample.abs() <= 1.0);
+        if !self.hop_set.is_empty() {
+            if !self.hop_interval_ms.is_finite() || self.hop_interval_ms <= 0.0 {
+                return Err(SignalConfigError::InvalidHopInterval(self.hop_interval_ms));
+            }
+            for &hop in &self.hop_set {
+                self.check_base_frequency(hop, allow_audible)?;
+            }
         }
+
+        if self.pilot_id.is_some() {
+            let (_, highest) = pilot::pilot_band();
+            let limit = self.sample_rate as f32 / 2.0 - self.nyquist_guard_hz;
+            if highest >= limit {
+                return Err(SignalConfigError::PilotAboveNyquist {
+                    highest,
+                    sample_rate: self.sample_rate,
+                });
+            }
+            let clash = std::iter::once(self.frequency)
+                .chain(self.hop_set.iter().copied())
+                .find_map(|base| self.pilot_clash_at(base));
+            if let Some(tone) = clash {
+                return Err(SignalConfigError::PilotClash {
+                    tone,
+                    pilot: pilot::PILOT_FREQUENCY_HZ,
+                });
+            }
+        }
+
+        Ok(())
     }
 
-    #[test]
-    fn test_multi_tone_generation() {
-        let config = SignalConfig {
-            num_tones: 5,
-            ..Default::default()
+    /// Non-fatal issues with the configuration, for display to the user
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let (lowest, _) = self.tone_range();
+
+        if self.waveform.is_harmonic_rich() {
+            if lowest * 2.0 >= nyquist {
+                warnings.push(format!(
+                    "{:?} waveform has no harmonics below the {:.0} Hz Nyquist limit at {} Hz \
+                     and reduces to a sine",
+                    self.waveform, nyquist, self.sample_rate
+                ));
+            } else {
+                warnings.push(format!(
+                    "{:?} waveform is band-limited to harmonics below {:.0} Hz",
+                    self.waveform, nyquist
+                ));
+            }
+        }
+
+        if self.frequency_tilt_db_per_octave != 0.0 && self.waveform == WaveformType::BandNoise {
+            warnings.push("Frequency tilt does not apply to band noise and is ignored".to_string());
+        }
+
+        let peak = self.summed_tone_peak();
+        if self.frequency_tilt_db_per_octave != 0.0 && peak > 1.0 {
+            warnings.push(format!(
+                "Frequency tilt raises the summed tone peak to {:.2}, so the tones clip \
+                 where they line up; lower the amplitude or master gain",
+                peak
+            ));
+        }
+
+        if self.beamform.is_some() && self.waveform == WaveformType::BandNoise {
+            warnings.push("Beamforming does not apply to band noise and is ignored".to_string());
+        }
+
+        if self.safety_highpass && SAFETY_HIGHPASS_HZ >= nyquist {
+            warnings.push(format!(
+                "Safety high-pass at {:.0} Hz is above the Nyquist limit at {} Hz and is disabled",
+                SAFETY_HIGHPASS_HZ, self.sample_rate
+            ));
+        }
+
+        warnings
+    }
+
+    /// Render half a second of the signal and rate its audible-band leakage
+    ///
+    /// Covers everything the generator adds, including the safety high-pass,
+    /// hop crossfades and band-limited harmonics.
+    pub fn audibility_report(&self) -> AudibilityReport {
+        let len = ((self.sample_rate as f32 * AUDIBILITY_RENDER_SECS) as usize).max(2);
+        let mut buffer = vec![0.0; len];
+        // An unseeded config is rated the same way every time
+        SignalGenerator::new(SignalConfig {
+            seed: self.seed.or(Some(0)),
+            ..self.clone()
+        })
+        .generate_buffer(&mut buffer);
+
+        let spectrum =
+            analyze_samples(&buffer, self.sample_rate).expect("buffer has at least two samples");
+        AudibilityReport::from(&spectrum)
+    }
+
+    /// Loudest copy of this config whose audible-band peak stays at or
+    /// below `max_audible_dbfs`
+    ///
+    /// Binary-searches the amplitude between zero and the configured value,
+    /// rating each step with [`Self::audibility_report`], so it never turns
+    /// the signal up. Returns an amplitude of zero if nothing louder passes.
+    pub fn fit_to_audibility_budget(&self, max_audible_dbfs: f64) -> SignalConfig {
+        let with_amplitude = |amplitude| SignalConfig {
+            amplitude,
+            ..self.clone()
         };
-        let mut generator = SignalGenerator::new(config);
+        if self.audibility_report().audible_peak_dbfs <= max_audible_dbfs {
+            return self.clone();
+        }
 
-        let mut buffer = vec![0.0; 1000];
-        generator.generate_buffer(&mut buffer);
+        // Silence always passes, so it is never rendered
+        let (mut passes, mut fails) = (0.0, self.amplitude);
+        for _ in 0..BUDGET_FIT_STEPS {
+            let amplitude = (passes + fails) / 2.0;
+            if with_amplitude(amplitude)
+                .audibility_report()
+                .audible_peak_dbfs
+                <= max_audible_dbfs
+            {
+                passes = amplitude;
+            } else {
+                fails = amplitude;
+            }
+        }
+        with_amplitude(passes)
+    }
 
-        // Verify samples are within expected range
-        for sample in &buffer {
-            assert!(sample.abs() <= 1.0);
+    /// Center frequency and bandwidth in Hz of the band-noise waveform
+    fn noise_band(&self) -> (f32, f32) {
+        let (lowest, highest) = self.tone_range();
+        (
+            (lowest + highest) / 2.0,
+            (highest - lowest).max(MIN_NOISE_BANDWIDTH_HZ),
+        )
+    }
+
+    /// Copy `other` into `self`, reusing the list fields' allocations
+    fn assign_from(&mut self, other: &SignalConfig) {
+        let mut hop_set = std::mem::take(&mut self.hop_set);
+        let mut channel_phase_offsets = std::mem::take(&mut self.channel_phase_offsets);
+        let mut tone_distribution = std::mem::take(&mut self.tone_distribution);
+        let mut beamform = self.beamform.take();
+        hop_set.clone_from(&other.hop_set);
+        channel_phase_offsets.clone_from(&other.channel_phase_offsets);
+        tone_distribution.assign_from(&other.tone_distribution);
+        Beamform::assign(&mut beamform, &other.beamform);
+        *self = SignalConfig {
+            hop_set,
+            channel_phase_offsets,
+            tone_distribution,
+            beamform,
+            ..*other
+        };
+    }
+
+    /// Phase offset of `channel` in cycles
+    fn phase_offset(&self, channel: usize) -> f32 {
+        self.channel_phase_offsets
+            .get(channel)
+            .map_or(0.0, |degrees| degrees / 360.0)
+    }
+
+    /// Raise the base frequency so every tone stays above 20 kHz
+    ///
+    /// Returns the original frequency if it had to be changed.
+    pub fn adjust_to_ultrasonic(&mut self) -> Option<f32> {
+        let (lowest, _) = self.tone_range();
+        if lowest >= MIN_ULTRASONIC_FREQUENCY {
+            return None;
+        }
+
+        let original = self.frequency;
+        self.frequency += MIN_ULTRASONIC_FREQUENCY + ADJUST_MARGIN_HZ - lowest;
+        Some(original)
+    }
+}
+
+/// Advance a xorshift64 state and return the new value
+pub(crate) fn xorshift64(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Fresh seed for a config that did not set one
+fn entropy_seed() -> u64 {
+    // Each RandomState is keyed from the OS's random source
+    RandomState::new().build_hasher().finish()
+}
+
+/// Starting xorshift state of one random stream of `config`
+///
+/// Mixes the config's seed with the stream's own `seed` and `constant`.
+/// A seed of zero leaves the stream as it was before seeds existed.
+fn stream_state(config: &SignalConfig, seed: u64, constant: u64) -> u64 {
+    let shared = config.seed.unwrap_or(0).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    // xorshift must not start at zero
+    (seed ^ constant ^ shared).max(1)
+}
+
+/// White noise through a band-pass biquad
+struct BandNoise {
+    rng: u64,
+    filter: Biquad,
+}
+
+impl BandNoise {
+    fn new(config: &SignalConfig) -> Self {
+        let (center, bandwidth) = config.noise_band();
+        Self {
+            rng: stream_state(config, 0, 0x853c_49e6_748f_ea9b),
+            filter: Biquad::band_pass(center, bandwidth, config.sample_rate),
+        }
+    }
+
+    fn next(&mut self) -> f32 {
+        let x = (xorshift64(&mut self.rng) >> 40) as f32 / (1u64 << 23) as f32 - 1.0;
+
+        self.filter.process(x)
+    }
+}
+
+/// Starting phase of each tone, zero or seeded from `phase_seed`
+fn initial_phases(config: &SignalConfig) -> impl Iterator<Item = f64> {
+    let random = config.random_phases;
+    let mut rng = stream_state(config, config.phase_seed, 0x2545_f491_4f6c_dd1d);
+    (0..config.num_tones).map(move |_| {
+        if random {
+            (xorshift64(&mut rng) >> 40) as f64 / (1u64 << 24) as f64
+        } else {
+            0.0
+        }
+    })
+}
+
+/// Slow random wander between -1 and 1 that scales the tone spacing
+///
+/// Glides linearly to a new random target at the configured rate, so the
+/// spacing never jumps.
+struct SpreadDither {
+    rng: u64,
+    value: f32,
+    step: f32,
+    period: usize,
+    until_target: usize,
+}
+
+impl SpreadDither {
+    /// Returns `None` when the config does not dither its spacing
+    fn new(config: &SignalConfig) -> Option<Self> {
+        if !config.has_spread_dither() {
+            return None;
+        }
+
+        let period =
+            ((config.sample_rate as f32 / config.spread_dither_rate_hz).round() as usize).max(1);
+        Some(Self {
+            rng: stream_state(config, config.phase_seed, 0xd1b5_4a32_d192_ed03),
+            value: 0.0,
+            step: 0.0,
+            period,
+            until_target: 0,
+        })
+    }
+
+    /// Advance one sample and return the current position
+    fn next(&mut self) -> f32 {
+        if self.until_target == 0 {
+            let target = (xorshift64(&mut self.rng) >> 40) as f32 / (1u64 << 23) as f32 - 1.0;
+            self.step = (target - self.value) / self.period as f32;
+            self.until_target = self.period;
+        }
+        self.until_target -= 1;
+        self.value = (self.value + self.step).clamp(-1.0, 1.0);
+        self.value
+    }
+}
+
+/// Oscillator state for one configuration's set of tones
+struct ToneSet {
+    config: SignalConfig,
+    phase: f64,
+    tone_phases: Vec<f64>,
+    /// Frequency of each tone, kept in step with `config`
+    tone_frequencies: Vec<f32>,
+    /// Tilt gain of each tone at its nominal frequency
+    tone_gains: Vec<f32>,
+    /// Beamforming delay of each channel in seconds, empty without it
+    channel_delays: Vec<f32>,
+    noise: BandNoise,
+    dither: Option<SpreadDither>,
+}
+
+impl ToneSet {
+    fn new(config: SignalConfig) -> Self {
+        let tone_phases = initial_phases(&config).collect();
+        let tone_frequencies = config.tone_frequencies();
+        let tone_gains = tone_frequencies
+            .iter()
+            .map(|&f| config.tilt_gain(f))
+            .collect();
+        let channel_delays = config
+            .beamform
+            .as_ref()
+            .map_or_else(Vec::new, Beamform::channel_delays);
+        let noise = BandNoise::new(&config);
+        let dither = SpreadDither::new(&config);
+        Self {
+            config,
+            phase: 0.0,
+            tone_phases,
+            tone_frequencies,
+            tone_gains,
+            channel_delays,
+            noise,
+            dither,
+        }
+    }
+
+    /// Start over from `config`, reusing allocations where capacity allows
+    ///
+    /// Behaves like [`ToneSet::new`], but does not allocate when switching
+    /// between configs of the same shape, as hops do.
+    fn reset_to(&mut self, config: &SignalConfig) {
+        self.config.assign_from(config);
+        self.phase = 0.0;
+        self.tone_phases.clear();
+        self.tone_phases.extend(initial_phases(config));
+        match &config.beamform {
+            Some(beam) => beam.write_delays(&mut self.channel_delays),
+            None => self.channel_delays.clear(),
         }
+        self.dither = SpreadDither::new(config);
+        self.update_tones();
+    }
+
+    /// Move the base frequency, keeping the oscillator phases
+    fn set_frequency(&mut self, frequency: f32) {
+        self.config.frequency = frequency;
+        self.update_tones();
+    }
+
+    /// Recompute what depends on the tone frequencies after a config change
+    fn update_tones(&mut self) {
+        self.tone_frequencies.clear();
+        self.tone_frequencies
+            .extend(self.config.tone_frequencies_at(self.config.frequency));
+        self.tone_gains.clear();
+        self.tone_gains.extend(
+            self.tone_frequencies
+                .iter()
+                .map(|&f| self.config.tilt_gain(f)),
+        );
+        self.noise = BandNoise::new(&self.config);
+    }
+
+    /// Fill one sample per channel, each tone shifted by the channel's
+    /// offset and delayed by its beamforming delay
+    ///
+    /// Band noise has no phase to shift and is the same on every channel.
+    fn next_frame<S: Sample>(&mut self, frame: &mut [S]) {
+        let waveform = self.config.waveform;
+        let sample_rate = self.config.sample_rate;
+
+        if waveform == WaveformType::BandNoise {
+            frame.fill(S::from_f32(
+                self.config.amplitude * self.config.master_gain * self.noise.next(),
+            ));
+            return;
+        }
+
+        // A single tone keeps its own phase; multi-tone uses one per tone
+        let phases = if self.config.num_tones == 1 {
+            std::slice::from_mut(&mut self.phase)
+        } else {
+            &mut self.tone_phases[..]
+        };
+        // The master gain scales the sum; it is folded into each tone here
+        let amplitude_per_tone =
+            self.config.amplitude * self.config.master_gain / self.config.num_tones as f32;
+        let base = self.config.frequency;
+        let spacing = match &mut self.dither {
+            Some(dither) => 1.0 + self.config.spread_dither_depth * dither.next(),
+            None => 1.0,
+        };
+
+        // Channels past the listed offsets and speakers repeat the first
+        // instead of evaluating every tone again
+        let distinct = self
+            .config
+            .channel_phase_offsets
+            .len()
+            .max(self.channel_delays.len())
+            .max(1);
+        let (own, copies) = frame.split_at_mut(distinct.min(frame.len()));
+
+        own.fill(S::default());
+        for ((phase, &nominal), &gain) in phases
+            .iter_mut()
+            .zip(&self.tone_frequencies)
+            .zip(&self.tone_gains)
+        {
+            let freq = base + (nominal - base) * spacing;
+            let amplitude = S::from_f32(amplitude_per_tone * gain);
+            for (channel, sample) in own.iter_mut().enumerate() {
+                // A delay of t seconds sets the tone back by freq * t cycles
+                let delay = self.channel_delays.get(channel).map_or(0.0, |&t| freq * t);
+                let offset = self.config.phase_offset(channel) - delay.fract();
+                let shifted = S::shifted(*phase, offset);
+                *sample += amplitude * waveform.sample(shifted, freq, sample_rate);
+            }
+            S::advance(phase, freq, sample_rate);
+        }
+        if let Some(&first) = own.first() {
+            copies.fill(first);
+        }
+    }
+}
+
+/// Seeded pseudo-random carrier schedule for frequency hopping
+struct Hopper {
+    rng: u64,
+    interval: usize,
+    until_hop: usize,
+}
+
+impl Hopper {
+    /// Returns `None` when the config has no hop set
+    fn new(config: &SignalConfig) -> Option<Self> {
+        if config.hop_set.is_empty() {
+            return None;
+        }
+
+        let interval =
+            ((config.hop_interval_ms / 1000.0 * config.sample_rate as f32).round() as usize).max(1);
+        Some(Self {
+            rng: stream_state(config, config.hop_seed, 0x9e37_79b9_7f4a_7c15),
+            interval,
+            until_hop: interval,
+        })
+    }
+
+    /// Pick the next carrier, avoiding a repeat of `current` when possible
+    ///
+    /// Does not allocate, since hops happen on the audio thread.
+    fn pick(&mut self, hop_set: &[f32], current: Option<f32>) -> f32 {
+        let allowed = |f: &f32| hop_set.len() == 1 || Some(*f) != current;
+        let count = match hop_set.iter().filter(|f| allowed(f)).count() {
+            0 => hop_set.len(),
+            count => count,
+        };
+        let index = (xorshift64(&mut self.rng) % count as u64) as usize;
+
+        if count == hop_set.len() {
+            hop_set[index]
+        } else {
+            hop_set.iter().copied().filter(allowed).nth(index).unwrap()
+        }
+    }
+
+    /// Count down one sample; true when it is time to hop
+    fn tick(&mut self) -> bool {
+        self.until_hop -= 1;
+        if self.until_hop == 0 {
+            self.until_hop = self.interval;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Oscillator and hop position written by [`SignalGenerator::save_state`]
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct GeneratorState {
+    /// Config of the current tone set, including the current hop carrier
+    config: SignalConfig,
+    phase: f64,
+    tone_phases: Vec<f64>,
+    hop: Option<HopState>,
+}
+
+impl GeneratorState {
+    /// Empty state with room for a generator running `config`
+    pub(crate) fn with_capacity_for(config: &SignalConfig) -> Self {
+        Self {
+            config: config.clone(),
+            phase: 0.0,
+            tone_phases: Vec::with_capacity(config.num_tones),
+            hop: None,
+        }
+    }
+
+    /// Write the state to `path`, replacing it atomically
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HopState {
+    rng: u64,
+    until_hop: usize,
+}
+
+/// Previous tone set fading out after a config change
+struct Crossfade {
+    old: ToneSet,
+    remaining: usize,
+    total: usize,
+}
+
+/// A config change built ahead of [`SignalGenerator::apply`]
+///
+/// Holds everything the switch allocates, so it can be prepared on one
+/// thread and applied on the audio thread.
+pub(crate) struct PreparedConfig {
+    tones: ToneSet,
+    spare: ToneSet,
+    highpass: Vec<HighPass>,
+}
+
+impl PreparedConfig {
+    /// Build `config` for a generator rendering frames of `channels`
+    pub(crate) fn new(config: SignalConfig, channels: usize) -> Self {
+        Self {
+            highpass: SignalGenerator::safety_filter(&config, channels),
+            spare: ToneSet::new(config.clone()),
+            tones: ToneSet::new(config),
+        }
+    }
+}
+
+/// Parts swapped out by [`SignalGenerator::apply`], to be dropped elsewhere
+pub(crate) struct Displaced {
+    _tones: [Option<ToneSet>; 3],
+    _highpass: Vec<HighPass>,
+}
+
+/// Output gain rising linearly from zero to one
+struct Ramp {
+    position: usize,
+    total: usize,
+}
+
+/// Generator for ultrasonic signals
+pub struct SignalGenerator {
+    current: ToneSet,
+    fade: Option<Crossfade>,
+    /// Tone set kept for the next hop, so hopping does not allocate
+    spare: Option<ToneSet>,
+    /// Tone set waiting to be freed off the audio thread
+    retired: Option<ToneSet>,
+    ramp: Option<Ramp>,
+    /// Old tone set's frame during a crossfade
+    fade_frame: Vec<f32>,
+    /// Safety high-pass for each channel, empty when disabled
+    highpass: Vec<HighPass>,
+    hopper: Option<Hopper>,
+    /// Kept across retunes and hops, so its frames are never cut short
+    pilot: Option<PilotTone>,
+    /// Whether the seed was drawn rather than chosen, so saved state may
+    /// bring its own
+    seed_from_entropy: bool,
+}
+
+impl SignalGenerator {
+    /// Create a new signal generator with the given configuration
+    ///
+    /// With a hop set, the first carrier is drawn from the hop schedule and
+    /// replaces `config.frequency`. Without a seed, one is drawn from entropy.
+    pub fn new(config: SignalConfig) -> Self {
+        Self::with_channels(config, 1)
+    }
+
+    /// Create a generator for frames of `channels` samples
+    ///
+    /// Rendering frames that wide never allocates; [`new`](Self::new)
+    /// sizes for mono and grows on the first wider frame.
+    pub fn with_channels(mut config: SignalConfig, channels: usize) -> Self {
+        let channels = channels.max(1);
+        let seed_from_entropy = config.seed.is_none();
+        config.seed.get_or_insert_with(entropy_seed);
+        let highpass = Self::safety_filter(&config, channels);
+        let mut hopper = Hopper::new(&config);
+        if let Some(hopper) = &mut hopper {
+            config.frequency = hopper.pick(&config.hop_set, None);
+        }
+        let pilot = config
+            .pilot_id
+            .map(|id| PilotTone::new(id, config.sample_rate));
+
+        Self {
+            spare: Some(ToneSet::new(config.clone())),
+            retired: None,
+            current: ToneSet::new(config),
+            fade: None,
+            ramp: None,
+            fade_frame: vec![0.0; channels],
+            highpass,
+            hopper,
+            pilot,
+            seed_from_entropy,
+        }
+    }
+
+    /// One filter per channel, or none when disabled
+    fn safety_filter(config: &SignalConfig, channels: usize) -> Vec<HighPass> {
+        if !config.safety_highpass {
+            return Vec::new();
+        }
+        HighPass::butterworth(SAFETY_HIGHPASS_HZ, config.sample_rate)
+            .map(|filter| vec![filter; channels])
+            .unwrap_or_default()
+    }
+
+    /// Frame width the buffers and filters are sized for
+    fn channels(&self) -> usize {
+        self.fade_frame.len()
+    }
+
+    /// Grow the buffers and filters for frames of `channels` samples
+    fn grow_channels(&mut self, channels: usize) {
+        self.fade_frame.resize(channels, 0.0);
+        if let Some(first) = self.highpass.first() {
+            let mut filter = first.clone();
+            filter.reset();
+            self.highpass.resize(channels, filter);
+        }
+    }
+
+    /// Generate the next sample, on the first channel's phase
+    pub fn next_sample(&mut self) -> f32 {
+        let mut frame = [0.0];
+        self.next_frame(&mut frame);
+        frame[0]
+    }
+
+    /// Generate the next frame, one sample per channel
+    ///
+    /// Each channel is shifted by its entry in `channel_phase_offsets`.
+    /// Output is limited to -1.0..=1.0, since band-limited square and
+    /// sawtooth waves overshoot their nominal amplitude.
+    pub fn next_frame(&mut self, frame: &mut [f32]) {
+        if frame.len() > self.channels() {
+            self.grow_channels(frame.len());
+        }
+        let mut fade_frame = std::mem::take(&mut self.fade_frame);
+        self.render(frame, &mut fade_frame[..frame.len()]);
+        self.fade_frame = fade_frame;
+    }
+
+    /// Generate the next mono sample at full double precision
+    ///
+    /// Same signal as [`next_sample`](Self::next_sample), but with the
+    /// oscillators stepped and summed in `f64`, so long renders do not
+    /// drift off frequency. Band noise and the pilot are still drawn in
+    /// `f32`. Both paths advance the same generator.
+    pub fn next_sample_f64(&mut self) -> f64 {
+        let (mut frame, mut fade_frame) = ([0.0], [0.0]);
+        self.render(&mut frame, &mut fade_frame);
+        frame[0]
+    }
+
+    /// Generate a buffer of mono samples at full double precision
+    ///
+    /// See [`next_sample_f64`](Self::next_sample_f64).
+    pub fn generate_buffer_f64(&mut self, buffer: &mut [f64]) {
+        buffer
+            .iter_mut()
+            .for_each(|sample| *sample = self.next_sample_f64());
+    }
+
+    /// Fill `frame`, with the old tone set rendered into `fade_frame`, a
+    /// buffer of the same length, during a crossfade
+    fn render<S: Sample>(&mut self, frame: &mut [S], fade_frame: &mut [S]) {
+        self.raw_frame(frame, fade_frame);
+        // Added after any crossfade, so retunes leave it at full level
+        if let Some(pilot) = &mut self.pilot {
+            let sample =
+                S::from_f32(PILOT_AMPLITUDE * self.current.config.master_gain * pilot.next());
+            frame.iter_mut().for_each(|s| *s += sample);
+        }
+
+        if let Some(ramp) = &mut self.ramp {
+            let gain = S::ratio(ramp.position, ramp.total);
+            frame.iter_mut().for_each(|sample| *sample *= gain);
+            ramp.position += 1;
+            if ramp.position >= ramp.total {
+                self.ramp = None;
+            }
+        }
+
+        for (channel, sample) in frame.iter_mut().enumerate() {
+            if let Some(filter) = self.highpass.get_mut(channel) {
+                *sample = sample.high_pass(filter);
+            }
+            *sample = sample.clamp_unit();
+        }
+    }
+
+    fn raw_frame<S: Sample>(&mut self, frame: &mut [S], fade_frame: &mut [S]) {
+        let mut hop = None;
+        if let Some(hopper) = &mut self.hopper {
+            if hopper.tick() {
+                let config = &self.current.config;
+                let frequency = hopper.pick(&config.hop_set, Some(config.frequency));
+                // Keep the fade well inside the hop interval
+                let fade_samples = Self::fade_samples(config).min(hopper.interval / 2);
+                hop = Some((frequency, fade_samples));
+            }
+        }
+        if let Some((frequency, fade_samples)) = hop {
+            let mut next = match self.spare.take() {
+                Some(spare) => spare,
+                None => ToneSet::new(self.current.config.clone()),
+            };
+            next.reset_to(&self.current.config);
+            next.set_frequency(frequency);
+            for displaced in self.crossfade_to(next, fade_samples).into_iter().flatten() {
+                self.recycle(displaced);
+            }
+        }
+
+        self.current.next_frame(frame);
+
+        let Some(fade) = &mut self.fade else {
+            return;
+        };
+
+        // Linear gains keep the sum within the configured amplitude
+        let one = S::from_f32(1.0);
+        let gain = one - S::ratio(fade.remaining, fade.total);
+        fade.old.next_frame(fade_frame);
+        for (sample, &old) in frame.iter_mut().zip(fade_frame.iter()) {
+            *sample = old * (one - gain) + *sample * gain;
+        }
+
+        fade.remaining -= 1;
+        if fade.remaining == 0 {
+            if let Some(fade) = self.fade.take() {
+                self.recycle(fade.old);
+            }
+        }
+    }
+
+    /// Generate a buffer of mono samples
+    pub fn generate_buffer(&mut self, buffer: &mut [f32]) {
+        self.generate_interleaved(buffer, 1);
+    }
+
+    /// Generate the next `N` mono samples as an array
+    ///
+    /// The same samples [`generate_buffer`](Self::generate_buffer) writes to
+    /// an `N`-sample slice, for fixed-size processing loops that keep the
+    /// block on the stack.
+    pub fn next_block<const N: usize>(&mut self) -> [f32; N] {
+        let mut block = [0.0; N];
+        self.generate_buffer(&mut block);
+        block
+    }
+
+    /// Render `duration` of mono signal and measure its levels
+    ///
+    /// Advances the generator, so measure a fresh one to check a config
+    /// before playing it.
+    pub fn measure(&mut self, duration: Duration) -> SignalStats {
+        let len = (duration.as_secs_f64() * self.config().sample_rate as f64) as usize;
+        let mut buffer = vec![0.0; len];
+        self.generate_buffer(&mut buffer);
+        SignalStats::measure(&buffer)
+    }
+
+    /// Fill an interleaved buffer of `channels` channels
+    ///
+    /// The signal advances once per frame, with each channel on its
+    /// `channel_phase_offsets` entry. A trailing partial frame gets the
+    /// first channels of a full one. `channels` of 0 is treated as mono.
+    pub fn generate_interleaved(&mut self, buffer: &mut [f32], channels: usize) {
+        for frame in buffer.chunks_mut(channels.max(1)) {
+            self.next_frame(frame);
+        }
+    }
+
+    /// Get the current configuration
+    pub fn config(&self) -> &SignalConfig {
+        &self.current.config
+    }
+
+    /// Fade the output in from silence over `duration`
+    ///
+    /// The level rises linearly and reaches the configured amplitude
+    /// `duration` after the next sample. Restarts any fade in progress.
+    pub fn ramp_amplitude(&mut self, duration: Duration) {
+        let total = (duration.as_secs_f64() * self.current.config.sample_rate as f64).round();
+        self.ramp = (total >= 1.0).then_some(Ramp {
+            position: 0,
+            total: total as usize,
+        });
+    }
+
+    /// Whether the output is still fading in
+    pub fn is_ramping(&self) -> bool {
+        self.ramp.is_some()
+    }
+
+    /// Whether a crossfade from a previous configuration is in progress
+    pub fn is_crossfading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Update the configuration
+    ///
+    /// The previous tones keep running and fade out over the new config's
+    /// `crossfade_ms` while the new ones fade in, so retuning does not
+    /// click. A sample rate change restarts the generator instead. A hop
+    /// set restarts its schedule from the new config. A config without a
+    /// seed keeps the current one.
+    pub fn update_config(&mut self, mut config: SignalConfig) {
+        if config.seed.is_none() {
+            config.seed = self.current.config.seed;
+        }
+        drop(self.apply(PreparedConfig::new(config, self.channels())));
+    }
+
+    /// Switch to a prepared config without allocating or freeing memory
+    pub(crate) fn apply(&mut self, prepared: PreparedConfig) -> Displaced {
+        let PreparedConfig {
+            mut tones,
+            spare,
+            highpass,
+        } = prepared;
+
+        let config = &tones.config;
+        self.seed_from_entropy &= config.seed == self.current.config.seed;
+        let sample_rate_changed = config.sample_rate != self.current.config.sample_rate;
+        let highpass = if sample_rate_changed
+            || config.safety_highpass != self.current.config.safety_highpass
+        {
+            std::mem::replace(&mut self.highpass, highpass)
+        } else {
+            highpass
+        };
+
+        self.pilot = match (tones.config.pilot_id, self.pilot.take()) {
+            (Some(id), Some(pilot)) if pilot.matches(id, tones.config.sample_rate) => Some(pilot),
+            (Some(id), _) => Some(PilotTone::new(id, tones.config.sample_rate)),
+            (None, _) => None,
+        };
+
+        self.hopper = Hopper::new(&tones.config);
+        if let Some(hopper) = &mut self.hopper {
+            let frequency = hopper.pick(&tones.config.hop_set, Some(self.current.config.frequency));
+            tones.set_frequency(frequency);
+        }
+
+        let fade_samples = if sample_rate_changed {
+            0
+        } else {
+            Self::fade_samples(&tones.config)
+        };
+        let old_spare = self.spare.replace(spare);
+        let [current, faded] = self.crossfade_to(tones, fade_samples);
+
+        Displaced {
+            _tones: [old_spare, current, faded],
+            _highpass: highpass,
+        }
+    }
+
+    /// Save the oscillator phases and hop position to `path`
+    ///
+    /// The file is replaced atomically, so it can be saved periodically
+    /// from a process that may be killed at any time.
+    pub fn save_state(&self, path: &Path) -> io::Result<()> {
+        let mut state = GeneratorState::with_capacity_for(&self.current.config);
+        self.snapshot_into(&mut state);
+        state.write(path)
+    }
+
+    /// Copy the oscillator and hop position into `state`
+    ///
+    /// Does not allocate if `state` came from
+    /// [`GeneratorState::with_capacity_for`] with a config of the same shape.
+    pub(crate) fn snapshot_into(&self, state: &mut GeneratorState) {
+        state.config.assign_from(&self.current.config);
+        state.phase = self.current.phase;
+        state.tone_phases.clone_from(&self.current.tone_phases);
+        state.hop = self.hopper.as_ref().map(|hopper| HopState {
+            rng: hopper.rng,
+            until_hop: hopper.until_hop,
+        });
+    }
+
+    /// Config of the tone set in state saved by [`save_state`](Self::save_state),
+    /// or `None` if nothing was saved at `path`
+    pub fn saved_config(path: &Path) -> io::Result<Option<SignalConfig>> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(
+                serde_json::from_slice::<GeneratorState>(&contents)?.config,
+            )),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Resume from state saved by [`save_state`](Self::save_state)
+    ///
+    /// Returns `false` and leaves the generator untouched if there is no
+    /// saved state or it was saved for a different configuration. With a
+    /// hop set, the saved carrier replaces the one picked at creation. A
+    /// generator whose seed came from entropy takes the saved seed.
+    pub fn restore_state(&mut self, path: &Path) -> io::Result<bool> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        let state: GeneratorState = serde_json::from_slice(&contents)?;
+
+        let mut expected = self.current.config.clone();
+        if self.hopper.is_some() {
+            expected.frequency = state.config.frequency;
+        }
+        if self.seed_from_entropy {
+            expected.seed = state.config.seed;
+        }
+        if state.config != expected
+            || state.tone_phases.len() != expected.num_tones
+            || state.hop.is_some() != self.hopper.is_some()
+        {
+            info!("Ignoring generator state saved for a different configuration");
+            return Ok(false);
+        }
+
+        if let (Some(hopper), Some(hop)) = (&mut self.hopper, &state.hop) {
+            hopper.rng = hop.rng.max(1);
+            hopper.until_hop = hop.until_hop.clamp(1, hopper.interval);
+        }
+        self.current = ToneSet::new(state.config);
+        self.current.phase = state.phase;
+        self.current.tone_phases = state.tone_phases;
+        self.fade = None;
+
+        Ok(true)
+    }
+
+    /// Length of the crossfade for `config` in samples
+    fn fade_samples(config: &SignalConfig) -> usize {
+        (config.crossfade_ms.max(0.0) / 1000.0 * config.sample_rate as f32).round() as usize
+    }
+
+    /// Switch to a new tone set, fading out the current one
+    ///
+    /// Returns the tone sets no longer playing: the current one if there is
+    /// no fade, and the oldest one when retuning again mid-fade.
+    fn crossfade_to(&mut self, tones: ToneSet, fade_samples: usize) -> [Option<ToneSet>; 2] {
+        let old = std::mem::replace(&mut self.current, tones);
+        let faded = self.fade.take().map(|fade| fade.old);
+
+        if fade_samples == 0 {
+            return [Some(old), faded];
+        }
+        self.fade = Some(Crossfade {
+            old,
+            remaining: fade_samples,
+            total: fade_samples,
+        });
+        [None, faded]
+    }
+
+    /// Keep a tone set that stopped playing as the spare for the next hop,
+    /// or set it aside for [`take_retired`](Self::take_retired)
+    fn recycle(&mut self, tones: ToneSet) {
+        if self.spare.is_none() {
+            self.spare = Some(tones);
+        } else {
+            self.retired = Some(tones);
+        }
+    }
+
+    /// Hand over a tone set that stopped playing, to be dropped elsewhere
+    pub(crate) fn take_retired(&mut self) -> Option<Displaced> {
+        self.retired.take().map(|tones| Displaced {
+            _tones: [Some(tones), None, None],
+            _highpass: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::AudibilityRisk;
+
+    #[test]
+    fn test_signal_generation() {
+        let config = SignalConfig::default();
+        let mut generator = SignalGenerator::new(config);
+
+        let mut buffer = vec![0.0; 1000];
+        generator.generate_buffer(&mut buffer);
+
+        // Verify samples are within expected range
+        for sample in &buffer {
+            assert!(sample.abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_multi_tone_generation() {
+        let config = SignalConfig {
+            num_tones: 5,
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(config);
+
+        let mut buffer = vec![0.0; 1000];
+        generator.generate_buffer(&mut buffer);
+
+        // Verify samples are within expected range
+        for sample in &buffer {
+            assert!(sample.abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_waveforms_are_band_limited() {
+        // At 48 kHz a 23 kHz tone has no harmonics below Nyquist
+        for waveform in [WaveformType::Square, WaveformType::Triangle] {
+            let sine = WaveformType::Sine.sample(0.1f32, 23000.0, 48000);
+            let shaped = waveform.sample(0.1f32, 23000.0, 48000);
+            assert!((shaped.abs() / sine.abs() - 1.0).abs() < 0.3);
+        }
+
+        // At 192 kHz the third harmonic of a 21 kHz square wave fits
+        let config = SignalConfig {
+            frequency: 21000.0,
+            sample_rate: 192000,
+            num_tones: 1,
+            waveform: WaveformType::Square,
+            ..Default::default()
+        };
+        let mut samples = vec![0.0; 19200];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut samples);
+        let report = crate::analysis::analyze_samples(&samples, 192000).unwrap();
+        assert!(report.is_ultrasonic());
+        assert!(report
+            .peak_frequencies
+            .iter()
+            .any(|f| (f - 63000.0).abs() < 50.0));
+        assert!(config.warnings()[0].contains("band-limited"));
+    }
+
+    #[test]
+    fn test_band_noise_stays_ultrasonic() {
+        let config = SignalConfig {
+            waveform: WaveformType::BandNoise,
+            ..Default::default()
+        };
+        let mut samples = vec![0.0; 48000];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut samples);
+
+        assert!(samples.iter().all(|s| s.abs() <= config.amplitude));
+        assert!(samples.iter().any(|s| s.abs() > 0.01));
+        let report = crate::analysis::analyze_samples(&samples, 48000).unwrap();
+        assert!(report.audible_energy_fraction < 0.05);
+        assert!(config.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_channel_phase_offsets() {
+        let config = SignalConfig {
+            num_tones: 1,
+            channel_phase_offsets: vec![0.0, 90.0],
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(config.clone());
+        let mut mono = SignalGenerator::new(SignalConfig {
+            channel_phase_offsets: Vec::new(),
+            ..config
+        });
+
+        let (mut dot, mut left_energy, mut right_energy) = (0.0, 0.0, 0.0);
+        for _ in 0..4800 {
+            let mut frame = [0.0; 3];
+            generator.next_frame(&mut frame);
+            let [left, right, extra] = frame;
+
+            // The first channel is unchanged; unlisted channels follow it
+            assert_eq!(left, mono.next_sample());
+            assert_eq!(extra, left);
+            dot += left * right;
+            left_energy += left * left;
+            right_energy += right * right;
+        }
+
+        // In quadrature: same level, uncorrelated
+        assert!((left_energy - right_energy).abs() / left_energy < 0.01);
+        assert!(dot.abs() / left_energy < 0.01, "{}", dot / left_energy);
+    }
+
+    #[test]
+    fn test_beamform_delays_channels() {
+        let beam = Beamform {
+            speaker_positions: vec![[-0.1, 0.0], [0.1, 0.0]],
+            target_angle_deg: 30.0,
+        };
+        let config = SignalConfig {
+            num_tones: 1,
+            beamform: Some(beam.clone()),
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+
+        // For a single tone, a delay is a phase offset of -freq * delay
+        let lag = config.frequency * beam.channel_delays()[1] * 360.0;
+        let mut steered = SignalGenerator::new(config.clone());
+        let mut shifted = SignalGenerator::new(SignalConfig {
+            beamform: None,
+            channel_phase_offsets: vec![0.0, -lag],
+            ..config.clone()
+        });
+        for _ in 0..4800 {
+            let (mut a, mut b) = ([0.0; 3], [0.0; 3]);
+            steered.next_frame(&mut a);
+            shifted.next_frame(&mut b);
+            assert_eq!(a[0], b[0]);
+            assert!((a[1] - b[1]).abs() < 1e-4, "{:?} {:?}", a, b);
+            assert_eq!(a[2], a[0]);
+        }
+
+        let bad = |beam| SignalConfig {
+            beamform: Some(beam),
+            ..config.clone()
+        };
+        assert_eq!(
+            bad(Beamform {
+                speaker_positions: Vec::new(),
+                ..beam.clone()
+            })
+            .validate(),
+            Err(SignalConfigError::NoSpeakers)
+        );
+        assert!(matches!(
+            bad(Beamform {
+                target_angle_deg: f32::NAN,
+                ..beam
+            })
+            .validate(),
+            Err(SignalConfigError::InvalidBeamAngle(_))
+        ));
+    }
+
+    #[test]
+    fn test_generate_interleaved() {
+        let config = SignalConfig {
+            channel_phase_offsets: vec![0.0, 90.0, 180.0, 270.0],
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        for channels in [1, 2, 4] {
+            let mut interleaved = vec![0.0; 480 * channels];
+            SignalGenerator::new(config.clone()).generate_interleaved(&mut interleaved, channels);
+
+            // Same as frame-by-frame generation, one phase step per frame
+            let mut generator = SignalGenerator::new(config.clone());
+            let mut frame = vec![0.0; channels];
+            for expected in interleaved.chunks(channels) {
+                generator.next_frame(&mut frame);
+                assert_eq!(expected, &frame[..]);
+            }
+
+            // Each channel is the first shifted by its offset
+            if channels == 4 {
+                let mut opposite = 0.0;
+                for frame in interleaved.chunks(4) {
+                    opposite += (frame[0] + frame[2]).abs() + (frame[1] + frame[3]).abs();
+                }
+                assert!(opposite / 480.0 < 1e-3, "{}", opposite);
+            }
+        }
+
+        // Mono interleaving is the plain buffer
+        let mut mono = vec![0.0; 480];
+        let mut buffer = vec![0.0; 480];
+        SignalGenerator::new(config.clone()).generate_interleaved(&mut mono, 1);
+        SignalGenerator::new(config.clone()).generate_buffer(&mut buffer);
+        assert_eq!(mono, buffer);
+
+        // So are consecutive fixed-size blocks
+        let mut generator = SignalGenerator::new(config);
+        let blocks = [generator.next_block::<240>(), generator.next_block::<240>()];
+        assert_eq!(blocks.as_flattened(), &buffer[..]);
+    }
+
+    #[test]
+    fn test_random_phases_lower_startup_peak() {
+        let startup_peak = |random_phases, phase_seed| {
+            let config = SignalConfig {
+                num_tones: 5,
+                random_phases,
+                phase_seed,
+                seed: Some(0),
+                ..Default::default()
+            };
+            // The first 10 ms, where in-phase tones line up
+            let mut buffer = vec![0.0; 480];
+            SignalGenerator::new(config).generate_buffer(&mut buffer);
+            buffer.iter().fold(0.0f32, |m, s| m.max(s.abs()))
+        };
+
+        let aligned = startup_peak(false, 0);
+        assert!(aligned > 0.24, "{}", aligned);
+
+        // Any one draw can land close to alignment, but on average it does not
+        let seeds = 32;
+        let spread = (0..seeds).map(|seed| startup_peak(true, seed)).sum::<f32>() / seeds as f32;
+        assert!(spread < aligned * 0.8, "{} vs {}", spread, aligned);
+
+        // Seeded, so the phases repeat
+        let config = SignalConfig {
+            num_tones: 5,
+            phase_seed: 7,
+            ..Default::default()
+        };
+        let phases = |config| initial_phases(config).collect::<Vec<_>>();
+        assert_eq!(phases(&config), phases(&config));
+        assert_ne!(phases(&config), phases(&SignalConfig::default()));
+    }
+
+    #[test]
+    fn test_ramp_reaches_target_on_time() {
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut ramped = SignalGenerator::new(config.clone());
+        let mut plain = SignalGenerator::new(config);
+        ramped.ramp_amplitude(Duration::from_millis(10));
+
+        for i in 0..960 {
+            let (sample, expected) = (ramped.next_sample(), plain.next_sample());
+            if i < 480 {
+                let gain = i as f32 / 480.0;
+                assert!((sample - expected * gain).abs() < 1e-6, "sample {}", i);
+                assert!(ramped.is_ramping() || i == 479);
+            } else {
+                assert_eq!(sample, expected, "sample {}", i);
+            }
+        }
+        assert!(!ramped.is_ramping());
+    }
+
+    #[test]
+    fn test_invalid_phase_offset() {
+        let config = SignalConfig {
+            channel_phase_offsets: vec![0.0, f32::NAN],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(SignalConfigError::InvalidPhaseOffset(_))
+        ));
+    }
+
+    #[test]
+    fn test_safety_highpass_keeps_signal() {
+        let config = SignalConfig {
+            safety_highpass: true,
+            ..Default::default()
+        };
+        let mut samples = vec![0.0; 48000];
+        SignalGenerator::new(config).generate_buffer(&mut samples);
+
+        let report = crate::analysis::analyze_samples(&samples, 48000).unwrap();
+        assert!(report.is_ultrasonic());
+        assert_eq!(report.peak_frequencies.len(), 3);
+    }
+
+    /// Largest jump between adjacent samples, and audible-band peak in dB,
+    /// around a retune from 23 kHz to 21 kHz
+    fn retune_artifacts(crossfade_ms: f32) -> (f32, f64) {
+        let from = SignalConfig {
+            num_tones: 1,
+            sample_rate: 192000,
+            crossfade_ms,
+            ..Default::default()
+        };
+        let to = SignalConfig {
+            frequency: 21000.0,
+            ..from.clone()
+        };
+
+        let mut generator = SignalGenerator::new(from);
+        let mut samples = vec![0.0; 19200];
+        // Retune where the old tone is far from zero, so a hard switch jumps
+        let (before, after) = samples.split_at_mut(9553);
+        generator.generate_buffer(before);
+        generator.update_config(to);
+        generator.generate_buffer(after);
+
+        let max_delta = samples
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0, f32::max);
+        let report = crate::analysis::analyze_samples(&samples, 192000).unwrap();
+        (max_delta, report.audible_peak_db)
+    }
+
+    #[test]
+    fn test_retune_crossfade_avoids_click() {
+        // A steady 23 kHz tone at 192 kHz moves at most 2·A·sin(π·23/192) per sample
+        let steady_delta = 2.0 * 0.25 * (PI * 23000.0 / 192000.0).sin();
+
+        let (hard_delta, hard_audible) = retune_artifacts(0.0);
+        let (faded_delta, faded_audible) = retune_artifacts(20.0);
+
+        assert!(faded_delta <= steady_delta * 1.01, "{}", faded_delta);
+        assert!(hard_delta > steady_delta * 1.2, "{}", hard_delta);
+        assert!(faded_audible < hard_audible - 20.0);
+    }
+
+    #[test]
+    fn test_crossfade_finishes_on_new_config() {
+        let mut generator = SignalGenerator::new(SignalConfig::default());
+        let retuned = SignalConfig {
+            frequency: 22000.0,
+            crossfade_ms: 10.0,
+            ..Default::default()
+        };
+        generator.update_config(retuned);
+        assert!(generator.is_crossfading());
+        assert_eq!(generator.config().frequency, 22000.0);
+
+        let mut buffer = vec![0.0; 480];
+        generator.generate_buffer(&mut buffer);
+        assert!(!generator.is_crossfading());
+
+        // Afterwards the output matches a fresh generator advanced in lockstep
+        let mut fresh = SignalGenerator::new(generator.config().clone());
+        fresh.generate_buffer(&mut buffer);
+        for _ in 0..100 {
+            assert!((generator.next_sample() - fresh.next_sample()).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_seed_makes_output_repeatable() {
+        let render = |seed| {
+            let mut generator = SignalGenerator::new(SignalConfig {
+                spread_dither_depth: 0.05,
+                seed,
+                ..Default::default()
+            });
+            let mut buffer = vec![0.0; 4800];
+            generator.generate_buffer(&mut buffer);
+            (generator.config().seed, buffer)
+        };
+        assert_eq!(render(Some(5)), render(Some(5)));
+        assert_ne!(render(Some(5)).1, render(Some(6)).1);
+
+        // Without a seed each generator draws its own and reports it
+        let (drawn, unseeded) = render(None);
+        assert!(drawn.is_some());
+        assert_ne!(render(None).0, drawn);
+        assert_eq!(render(drawn).1, unseeded);
+
+        // Retuning without a seed keeps the current one
+        let mut generator = SignalGenerator::new(SignalConfig {
+            seed: Some(5),
+            ..Default::default()
+        });
+        generator.update_config(SignalConfig {
+            frequency: 22000.0,
+            ..Default::default()
+        });
+        assert_eq!(generator.config().seed, Some(5));
+    }
+
+    #[test]
+    fn test_restore_state_resumes_hop_schedule() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut original = SignalGenerator::new(hopping_config(7));
+        let mut buffer = vec![0.0; 2500];
+        original.generate_buffer(&mut buffer);
+        original.save_state(&path).unwrap();
+
+        let saved = SignalGenerator::saved_config(&path).unwrap().unwrap();
+        assert_eq!(&saved, original.config());
+
+        let mut restored = SignalGenerator::new(hopping_config(7));
+        assert!(restored.restore_state(&path).unwrap());
+        assert_eq!(restored.config().frequency, original.config().frequency);
+
+        // Continues sample for sample, across the next hops
+        for _ in 0..5000 {
+            assert_eq!(restored.next_sample(), original.next_sample());
+        }
+    }
+
+    #[test]
+    fn test_restore_state_ignores_other_config() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut generator = SignalGenerator::new(SignalConfig::default());
+        assert!(!generator.restore_state(&path).unwrap());
+        assert!(SignalGenerator::saved_config(&path).unwrap().is_none());
+        generator.save_state(&path).unwrap();
+
+        // A seed drawn from entropy gives way to the saved one
+        let mut resumed = SignalGenerator::new(SignalConfig::default());
+        assert!(resumed.restore_state(&path).unwrap());
+        assert_eq!(resumed.config().seed, generator.config().seed);
+
+        let config = SignalConfig {
+            amplitude: 0.1,
+            ..Default::default()
+        };
+        let mut other = SignalGenerator::new(config);
+        assert!(!other.restore_state(&path).unwrap());
+
+        let mut fresh = SignalGenerator::new(other.config().clone());
+        for _ in 0..100 {
+            assert_eq!(other.next_sample(), fresh.next_sample());
+        }
+    }
+
+    #[test]
+    fn test_audibility_report_clean_config() {
+        let report = SignalConfig::default().audibility_report();
+        assert_eq!(report.risk, AudibilityRisk::Safe);
+        assert!(report.headroom_db > 10.0, "{}", report.headroom_db);
+    }
+
+    #[test]
+    fn test_audibility_report_leaky_config() {
+        // A 16 kHz tone is plainly audible
+        let config = SignalConfig {
+            frequency: 16000.0,
+            num_tones: 1,
+            ..Default::default()
+        };
+        let report = config.audibility_report();
+        assert_eq!(report.risk, AudibilityRisk::Audible);
+        let amplitude_dbfs = 20.0 * (config.amplitude as f64).log10();
+        assert!((report.audible_peak_dbfs - amplitude_dbfs).abs() < 0.5);
+
+        // Hard carrier switches click audibly; crossfades cut that by >20 dB
+        let hard = SignalConfig {
+            hop_set: vec![21000.0, 23000.0],
+            hop_interval_ms: 50.0,
+            crossfade_ms: 0.0,
+            ..Default::default()
+        }
+        .audibility_report();
+        assert_eq!(hard.risk, AudibilityRisk::Audible);
+
+        let faded = SignalConfig {
+            hop_set: vec![21000.0, 23000.0],
+            hop_interval_ms: 50.0,
+            crossfade_ms: 20.0,
+            ..Default::default()
+        }
+        .audibility_report();
+        assert!(faded.headroom_db > hard.headroom_db + 20.0);
+    }
+
+    #[test]
+    fn test_f64_path_holds_frequency_over_long_runs() {
+        let config = SignalConfig {
+            frequency: 21001.7,
+            num_tones: 1,
+            amplitude: 0.5,
+            ..Default::default()
+        };
+        let len = 30 * config.sample_rate as usize;
+        let step = config.frequency as f64 / config.sample_rate as f64;
+        // Worst error against an exact sine over the last thousand samples
+        let drift = |samples: &[f64]| {
+            (len - 1000..len)
+                .map(|n| {
+                    let exact = 0.5 * (std::f64::consts::TAU * (step * n as f64).fract()).sin();
+                    (samples[n] - exact).abs()
+                })
+                .fold(0.0, f64::max)
+        };
+
+        let mut single = vec![0.0f32; len];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut single);
+        let single: Vec<f64> = single.into_iter().map(f64::from).collect();
+        let mut double = vec![0.0f64; len];
+        SignalGenerator::new(config).generate_buffer_f64(&mut double);
+
+        let (single, double) = (drift(&single), drift(&double));
+        assert!(double < 1e-6, "{}", double);
+        assert!(single > 1000.0 * double, "{} vs {}", single, double);
+    }
+
+    #[test]
+    fn test_fit_to_audibility_budget() {
+        // Nothing audible to back off from
+        let clean = SignalConfig::default();
+        assert_eq!(clean.fit_to_audibility_budget(-100.0), clean);
+
+        let leaky = SignalConfig {
+            frequency: 16000.0,
+            num_tones: 1,
+            ..Default::default()
+        };
+        let fitted = leaky.fit_to_audibility_budget(-40.0);
+        assert!(fitted.amplitude < leaky.amplitude / 10.0);
+        let peak = fitted.audibility_report().audible_peak_dbfs;
+        assert!((-40.5..=-40.0).contains(&peak), "{}", peak);
+        assert_eq!(
+            SignalConfig {
+                amplitude: leaky.amplitude,
+                ..fitted
+            },
+            leaky
+        );
+    }
+
+    fn hopping_config(seed: u64) -> SignalConfig {
+        SignalConfig {
+            num_tones: 1,
+            sample_rate: 96000,
+            hop_set: vec![22000.0, 23000.0, 24000.0],
+            hop_interval_ms: 10.0,
+            hop_seed: seed,
+            crossfade_ms: 2.0,
+            seed: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Carrier frequency at the end of each hop interval
+    fn hop_schedule(seed: u64, hops: usize) -> Vec<f32> {
+        let mut generator = SignalGenerator::new(hopping_config(seed));
+        let mut buffer = vec![0.0; 960];
+        (0..hops)
+            .map(|_| {
+                generator.generate_buffer(&mut buffer[..959]);
+                let frequency = generator.config().frequency;
+                generator.next_sample();
+                frequency
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hopping_is_seeded_and_changes_carrier() {
+        let schedule = hop_schedule(7, 20);
+        assert_eq!(schedule, hop_schedule(7, 20));
+        assert_ne!(schedule, hop_schedule(8, 20));
+
+        for pair in schedule.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
+        for hop in [22000.0, 23000.0, 24000.0] {
+            assert!(schedule.contains(&hop));
+        }
+    }
+
+    #[test]
+    fn test_hopping_stays_ultrasonic() {
+        let mut samples = vec![0.0; 96000];
+        SignalGenerator::new(hopping_config(1)).generate_buffer(&mut samples);
+
+        let report = crate::analysis::analyze_samples(&samples, 96000).unwrap();
+        assert!(report.is_ultrasonic());
+        assert!(report.audible_peak_db < -60.0, "{}", report.audible_peak_db);
+    }
+
+    #[test]
+    fn test_measure_flags_peaks_above_safe_limit() {
+        let mut hot = SignalGenerator::new(SignalConfig {
+            amplitude: 1.0,
+            num_tones: 1,
+            seed: Some(0),
+            ..Default::default()
+        });
+        let stats = hot.measure(Duration::from_millis(100));
+        assert!(stats.exceeds_safe_peak(), "{:?}", stats);
+
+        let mut balanced = SignalGenerator::new(SignalConfig {
+            seed: Some(0),
+            ..Default::default()
+        });
+        let stats = balanced.measure(Duration::from_millis(100));
+        assert!(!stats.exceeds_safe_peak(), "{:?}", stats);
+        assert!(stats.true_peak > 0.0);
+    }
+
+    #[test]
+    fn test_nyquist_guard_scales_with_sample_rate() {
+        for (sample_rate, highest_allowed) in [
+            (44100, 20050.0),
+            (48000, 22000.0),
+            (96000, 46000.0),
+            (192000, 94000.0),
+        ] {
+            let at_limit = SignalConfig {
+                frequency: highest_allowed,
+                num_tones: 1,
+                sample_rate,
+                nyquist_guard_hz: 2000.0,
+                ..Default::default()
+            };
+            assert_eq!(at_limit.validate(), Ok(()), "{} Hz", sample_rate);
+
+            let above = SignalConfig {
+                frequency: highest_allowed + 10.0,
+                ..at_limit.clone()
+            };
+            assert_eq!(
+                above.validate(),
+                Err(SignalConfigError::InGuardBand {
+                    highest: highest_allowed + 10.0,
+                    limit: highest_allowed,
+                }),
+                "{} Hz",
+                sample_rate
+            );
+
+            // Without a guard anything below Nyquist is allowed
+            let unguarded = SignalConfig {
+                nyquist_guard_hz: 0.0,
+                ..above
+            };
+            assert_eq!(unguarded.validate(), Ok(()), "{} Hz", sample_rate);
+        }
+
+        // The guard covers hop carriers too
+        let config = SignalConfig {
+            frequency: 21000.0,
+            num_tones: 1,
+            hop_set: vec![21000.0, 23000.0],
+            nyquist_guard_hz: 2000.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(SignalConfigError::InGuardBand { .. })
+        ));
+
+        for guard in [-1.0, 24000.0, f32::NAN] {
+            let config = SignalConfig {
+                nyquist_guard_hz: guard,
+                ..Default::default()
+            };
+            assert!(matches!(
+                config.validate(),
+                Err(SignalConfigError::InvalidNyquistGuard(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_checks_hop_set() {
+        assert!(hopping_config(0).validate().is_ok());
+
+        // 24 kHz is exactly Nyquist at 48 kHz
+        let config = SignalConfig {
+            sample_rate: 48000,
+            ..hopping_config(0)
+        };
+        assert_eq!(
+            config.validate(),
+            Err(SignalConfigError::AboveNyquist {
+                highest: 24000.0,
+                sample_rate: 48000
+            })
+        );
+
+        let config = SignalConfig {
+            hop_set: vec![19000.0],
+            ..hopping_config(0)
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(SignalConfigError::AudibleTone { .. })
+        ));
+    }
+
+    #[test]
+    fn test_required_sample_rate() {
+        let at = |config: &SignalConfig, sample_rate| SignalConfig {
+            sample_rate,
+            ..config.clone()
+        };
+        let needed = |config: &SignalConfig| {
+            let rate = config.required_sample_rate();
+            assert!(at(config, rate).validate().is_ok(), "{:?}", config);
+            assert!(at(config, rate - 1).validate().is_err(), "{:?}", config);
+            rate
+        };
+
+        // 23.3 kHz top tone needs more than 44.1 kHz
+        assert_eq!(needed(&SignalConfig::default()), 46601);
+        assert_eq!(
+            needed(&SignalConfig {
+                hop_set: vec![22000.0, 23000.0, 24000.0],
+                ..Default::default()
+            }),
+            48601
+        );
+        // The pilot's band can sit above every tone
+        needed(&SignalConfig {
+            frequency: 20400.0,
+            num_tones: 1,
+            pilot_id: Some(1),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_validate_rejects_audible_tones() {
+        assert!(SignalConfig::default().validate().is_ok());
+
+        let config = SignalConfig {
+            frequency: 20500.0,
+            num_tones: 5,
+            frequency_spread: 500.0,
+            ..Default::default()
+        };
+        assert_eq!(config.tone_range(), (19500.0, 21500.0));
+        assert_eq!(
+            config.validate(),
+            Err(SignalConfigError::AudibleTone {
+                lowest: 19500.0,
+                limit: MIN_ULTRASONIC_FREQUENCY
+            })
+        );
+        // Research sweeps may go audible, but not past Nyquist
+        assert_eq!(config.validate_allowing_audible(), Ok(()));
+        let config = SignalConfig {
+            frequency: 18000.0,
+            sample_rate: 32000,
+            ..config
+        };
+        assert!(matches!(
+            config.validate_allowing_audible(),
+            Err(SignalConfigError::AboveNyquist { .. })
+        ));
+
+        let config = SignalConfig {
+            amplitude: 1.5,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(SignalConfigError::InvalidAmplitude(1.5))
+        );
+    }
+
+    #[test]
+    fn test_adjust_to_ultrasonic() {
+        let mut config = SignalConfig {
+            frequency: 20500.0,
+            num_tones: 5,
+            frequency_spread: 500.0,
+            ..Default::default()
+        };
+        assert_eq!(config.adjust_to_ultrasonic(), Some(20500.0));
+        assert_eq!(config.tone_range().0, 20500.0);
+        assert!(config.validate().is_ok());
+
+        // Already ultrasonic configs are left alone
+        let mut config = SignalConfig::default();
+        assert_eq!(config.adjust_to_ultrasonic(), None);
+        assert_eq!(config.frequency, 23000.0);
+    }
+
+    #[test]
+    fn test_tone_distributions() {
+        assert_eq!(
+            SignalConfig::default().tone_frequencies(),
+            vec![22700.0, 23000.0, 23300.0]
+        );
+
+        // Same span as even spacing, with gaps widening towards the top
+        let geometric = SignalConfig {
+            num_tones: 5,
+            frequency_spread: 500.0,
+            tone_distribution: ToneDistribution::Geometric,
+            ..Default::default()
+        };
+        let tones = geometric.tone_frequencies();
+        assert_eq!(geometric.tone_range(), (22000.0, 24000.0));
+        assert!((tones[0] - 22000.0).abs() < 0.01 && (tones[4] - 24000.0).abs() < 0.01);
+        assert!(tones.windows(3).all(|w| w[2] - w[1] > w[1] - w[0]));
+
+        let custom = SignalConfig {
+            tone_distribution: ToneDistribution::Custom(vec![-2000.0, 0.0, 500.0]),
+            ..Default::default()
+        };
+        assert_eq!(custom.tone_range(), (21000.0, 23500.0));
+        assert!(custom.validate().is_ok());
+        let mut generator = SignalGenerator::new(custom.clone());
+        let mut buffer = vec![0.0; 48000];
+        generator.generate_buffer(&mut buffer);
+        let mut peaks = analyze_samples(&buffer, 48000).unwrap().peak_frequencies;
+        peaks.truncate(3);
+        peaks.sort_by(f32::total_cmp);
+        for (peak, expected) in peaks.iter().zip([21000.0, 23000.0, 23500.0]) {
+            assert!((peak - expected).abs() < 2.0, "{:?}", peaks);
+        }
+    }
+
+    #[test]
+    fn test_spread_dither_stays_ultrasonic() {
+        let config = SignalConfig {
+            frequency: 21000.0,
+            num_tones: 5,
+            frequency_spread: 400.0,
+            spread_dither_depth: 0.2,
+            spread_dither_rate_hz: 5.0,
+            ..Default::default()
+        };
+        assert!(SpreadDither::new(&SignalConfig::default()).is_none());
+
+        // The checked range covers the widest spacing, not the nominal one
+        assert_eq!(config.tone_range(), (20040.0, 21960.0));
+        assert!(config.validate().is_ok());
+        let too_deep = SignalConfig {
+            spread_dither_depth: 0.5,
+            ..config.clone()
+        };
+        assert!(matches!(
+            too_deep.validate(),
+            Err(SignalConfigError::AudibleTone { .. })
+        ));
+        assert_eq!(
+            SignalConfig {
+                spread_dither_depth: 1.5,
+                ..config.clone()
+            }
+            .validate(),
+            Err(SignalConfigError::InvalidSpreadDither(1.5))
+        );
+        assert_eq!(
+            SignalConfig {
+                spread_dither_rate_hz: 0.0,
+                ..config.clone()
+            }
+            .validate(),
+            Err(SignalConfigError::InvalidSpreadDitherRate(0.0))
+        );
+
+        // The walk covers most of its range without leaving it
+        let mut dither = SpreadDither::new(&config).unwrap();
+        let walk: Vec<f32> = (0..48000 * 4).map(|_| dither.next()).collect();
+        assert!(walk.iter().all(|v| (-1.0..=1.0).contains(v)));
+        let (low, high) = walk
+            .iter()
+            .fold((1.0f32, -1.0f32), |(l, h), &v| (l.min(v), h.max(v)));
+        assert!(low < -0.5 && high > 0.5, "{} {}", low, high);
+
+        // Dithering moves the signal but keeps it out of the audible band
+        let mut samples = vec![0.0; 96000];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut samples);
+        let mut fixed = vec![0.0; 96000];
+        SignalGenerator::new(SignalConfig {
+            spread_dither_depth: 0.0,
+            ..config
+        })
+        .generate_buffer(&mut fixed);
+        assert_ne!(samples, fixed);
+        let report = crate::analysis::analyze_samples(&samples, 48000).unwrap();
+        assert!(
+            report.audible_energy_fraction < 0.05,
+            "{}",
+            report.audible_energy_fraction
+        );
+    }
+
+    #[test]
+    fn test_validate_checks_tone_distribution() {
+        let custom = |offsets: Vec<f32>| SignalConfig {
+            num_tones: offsets.len(),
+            tone_distribution: ToneDistribution::Custom(offsets),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            custom(vec![-3500.0, 0.0]).validate(),
+            Err(SignalConfigError::AudibleTone { .. })
+        ));
+        assert!(matches!(
+            custom(vec![0.0, 1000.0]).validate(),
+            Err(SignalConfigError::AboveNyquist { .. })
+        ));
+        assert!(matches!(
+            custom(vec![0.0, f32::NAN]).validate(),
+            Err(SignalConfigError::InvalidToneOffset(_))
+        ));
+        assert_eq!(
+            SignalConfig {
+                num_tones: 3,
+                ..custom(vec![0.0, 100.0])
+            }
+            .validate(),
+            Err(SignalConfigError::ToneCountMismatch {
+                offsets: 2,
+                tones: 3
+            })
+        );
+
+        // Every hop carrier is checked with the same offsets
+        let hopping = SignalConfig {
+            frequency: 22000.0,
+            hop_set: vec![21000.0, 23000.0],
+            ..custom(vec![0.0, 1500.0])
+        };
+        assert!(SignalConfig {
+            hop_set: Vec::new(),
+            ..hopping.clone()
+        }
+        .validate()
+        .is_ok());
+        assert!(matches!(
+            hopping.validate(),
+            Err(SignalConfigError::AboveNyquist { .. })
+        ));
+    }
+
+    #[test]
+    fn test_master_gain_scales_the_sum() {
+        let config = SignalConfig {
+            amplitude: 0.6,
+            frequency_tilt_db_per_octave: 3.0,
+            seed: Some(3),
+            ..Default::default()
+        };
+        let scaled = SignalConfig {
+            master_gain: 0.5,
+            ..config.clone()
+        };
+
+        // Same tones and balance, every sample at half the level
+        let mut full = vec![0.0; 4800];
+        let mut half = vec![0.0; 4800];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut full);
+        SignalGenerator::new(scaled.clone()).generate_buffer(&mut half);
+        for (full, half) in full.iter().zip(&half) {
+            assert!((full * 0.5 - half).abs() < 1e-6, "{} vs {}", full, half);
+        }
+        assert!((scaled.summed_tone_peak() - config.summed_tone_peak() * 0.5).abs() < 1e-6);
+
+        // The gain makes room for a tilt that would clip at full amplitude
+        let hot = SignalConfig {
+            amplitude: 1.0,
+            ..config
+        };
+        assert!(hot
+            .warnings()
+            .iter()
+            .any(|w| w.contains("summed tone peak")));
+        assert!(SignalConfig {
+            master_gain: 0.5,
+            ..hot.clone()
+        }
+        .warnings()
+        .is_empty());
+
+        for gain in [-0.1, 1.5, f32::NAN] {
+            assert!(matches!(
+                SignalConfig {
+                    master_gain: gain,
+                    ..hot.clone()
+                }
+                .validate(),
+                Err(SignalConfigError::InvalidMasterGain(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate_keeps_tones_clear_of_the_pilot() {
+        let config = SignalConfig {
+            pilot_id: Some(1),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert!(config.summed_tone_peak() > SignalConfig::default().summed_tone_peak());
+
+        // The aggressive preset puts a tone right on 21 kHz
+        let aggressive = SignalConfig {
+            pilot_id: Some(1),
+            ..SignalConfig::preset("aggressive").unwrap()
+        };
+        assert_eq!(
+            aggressive.validate(),
+            Err(SignalConfigError::PilotClash {
+                tone: 21000.0,
+                pilot: pilot::PILOT_FREQUENCY_HZ
+            })
+        );
+
+        // A hop carrier counts too
+        let hopping = SignalConfig {
+            hop_set: vec![23000.0, 21200.0],
+            ..config.clone()
+        };
+        assert!(matches!(
+            hopping.validate(),
+            Err(SignalConfigError::PilotClash { .. })
+        ));
+
+        let guarded = SignalConfig {
+            frequency: 20500.0,
+            num_tones: 1,
+            sample_rate: 44100,
+            nyquist_guard_hz: 1000.0,
+            ..config
+        };
+        assert!(matches!(
+            guarded.validate(),
+            Err(SignalConfigError::PilotAboveNyquist { .. })
+        ));
+    }
+
+    #[test]
+    fn test_frequency_tilt_boosts_high_tones() {
+        let config = SignalConfig {
+            frequency: 22000.0,
+            num_tones: 2,
+            frequency_spread: 2000.0,
+            frequency_tilt_db_per_octave: 6.0,
+            seed: Some(0),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.tilt_gain(MIN_ULTRASONIC_FREQUENCY), 1.0);
+        assert!(config.summed_tone_peak() > config.amplitude);
+
+        let mut samples = vec![0.0; 48000];
+        SignalGenerator::new(config.clone()).generate_buffer(&mut samples);
+        let low = crate::analysis::band_level(&samples, 48000, 20900.0, 21100.0).unwrap();
+        let high = crate::analysis::band_level(&samples, 48000, 22900.0, 23100.0).unwrap();
+        let expected = 6.0 * (23000.0f64 / 21000.0).log2();
+        let measured = high.band_dbfs - low.band_dbfs;
+        assert!(
+            (measured - expected).abs() < 0.2,
+            "{} vs {}",
+            measured,
+            expected
+        );
+
+        // A flat config is unchanged
+        assert_eq!(SignalConfig::default().summed_tone_peak(), 0.25);
+
+        // Boosting a full-scale signal may clip, which is worth a warning
+        // but still a usable config
+        let hot = SignalConfig {
+            amplitude: 1.0,
+            ..config.clone()
+        };
+        assert!(hot.summed_tone_peak() > 1.0);
+        assert!(hot.validate().is_ok());
+        assert!(hot
+            .warnings()
+            .iter()
+            .any(|w| w.contains("summed tone peak")));
+        assert_eq!(
+            SignalConfig {
+                frequency_tilt_db_per_octave: f32::NAN,
+                ..config
+            }
+            .validate()
+            .map_err(|e| e.to_string())
+            .unwrap_err(),
+            "Frequency tilt must be finite, got NaN dB/octave"
+        );
+    }
+
+    #[test]
+    fn test_presets_are_valid_and_unique() {
+        let presets = SignalConfig::presets();
+        for (i, (name, config, description)) in presets.iter().enumerate() {
+            assert!(config.validate().is_ok(), "{}", name);
+            assert!(!description.is_empty(), "{}", name);
+            assert!(presets[..i].iter().all(|(other, _, _)| other != name));
+        }
+
+        assert_eq!(
+            SignalConfig::preset("balanced"),
+            Some(SignalConfig::default())
+        );
+        assert_eq!(SignalConfig::preset("stealth").unwrap().amplitude, 0.1);
+        assert!(SignalConfig::preset("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_partial_config_deserializes_with_defaults() {
+        let config: SignalConfig =
+            serde_json::from_str(r#"{"frequency": 22000.0, "num_tones": 5}"#).unwrap();
+
+        assert_eq!(config.frequency, 22000.0);
+        assert_eq!(config.num_tones, 5);
+        assert_eq!(config.amplitude, SignalConfig::default().amplitude);
+        assert_eq!(
+            config.frequency_spread,
+            SignalConfig::default().frequency_spread
+        );
     }
 }
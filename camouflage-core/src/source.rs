@@ -0,0 +1,475 @@
+//! Realtime-safe signal source for audio callbacks owned by the host
+//!
+//! [`SignalSource`] lives on the audio thread and [`SignalController`] on
+//! any other. They share bounded lock-free queues: retunes travel to the
+//! source fully built, and the parts they replace travel back to be freed
+//! by the controller. State snapshots make the same round trip.
+
+use crate::error::{RetuneError, SignalConfigError};
+use crate::signal::{Displaced, GeneratorState, PreparedConfig, SignalConfig, SignalGenerator};
+use crossbeam_queue::ArrayQueue;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Commands that can wait for the audio thread to pick them up
+const COMMAND_QUEUE_CAPACITY: usize = 16;
+
+/// Replaced parts awaiting the controller; each retune yields up to two
+const GARBAGE_QUEUE_CAPACITY: usize = 2 * COMMAND_QUEUE_CAPACITY;
+
+/// How long [`SignalController::save_state`] waits for the audio thread
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often [`SignalController::save_state`] checks for the snapshot
+const SNAPSHOT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Work for the audio thread, prepared so it never allocates
+// Boxing the retune would leave the audio thread a box to free
+#[allow(clippy::large_enum_variant)]
+enum Command {
+    Retune(PreparedConfig),
+    /// Fill in the state and send it back
+    Snapshot(Box<GeneratorState>),
+}
+
+/// Queues shared by a source and its controllers
+struct Channels {
+    commands: ArrayQueue<Command>,
+    garbage: ArrayQueue<Displaced>,
+    snapshots: ArrayQueue<Box<GeneratorState>>,
+}
+
+/// Pull-based signal generator for an audio engine's own callback
+///
+/// # Realtime guarantees
+///
+/// [`fill`](Self::fill) never locks, blocks or makes system calls, and does
+/// not allocate or free memory, including while hopping, crossfading or
+/// applying a retune. The one exception: if the controller stops freeing
+/// replaced parts while retunes keep coming, the queue back to it fills and
+/// further leftovers are freed in place.
+pub struct SignalSource {
+    generator: SignalGenerator,
+    channels: Arc<Channels>,
+}
+
+/// Retunes a [`SignalSource`] from outside the audio thread
+#[derive(Clone)]
+pub struct SignalController {
+    channels: Arc<Channels>,
+    /// Most recently queued config; only ever locked off the audio thread
+    latest: Arc<Mutex<SignalConfig>>,
+    /// Frame width retunes are built for
+    frame_channels: usize,
+}
+
+impl SignalSource {
+    /// Create a source and the controller that retunes it
+    ///
+    /// `config.sample_rate` must match the host's stream.
+    pub fn new(config: SignalConfig) -> Result<(Self, SignalController), SignalConfigError> {
+        Self::with_channels(config, 1)
+    }
+
+    /// Create a source whose [`render`](Self::render) writes frames of
+    /// `channels` samples without allocating
+    pub(crate) fn with_channels(
+        config: SignalConfig,
+        channels: usize,
+    ) -> Result<(Self, SignalController), SignalConfigError> {
+        config.validate()?;
+        let frame_channels = channels.max(1);
+
+        let channels = Arc::new(Channels {
+            commands: ArrayQueue::new(COMMAND_QUEUE_CAPACITY),
+            garbage: ArrayQueue::new(GARBAGE_QUEUE_CAPACITY),
+            snapshots: ArrayQueue::new(COMMAND_QUEUE_CAPACITY),
+        });
+        let generator = SignalGenerator::with_channels(config.clone(), frame_channels);
+        // Later retunes without a seed keep the one the generator drew
+        let latest = SignalConfig {
+            seed: generator.config().seed,
+            ..config
+        };
+        let controller = SignalController {
+            channels: Arc::clone(&channels),
+            latest: Arc::new(Mutex::new(latest)),
+            frame_channels,
+        };
+        let source = Self {
+            generator,
+            channels,
+        };
+        Ok((source, controller))
+    }
+
+    /// Apply pending retunes, then fill `buffer` with mono samples
+    pub fn fill(&mut self, buffer: &mut [f32]) {
+        self.render(|generator| generator.generate_buffer(buffer));
+    }
+
+    /// Apply pending commands, then run `f` on the generator
+    ///
+    /// Lets the jammer write interleaved frames with the same guarantees
+    /// as [`fill`](Self::fill).
+    pub(crate) fn render<R>(&mut self, f: impl FnOnce(&mut SignalGenerator) -> R) -> R {
+        while let Some(command) = self.channels.commands.pop() {
+            match command {
+                Command::Retune(prepared) => {
+                    let displaced = self.generator.apply(prepared);
+                    self.discard(displaced);
+                }
+                Command::Snapshot(mut state) => {
+                    self.generator.snapshot_into(&mut state);
+                    // A full queue means nobody is waiting; the state is dropped here
+                    let _ = self.channels.snapshots.push(state);
+                }
+            }
+        }
+        let result = f(&mut self.generator);
+        if let Some(displaced) = self.generator.take_retired() {
+            self.discard(displaced);
+        }
+        result
+    }
+
+    /// Send replaced parts to the controller to be freed
+    fn discard(&self, displaced: Displaced) {
+        // A full queue hands the parts back, and they are dropped here
+        let _ = self.channels.garbage.push(displaced);
+    }
+
+    /// The generator, for reads while no callback owns the source
+    pub(crate) fn generator(&self) -> &SignalGenerator {
+        &self.generator
+    }
+
+    /// The generator, for changes made while no callback owns the source
+    pub(crate) fn generator_mut(&mut self) -> &mut SignalGenerator {
+        &mut self.generator
+    }
+
+    /// Configuration currently playing
+    pub fn config(&self) -> &SignalConfig {
+        self.generator.config()
+    }
+}
+
+impl SignalController {
+    /// Queue a new configuration, crossfaded in at the source's next fill
+    ///
+    /// The config is validated and built here, so the audio thread only
+    /// swaps it in. A config without a seed keeps the current one.
+    pub fn retune(&self, mut config: SignalConfig) -> Result<(), RetuneError> {
+        config.validate()?;
+        self.collect_garbage();
+
+        // Holding the lock across the push keeps `latest` in queue order
+        let mut latest = self.latest.lock().unwrap_or_else(|e| e.into_inner());
+        if config.seed.is_none() {
+            config.seed = latest.seed;
+        }
+        self.channels
+            .commands
+            .push(Command::Retune(PreparedConfig::new(
+                config.clone(),
+                self.frame_channels,
+            )))
+            .map_err(|_| RetuneError::QueueFull)?;
+        *latest = config;
+        Ok(())
+    }
+
+    /// Most recently queued configuration
+    ///
+    /// The source may not have picked it up yet.
+    pub fn config(&self) -> SignalConfig {
+        self.latest
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Free the parts the source has replaced
+    ///
+    /// [`retune`](Self::retune) does this too; call it directly when
+    /// retunes are rare but memory should be released promptly.
+    pub fn collect_garbage(&self) {
+        while self.channels.garbage.pop().is_some() {}
+    }
+
+    /// Save the source's phase and hop position to `path`
+    ///
+    /// The snapshot is taken at the start of the source's next fill, so
+    /// this fails with [`io::ErrorKind::TimedOut`] if the audio thread does
+    /// not run within a second. Load it with
+    /// [`SignalGenerator::restore_state`].
+    pub fn save_state(&self, path: &Path) -> io::Result<()> {
+        // Drop replies left over from an earlier request that timed out
+        while self.channels.snapshots.pop().is_some() {}
+
+        let state = Box::new(GeneratorState::with_capacity_for(&self.config()));
+        if self
+            .channels
+            .commands
+            .push(Command::Snapshot(state))
+            .is_err()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "command queue is full; the audio thread has not caught up",
+            ));
+        }
+
+        let deadline = Instant::now() + SNAPSHOT_TIMEOUT;
+        loop {
+            if let Some(state) = self.channels.snapshots.pop() {
+                return state.write(path);
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "audio thread did not take a snapshot",
+                ));
+            }
+            thread::sleep(SNAPSHOT_POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::thread;
+
+    /// Counts allocations and frees made by the current thread
+    struct CountingAlloc;
+
+    thread_local! {
+        static HEAP_OPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn count_heap_op() {
+        let _ = HEAP_OPS.try_with(|ops| ops.set(ops.get() + 1));
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_heap_op();
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            count_heap_op();
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_heap_op();
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+    fn heap_ops() -> usize {
+        HEAP_OPS.with(Cell::get)
+    }
+
+    fn hopping_config() -> SignalConfig {
+        SignalConfig {
+            hop_set: vec![21000.0, 22000.0, 23000.0],
+            hop_interval_ms: 20.0,
+            safety_highpass: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fill_matches_generator() {
+        let retuned = SignalConfig {
+            frequency: 22000.0,
+            ..Default::default()
+        };
+        let (mut source, controller) = SignalSource::new(SignalConfig::default()).unwrap();
+        let mut generator = SignalGenerator::new(source.config().clone());
+
+        let mut buffer = vec![0.0; 1024];
+        let mut expected = vec![0.0; 1024];
+        source.fill(&mut buffer);
+        generator.generate_buffer(&mut expected);
+        assert_eq!(buffer, expected);
+
+        controller.retune(retuned.clone()).unwrap();
+        generator.update_config(retuned);
+        source.fill(&mut buffer);
+        generator.generate_buffer(&mut expected);
+        assert_eq!(buffer, expected);
+        assert_eq!(source.config().frequency, 22000.0);
+    }
+
+    #[test]
+    fn test_fill_does_not_allocate() {
+        let (mut source, controller) = SignalSource::new(hopping_config()).unwrap();
+        let mut buffer = vec![0.0; 512];
+
+        // Hops, crossfades and a retune, all on the audio path
+        let before = heap_ops();
+        for _ in 0..50 {
+            source.fill(&mut buffer);
+        }
+        assert_eq!(heap_ops(), before);
+
+        controller
+            .retune(SignalConfig {
+                amplitude: 0.1,
+                ..hopping_config()
+            })
+            .unwrap();
+        let before = heap_ops();
+        for _ in 0..50 {
+            source.fill(&mut buffer);
+        }
+        assert_eq!(heap_ops(), before);
+    }
+
+    #[test]
+    fn test_stereo_render_does_not_allocate() {
+        let (mut source, controller) = SignalSource::with_channels(hopping_config(), 2).unwrap();
+        let mut buffer = vec![0.0; 1024];
+        let mut render = |source: &mut SignalSource| {
+            for _ in 0..50 {
+                source.render(|generator| generator.generate_interleaved(&mut buffer, 2));
+            }
+        };
+
+        let before = heap_ops();
+        render(&mut source);
+        assert_eq!(heap_ops(), before);
+
+        // Switching the high-pass off and back on swaps in filters built here
+        for safety_highpass in [false, true] {
+            controller
+                .retune(SignalConfig {
+                    safety_highpass,
+                    ..hopping_config()
+                })
+                .unwrap();
+            let before = heap_ops();
+            render(&mut source);
+            assert_eq!(heap_ops(), before);
+        }
+    }
+
+    #[test]
+    fn test_retune_from_other_threads() {
+        let (mut source, controller) = SignalSource::new(SignalConfig::default()).unwrap();
+
+        let audio = thread::spawn(move || {
+            let mut buffer = [0.0f32; 256];
+            for _ in 0..2000 {
+                source.fill(&mut buffer);
+                assert!(buffer.iter().all(|s| s.is_finite() && s.abs() <= 1.0));
+            }
+            source
+        });
+
+        let retuners: Vec<_> = [21000.0, 22000.0]
+            .into_iter()
+            .map(|frequency| {
+                let controller = controller.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let config = SignalConfig {
+                            frequency,
+                            ..Default::default()
+                        };
+                        // The audio thread may fall behind; dropping a retune is fine
+                        let _ = controller.retune(config);
+                        thread::yield_now();
+                    }
+                })
+            })
+            .collect();
+        for retuner in retuners {
+            retuner.join().unwrap();
+        }
+
+        // Apply whatever is still queued, leaving room for one more
+        let mut source = audio.join().unwrap();
+        source.fill(&mut [0.0; 16]);
+        let last = SignalConfig {
+            frequency: 23500.0,
+            ..Default::default()
+        };
+        controller.retune(last).unwrap();
+        source.fill(&mut [0.0; 16]);
+        assert_eq!(source.config().frequency, 23500.0);
+    }
+
+    #[test]
+    fn test_save_state_from_audio_thread() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("state.json");
+
+        // Nothing runs the source, so the request times out
+        let (_idle, controller) = SignalSource::new(SignalConfig::default()).unwrap();
+        let err = controller.save_state(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // Saved between hops; filter state is not part of the snapshot
+        let config = SignalConfig {
+            hop_interval_ms: 50.0,
+            safety_highpass: false,
+            ..hopping_config()
+        };
+        let (mut source, controller) = SignalSource::new(config.clone()).unwrap();
+        source.fill(&mut [0.0; 1000]);
+        let audio = thread::spawn(move || {
+            while source.channels.commands.is_empty() {
+                thread::yield_now();
+            }
+            source.fill(&mut [0.0; 64]);
+            source
+        });
+        controller.save_state(&path).unwrap();
+        let mut source = audio.join().unwrap();
+
+        // The snapshot was taken before that last fill
+        let mut restored = SignalGenerator::new(config);
+        assert!(restored.restore_state(&path).unwrap());
+        restored.generate_buffer(&mut [0.0; 64]);
+        let mut expected = vec![0.0; 4096];
+        let mut buffer = vec![0.0; 4096];
+        restored.generate_buffer(&mut expected);
+        source.fill(&mut buffer);
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    fn test_invalid_retune_is_rejected() {
+        let (_source, controller) = SignalSource::new(SignalConfig::default()).unwrap();
+        let audible = SignalConfig {
+            frequency: 15000.0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            controller.retune(audible),
+            Err(RetuneError::InvalidConfig(_))
+        ));
+
+        for _ in 0..COMMAND_QUEUE_CAPACITY {
+            controller.retune(SignalConfig::default()).unwrap();
+        }
+        assert_eq!(
+            controller.retune(SignalConfig::default()),
+            Err(RetuneError::QueueFull)
+        );
+    }
+}
@@ -0,0 +1,904 @@
+//! Offline rendering of the signal to audio files
+//!
+//! Output is WAV or FLAC, chosen by extension. Lossy formats are rejected:
+//! their encoders low-pass around 16-20 kHz, which strips the signal.
+
+use crate::flac::write_flac;
+use crate::mix::{normalization_gain, MixCurve};
+use crate::signal::{xorshift64, SignalConfig, SignalGenerator};
+use anyhow::{Context, Result};
+use hound::{WavReader, WavSpec, WavWriter};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File extensions picked up by [`mix_directory`]
+const MIX_EXTENSIONS: [&str; 2] = ["wav", "flac"];
+
+/// Extensions of lossy formats, which cannot carry the ultrasonic signal
+const LOSSY_EXTENSIONS: [&str; 6] = ["mp3", "ogg", "oga", "opus", "m4a", "aac"];
+
+/// Container written by [`write_audio`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Wav,
+    Flac,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            None | Some("wav") => Ok(Self::Wav),
+            Some("flac") => Ok(Self::Flac),
+            Some(lossy) if LOSSY_EXTENSIONS.contains(&lossy) => anyhow::bail!(
+                "Cannot write .{}: lossy encoders low-pass around 16-20 kHz and strip the \
+                 ultrasonic signal. Use .wav or .flac instead",
+                lossy
+            ),
+            Some(other) => anyhow::bail!("Unsupported output format: .{}", other),
+        }
+    }
+}
+
+/// What happened at one point of a rendered file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignalEvent {
+    /// Frame the event takes effect on
+    pub sample: usize,
+    pub seconds: f64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum EventKind {
+    /// The signal starts on this carrier
+    Start { frequency_hz: f32 },
+    /// The carrier hops; the crossfade to the new one starts here
+    Hop { from_hz: f32, to_hz: f32 },
+}
+
+/// Where the signal changes in a rendered file, for aligning analysis
+///
+/// Written next to the audio by [`EventLog::write_sidecar`], since WAV
+/// cue chunks are not supported by the writer.
+#[derive(Debug, Clone, Serialize)]
+pub struct EventLog {
+    pub sample_rate: u32,
+    /// Length of the file in frames
+    pub frames: usize,
+    /// Config the signal was rendered from, with the seed that was used
+    pub config: SignalConfig,
+    pub events: Vec<SignalEvent>,
+}
+
+impl EventLog {
+    /// `<audio file>.events.json`
+    pub fn sidecar_path(audio_path: &Path) -> PathBuf {
+        let mut name = OsString::from(audio_path.as_os_str());
+        name.push(".events.json");
+        PathBuf::from(name)
+    }
+
+    /// Write the log as JSON next to `audio_path`, returning its path
+    pub fn write_sidecar(&self, audio_path: &Path) -> Result<PathBuf> {
+        let path = Self::sidecar_path(audio_path);
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+/// Fill an interleaved buffer like [`SignalGenerator::generate_interleaved`],
+/// logging where the carrier changes
+fn render_logged(config: &SignalConfig, buffer: &mut [f32], channels: usize) -> EventLog {
+    let mut generator = SignalGenerator::new(config.clone());
+    let seconds = |sample: usize| sample as f64 / config.sample_rate as f64;
+    let mut carrier = generator.config().frequency;
+    let mut events = vec![SignalEvent {
+        sample: 0,
+        seconds: 0.0,
+        kind: EventKind::Start {
+            frequency_hz: carrier,
+        },
+    }];
+
+    let frames = buffer.chunks_mut(channels.max(1));
+    let count = frames.len();
+    for (sample, frame) in frames.enumerate() {
+        generator.next_frame(frame);
+        let frequency = generator.config().frequency;
+        if frequency != carrier {
+            events.push(SignalEvent {
+                sample,
+                seconds: seconds(sample),
+                kind: EventKind::Hop {
+                    from_hz: carrier,
+                    to_hz: frequency,
+                },
+            });
+            carrier = frequency;
+        }
+    }
+
+    EventLog {
+        sample_rate: config.sample_rate,
+        frames: count,
+        config: SignalConfig {
+            seed: generator.config().seed,
+            ..config.clone()
+        },
+        events,
+    }
+}
+
+/// Outcome of mixing one file in a batch
+#[derive(Debug)]
+pub struct MixOutcome {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub result: Result<()>,
+}
+
+/// Read a WAV file as interleaved samples scaled to -1.0..1.0
+pub fn read_wav(path: &Path) -> Result<(WavSpec, Vec<f32>)> {
+    let mut reader = WavReader::open(path).context("Failed to open WAV file")?;
+    let spec = reader.spec();
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .context("Failed to read WAV samples")?,
+        hound::SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<Result<_, _>>()
+                .context("Failed to read WAV samples")?
+        }
+    };
+
+    Ok((spec, samples))
+}
+
+/// Read a FLAC file as interleaved samples, with the equivalent WAV format
+pub fn read_flac(path: &Path) -> Result<(WavSpec, Vec<f32>)> {
+    let mut reader = claxon::FlacReader::open(path).context("Failed to open FLAC file")?;
+    let info = reader.streaminfo();
+    let spec = WavSpec {
+        channels: info.channels as u16,
+        sample_rate: info.sample_rate,
+        bits_per_sample: info.bits_per_sample as u16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+    let samples = reader
+        .samples()
+        .map(|s| s.map(|v| v as f32 / scale))
+        .collect::<Result<_, _>>()
+        .context("Failed to decode FLAC samples")?;
+
+    Ok((spec, samples))
+}
+
+/// Read a WAV or FLAC file, chosen by extension
+pub fn read_audio(path: &Path) -> Result<(WavSpec, Vec<f32>)> {
+    if has_extension(path, "flac") {
+        read_flac(path)
+    } else {
+        read_wav(path)
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// Quantize a float sample to a `bits`-bit integer, saturating at full scale
+///
+/// Values beyond ±1.0, e.g. from tones peaking together, clip to full scale
+/// instead of wrapping to the opposite sign. NaN becomes 0.
+pub fn f32_to_int_sat(sample: f32, bits: u16) -> i32 {
+    let full_scale = ((1i64 << (bits - 1)) - 1) as f64;
+    (sample.clamp(-1.0, 1.0) as f64 * full_scale).round() as i32
+}
+
+/// TPDF dither added before quantizing to integer samples
+///
+/// Triangular noise spanning ±1 LSB decorrelates the rounding error from
+/// the signal, so a quiet signal leaves a flat noise floor instead of
+/// discrete spurs that can land in the audible band. Seeded, so a render
+/// can be repeated sample for sample.
+#[derive(Debug, Clone)]
+pub struct Dither {
+    rng: u64,
+}
+
+impl Dither {
+    pub fn new(seed: u64) -> Self {
+        // xorshift must not start at zero
+        Self {
+            rng: (seed ^ 0x94d0_49bb_1331_11eb).max(1),
+        }
+    }
+
+    /// Dither for a render of `config`, from its seed
+    fn for_config(config: &SignalConfig) -> Self {
+        Self::new(config.seed.unwrap_or(0))
+    }
+
+    /// Uniform value in -0.5..0.5
+    fn uniform(&mut self) -> f64 {
+        (xorshift64(&mut self.rng) >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+    }
+
+    /// Quantize like [`f32_to_int_sat`], with the dither added first
+    pub fn quantize(&mut self, sample: f32, bits: u16) -> i32 {
+        let full_scale = ((1i64 << (bits - 1)) - 1) as f64;
+        let tpdf = self.uniform() + self.uniform();
+        (sample.clamp(-1.0, 1.0) as f64 * full_scale + tpdf)
+            .round()
+            .clamp(-full_scale, full_scale) as i32
+    }
+}
+
+/// Quantize a float sample to 16 bits, saturating at full scale
+pub fn f32_to_i16_sat(sample: f32) -> i16 {
+    f32_to_int_sat(sample, 16) as i16
+}
+
+/// Quantize a float sample to 24 bits in an `i32`, saturating at full scale
+pub fn f32_to_i24_sat(sample: f32) -> i32 {
+    f32_to_int_sat(sample, 24)
+}
+
+/// Quantize a float sample to 32 bits, saturating at full scale
+pub fn f32_to_i32_sat(sample: f32) -> i32 {
+    f32_to_int_sat(sample, 32)
+}
+
+/// Quantize a sample to `bits`, dithered if there is a [`Dither`]
+fn quantize(sample: f32, bits: u16, dither: &mut Option<Dither>) -> i32 {
+    match dither {
+        Some(dither) => dither.quantize(sample, bits),
+        None => f32_to_int_sat(sample, bits),
+    }
+}
+
+/// Write one sample in the writer's format, saturating at full scale
+fn write_sample<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    spec: WavSpec,
+    sample: f32,
+    dither: &mut Option<Dither>,
+) -> hound::Result<()> {
+    match spec.sample_format {
+        hound::SampleFormat::Float => writer.write_sample(sample.clamp(-1.0, 1.0)),
+        hound::SampleFormat::Int => {
+            writer.write_sample(quantize(sample, spec.bits_per_sample, dither))
+        }
+    }
+}
+
+/// Write interleaved samples as WAV or FLAC, chosen by the file extension
+///
+/// FLAC has no float samples, so float specs are written as 24-bit FLAC.
+/// `dither` applies wherever samples are quantized to integers.
+pub fn write_audio(
+    path: &Path,
+    spec: WavSpec,
+    samples: &[f32],
+    mut dither: Option<Dither>,
+) -> Result<()> {
+    match OutputFormat::from_path(path)? {
+        OutputFormat::Wav => {
+            let mut writer = WavWriter::create(path, spec).context("Failed to create WAV file")?;
+            for &sample in samples {
+                write_sample(&mut writer, spec, sample, &mut dither)?;
+            }
+            writer.finalize().context("Failed to finalize WAV file")?;
+        }
+        OutputFormat::Flac => {
+            let bits_per_sample = match spec.sample_format {
+                hound::SampleFormat::Float => 24,
+                hound::SampleFormat::Int => spec.bits_per_sample,
+            };
+            let quantized: Vec<i32> = samples
+                .iter()
+                .map(|&s| quantize(s, bits_per_sample, &mut dither))
+                .collect();
+            write_flac(
+                path,
+                &quantized,
+                spec.channels,
+                spec.sample_rate,
+                bits_per_sample,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `duration` of the pure ultrasonic signal as mono 16-bit WAV or FLAC
+///
+/// With `dither`, TPDF dither seeded from the signal's seed is added
+/// before quantizing. Returns where the carrier changed, for
+/// [`EventLog::write_sidecar`].
+pub fn write_ultrasonic(
+    output_path: &Path,
+    config: &SignalConfig,
+    duration: Duration,
+    dither: bool,
+) -> Result<EventLog> {
+    config.validate().context("Invalid signal configuration")?;
+
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: config.sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let num_samples = (duration.as_secs_f64() * config.sample_rate as f64).round() as usize;
+    let mut samples = vec![0.0; num_samples];
+    let log = render_logged(config, &mut samples, 1);
+
+    let dither = dither.then(|| Dither::for_config(&log.config));
+    write_audio(output_path, spec, &samples, dither)?;
+    Ok(log)
+}
+
+/// Mix the ultrasonic signal into an existing recording
+///
+/// Input and output may each be WAV or FLAC. The output keeps the input's
+/// sample rate, channel count and sample format. Each channel gets the
+/// signal at its `channel_phase_offsets` entry, `curve` sets the gains
+/// for `mix_ratio`, and the mix is scaled down if the combined peak would
+/// exceed full scale. With `normalize_input`, the recording is first
+/// brought to a fixed loudness, so a ratio sounds the same for quiet and
+/// loud sources. `dither` and the returned log are as for [`write_ultrasonic`].
+pub fn mix_ultrasonic(
+    input_path: &Path,
+    output_path: &Path,
+    config: &SignalConfig,
+    mix_ratio: f32,
+    curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+) -> Result<EventLog> {
+    mix_signal(
+        input_path,
+        output_path,
+        config,
+        mix_ratio,
+        curve,
+        normalize_input,
+        dither,
+        false,
+    )
+}
+
+/// [`mix_ultrasonic`] for research sweeps that reach below 20 kHz
+///
+/// Validates with [`SignalConfig::validate_allowing_audible`], so tones
+/// may be audible but must stay below the input's Nyquist limit.
+pub fn mix_signal_allowing_audible(
+    input_path: &Path,
+    output_path: &Path,
+    config: &SignalConfig,
+    mix_ratio: f32,
+    curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+) -> Result<EventLog> {
+    mix_signal(
+        input_path,
+        output_path,
+        config,
+        mix_ratio,
+        curve,
+        normalize_input,
+        dither,
+        true,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn mix_signal(
+    input_path: &Path,
+    output_path: &Path,
+    config: &SignalConfig,
+    mix_ratio: f32,
+    curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+    allow_audible: bool,
+) -> Result<EventLog> {
+    if !(0.0..=1.0).contains(&mix_ratio) {
+        anyhow::bail!("Mix ratio must be between 0.0 and 1.0, got {}", mix_ratio);
+    }
+
+    let (spec, mut samples) = read_audio(input_path)?;
+    if normalize_input {
+        let gain = normalization_gain(&samples);
+        samples.iter_mut().for_each(|s| *s *= gain);
+    }
+    let config = SignalConfig {
+        sample_rate: spec.sample_rate,
+        ..config.clone()
+    };
+    let valid = if allow_audible {
+        config.validate_allowing_audible()
+    } else {
+        config.validate()
+    };
+    valid.with_context(|| {
+        format!(
+            "Invalid signal configuration for {} Hz input",
+            spec.sample_rate
+        )
+    })?;
+
+    // Pre-scale by the worst-case combined peak so the mix never exceeds full scale
+    let input_peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+    let (original_gain, signal_gain) = curve.gains(mix_ratio);
+    let combined_peak =
+        input_peak * original_gain + config.amplitude.abs() * config.master_gain * signal_gain;
+    let gain = if combined_peak > 1.0 {
+        1.0 / combined_peak
+    } else {
+        1.0
+    };
+
+    let mut mixed = vec![0.0; samples.len()];
+    let log = render_logged(&config, &mut mixed, spec.channels as usize);
+    for (sample, &original) in mixed.iter_mut().zip(&samples) {
+        *sample = (original * original_gain + *sample * signal_gain) * gain;
+    }
+
+    let dither = dither.then(|| Dither::for_config(&log.config));
+    write_audio(output_path, spec, &mixed, dither)?;
+    Ok(log)
+}
+
+/// Mix the signal into every WAV and FLAC file in `in_dir`, in parallel
+///
+/// Outputs go to `out_dir` under the same name and format, mixed and
+/// dithered as by [`mix_ultrasonic`]. Files that fail are reported in their [`MixOutcome`]
+/// instead of aborting the batch. Subdirectories are not searched.
+pub fn mix_directory(
+    in_dir: &Path,
+    out_dir: &Path,
+    config: &SignalConfig,
+    mix_ratio: f32,
+    curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+) -> Result<Vec<MixOutcome>> {
+    if !(0.0..=1.0).contains(&mix_ratio) {
+        anyhow::bail!("Mix ratio must be between 0.0 and 1.0, got {}", mix_ratio);
+    }
+
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(in_dir)
+        .with_context(|| format!("Failed to read directory {}", in_dir.display()))?
+    {
+        let path = entry.context("Failed to read directory entry")?.path();
+        if path.is_file() && MIX_EXTENSIONS.iter().any(|e| has_extension(&path, e)) {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+
+    fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create directory {}", out_dir.display()))?;
+    if fs::canonicalize(in_dir)? == fs::canonicalize(out_dir)? {
+        anyhow::bail!("Output directory must differ from the input directory");
+    }
+
+    Ok(inputs
+        .into_par_iter()
+        .map(|input| {
+            let extension = if has_extension(&input, "flac") {
+                "flac"
+            } else {
+                "wav"
+            };
+            let output = out_dir
+                .join(input.file_name().unwrap_or_default())
+                .with_extension(extension);
+            let result = mix_ultrasonic(
+                &input,
+                &output,
+                config,
+                mix_ratio,
+                curve,
+                normalize_input,
+                dither,
+            )
+            .map(drop);
+            MixOutcome {
+                input,
+                output,
+                result,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::{analyze_samples, analyze_wav};
+    use std::f32::consts::PI;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sample_conversion_saturates() {
+        assert_eq!(f32_to_i16_sat(1.0), i16::MAX);
+        assert_eq!(f32_to_i16_sat(1.01), i16::MAX);
+        assert_eq!(f32_to_i16_sat(1e9), i16::MAX);
+        assert_eq!(f32_to_i16_sat(-1.0), -i16::MAX);
+        assert_eq!(f32_to_i16_sat(-1.01), -i16::MAX);
+        assert_eq!(f32_to_i16_sat(f32::NEG_INFINITY), -i16::MAX);
+        assert_eq!(f32_to_i16_sat(0.5), 16384);
+        assert_eq!(f32_to_i16_sat(f32::NAN), 0);
+
+        assert_eq!(f32_to_i24_sat(1.0), 8_388_607);
+        assert_eq!(f32_to_i24_sat(1.5), 8_388_607);
+        assert_eq!(f32_to_i24_sat(-1.5), -8_388_607);
+
+        assert_eq!(f32_to_i32_sat(1.0), i32::MAX);
+        assert_eq!(f32_to_i32_sat(2.0), i32::MAX);
+        assert_eq!(f32_to_i32_sat(-2.0), -i32::MAX);
+        assert!(f32_to_i32_sat(0.999) > 0);
+    }
+
+    #[test]
+    fn test_dither_saturates_like_plain_quantizing() {
+        let mut dither = Dither::new(0);
+        let full_scale = i16::MAX as i32;
+        let loud: Vec<i32> = (0..1000).map(|_| dither.quantize(1.5, 16)).collect();
+        let quiet: Vec<i32> = (0..1000).map(|_| dither.quantize(-1.5, 16)).collect();
+        assert_eq!(loud.iter().max(), Some(&full_scale));
+        assert_eq!(quiet.iter().min(), Some(&-full_scale));
+        assert!(loud.iter().all(|&s| s >= full_scale - 2));
+        assert!(quiet.iter().all(|&s| s <= -full_scale + 2));
+        assert!((0..1000).all(|_| dither.quantize(0.0, 16).abs() <= 1));
+    }
+
+    #[test]
+    fn test_dithered_output_is_repeatable_and_lowers_spurs() {
+        let temp_dir = TempDir::new().unwrap();
+        // A few LSBs of signal, whose rounding error repeats with the tones
+        let config = SignalConfig {
+            amplitude: 0.0005,
+            seed: Some(3),
+            ..Default::default()
+        };
+        let write = |name: &str, dither| {
+            let path = temp_dir.path().join(name);
+            write_ultrasonic(&path, &config, Duration::from_secs(1), dither).unwrap();
+            path
+        };
+        let plain = write("plain.wav", false);
+        let dithered = write("dithered.wav", true);
+        assert_eq!(
+            read_wav(&dithered).unwrap(),
+            read_wav(&write("again.wav", true)).unwrap()
+        );
+
+        let plain_db = analyze_wav(&plain).unwrap().audible_peak_db;
+        let dithered_db = analyze_wav(&dithered).unwrap().audible_peak_db;
+        assert!(
+            dithered_db < plain_db - 6.0,
+            "dithered spur {:.1} dB vs undithered {:.1} dB",
+            dithered_db,
+            plain_db
+        );
+
+        // Mixing into a file dithers the same way
+        let silence = temp_dir.path().join("silence.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: config.sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        write_audio(&silence, spec, &vec![0.0; 48000], None).unwrap();
+        let mix = |name: &str, dither| {
+            let path = temp_dir.path().join(name);
+            let config = SignalConfig {
+                amplitude: 0.001,
+                ..config.clone()
+            };
+            mix_ultrasonic(
+                &silence,
+                &path,
+                &config,
+                0.5,
+                MixCurve::Linear,
+                false,
+                dither,
+            )
+            .unwrap();
+            analyze_wav(&path).unwrap().audible_peak_db
+        };
+        let plain_db = mix("mixed_plain.wav", false);
+        let dithered_db = mix("mixed_dithered.wav", true);
+        assert!(
+            dithered_db < plain_db - 6.0,
+            "{:.1} vs {:.1}",
+            dithered_db,
+            plain_db
+        );
+    }
+
+    #[test]
+    fn test_write_ultrasonic() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("jam.wav");
+        let config = SignalConfig::default();
+
+        write_ultrasonic(&path, &config, Duration::from_millis(500), false).unwrap();
+
+        let (spec, samples) = read_wav(&path).unwrap();
+        assert_eq!(spec.sample_rate, config.sample_rate);
+        assert_eq!(samples.len(), config.sample_rate as usize / 2);
+        assert!(analyze_wav(&path).unwrap().is_ultrasonic());
+    }
+
+    #[test]
+    fn test_event_log_marks_hops() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("hops.wav");
+        let config = SignalConfig {
+            hop_set: vec![21000.0, 22000.0, 23000.0],
+            hop_interval_ms: 100.0,
+            ..Default::default()
+        };
+
+        let log = write_ultrasonic(&path, &config, Duration::from_millis(550), false).unwrap();
+        assert_eq!(log.frames, 26400);
+        assert!(log.config.seed.is_some());
+        let EventKind::Start { frequency_hz } = log.events[0].kind else {
+            panic!("{:?}", log.events[0]);
+        };
+        let mut carrier = frequency_hz;
+        // One hop every 4800 frames
+        assert_eq!(log.events.len(), 6);
+        for (n, event) in log.events[1..].iter().enumerate() {
+            assert_eq!(event.sample, 4800 * (n + 1) - 1);
+            let EventKind::Hop { from_hz, to_hz } = event.kind else {
+                panic!("{:?}", event);
+            };
+            assert_eq!(from_hz, carrier);
+            assert!(to_hz != from_hz && config.hop_set.contains(&to_hz));
+            carrier = to_hz;
+        }
+
+        let sidecar = log.write_sidecar(&path).unwrap();
+        assert_eq!(sidecar, temp_dir.path().join("hops.wav.events.json"));
+        let json: serde_json::Value = serde_json::from_slice(&fs::read(&sidecar).unwrap()).unwrap();
+        assert_eq!(json["events"][1]["event"], "hop");
+        assert_eq!(json["events"][1]["sample"], 4799);
+    }
+
+    #[test]
+    fn test_ultrasonic_survives_flac() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("jam.wav");
+        let flac_path = temp_dir.path().join("jam.flac");
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        write_ultrasonic(&wav_path, &config, Duration::from_millis(500), false).unwrap();
+        write_ultrasonic(&flac_path, &config, Duration::from_millis(500), false).unwrap();
+
+        // Lossless: identical samples, so the spectrum is unchanged
+        let (wav_spec, wav_samples) = read_wav(&wav_path).unwrap();
+        let (flac_spec, flac_samples) = read_audio(&flac_path).unwrap();
+        assert_eq!(flac_spec, wav_spec);
+        assert_eq!(flac_samples, wav_samples);
+
+        let report = analyze_samples(&flac_samples, flac_spec.sample_rate).unwrap();
+        assert!(report.is_ultrasonic());
+        assert!((report.peak_frequencies[0] - config.frequency).abs() < 400.0);
+    }
+
+    #[test]
+    fn test_lossy_output_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["jam.mp3", "jam.OGG", "jam.opus"] {
+            let err = write_ultrasonic(
+                &temp_dir.path().join(name),
+                &SignalConfig::default(),
+                Duration::from_millis(10),
+                false,
+            )
+            .unwrap_err();
+            assert!(err.to_string().contains("lossy"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_mix_keeps_input_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("voice.wav");
+        let output_path = temp_dir.path().join("protected.wav");
+
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 96000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input_path, spec).unwrap();
+        for i in 0..96000 {
+            let t = i as f32 / 96000.0;
+            let sample = (0.5 * (2.0 * PI * 1000.0 * t).sin() * 8_388_607.0) as i32;
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config = SignalConfig::default();
+        mix_ultrasonic(
+            &input_path,
+            &output_path,
+            &config,
+            0.5,
+            MixCurve::Linear,
+            false,
+            false,
+        )
+        .unwrap();
+
+        let (mixed_spec, mixed) = read_wav(&output_path).unwrap();
+        assert_eq!(mixed_spec, spec);
+        assert_eq!(mixed.len(), 96000 * 2);
+
+        let report = analyze_wav(&output_path).unwrap();
+        assert!(report
+            .peak_frequencies
+            .iter()
+            .any(|f| (f - 1000.0).abs() < 2.0));
+        assert!(report
+            .peak_frequencies
+            .iter()
+            .any(|f| (f - config.frequency).abs() < 400.0));
+    }
+
+    #[test]
+    fn test_equal_power_mix_is_louder_at_half() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("voice.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(&input_path, spec).unwrap();
+        for i in 0..4800 {
+            let t = i as f32 / 48000.0;
+            writer
+                .write_sample(0.5 * (2.0 * PI * 1000.0 * t).sin())
+                .unwrap();
+        }
+        writer.finalize().unwrap();
+
+        // At 0.5, equal power gives both parts 0.71 where linear gives 0.5,
+        // and neither mix comes near full scale
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        let mut mixes = Vec::new();
+        for curve in [MixCurve::Linear, MixCurve::EqualPower] {
+            let output_path = temp_dir.path().join(format!("{:?}.wav", curve));
+            mix_ultrasonic(&input_path, &output_path, &config, 0.5, curve, false, false).unwrap();
+            mixes.push(read_wav(&output_path).unwrap().1);
+        }
+
+        for (linear, equal_power) in mixes[0].iter().zip(&mixes[1]) {
+            assert!(
+                (linear * std::f32::consts::SQRT_2 - equal_power).abs() < 1e-5,
+                "{} vs {}",
+                linear,
+                equal_power
+            );
+        }
+    }
+
+    #[test]
+    fn test_mix_directory_skips_bad_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let in_dir = temp_dir.path().join("recordings");
+        let out_dir = temp_dir.path().join("protected");
+        fs::create_dir(&in_dir).unwrap();
+
+        let config = SignalConfig::default();
+        write_ultrasonic(
+            &in_dir.join("a.wav"),
+            &config,
+            Duration::from_millis(100),
+            false,
+        )
+        .unwrap();
+        write_ultrasonic(
+            &in_dir.join("c.WAV"),
+            &config,
+            Duration::from_millis(100),
+            false,
+        )
+        .unwrap();
+        fs::write(in_dir.join("b.wav"), b"not a wav file").unwrap();
+        fs::write(in_dir.join("notes.txt"), b"ignored").unwrap();
+
+        let outcomes = mix_directory(
+            &in_dir,
+            &out_dir,
+            &config,
+            0.4,
+            MixCurve::Linear,
+            true,
+            false,
+        )
+        .unwrap();
+        let names: Vec<_> = outcomes
+            .iter()
+            .map(|o| o.input.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.wav", "b.wav", "c.WAV"]);
+
+        assert!(outcomes[0].result.is_ok());
+        assert!(outcomes[1].result.is_err());
+        assert!(outcomes[2].result.is_ok());
+        assert!(out_dir.join("a.wav").exists());
+        assert!(!out_dir.join("b.wav").exists());
+        assert_eq!(outcomes[2].output, out_dir.join("c.wav"));
+    }
+
+    #[test]
+    fn test_mix_rejects_low_sample_rate() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("phone.wav");
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input_path, spec).unwrap();
+        writer.write_sample(0i16).unwrap();
+        writer.finalize().unwrap();
+
+        let err = mix_ultrasonic(
+            &input_path,
+            &temp_dir.path().join("out.wav"),
+            &SignalConfig::default(),
+            0.5,
+            MixCurve::Linear,
+            false,
+            false,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("Nyquist"), "{:#}", err);
+    }
+}
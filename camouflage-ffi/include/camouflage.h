@@ -0,0 +1,52 @@
+/* C interface to the Camouflage signal generator (camouflage-ffi) */
+
+#ifndef CAMOUFLAGE_H
+#define CAMOUFLAGE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CAM_WAVEFORM_SINE 0
+#define CAM_WAVEFORM_SQUARE 1
+#define CAM_WAVEFORM_SAWTOOTH 2
+#define CAM_WAVEFORM_TRIANGLE 3
+#define CAM_WAVEFORM_BAND_NOISE 4
+
+typedef struct CamSignalConfig {
+    float frequency;
+    uint32_t sample_rate;
+    float amplitude;
+    uint32_t num_tones;
+    float frequency_spread;
+    uint32_t waveform; /* One of CAM_WAVEFORM_* */
+    bool safety_highpass;
+    float crossfade_ms;
+    bool random_phases;
+    uint64_t phase_seed;
+} CamSignalConfig;
+
+typedef struct CamGenerator CamGenerator;
+
+/* Default signal settings */
+CamSignalConfig cam_signal_config_default(void);
+
+/* Returns NULL if the configuration is invalid. The same configuration
+ * always produces the same samples. */
+CamGenerator *cam_generator_new(const CamSignalConfig *config);
+
+/* Writes len mono samples to buf; returns 0, or -1 on a NULL pointer */
+int32_t cam_generator_fill(CamGenerator *generator, float *buf, size_t len);
+
+/* NULL is ignored */
+void cam_generator_free(CamGenerator *generator);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CAMOUFLAGE_H */
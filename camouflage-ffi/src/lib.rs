@@ -0,0 +1,152 @@
+//! C ABI for embedding the signal generator
+//!
+//! Exposes [`SignalGenerator`] to non-Rust hosts such as mobile apps, which
+//! drive their own audio output and only need the DSP. The matching header
+//! is `include/camouflage.h`.
+
+use camouflage_core::{SignalConfig, SignalGenerator, WaveformType};
+use std::ptr;
+
+pub const CAM_WAVEFORM_SINE: u32 = 0;
+pub const CAM_WAVEFORM_SQUARE: u32 = 1;
+pub const CAM_WAVEFORM_SAWTOOTH: u32 = 2;
+pub const CAM_WAVEFORM_TRIANGLE: u32 = 3;
+pub const CAM_WAVEFORM_BAND_NOISE: u32 = 4;
+
+/// C-compatible subset of [`SignalConfig`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CamSignalConfig {
+    pub frequency: f32,
+    pub sample_rate: u32,
+    pub amplitude: f32,
+    pub num_tones: u32,
+    pub frequency_spread: f32,
+    /// One of the `CAM_WAVEFORM_*` constants
+    pub waveform: u32,
+    pub safety_highpass: bool,
+    pub crossfade_ms: f32,
+    pub random_phases: bool,
+    pub phase_seed: u64,
+}
+
+impl From<&SignalConfig> for CamSignalConfig {
+    fn from(config: &SignalConfig) -> Self {
+        Self {
+            frequency: config.frequency,
+            sample_rate: config.sample_rate,
+            amplitude: config.amplitude,
+            num_tones: config.num_tones as u32,
+            frequency_spread: config.frequency_spread,
+            waveform: match config.waveform {
+                WaveformType::Sine => CAM_WAVEFORM_SINE,
+                WaveformType::Square => CAM_WAVEFORM_SQUARE,
+                WaveformType::Sawtooth => CAM_WAVEFORM_SAWTOOTH,
+                WaveformType::Triangle => CAM_WAVEFORM_TRIANGLE,
+                WaveformType::BandNoise => CAM_WAVEFORM_BAND_NOISE,
+            },
+            safety_highpass: config.safety_highpass,
+            crossfade_ms: config.crossfade_ms,
+            random_phases: config.random_phases,
+            phase_seed: config.phase_seed,
+        }
+    }
+}
+
+impl TryFrom<&CamSignalConfig> for SignalConfig {
+    type Error = ();
+
+    /// Fails on an unknown waveform code
+    fn try_from(config: &CamSignalConfig) -> Result<Self, ()> {
+        let waveform = match config.waveform {
+            CAM_WAVEFORM_SINE => WaveformType::Sine,
+            CAM_WAVEFORM_SQUARE => WaveformType::Square,
+            CAM_WAVEFORM_SAWTOOTH => WaveformType::Sawtooth,
+            CAM_WAVEFORM_TRIANGLE => WaveformType::Triangle,
+            CAM_WAVEFORM_BAND_NOISE => WaveformType::BandNoise,
+            _ => return Err(()),
+        };
+
+        Ok(Self {
+            frequency: config.frequency,
+            sample_rate: config.sample_rate,
+            amplitude: config.amplitude,
+            num_tones: config.num_tones as usize,
+            frequency_spread: config.frequency_spread,
+            waveform,
+            safety_highpass: config.safety_highpass,
+            crossfade_ms: config.crossfade_ms,
+            random_phases: config.random_phases,
+            phase_seed: config.phase_seed,
+            // The C struct has no seed; keep its output reproducible
+            seed: Some(0),
+            ..Default::default()
+        })
+    }
+}
+
+/// Default signal settings, as a starting point for the caller
+#[no_mangle]
+pub extern "C" fn cam_signal_config_default() -> CamSignalConfig {
+    CamSignalConfig::from(&SignalConfig::default())
+}
+
+/// Create a generator, or return null if the configuration is invalid
+///
+/// The configuration is validated as the CLI does, so every tone must be
+/// ultrasonic and below the Nyquist limit of `sample_rate`.
+///
+/// # Safety
+///
+/// `config` must be null or point to a valid `CamSignalConfig`. A non-null
+/// result must be released with [`cam_generator_free`].
+#[no_mangle]
+pub unsafe extern "C" fn cam_generator_new(config: *const CamSignalConfig) -> *mut SignalGenerator {
+    let Some(config) = config.as_ref() else {
+        return ptr::null_mut();
+    };
+    let Ok(config) = SignalConfig::try_from(config) else {
+        return ptr::null_mut();
+    };
+    if config.validate().is_err() {
+        return ptr::null_mut();
+    }
+
+    Box::into_raw(Box::new(SignalGenerator::new(config)))
+}
+
+/// Fill `buf` with `len` mono samples; returns 0, or -1 on a null pointer
+///
+/// # Safety
+///
+/// `generator` must come from [`cam_generator_new`] and not be freed, and
+/// `buf` must be valid for writing `len` floats.
+#[no_mangle]
+pub unsafe extern "C" fn cam_generator_fill(
+    generator: *mut SignalGenerator,
+    buf: *mut f32,
+    len: usize,
+) -> i32 {
+    let Some(generator) = generator.as_mut() else {
+        return -1;
+    };
+    if buf.is_null() {
+        return -1;
+    }
+
+    generator.generate_buffer(std::slice::from_raw_parts_mut(buf, len));
+    0
+}
+
+/// Release a generator; null is ignored
+///
+/// # Safety
+///
+/// `generator` must be null or come from [`cam_generator_new`], and must not
+/// be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cam_generator_free(generator: *mut SignalGenerator) {
+    if !generator.is_null() {
+        drop(Box::from_raw(generator));
+    }
+}
@@ -0,0 +1,71 @@
+use camouflage_core::{SignalConfig, SignalGenerator};
+use camouflage_ffi::{
+    cam_generator_fill, cam_generator_free, cam_generator_new, cam_signal_config_default,
+    CamSignalConfig, CAM_WAVEFORM_TRIANGLE,
+};
+use std::ptr;
+
+#[test]
+fn test_generator_through_c_abi() {
+    let config = CamSignalConfig {
+        sample_rate: 96000,
+        num_tones: 5,
+        ..cam_signal_config_default()
+    };
+
+    let mut samples = vec![0.0f32; 4800];
+    unsafe {
+        let generator = cam_generator_new(&config);
+        assert!(!generator.is_null());
+        assert_eq!(
+            cam_generator_fill(generator, samples.as_mut_ptr(), samples.len()),
+            0
+        );
+        cam_generator_free(generator);
+    }
+
+    // Same output as the Rust API with the same settings, which the C API
+    // always seeds with zero
+    let mut expected = vec![0.0f32; samples.len()];
+    SignalGenerator::new(SignalConfig {
+        sample_rate: 96000,
+        num_tones: 5,
+        seed: Some(0),
+        ..Default::default()
+    })
+    .generate_buffer(&mut expected);
+    assert_eq!(samples, expected);
+}
+
+#[test]
+fn test_invalid_input_is_rejected() {
+    let audible = CamSignalConfig {
+        frequency: 15000.0,
+        ..cam_signal_config_default()
+    };
+    let unknown_waveform = CamSignalConfig {
+        waveform: CAM_WAVEFORM_TRIANGLE + 100,
+        ..cam_signal_config_default()
+    };
+
+    unsafe {
+        assert!(cam_generator_new(&audible).is_null());
+        assert!(cam_generator_new(&unknown_waveform).is_null());
+        assert!(cam_generator_new(ptr::null()).is_null());
+
+        let mut buf = [0.0f32; 4];
+        assert_eq!(cam_generator_fill(ptr::null_mut(), buf.as_mut_ptr(), 4), -1);
+        cam_generator_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_config_layout_matches_header() {
+    use std::mem::{offset_of, size_of};
+
+    assert_eq!(offset_of!(CamSignalConfig, waveform), 20);
+    assert_eq!(offset_of!(CamSignalConfig, safety_highpass), 24);
+    assert_eq!(offset_of!(CamSignalConfig, crossfade_ms), 28);
+    assert_eq!(offset_of!(CamSignalConfig, phase_seed), 40);
+    assert_eq!(size_of::<CamSignalConfig>(), 48);
+}
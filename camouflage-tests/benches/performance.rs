@@ -4,6 +4,8 @@
 //! - Signal generation performance across different tone configurations
 //! - Impact of sample rate on performance
 //! - Buffer size effects on throughput
+//! - Interleaved multichannel generation, as the jammer feeds the speakers
+//! - Fixed-size blocks returned by value against filling a slice
 //!
 //! Run with: cargo bench
 
@@ -85,10 +87,74 @@ fn benchmark_buffer_sizes(c: &mut Criterion) {
     group.finish();
 }
 
+/// Benchmark interleaved multichannel output against duplicating a mono sample
+///
+/// `per_frame` is the real path, advancing phase once per frame with each
+/// channel on its own offset; `duplicate` renders mono and copies it to
+/// every channel, as a baseline for the per-channel overhead.
+fn benchmark_interleaved(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interleaved");
+    const FRAMES: usize = 1024;
+
+    for sample_rate in [48000, 96000, 192000] {
+        for channels in [2, 4, 8] {
+            let config = SignalConfig {
+                sample_rate,
+                channel_phase_offsets: vec![0.0, 90.0],
+                ..Default::default()
+            };
+            let label = format!("{}ch/{}", channels, sample_rate);
+
+            group.bench_function(BenchmarkId::new("per_frame", &label), |b| {
+                let mut generator = SignalGenerator::new(config.clone());
+                let mut buffer = vec![0.0f32; FRAMES * channels];
+
+                b.iter(|| generator.generate_interleaved(black_box(&mut buffer), channels));
+            });
+
+            group.bench_function(BenchmarkId::new("duplicate", &label), |b| {
+                let mut generator = SignalGenerator::new(config.clone());
+                let mut mono = vec![0.0f32; FRAMES];
+                let mut buffer = vec![0.0f32; FRAMES * channels];
+
+                b.iter(|| {
+                    generator.generate_buffer(&mut mono);
+                    for (frame, &sample) in buffer.chunks_mut(channels).zip(&mono) {
+                        frame.fill(sample);
+                    }
+                    black_box(&mut buffer);
+                });
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// Benchmark `next_block` against `generate_buffer` on a slice of the same size
+fn benchmark_block(c: &mut Criterion) {
+    let mut group = c.benchmark_group("block_1024");
+
+    group.bench_function("next_block", |b| {
+        let mut generator = SignalGenerator::new(SignalConfig::default());
+        b.iter(|| black_box(generator.next_block::<1024>()));
+    });
+
+    group.bench_function("generate_buffer", |b| {
+        let mut generator = SignalGenerator::new(SignalConfig::default());
+        let mut buffer = [0.0f32; 1024];
+        b.iter(|| generator.generate_buffer(black_box(&mut buffer)));
+    });
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_signal_generation,
     benchmark_sample_rates,
-    benchmark_buffer_sizes
+    benchmark_buffer_sizes,
+    benchmark_interleaved,
+    benchmark_block
 );
 criterion_main!(benches);
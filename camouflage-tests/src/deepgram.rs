@@ -1,18 +1,38 @@
+use crate::retry::RetryPolicy;
+use crate::transcriber::JammingCriteria;
 use anyhow::{Context, Result};
 use reqwest::Client;
-use serde::Deserialize;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tracing::info;
 
 const DEEPGRAM_API_URL: &str = "https://api.deepgram.com/v1/listen";
 
+/// Body for transcribing audio Deepgram fetches itself
+#[derive(Debug, Serialize)]
+struct UrlRequest<'a> {
+    url: &'a str,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeepgramResponse {
     pub results: DeepgramResults,
 }
 
+impl DeepgramResponse {
+    /// The first channel's top alternative, if Deepgram returned one
+    ///
+    /// Silent or ultrasonic-only audio can come back with no channels or
+    /// no alternatives at all.
+    pub fn best_alternative(&self) -> Option<&DeepgramAlternative> {
+        self.results.channels.first()?.alternatives.first()
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeepgramResults {
     pub channels: Vec<DeepgramChannel>,
@@ -37,10 +57,35 @@ pub struct DeepgramResult {
     pub word_count: usize,
 }
 
+impl From<&DeepgramResponse> for DeepgramResult {
+    /// An empty transcript with zero confidence if nothing was recognized
+    fn from(response: &DeepgramResponse) -> Self {
+        let Some(alternative) = response.best_alternative() else {
+            return Self {
+                transcript: String::new(),
+                confidence: 0.0,
+                word_count: 0,
+            };
+        };
+        let transcript = alternative.transcript.trim().to_string();
+        Self {
+            word_count: transcript.split_whitespace().count(),
+            confidence: alternative.confidence,
+            transcript,
+        }
+    }
+}
+
 impl DeepgramResult {
     /// Determine if audio is effectively jammed
     pub fn is_effectively_jammed(&self) -> bool {
-        self.word_count == 0 || self.transcript.trim().is_empty() || self.confidence < 0.1
+        self.is_jammed_with(&JammingCriteria::default())
+    }
+
+    /// Determine if audio is jammed under custom thresholds
+    pub fn is_jammed_with(&self, criteria: &JammingCriteria) -> bool {
+        self.transcript.trim().is_empty()
+            || criteria.is_jammed(self.word_count, Some(self.confidence), None)
     }
 
     /// Get a quality score (0.0 = completely jammed, 1.0 = transcribed)
@@ -51,11 +96,35 @@ impl DeepgramResult {
             self.confidence
         }
     }
+
+    /// Word error rate against the clean phrase, capped at 1.0; see [`crate::wer::jam_score`]
+    pub fn jam_score(&self, clean_reference: &str) -> f64 {
+        crate::wer::jam_score(clean_reference, &self.transcript)
+    }
 }
 
 pub struct DeepgramClient {
     client: Client,
     api_key: String,
+    api_url: String,
+    retry: RetryPolicy,
+}
+
+/// Map an audio file's extension to the MIME type Deepgram expects
+pub fn content_type_for_path(audio_path: &Path) -> Result<&'static str> {
+    let extension = audio_path
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_ascii_lowercase())
+        .with_context(|| format!("Audio file has no extension: {}", audio_path.display()))?;
+
+    match extension.as_str() {
+        "wav" => Ok("audio/wav"),
+        "flac" => Ok("audio/flac"),
+        "mp3" => Ok("audio/mpeg"),
+        "ogg" => Ok("audio/ogg"),
+        other => anyhow::bail!("Unsupported audio file extension: .{}", other),
+    }
 }
 
 impl DeepgramClient {
@@ -63,9 +132,29 @@ impl DeepgramClient {
         Self {
             client: Client::new(),
             api_key,
+            api_url: DEEPGRAM_API_URL.to_string(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// Set how many times a transient (429/5xx or network) failure is retried
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time limit for each request attempt (default 60s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.retry.timeout = timeout;
+        self
+    }
+
+    /// Override the API endpoint (e.g. for a proxy or mock server)
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
     /// Transcribe audio file with Deepgram
     pub async fn transcribe_file(&self, audio_path: &Path) -> Result<DeepgramResult> {
         info!(
@@ -73,6 +162,8 @@ impl DeepgramClient {
             audio_path.display()
         );
 
+        let content_type = content_type_for_path(audio_path)?;
+
         // Read audio file
         let mut file = File::open(audio_path)
             .await
@@ -85,53 +176,80 @@ impl DeepgramClient {
 
         // Make API request
         let response = self
-            .client
-            .post(DEEPGRAM_API_URL)
-            .header("Authorization", format!("Token {}", self.api_key))
-            .header("Content-Type", "audio/wav")
-            .body(audio_data)
-            .send()
+            .retry
+            .send(|| {
+                Ok(self
+                    .client
+                    .post(&self.api_url)
+                    .header("Authorization", format!("Token {}", self.api_key))
+                    .header("Content-Type", content_type)
+                    .body(audio_data.clone()))
+            })
             .await
             .context("Failed to send transcription request")?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Deepgram API error ({}): {}", status, error_text);
-        }
+        parse_response(response).await
+    }
 
-        let deepgram_response: DeepgramResponse = response
-            .json()
+    /// Transcribe audio hosted at `url`, which Deepgram downloads itself
+    ///
+    /// Avoids uploading large files that are already on object storage.
+    pub async fn transcribe_url(&self, url: &str) -> Result<DeepgramResult> {
+        info!("Transcribing remote audio with Deepgram: {}", url);
+
+        let response = self
+            .retry
+            .send(|| {
+                Ok(self
+                    .client
+                    .post(&self.api_url)
+                    .header("Authorization", format!("Token {}", self.api_key))
+                    .json(&UrlRequest { url }))
+            })
             .await
-            .context("Failed to parse Deepgram response")?;
+            .context("Failed to send transcription request")?;
 
-        let alternative = &deepgram_response.results.channels[0].alternatives[0];
-        let transcript = alternative.transcript.trim().to_string();
-        let confidence = alternative.confidence;
-        let word_count = transcript.split_whitespace().count();
+        parse_response(response).await
+    }
+}
 
-        let result = DeepgramResult {
-            transcript: transcript.clone(),
-            confidence,
-            word_count,
-        };
+/// Turn a Deepgram HTTP response into a result, reporting API errors
+async fn parse_response(response: Response) -> Result<DeepgramResult> {
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        anyhow::bail!("Deepgram API error ({}): {}", status, error_text);
+    }
 
-        info!("Deepgram transcription result:");
-        info!("  Transcript: '{}'", transcript);
-        info!("  Confidence: {:.2}", confidence);
-        info!("  Word count: {}", word_count);
-        info!("  Effectively jammed: {}", result.is_effectively_jammed());
+    let deepgram_response: DeepgramResponse = response
+        .json()
+        .await
+        .context("Failed to parse Deepgram response")?;
 
-        Ok(result)
-    }
+    let result = DeepgramResult::from(&deepgram_response);
+    let DeepgramResult {
+        transcript,
+        confidence,
+        word_count,
+    } = &result;
+
+    info!("Deepgram transcription result:");
+    info!("  Transcript: '{}'", transcript);
+    info!("  Confidence: {:.2}", confidence);
+    info!("  Word count: {}", word_count);
+    info!("  Effectively jammed: {}", result.is_effectively_jammed());
+
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wiremock::matchers::{body_json, header, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[test]
     fn test_transcription_quality() {
@@ -153,4 +271,162 @@ mod tests {
         assert!(jammed_result.is_effectively_jammed());
         assert!(jammed_result.quality_score() < 0.1);
     }
+
+    #[test]
+    fn test_empty_channels_count_as_jammed() {
+        // As returned for audio with nothing Deepgram recognizes as speech
+        let empty: DeepgramResponse = serde_json::from_str(
+            r#"{
+                "metadata": {
+                    "request_id": "5f0b1c6e-2c1d-4a8e-9f3b-7d2e4c1a9b00",
+                    "duration": 3.0,
+                    "channels": 1
+                },
+                "results": { "channels": [] }
+            }"#,
+        )
+        .unwrap();
+        let result = DeepgramResult::from(&empty);
+        assert_eq!(result.transcript, "");
+        assert_eq!(result.confidence, 0.0);
+        assert_eq!(result.word_count, 0);
+        assert!(result.is_effectively_jammed());
+
+        let no_alternatives: DeepgramResponse =
+            serde_json::from_str(r#"{ "results": { "channels": [{ "alternatives": [] }] } }"#)
+                .unwrap();
+        assert!(DeepgramResult::from(&no_alternatives).is_effectively_jammed());
+    }
+
+    #[test]
+    fn test_content_type_for_path() {
+        assert_eq!(
+            content_type_for_path(Path::new("clean.wav")).unwrap(),
+            "audio/wav"
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("clean_voice.flac")).unwrap(),
+            "audio/flac"
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("speech.MP3")).unwrap(),
+            "audio/mpeg"
+        );
+        assert_eq!(
+            content_type_for_path(Path::new("/tmp/speech.ogg")).unwrap(),
+            "audio/ogg"
+        );
+
+        assert!(content_type_for_path(Path::new("notes.txt")).is_err());
+        assert!(content_type_for_path(Path::new("no_extension")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_retries_transient_errors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "results": {
+                    "channels": [{
+                        "alternatives": [{ "transcript": "hello world", "confidence": 0.9 }]
+                    }]
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audio_path = temp_dir.path().join("audio.wav");
+        std::fs::write(&audio_path, b"RIFF").unwrap();
+
+        let client = DeepgramClient::new("test".to_string())
+            .with_retries(2)
+            .with_api_url(server.uri());
+        let result = client.transcribe_file(&audio_path).await.unwrap();
+
+        assert_eq!(result.transcript, "hello world");
+        assert_eq!(result.word_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_does_not_retry_client_errors() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audio_path = temp_dir.path().join("audio.wav");
+        std::fs::write(&audio_path, b"RIFF").unwrap();
+
+        let client = DeepgramClient::new("bad".to_string())
+            .with_retries(3)
+            .with_api_url(server.uri());
+        assert!(client.transcribe_file(&audio_path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_times_out_on_slow_server() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audio_path = temp_dir.path().join("audio.wav");
+        std::fs::write(&audio_path, b"RIFF").unwrap();
+
+        let client = DeepgramClient::new("test".to_string())
+            .with_retries(0)
+            .with_timeout(Duration::from_millis(200))
+            .with_api_url(server.uri());
+        let started = std::time::Instant::now();
+        let err = client.transcribe_file(&audio_path).await.unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(format!("{:#}", err).contains("timed out"), "{:#}", err);
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_url_posts_json() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(serde_json::json!({
+                "url": "https://example.com/clean.wav"
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "results": {
+                    "channels": [{
+                        "alternatives": [{ "transcript": " hello world ", "confidence": 0.8 }]
+                    }]
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = DeepgramClient::new("test".to_string()).with_api_url(server.uri());
+        let result = client
+            .transcribe_url("https://example.com/clean.wav")
+            .await
+            .unwrap();
+
+        assert_eq!(result.transcript, "hello world");
+        assert_eq!(result.confidence, 0.8);
+    }
 }
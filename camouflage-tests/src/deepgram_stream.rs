@@ -0,0 +1,286 @@
+use crate::deepgram::DeepgramResult;
+use anyhow::{Context, Result};
+use camouflage_core::{wav, SignalConfig, SignalGenerator};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info};
+
+const DEEPGRAM_STREAM_URL: &str = "wss://api.deepgram.com/v1/listen";
+
+/// Default size of each PCM frame sent over the socket, in milliseconds
+const DEFAULT_FRAME_MS: u32 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct DeepgramStreamMessage {
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(default)]
+    pub is_final: bool,
+    pub channel: Option<DeepgramStreamChannel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeepgramStreamChannel {
+    pub alternatives: Vec<DeepgramStreamAlternative>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeepgramStreamAlternative {
+    pub transcript: String,
+    pub confidence: f64,
+}
+
+/// Accumulates interim and final transcripts from a streaming session
+#[derive(Debug, Default)]
+struct TranscriptCollector {
+    finals: Vec<String>,
+    confidences: Vec<f64>,
+    last_interim: Option<(String, f64)>,
+}
+
+impl TranscriptCollector {
+    fn handle_message(&mut self, message: DeepgramStreamMessage) {
+        if message.message_type != "Results" {
+            debug!("Deepgram stream message: {}", message.message_type);
+            return;
+        }
+
+        let Some(alternative) = message
+            .channel
+            .and_then(|c| c.alternatives.into_iter().next())
+        else {
+            return;
+        };
+
+        let transcript = alternative.transcript.trim().to_string();
+
+        if message.is_final {
+            debug!("  Final: '{}'", transcript);
+            self.last_interim = None;
+            if !transcript.is_empty() {
+                self.finals.push(transcript);
+                self.confidences.push(alternative.confidence);
+            }
+        } else {
+            debug!("  Interim: '{}'", transcript);
+            if !transcript.is_empty() {
+                self.last_interim = Some((transcript, alternative.confidence));
+            }
+        }
+    }
+
+    fn into_result(mut self) -> DeepgramResult {
+        // An interim that never got finalized still counts as recognized speech
+        if let Some((transcript, confidence)) = self.last_interim.take() {
+            self.finals.push(transcript);
+            self.confidences.push(confidence);
+        }
+
+        let transcript = self.finals.join(" ");
+        let word_count = transcript.split_whitespace().count();
+        let confidence = if self.confidences.is_empty() {
+            0.0
+        } else {
+            self.confidences.iter().sum::<f64>() / self.confidences.len() as f64
+        };
+
+        DeepgramResult {
+            transcript,
+            confidence,
+            word_count,
+        }
+    }
+}
+
+/// Deepgram live (WebSocket) transcription client
+///
+/// Unlike [`crate::DeepgramClient`], audio is fed as raw 16-bit PCM frames
+/// paced in real time, exercising Deepgram's streaming recognizer.
+pub struct DeepgramStreamClient {
+    api_key: String,
+    frame_ms: u32,
+}
+
+impl DeepgramStreamClient {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            api_key,
+            frame_ms: DEFAULT_FRAME_MS,
+        }
+    }
+
+    /// Set the duration of each PCM frame sent to Deepgram
+    pub fn with_frame_ms(mut self, frame_ms: u32) -> Self {
+        self.frame_ms = frame_ms.max(1);
+        self
+    }
+
+    /// Stream a freshly generated ultrasonic signal to Deepgram
+    pub async fn transcribe_signal(
+        &self,
+        config: &SignalConfig,
+        duration_secs: f32,
+    ) -> Result<DeepgramResult> {
+        info!(
+            "Streaming {:.1}s of generated signal to Deepgram ({} Hz, {} tones)",
+            duration_secs, config.frequency, config.num_tones
+        );
+
+        let mut generator = SignalGenerator::new(config.clone());
+        let num_samples = (config.sample_rate as f32 * duration_secs) as usize;
+        let samples = (0..num_samples).map(move |_| wav::f32_to_i16_sat(generator.next_sample()));
+
+        self.transcribe_pcm(samples, config.sample_rate, 1).await
+    }
+
+    /// Stream a 16-bit PCM WAV file (e.g. a mixed sample) to Deepgram
+    pub async fn transcribe_wav_file(&self, audio_path: &Path) -> Result<DeepgramResult> {
+        info!("Streaming audio file to Deepgram: {}", audio_path.display());
+
+        let reader = hound::WavReader::open(audio_path).context("Failed to open WAV file")?;
+        let spec = reader.spec();
+
+        if spec.bits_per_sample != 16 || spec.sample_format != hound::SampleFormat::Int {
+            anyhow::bail!(
+                "Streaming requires 16-bit PCM WAV, got {}-bit {:?}",
+                spec.bits_per_sample,
+                spec.sample_format
+            );
+        }
+
+        let samples = reader
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to read WAV samples")?;
+
+        self.transcribe_pcm(samples.into_iter(), spec.sample_rate, spec.channels)
+            .await
+    }
+
+    /// Stream interleaved 16-bit PCM samples to Deepgram in real time
+    pub async fn transcribe_pcm<I>(
+        &self,
+        samples: I,
+        sample_rate: u32,
+        channels: u16,
+    ) -> Result<DeepgramResult>
+    where
+        I: Iterator<Item = i16> + Send + 'static,
+    {
+        let url = format!(
+            "{}?encoding=linear16&sample_rate={}&channels={}&interim_results=true",
+            DEEPGRAM_STREAM_URL, sample_rate, channels
+        );
+
+        let mut request = url
+            .into_client_request()
+            .context("Failed to build WebSocket request")?;
+        request.headers_mut().insert(
+            "Authorization",
+            format!("Token {}", self.api_key)
+                .parse()
+                .context("Invalid API key header")?,
+        );
+
+        let (socket, _) = tokio_tungstenite::connect_async(request)
+            .await
+            .context("Failed to connect to Deepgram streaming API")?;
+        let (mut sink, mut stream) = socket.split();
+
+        let frame_ms = self.frame_ms;
+        let frame_len =
+            (sample_rate as usize * channels as usize * frame_ms as usize / 1000).max(1);
+
+        // Feed frames on a separate task so responses are read while sending
+        let sender = tokio::spawn(async move {
+            let mut samples = samples.peekable();
+            let mut interval = tokio::time::interval(Duration::from_millis(frame_ms as u64));
+
+            while samples.peek().is_some() {
+                interval.tick().await;
+                let frame: Vec<u8> = samples
+                    .by_ref()
+                    .take(frame_len)
+                    .flat_map(i16::to_le_bytes)
+                    .collect();
+                sink.send(Message::Binary(frame))
+                    .await
+                    .context("Failed to send audio frame")?;
+            }
+
+            sink.send(Message::Text(r#"{"type":"CloseStream"}"#.to_string()))
+                .await
+                .context("Failed to close Deepgram stream")?;
+
+            anyhow::Ok(())
+        });
+
+        let mut collector = TranscriptCollector::default();
+
+        while let Some(message) = stream.next().await {
+            match message.context("Deepgram stream error")? {
+                Message::Text(text) => {
+                    let parsed: DeepgramStreamMessage = serde_json::from_str(&text)
+                        .context("Failed to parse Deepgram stream message")?;
+                    collector.handle_message(parsed);
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+
+        sender.await.context("Audio sender task panicked")??;
+
+        let result = collector.into_result();
+
+        info!("Deepgram streaming result:");
+        info!("  Transcript: '{}'", result.transcript);
+        info!("  Confidence: {:.2}", result.confidence);
+        info!("  Word count: {}", result.word_count);
+        info!("  Effectively jammed: {}", result.is_effectively_jammed());
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results_message(transcript: &str, confidence: f64, is_final: bool) -> DeepgramStreamMessage {
+        let json = format!(
+            r#"{{"type":"Results","is_final":{},"channel":{{"alternatives":[{{"transcript":"{}","confidence":{}}}]}}}}"#,
+            is_final, transcript, confidence
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_collector_joins_finals() {
+        let mut collector = TranscriptCollector::default();
+        collector.handle_message(results_message("the quick", 0.5, false));
+        collector.handle_message(results_message("the quick brown fox", 0.9, true));
+        collector.handle_message(results_message("jumps", 0.7, true));
+
+        let result = collector.into_result();
+        assert_eq!(result.transcript, "the quick brown fox jumps");
+        assert_eq!(result.word_count, 5);
+        assert!((result.confidence - 0.8).abs() < 1e-9);
+        assert!(!result.is_effectively_jammed());
+    }
+
+    #[test]
+    fn test_collector_empty_stream_is_jammed() {
+        let mut collector = TranscriptCollector::default();
+        collector.handle_message(serde_json::from_str(r#"{"type":"Metadata"}"#).unwrap());
+        collector.handle_message(results_message("", 0.0, true));
+
+        let result = collector.into_result();
+        assert!(result.is_effectively_jammed());
+        assert_eq!(result.quality_score(), 0.0);
+    }
+}
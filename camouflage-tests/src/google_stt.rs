@@ -0,0 +1,264 @@
+use crate::retry::RetryPolicy;
+use crate::transcriber::JammingCriteria;
+use anyhow::{Context, Result};
+use base64::Engine;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tracing::info;
+
+const GOOGLE_STT_API_URL: &str = "https://speech.googleapis.com/v1/speech:recognize";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecognizeRequest {
+    config: RecognitionConfig,
+    audio: RecognitionAudio,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecognitionConfig {
+    language_code: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RecognitionAudio {
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleSttResponse {
+    #[serde(default)]
+    pub results: Vec<GoogleSttResultEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleSttResultEntry {
+    #[serde(default)]
+    pub alternatives: Vec<GoogleSttAlternative>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GoogleSttAlternative {
+    #[serde(default)]
+    pub transcript: String,
+    #[serde(default)]
+    pub confidence: f64,
+}
+
+/// Transcription result from Google Cloud Speech-to-Text
+#[derive(Debug, Clone)]
+pub struct GoogleSttResult {
+    pub transcript: String,
+    pub confidence: f64,
+    pub word_count: usize,
+}
+
+impl GoogleSttResult {
+    /// Determine if audio is effectively jammed
+    pub fn is_effectively_jammed(&self) -> bool {
+        self.is_jammed_with(&JammingCriteria::default())
+    }
+
+    /// Determine if audio is jammed under custom thresholds
+    pub fn is_jammed_with(&self, criteria: &JammingCriteria) -> bool {
+        self.transcript.trim().is_empty()
+            || criteria.is_jammed(self.word_count, Some(self.confidence), None)
+    }
+
+    /// Get a quality score (0.0 = completely jammed, 1.0 = transcribed)
+    pub fn quality_score(&self) -> f64 {
+        if self.word_count == 0 {
+            0.0
+        } else {
+            self.confidence
+        }
+    }
+
+    /// Word error rate against the clean phrase, capped at 1.0; see [`crate::wer::jam_score`]
+    pub fn jam_score(&self, clean_reference: &str) -> f64 {
+        crate::wer::jam_score(clean_reference, &self.transcript)
+    }
+}
+
+impl From<GoogleSttResponse> for GoogleSttResult {
+    fn from(response: GoogleSttResponse) -> Self {
+        // Long audio is split into several results; join their top alternatives
+        let alternatives: Vec<&GoogleSttAlternative> = response
+            .results
+            .iter()
+            .filter_map(|r| r.alternatives.first())
+            .filter(|a| !a.transcript.trim().is_empty())
+            .collect();
+
+        let transcript = alternatives
+            .iter()
+            .map(|a| a.transcript.trim())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let confidence = if alternatives.is_empty() {
+            0.0
+        } else {
+            alternatives.iter().map(|a| a.confidence).sum::<f64>() / alternatives.len() as f64
+        };
+        let word_count = transcript.split_whitespace().count();
+
+        Self {
+            transcript,
+            confidence,
+            word_count,
+        }
+    }
+}
+
+/// Google Cloud Speech-to-Text client using the REST `speech:recognize` endpoint
+///
+/// Authenticates with an API key (`GOOGLE_API_KEY`). WAV and FLAC files are
+/// sent as-is; Google reads the encoding and sample rate from the header.
+pub struct GoogleSttClient {
+    client: Client,
+    api_key: String,
+    api_url: String,
+    language_code: String,
+    retry: RetryPolicy,
+}
+
+impl GoogleSttClient {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_key,
+            api_url: GOOGLE_STT_API_URL.to_string(),
+            language_code: "en-US".to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Set the BCP-47 language code used for recognition
+    pub fn with_language(mut self, language_code: impl Into<String>) -> Self {
+        self.language_code = language_code.into();
+        self
+    }
+
+    /// Set how many times a transient (429/5xx or network) failure is retried
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time limit for each request attempt (default 60s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.retry.timeout = timeout;
+        self
+    }
+
+    /// Override the API endpoint (e.g. for a proxy or mock server)
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    /// Transcribe audio file with Google Speech-to-Text
+    pub async fn transcribe_file(&self, audio_path: &Path) -> Result<GoogleSttResult> {
+        info!(
+            "Transcribing audio file with Google Speech-to-Text: {}",
+            audio_path.display()
+        );
+
+        // Read audio file
+        let mut file = File::open(audio_path)
+            .await
+            .context("Failed to open audio file")?;
+
+        let mut audio_data = Vec::new();
+        file.read_to_end(&mut audio_data)
+            .await
+            .context("Failed to read audio file")?;
+
+        let request = RecognizeRequest {
+            config: RecognitionConfig {
+                language_code: self.language_code.clone(),
+            },
+            audio: RecognitionAudio {
+                content: base64::engine::general_purpose::STANDARD.encode(&audio_data),
+            },
+        };
+
+        // Make API request
+        let response = self
+            .retry
+            .send(|| {
+                Ok(self
+                    .client
+                    .post(&self.api_url)
+                    .query(&[("key", &self.api_key)])
+                    .json(&request))
+            })
+            .await
+            .context("Failed to send transcription request")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            anyhow::bail!(
+                "Google Speech-to-Text API error ({}): {}",
+                status,
+                error_text
+            );
+        }
+
+        let google_response: GoogleSttResponse = response
+            .json()
+            .await
+            .context("Failed to parse Google Speech-to-Text response")?;
+
+        let result = GoogleSttResult::from(google_response);
+
+        info!("Google Speech-to-Text transcription result:");
+        info!("  Transcript: '{}'", result.transcript);
+        info!("  Confidence: {:.2}", result.confidence);
+        info!("  Word count: {}", result.word_count);
+        info!("  Effectively jammed: {}", result.is_effectively_jammed());
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_joins_results() {
+        let response: GoogleSttResponse = serde_json::from_str(
+            r#"{"results": [
+                {"alternatives": [{"transcript": "the quick brown fox", "confidence": 0.9}]},
+                {"alternatives": [{"transcript": " jumps over", "confidence": 0.7}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let result = GoogleSttResult::from(response);
+        assert_eq!(result.transcript, "the quick brown fox jumps over");
+        assert_eq!(result.word_count, 6);
+        assert!((result.confidence - 0.8).abs() < 1e-9);
+        assert!(!result.is_effectively_jammed());
+    }
+
+    #[test]
+    fn test_empty_response_is_jammed() {
+        // Google omits `results` entirely when no speech is detected
+        let response: GoogleSttResponse = serde_json::from_str("{}").unwrap();
+
+        let result = GoogleSttResult::from(response);
+        assert!(result.is_effectively_jammed());
+        assert_eq!(result.quality_score(), 0.0);
+    }
+}
@@ -1,9 +1,33 @@
 pub mod deepgram;
+pub mod deepgram_stream;
+pub mod google_stt;
 pub mod openai_tts;
+pub mod pilot;
+pub mod piper;
+pub mod quality_gate;
+pub mod results;
+pub mod retry;
+pub mod speech;
+pub mod sweep;
 pub mod test_utils;
+pub mod transcriber;
+pub mod wer;
 pub mod whisper;
 
 pub use deepgram::{DeepgramClient, DeepgramResult};
-pub use openai_tts::OpenAITTS;
-pub use test_utils::generate_pure_ultrasonic;
-pub use whisper::{WhisperClient, WhisperResult};
+pub use deepgram_stream::DeepgramStreamClient;
+pub use google_stt::{GoogleSttClient, GoogleSttResult};
+pub use openai_tts::{OpenAITTS, TtsOptions};
+pub use pilot::{decode_pilot, PilotDetection};
+pub use piper::PiperTts;
+pub use quality_gate::{check_engine, EngineSanityError};
+pub use results::{record_result, ResultRecord};
+pub use retry::RetryPolicy;
+pub use speech::{speech_source_from_env, SpeechSource};
+pub use sweep::{FrequencyPoint, FrequencySweepConfig, SweepConfig, SweepPoint};
+pub use test_utils::{
+    analyze_spectrum, generate_pure_ultrasonic, generate_ultrasonic_wav, SpectrumReport, WavOptions,
+};
+pub use transcriber::{transcribe_batch, JammingCriteria, Transcriber, TranscriptionResult};
+pub use wer::{jam_score, word_error_rate};
+pub use whisper::{SegmentTiming, WhisperClient, WhisperResult, WordTiming};
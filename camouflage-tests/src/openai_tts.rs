@@ -1,7 +1,9 @@
+use crate::retry::RetryPolicy;
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde::Serialize;
 use std::path::Path;
+use std::time::Duration;
 use tokio::io::AsyncWriteExt;
 use tracing::info;
 
@@ -14,11 +16,45 @@ struct TTSRequest {
     voice: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<String>,
+    speed: f32,
+}
+
+/// Options controlling how OpenAI TTS renders speech
+#[derive(Debug, Clone)]
+pub struct TtsOptions {
+    /// TTS model (e.g. "tts-1", "tts-1-hd")
+    pub model: String,
+    /// Voice name (e.g. "alloy", "nova", "onyx")
+    pub voice: String,
+    /// Audio container: "flac", "wav", "mp3", "opus", "aac" or "pcm"
+    pub response_format: String,
+    /// Playback speed (0.25-4.0)
+    pub speed: f32,
+}
+
+impl Default for TtsOptions {
+    fn default() -> Self {
+        Self {
+            model: "tts-1".to_string(),
+            voice: "alloy".to_string(),
+            response_format: "flac".to_string(),
+            speed: 1.0,
+        }
+    }
+}
+
+impl TtsOptions {
+    /// File extension matching the requested response format
+    pub fn file_extension(&self) -> &str {
+        &self.response_format
+    }
 }
 
 pub struct OpenAITTS {
     client: Client,
     api_key: String,
+    api_url: String,
+    retry: RetryPolicy,
 }
 
 impl OpenAITTS {
@@ -26,32 +62,67 @@ impl OpenAITTS {
         Self {
             client: Client::new(),
             api_key,
+            api_url: TTS_API_URL.to_string(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// Set how many times a transient (429/5xx or network) failure is retried
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time limit for each request attempt (default 60s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.retry.timeout = timeout;
+        self
+    }
+
+    /// Override the API endpoint (e.g. for a proxy or mock server)
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
     /// Generate speech from text using OpenAI TTS
     pub async fn generate_speech(
         &self,
         text: &str,
         output_path: &Path,
-        voice: Option<&str>,
+        options: &TtsOptions,
     ) -> Result<()> {
+        if !(0.25..=4.0).contains(&options.speed) {
+            anyhow::bail!(
+                "TTS speed must be between 0.25 and 4.0, got {}",
+                options.speed
+            );
+        }
+
         info!("Generating speech with OpenAI TTS");
         info!("  Text: {}", text);
+        info!(
+            "  Model: {}, voice: {}, format: {}, speed: {}",
+            options.model, options.voice, options.response_format, options.speed
+        );
 
         let request = TTSRequest {
-            model: "tts-1".to_string(),
+            model: options.model.clone(),
             input: text.to_string(),
-            voice: voice.unwrap_or("alloy").to_string(),
-            response_format: Some("flac".to_string()),
+            voice: options.voice.clone(),
+            response_format: Some(options.response_format.clone()),
+            speed: options.speed,
         };
 
         let response = self
-            .client
-            .post(TTS_API_URL)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&request)
-            .send()
+            .retry
+            .send(|| {
+                Ok(self
+                    .client
+                    .post(&self.api_url)
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .json(&request))
+            })
             .await
             .context("Failed to send TTS request")?;
 
@@ -73,3 +144,27 @@ impl OpenAITTS {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_options_match_legacy_request() {
+        let options = TtsOptions::default();
+        let request = TTSRequest {
+            model: options.model.clone(),
+            input: "hello".to_string(),
+            voice: options.voice.clone(),
+            response_format: Some(options.response_format.clone()),
+            speed: options.speed,
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["model"], "tts-1");
+        assert_eq!(json["voice"], "alloy");
+        assert_eq!(json["response_format"], "flac");
+        assert_eq!(json["speed"], 1.0);
+        assert_eq!(options.file_extension(), "flac");
+    }
+}
@@ -0,0 +1,139 @@
+//! Recover a jammer's pilot ID from a recording
+//!
+//! Pairs with `SignalConfig::pilot_id`: a monitoring device records the
+//! room and checks which jammers it can hear. Needs at least two frames
+//! (about 1.3 s) of audio to be sure of catching one whole.
+
+use camouflage_core::analysis::spectrum_dbfs;
+use camouflage_core::pilot::{self, PILOT_DEVIATION_HZ, PILOT_FRAME_BITS, PILOT_FREQUENCY_HZ};
+use std::collections::HashMap;
+
+/// Alignments tried within one bit, since the recording starts anywhere
+const BIT_PHASES: usize = 8;
+
+/// How much louder the winning tone must be in every bit of a frame, in dB
+///
+/// Noise alone clears this in a bit now and then, but practically never
+/// in all 32 bits of a frame with a valid checksum.
+const MIN_BIT_MARGIN_DB: f64 = 6.0;
+
+/// A pilot found by [`decode_pilot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PilotDetection {
+    /// ID the jammer is sending
+    pub id: u16,
+    /// Whole frames that carried the ID
+    pub frames: usize,
+}
+
+/// One decoded bit and how clearly it was received
+struct Bit {
+    one: bool,
+    margin_db: f64,
+}
+
+/// Read the bit in `window` from the levels of the two FSK tones
+fn read_bit(window: &[f32], sample_rate: u32) -> Option<Bit> {
+    let spectrum = spectrum_dbfs(window, sample_rate).ok()?;
+    let level = |frequency: f32| {
+        spectrum
+            .iter()
+            .filter(|(f, _)| (f - frequency).abs() <= PILOT_DEVIATION_HZ / 2.0)
+            .map(|&(_, db)| db)
+            .fold(f64::NEG_INFINITY, f64::max)
+    };
+    let one = level(PILOT_FREQUENCY_HZ + PILOT_DEVIATION_HZ);
+    let zero = level(PILOT_FREQUENCY_HZ - PILOT_DEVIATION_HZ);
+    Some(Bit {
+        one: one > zero,
+        margin_db: (one - zero).abs(),
+    })
+}
+
+/// Count the frames in `bits` that carry an ID, by ID
+fn count_frames(bits: &[Bit]) -> HashMap<u16, usize> {
+    let mut counts = HashMap::new();
+    for frame in bits.windows(PILOT_FRAME_BITS) {
+        if frame.iter().any(|bit| bit.margin_db < MIN_BIT_MARGIN_DB) {
+            continue;
+        }
+        let word = frame
+            .iter()
+            .fold(0u32, |word, bit| word << 1 | bit.one as u32);
+        if let Some(id) = pilot::decode_frame(word) {
+            *counts.entry(id).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Find a pilot in mono `samples` and recover its ID
+///
+/// Returns the ID carried by the most frames, or `None` if no whole frame
+/// was received cleanly.
+pub fn decode_pilot(samples: &[f32], sample_rate: u32) -> Option<PilotDetection> {
+    let bit_len = pilot::bit_samples(sample_rate);
+    (0..BIT_PHASES)
+        .flat_map(|phase| {
+            let offset = phase * bit_len / BIT_PHASES;
+            let bits: Vec<Bit> = samples
+                .get(offset..)
+                .unwrap_or_default()
+                .chunks_exact(bit_len)
+                .filter_map(|window| read_bit(window, sample_rate))
+                .collect();
+            count_frames(&bits)
+        })
+        .max_by_key(|&(id, frames)| (frames, id))
+        .map(|(id, frames)| PilotDetection { id, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camouflage_core::{SignalConfig, SignalGenerator};
+
+    fn render(generator: &mut SignalGenerator, secs: f32) -> Vec<f32> {
+        let mut buffer = vec![0.0; (secs * 48000.0) as usize];
+        generator.generate_buffer(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn test_decode_pilot_through_a_retune() {
+        let config = SignalConfig {
+            seed: Some(3),
+            pilot_id: Some(0xBEEF),
+            ..Default::default()
+        };
+        let mut generator = SignalGenerator::new(config.clone());
+
+        // Start mid-bit and retune partway, as a live recording might
+        let mut samples = render(&mut generator, 1.0)[1234..].to_vec();
+        generator.update_config(SignalConfig {
+            frequency: 22500.0,
+            ..config
+        });
+        samples.extend(render(&mut generator, 1.5));
+
+        let detection = decode_pilot(&samples, 48000).unwrap();
+        assert_eq!(detection.id, 0xBEEF);
+        assert!(detection.frames >= 2, "{:?}", detection);
+    }
+
+    #[test]
+    fn test_no_pilot_decodes_to_none() {
+        let mut generator = SignalGenerator::new(SignalConfig {
+            seed: Some(3),
+            ..Default::default()
+        });
+        assert_eq!(decode_pilot(&render(&mut generator, 2.0), 48000), None);
+
+        // Too short for a whole frame
+        let mut generator = SignalGenerator::new(SignalConfig {
+            pilot_id: Some(7),
+            ..Default::default()
+        });
+        assert_eq!(decode_pilot(&render(&mut generator, 0.5), 48000), None);
+    }
+}
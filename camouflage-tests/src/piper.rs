@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::info;
+
+/// Offline text-to-speech through a local `piper` install
+///
+/// Needs the `piper` binary and a voice model (`.onnx` with its `.onnx.json`
+/// next to it), e.g. from <https://github.com/rhasspy/piper>. No API key or
+/// network access is used.
+pub struct PiperTts {
+    binary: PathBuf,
+    model: PathBuf,
+}
+
+impl PiperTts {
+    /// Use the voice model at `model` with the `piper` found on `PATH`
+    pub fn new(model: impl Into<PathBuf>) -> Self {
+        Self {
+            binary: PathBuf::from("piper"),
+            model: model.into(),
+        }
+    }
+
+    /// Configure from `PIPER_MODEL` and, optionally, `PIPER_BIN`
+    pub fn from_env() -> Option<Self> {
+        let model = std::env::var_os("PIPER_MODEL")?;
+        let tts = Self::new(model);
+        Some(match std::env::var_os("PIPER_BIN") {
+            Some(binary) => tts.with_binary(binary),
+            None => tts,
+        })
+    }
+
+    /// Run this `piper` executable instead of the one on `PATH`
+    pub fn with_binary(mut self, binary: impl Into<PathBuf>) -> Self {
+        self.binary = binary.into();
+        self
+    }
+
+    /// Speak `text` into a WAV file at the model's native sample rate
+    pub async fn generate_speech(&self, text: &str, output_path: &Path) -> Result<()> {
+        info!("Generating speech with Piper");
+        info!("  Text: {}", text);
+        info!("  Model: {}", self.model.display());
+
+        let mut child = Command::new(&self.binary)
+            .arg("--model")
+            .arg(&self.model)
+            .arg("--output_file")
+            .arg(output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run {}", self.binary.display()))?;
+
+        let mut stdin = child.stdin.take().context("Piper stdin unavailable")?;
+        stdin
+            .write_all(text.as_bytes())
+            .await
+            .context("Failed to send text to Piper")?;
+        drop(stdin);
+
+        let output = child
+            .wait_with_output()
+            .await
+            .context("Failed to wait for Piper")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Piper failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        hound::WavReader::open(output_path)
+            .with_context(|| format!("Piper wrote no WAV file to {}", output_path.display()))?;
+
+        info!("Speech saved to: {}", output_path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::generate_sine_wav;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_generate_speech_runs_piper() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let fixture = temp_dir.path().join("fixture.wav");
+        generate_sine_wav(&fixture, 440.0, 0.2, 0.1, 22050).unwrap();
+
+        // Stand-in that checks its arguments and copies the fixture
+        let fake_piper = temp_dir.path().join("piper");
+        std::fs::write(
+            &fake_piper,
+            format!(
+                "#!/bin/sh\n[ \"$1 $2 $3\" = \"--model voice.onnx --output_file\" ] || exit 2\n\
+                 cat > \"$4.txt\"\ncp '{}' \"$4\"\n",
+                fixture.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_piper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = temp_dir.path().join("speech.wav");
+        PiperTts::new("voice.onnx")
+            .with_binary(&fake_piper)
+            .generate_speech("hello there", &output)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            hound::WavReader::open(&output).unwrap().spec().sample_rate,
+            22050
+        );
+        let spoken = std::fs::read_to_string(temp_dir.path().join("speech.wav.txt")).unwrap();
+        assert_eq!(spoken, "hello there");
+    }
+
+    #[tokio::test]
+    async fn test_missing_binary_is_reported() {
+        let temp_dir = TempDir::new().unwrap();
+        let err = PiperTts::new("voice.onnx")
+            .with_binary(temp_dir.path().join("no-such-piper"))
+            .generate_speech("hello", &temp_dir.path().join("out.wav"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no-such-piper"), "{:#}", err);
+    }
+}
@@ -0,0 +1,167 @@
+//! Engine sanity checks that gate the jamming assertions
+//!
+//! A jamming test only means something if the engine transcribes clean
+//! speech. Each engine is first given a clean sample; if it cannot
+//! transcribe it, the test fails with [`EngineSanityError`] instead of
+//! reporting the jammed audio as a success.
+
+use crate::deepgram::DeepgramClient;
+use crate::google_stt::GoogleSttClient;
+use crate::transcriber::{Transcriber, TranscriptionResult};
+use crate::whisper::WhisperClient;
+use std::fmt;
+use std::path::Path;
+use tracing::info;
+
+/// Highest word error rate at which clean audio counts as transcribed
+pub const MAX_CLEAN_WER: f64 = 0.2;
+
+/// An engine failed to transcribe clean speech, so it cannot judge jamming
+#[derive(Debug)]
+pub enum EngineSanityError {
+    /// The request itself failed, e.g. a bad key or an outage
+    TranscriptionFailed {
+        engine: String,
+        source: anyhow::Error,
+    },
+    /// The engine answered, but not with the clean phrase
+    PoorTranscription {
+        engine: String,
+        word_error_rate: f64,
+        max_word_error_rate: f64,
+        transcript: String,
+    },
+}
+
+impl fmt::Display for EngineSanityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TranscriptionFailed { engine, source } => write!(
+                f,
+                "Engine sanity check failed for {}: clean audio could not be transcribed: {:#}",
+                engine, source
+            ),
+            Self::PoorTranscription {
+                engine,
+                word_error_rate,
+                max_word_error_rate,
+                transcript,
+            } => write!(
+                f,
+                "Engine sanity check failed for {}: clean audio transcribed with WER {:.2} \
+                 (at most {:.2} expected): '{}'",
+                engine, word_error_rate, max_word_error_rate, transcript
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EngineSanityError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::TranscriptionFailed { source, .. } => Some(source.as_ref()),
+            Self::PoorTranscription { .. } => None,
+        }
+    }
+}
+
+/// Check that `result`, a transcription of clean audio saying `reference`,
+/// is within `max_wer` of it
+pub fn check_clean_result(
+    result: &TranscriptionResult,
+    reference: &str,
+    max_wer: f64,
+) -> Result<(), EngineSanityError> {
+    let word_error_rate = crate::wer::word_error_rate(reference, &result.transcript);
+    if word_error_rate > max_wer {
+        return Err(EngineSanityError::PoorTranscription {
+            engine: result.engine.clone(),
+            word_error_rate,
+            max_word_error_rate: max_wer,
+            transcript: result.transcript.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Transcribe `clean_audio` with `engine` and check it against `reference`
+///
+/// Returns the clean transcription, for logging or as a baseline.
+pub async fn check_engine(
+    engine: &dyn Transcriber,
+    clean_audio: &Path,
+    reference: &str,
+    max_wer: f64,
+) -> Result<TranscriptionResult, EngineSanityError> {
+    let result = engine
+        .transcribe_file(clean_audio)
+        .await
+        .map_err(|source| EngineSanityError::TranscriptionFailed {
+            engine: engine.name().to_string(),
+            source,
+        })?;
+    check_clean_result(&result, reference, max_wer)?;
+    info!(
+        "✓ {} transcribes clean audio: '{}'",
+        engine.name(),
+        result.transcript
+    );
+    Ok(result)
+}
+
+/// Every engine whose API key is set in the environment
+///
+/// Whisper uses `OPENAI_API_KEY`, Deepgram `DEEPGRAM_API_KEY` and Google
+/// Speech-to-Text `GOOGLE_API_KEY`.
+pub fn transcribers_from_env() -> Vec<Box<dyn Transcriber>> {
+    let mut engines: Vec<Box<dyn Transcriber>> = Vec::new();
+    if let Ok(key) = std::env::var("OPENAI_API_KEY") {
+        engines.push(Box::new(WhisperClient::new(key)));
+    }
+    if let Ok(key) = std::env::var("DEEPGRAM_API_KEY") {
+        engines.push(Box::new(DeepgramClient::new(key)));
+    }
+    if let Ok(key) = std::env::var("GOOGLE_API_KEY") {
+        engines.push(Box::new(GoogleSttClient::new(key)));
+    }
+    engines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+
+    fn result(transcript: &str) -> TranscriptionResult {
+        TranscriptionResult {
+            engine: "Deepgram".to_string(),
+            transcript: transcript.to_string(),
+            confidence: Some(0.9),
+            no_speech_prob: None,
+            word_count: transcript.split_whitespace().count(),
+            jammed: false,
+        }
+    }
+
+    #[test]
+    fn test_check_clean_result() {
+        // One word in nine wrong is within the threshold
+        let close = result("the quick brown fox jumps over the lazy log");
+        assert!(check_clean_result(&close, PHRASE, MAX_CLEAN_WER).is_ok());
+
+        // An empty transcript of clean speech means the engine is broken
+        let err = check_clean_result(&result(""), PHRASE, MAX_CLEAN_WER).unwrap_err();
+        assert!(matches!(
+            err,
+            EngineSanityError::PoorTranscription { word_error_rate, .. } if word_error_rate == 1.0
+        ));
+        assert!(err
+            .to_string()
+            .starts_with("Engine sanity check failed for Deepgram"));
+
+        // Distinct from other failures once wrapped for a test
+        let err = anyhow::Error::from(err);
+        assert!(err.downcast_ref::<EngineSanityError>().is_some());
+    }
+}
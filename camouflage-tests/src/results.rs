@@ -0,0 +1,142 @@
+//! Persistent log of e2e effectiveness results
+//!
+//! Every transcription an e2e test makes can be appended as one JSON line,
+//! building a dataset that tracks effectiveness across runs and engine
+//! model updates.
+
+use crate::transcriber::TranscriptionResult;
+use anyhow::{Context, Result};
+use camouflage_core::SignalConfig;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable naming the directory the results file is kept in
+pub const RESULTS_DIR_ENV: &str = "CAMOUFLAGE_RESULTS_DIR";
+
+const RESULTS_FILE: &str = "effectiveness.jsonl";
+
+/// One transcription of one jamming configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultRecord {
+    /// Seconds since the Unix epoch when the record was made
+    pub timestamp: u64,
+    /// Name of the test or configuration that produced the record
+    pub label: String,
+    pub engine: String,
+    pub config: SignalConfig,
+    pub transcript: String,
+    pub confidence: Option<f64>,
+    pub word_count: usize,
+    pub jammed: bool,
+    /// Word error rate against the clean phrase, capped at 1.0
+    pub jam_score: f64,
+}
+
+impl ResultRecord {
+    /// Record `result` for `config`, scored against the phrase `reference`
+    pub fn new(
+        label: &str,
+        config: &SignalConfig,
+        result: &TranscriptionResult,
+        reference: &str,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            label: label.to_string(),
+            engine: result.engine.clone(),
+            config: config.clone(),
+            transcript: result.transcript.clone(),
+            confidence: result.confidence,
+            word_count: result.word_count,
+            jammed: result.is_effectively_jammed(),
+            jam_score: result.jam_score(reference),
+        }
+    }
+}
+
+/// Results file in `CAMOUFLAGE_RESULTS_DIR`, or in `target/e2e-results`
+pub fn results_path() -> PathBuf {
+    let dir = std::env::var_os(RESULTS_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/e2e-results"));
+    dir.join(RESULTS_FILE)
+}
+
+/// Append `record` as one JSON line, creating the file and its directory
+pub fn append_result(record: &ResultRecord, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    file.write_all(&line)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Append a record for `result` to the results file from [`results_path`]
+pub fn record_result(
+    label: &str,
+    config: &SignalConfig,
+    result: &TranscriptionResult,
+    reference: &str,
+) -> Result<PathBuf> {
+    let path = results_path();
+    append_result(&ResultRecord::new(label, config, result, reference), &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_append_result_keeps_earlier_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join(RESULTS_FILE);
+
+        let jammed = TranscriptionResult {
+            engine: "Deepgram".to_string(),
+            transcript: String::new(),
+            confidence: Some(0.0),
+            no_speech_prob: None,
+            word_count: 0,
+            jammed: true,
+        };
+        let clear = TranscriptionResult {
+            transcript: "hello world".to_string(),
+            confidence: Some(0.9),
+            word_count: 2,
+            jammed: false,
+            ..jammed.clone()
+        };
+        let config = SignalConfig::default();
+        let first = ResultRecord::new("multi_tone_3", &config, &jammed, "hello world");
+        let second = ResultRecord::new("multi_tone_3", &config, &clear, "hello world");
+        append_result(&first, &path).unwrap();
+        append_result(&second, &path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let records: Vec<ResultRecord> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, vec![first, second]);
+        assert_eq!(records[0].jam_score, 1.0);
+        assert_eq!(records[1].jam_score, 0.0);
+        assert!(records[0].jammed && !records[1].jammed);
+    }
+}
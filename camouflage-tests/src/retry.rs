@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tracing::warn;
+
+/// Retry policy with exponential backoff and jitter for HTTP API calls
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on each subsequent retry
+    pub base_delay: Duration,
+    /// Upper bound on any single delay
+    pub max_delay: Duration,
+    /// Time limit for each attempt, from connecting to the end of the body
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy with the default backoff and the given retry count
+    pub fn with_retries(max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (0-based), with jitter in [delay/2, delay]
+    fn delay_for(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << attempt.min(16))
+            .min(self.max_delay);
+        let millis = delay.as_millis() as u64;
+        let jittered = rand::thread_rng().gen_range(millis / 2..=millis);
+        Duration::from_millis(jittered)
+    }
+
+    /// Send a request, retrying on retryable status codes and network errors
+    ///
+    /// `build` is called once per attempt since request bodies (e.g. multipart
+    /// forms) cannot be reused. Non-retryable responses are returned as-is so
+    /// callers can report the API error.
+    pub async fn send<F>(&self, mut build: F) -> Result<Response>
+    where
+        F: FnMut() -> Result<RequestBuilder>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let outcome = build()?.timeout(self.timeout).send().await;
+            let retries_left = attempt < self.max_retries;
+
+            match outcome {
+                Ok(response) if retries_left && is_retryable_status(response.status()) => {
+                    warn!(
+                        "Request failed with {} (attempt {}/{}), retrying",
+                        response.status(),
+                        attempt + 1,
+                        self.max_retries + 1
+                    );
+                }
+                Ok(response) => return Ok(response),
+                Err(err) if retries_left && is_retryable_error(&err) => {
+                    warn!(
+                        "Request error: {} (attempt {}/{}), retrying",
+                        err,
+                        attempt + 1,
+                        self.max_retries + 1
+                    );
+                }
+                Err(err) if err.is_timeout() => {
+                    return Err(anyhow!(err).context(format!(
+                        "Request timed out after {}s",
+                        self.timeout.as_secs_f32()
+                    )))
+                }
+                Err(err) => return Err(err.into()),
+            }
+
+            tokio::time::sleep(self.delay_for(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Whether an HTTP status is worth retrying (rate limits and server errors)
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status.is_server_error()
+}
+
+fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_connect() || err.is_timeout() || err.is_request()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retryable_status() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let policy = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            ..Default::default()
+        };
+
+        for attempt in 0..10 {
+            let delay = policy.delay_for(attempt);
+            let expected = (100u64 << attempt).min(1000);
+            assert!(delay.as_millis() as u64 >= expected / 2);
+            assert!(delay.as_millis() as u64 <= expected);
+        }
+    }
+}
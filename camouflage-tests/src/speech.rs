@@ -0,0 +1,59 @@
+//! Common interface over the text-to-speech sources for clean samples
+
+use crate::openai_tts::{OpenAITTS, TtsOptions};
+use crate::piper::PiperTts;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+
+/// Something that can speak a phrase into a WAV file
+#[async_trait]
+pub trait SpeechSource: Send + Sync {
+    /// Human-readable source name
+    fn name(&self) -> &str;
+
+    /// Speak `text` into a WAV file at `output_path`
+    async fn speak_to_wav(&self, text: &str, output_path: &Path) -> Result<()>;
+}
+
+#[async_trait]
+impl SpeechSource for OpenAITTS {
+    fn name(&self) -> &str {
+        "OpenAI TTS"
+    }
+
+    async fn speak_to_wav(&self, text: &str, output_path: &Path) -> Result<()> {
+        let options = TtsOptions {
+            response_format: "wav".to_string(),
+            ..Default::default()
+        };
+        self.generate_speech(text, output_path, &options).await
+    }
+}
+
+#[async_trait]
+impl SpeechSource for PiperTts {
+    fn name(&self) -> &str {
+        "Piper"
+    }
+
+    async fn speak_to_wav(&self, text: &str, output_path: &Path) -> Result<()> {
+        self.generate_speech(text, output_path).await
+    }
+}
+
+/// Pick a speech source from the environment
+///
+/// Prefers offline Piper when `PIPER_MODEL` is set, then OpenAI TTS when
+/// `OPENAI_API_KEY` is set.
+pub fn speech_source_from_env() -> Result<Box<dyn SpeechSource>> {
+    if let Some(piper) = PiperTts::from_env() {
+        return Ok(Box::new(piper));
+    }
+    match std::env::var("OPENAI_API_KEY") {
+        Ok(api_key) => Ok(Box::new(OpenAITTS::new(api_key))),
+        Err(_) => anyhow::bail!(
+            "No speech source: set PIPER_MODEL for offline Piper or OPENAI_API_KEY for OpenAI TTS"
+        ),
+    }
+}
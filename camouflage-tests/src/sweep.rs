@@ -0,0 +1,526 @@
+//! Effectiveness sweep harness
+//!
+//! Mixes clean audio with the ultrasonic signal over a grid of amplitudes and
+//! mix ratios, transcribes every sample, and reports where jamming starts to
+//! defeat the engine. A frequency sweep instead moves a single tone across
+//! the band to show which frequencies disrupt a recorder most.
+
+use crate::speech::SpeechSource;
+use crate::test_utils::{mix_audio_with_ultrasonic, resample_wav};
+use crate::transcriber::{transcribe_batch, Transcriber};
+use anyhow::{Context, Result};
+use camouflage_core::wav;
+use camouflage_core::{MixCurve, SignalConfig};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use tracing::info;
+
+/// Grid of parameters to sweep
+#[derive(Debug, Clone)]
+pub struct SweepConfig {
+    /// Ultrasonic amplitudes to try, in ascending order
+    pub amplitudes: Vec<f32>,
+    /// Mix ratios to try for each amplitude
+    pub mix_ratios: Vec<f32>,
+    /// How each mix ratio is turned into gains
+    pub mix_curve: MixCurve,
+    /// Bring the clean audio to a fixed loudness before mixing
+    pub normalize_input: bool,
+    /// Signal settings other than amplitude
+    pub signal: SignalConfig,
+    /// Transcription requests to keep in flight at once
+    pub concurrency: usize,
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        Self {
+            amplitudes: (1..=10).map(|i| i as f32 * 0.05).collect(),
+            mix_ratios: vec![0.25, 0.5, 0.75],
+            mix_curve: MixCurve::Linear,
+            normalize_input: false,
+            signal: SignalConfig::default(),
+            concurrency: 4,
+        }
+    }
+}
+
+/// Single-tone frequency sweep at a fixed level
+#[derive(Debug, Clone)]
+pub struct FrequencySweepConfig {
+    /// First tone frequency in Hz
+    pub start_hz: f32,
+    /// Last tone frequency in Hz, included if a whole number of steps away
+    pub end_hz: f32,
+    pub step_hz: f32,
+    pub amplitude: f32,
+    pub mix_ratio: f32,
+    pub mix_curve: MixCurve,
+    pub normalize_input: bool,
+    /// Signal settings other than frequency, amplitude and tone count; the
+    /// sample rate must put `end_hz` below Nyquist
+    ///
+    /// Tones below 20 kHz are allowed, so the sweep can show where jamming
+    /// stops working.
+    pub signal: SignalConfig,
+    /// Transcription requests to keep in flight at once
+    pub concurrency: usize,
+}
+
+impl Default for FrequencySweepConfig {
+    fn default() -> Self {
+        Self {
+            start_hz: 18000.0,
+            end_hz: 30000.0,
+            step_hz: 250.0,
+            amplitude: 0.3,
+            mix_ratio: 0.5,
+            mix_curve: MixCurve::Linear,
+            normalize_input: false,
+            signal: SignalConfig {
+                sample_rate: 96000,
+                ..Default::default()
+            },
+            concurrency: 4,
+        }
+    }
+}
+
+impl FrequencySweepConfig {
+    /// Tone frequencies to try, in ascending order
+    pub fn frequencies(&self) -> Vec<f32> {
+        if self.step_hz <= 0.0 || self.end_hz < self.start_hz {
+            return vec![self.start_hz];
+        }
+        let steps = ((self.end_hz - self.start_hz) / self.step_hz + 1e-3).floor() as usize;
+        (0..=steps)
+            .map(|i| self.start_hz + i as f32 * self.step_hz)
+            .collect()
+    }
+}
+
+/// One measured point of the sweep
+#[derive(Debug, Clone)]
+pub struct SweepPoint {
+    pub amplitude: f32,
+    pub mix_ratio: f32,
+    pub transcript: String,
+    pub word_count: usize,
+    pub jammed: bool,
+}
+
+/// One measured point of a frequency sweep
+#[derive(Debug, Clone)]
+pub struct FrequencyPoint {
+    pub frequency: f32,
+    pub transcript: String,
+    pub confidence: Option<f64>,
+    pub word_count: usize,
+    /// Word error rate against the clean phrase, capped at 1.0
+    pub jam_score: f64,
+    pub jammed: bool,
+}
+
+/// Resample `clean_audio` into `work_dir` if it is below `sample_rate`
+fn prepare_clean_audio(clean_audio: &Path, sample_rate: u32, work_dir: &Path) -> Result<PathBuf> {
+    let input_rate = hound::WavReader::open(clean_audio)
+        .context("Failed to open clean audio")?
+        .spec()
+        .sample_rate;
+    if input_rate >= sample_rate {
+        return Ok(clean_audio.to_path_buf());
+    }
+
+    info!(
+        "Resampling clean audio from {} Hz to {} Hz",
+        input_rate, sample_rate
+    );
+    let resampled = work_dir.join("clean_resampled.wav");
+    resample_wav(clean_audio, &resampled, sample_rate)?;
+    Ok(resampled)
+}
+
+/// Sweep a clean 16-bit WAV file against a transcription engine
+///
+/// Input below the signal's sample rate is resampled first so the ultrasonic
+/// band fits.
+pub async fn run_sweep(
+    clean_audio: &Path,
+    sweep: &SweepConfig,
+    transcriber: &dyn Transcriber,
+) -> Result<Vec<SweepPoint>> {
+    let work_dir = TempDir::new().context("Failed to create sweep directory")?;
+
+    let clean_audio = prepare_clean_audio(clean_audio, sweep.signal.sample_rate, work_dir.path())?;
+
+    let mut grid = Vec::new();
+    let mut mixed_paths = Vec::new();
+    for &mix_ratio in &sweep.mix_ratios {
+        for &amplitude in &sweep.amplitudes {
+            let config = SignalConfig {
+                amplitude,
+                ..sweep.signal.clone()
+            };
+            let mixed_path = work_dir
+                .path()
+                .join(format!("mixed_a{:.3}_m{:.3}.wav", amplitude, mix_ratio));
+
+            mix_audio_with_ultrasonic(
+                &clean_audio,
+                &mixed_path,
+                &config,
+                mix_ratio,
+                sweep.mix_curve,
+                sweep.normalize_input,
+                false,
+            )?;
+            grid.push((amplitude, mix_ratio));
+            mixed_paths.push(mixed_path);
+        }
+    }
+
+    let results = transcribe_batch(transcriber, &mixed_paths, sweep.concurrency).await;
+    let mut points = Vec::new();
+    for ((amplitude, mix_ratio), result) in grid.into_iter().zip(results) {
+        let result = result?;
+
+        info!(
+            "Sweep {} amplitude {:.3}, mix {:.2}: {} words, jammed: {}",
+            transcriber.name(),
+            amplitude,
+            mix_ratio,
+            result.word_count,
+            result.is_effectively_jammed()
+        );
+
+        points.push(SweepPoint {
+            amplitude,
+            mix_ratio,
+            jammed: result.is_effectively_jammed(),
+            transcript: result.transcript,
+            word_count: result.word_count,
+        });
+    }
+
+    Ok(points)
+}
+
+/// Move a single tone across the band and transcribe clean audio mixed with each
+///
+/// `reference` is what the clean audio says, for the jam score.
+pub async fn run_frequency_sweep(
+    clean_audio: &Path,
+    reference: &str,
+    sweep: &FrequencySweepConfig,
+    transcriber: &dyn Transcriber,
+) -> Result<Vec<FrequencyPoint>> {
+    let work_dir = TempDir::new().context("Failed to create sweep directory")?;
+    let clean_audio = prepare_clean_audio(clean_audio, sweep.signal.sample_rate, work_dir.path())?;
+
+    let frequencies = sweep.frequencies();
+    let mut mixed_paths = Vec::new();
+    for &frequency in &frequencies {
+        let config = SignalConfig {
+            frequency,
+            amplitude: sweep.amplitude,
+            num_tones: 1,
+            ..sweep.signal.clone()
+        };
+        config
+            .validate_allowing_audible()
+            .with_context(|| format!("Cannot sweep a tone at {} Hz", frequency))?;
+        let mixed_path = work_dir.path().join(format!("mixed_f{:.0}.wav", frequency));
+
+        wav::mix_signal_allowing_audible(
+            &clean_audio,
+            &mixed_path,
+            &config,
+            sweep.mix_ratio,
+            sweep.mix_curve,
+            sweep.normalize_input,
+            false,
+        )?;
+        mixed_paths.push(mixed_path);
+    }
+
+    let results = transcribe_batch(transcriber, &mixed_paths, sweep.concurrency).await;
+    let mut points = Vec::new();
+    for (frequency, result) in frequencies.into_iter().zip(results) {
+        let result = result?;
+        let jam_score = result.jam_score(reference);
+
+        info!(
+            "Sweep {} at {:.0} Hz: {} words, jam score {:.2}",
+            transcriber.name(),
+            frequency,
+            result.word_count,
+            jam_score
+        );
+
+        points.push(FrequencyPoint {
+            frequency,
+            jammed: result.is_effectively_jammed(),
+            confidence: result.confidence,
+            transcript: result.transcript,
+            word_count: result.word_count,
+            jam_score,
+        });
+    }
+
+    Ok(points)
+}
+
+/// Speak `phrase` with `speech` and run a frequency sweep on the result
+pub async fn frequency_sweep_phrase(
+    speech: &dyn SpeechSource,
+    phrase: &str,
+    sweep: &FrequencySweepConfig,
+    transcriber: &dyn Transcriber,
+) -> Result<Vec<FrequencyPoint>> {
+    let temp_dir = TempDir::new().context("Failed to create sweep directory")?;
+    let clean_path = temp_dir.path().join("clean_voice.wav");
+    speech.speak_to_wav(phrase, &clean_path).await?;
+
+    run_frequency_sweep(&clean_path, phrase, sweep, transcriber).await
+}
+
+/// Speak `phrase` with `speech` and sweep the result
+pub async fn sweep_phrase(
+    speech: &dyn SpeechSource,
+    phrase: &str,
+    sweep: &SweepConfig,
+    transcriber: &dyn Transcriber,
+) -> Result<Vec<SweepPoint>> {
+    let temp_dir = TempDir::new().context("Failed to create sweep directory")?;
+    let clean_path = temp_dir.path().join("clean_voice.wav");
+    speech.speak_to_wav(phrase, &clean_path).await?;
+
+    run_sweep(&clean_path, sweep, transcriber).await
+}
+
+/// Lowest amplitude at which jamming holds for every higher amplitude
+///
+/// Returns `None` if the highest swept amplitude still transcribes.
+pub fn minimum_jamming_amplitude(points: &[SweepPoint], mix_ratio: f32) -> Option<f32> {
+    let mut at_ratio: Vec<&SweepPoint> = points
+        .iter()
+        .filter(|p| (p.mix_ratio - mix_ratio).abs() < f32::EPSILON)
+        .collect();
+    at_ratio.sort_by(|a, b| a.amplitude.total_cmp(&b.amplitude));
+
+    let mut boundary = None;
+    for point in at_ratio.iter().rev() {
+        if !point.jammed {
+            break;
+        }
+        boundary = Some(point.amplitude);
+    }
+    boundary
+}
+
+/// Write sweep points as CSV
+pub fn write_sweep_csv(points: &[SweepPoint], output_path: &Path) -> Result<()> {
+    let mut file = std::fs::File::create(output_path).context("Failed to create CSV file")?;
+
+    writeln!(file, "amplitude,mix_ratio,word_count,jammed,transcript")?;
+    for point in points {
+        writeln!(
+            file,
+            "{},{},{},{},\"{}\"",
+            point.amplitude,
+            point.mix_ratio,
+            point.word_count,
+            point.jammed,
+            point.transcript.replace('"', "\"\"")
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write frequency sweep points as CSV; confidence is empty when not reported
+pub fn write_frequency_csv(points: &[FrequencyPoint], output_path: &Path) -> Result<()> {
+    let mut file = std::fs::File::create(output_path).context("Failed to create CSV file")?;
+
+    writeln!(
+        file,
+        "frequency_hz,word_count,confidence,jam_score,jammed,transcript"
+    )?;
+    for point in points {
+        writeln!(
+            file,
+            "{},{},{},{:.4},{},\"{}\"",
+            point.frequency,
+            point.word_count,
+            point.confidence.map(|c| c.to_string()).unwrap_or_default(),
+            point.jam_score,
+            point.jammed,
+            point.transcript.replace('"', "\"\"")
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{analyze_spectrum, generate_sine_wav};
+    use crate::transcriber::TranscriptionResult;
+    use async_trait::async_trait;
+
+    /// Offline stand-in engine: "jammed" once most energy is ultrasonic
+    struct SpectralTranscriber;
+
+    #[async_trait]
+    impl Transcriber for SpectralTranscriber {
+        fn name(&self) -> &str {
+            "Spectral stand-in"
+        }
+
+        async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+            let report = analyze_spectrum(audio_path)?;
+            Ok(TranscriptionResult {
+                engine: self.name().to_string(),
+                transcript: String::new(),
+                confidence: None,
+                no_speech_prob: None,
+                word_count: 0,
+                jammed: report.audible_energy_fraction < 0.5,
+            })
+        }
+    }
+
+    fn point(amplitude: f32, jammed: bool) -> SweepPoint {
+        SweepPoint {
+            amplitude,
+            mix_ratio: 0.5,
+            transcript: String::new(),
+            word_count: 0,
+            jammed,
+        }
+    }
+
+    #[test]
+    fn test_minimum_jamming_amplitude() {
+        let points = vec![
+            point(0.1, false),
+            point(0.2, true),
+            point(0.3, false),
+            point(0.4, true),
+            point(0.5, true),
+        ];
+        assert_eq!(minimum_jamming_amplitude(&points, 0.5), Some(0.4));
+        assert_eq!(minimum_jamming_amplitude(&points, 0.25), None);
+
+        let never = vec![point(0.1, false), point(0.5, false)];
+        assert_eq!(minimum_jamming_amplitude(&never, 0.5), None);
+    }
+
+    #[test]
+    fn test_write_sweep_csv_escapes_quotes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("sweep.csv");
+
+        let mut p = point(0.25, false);
+        p.transcript = r#"say "hi""#.to_string();
+        p.word_count = 2;
+        write_sweep_csv(&[p], &path).unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "amplitude,mix_ratio,word_count,jammed,transcript");
+        assert_eq!(lines[1], r#"0.25,0.5,2,false,"say ""hi""""#);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_with_spectral_stand_in() {
+        let temp_dir = TempDir::new().unwrap();
+        let clean_path = temp_dir.path().join("tone.wav");
+        generate_sine_wav(&clean_path, 440.0, 0.2, 0.5, 24000).unwrap();
+
+        let sweep = SweepConfig {
+            amplitudes: vec![0.05, 0.2, 0.5],
+            mix_ratios: vec![0.5],
+            ..Default::default()
+        };
+
+        let points = run_sweep(&clean_path, &sweep, &SpectralTranscriber)
+            .await
+            .unwrap();
+
+        assert_eq!(points.len(), 3);
+        assert!(!points[0].jammed);
+        assert!(!points[1].jammed);
+        assert_eq!(minimum_jamming_amplitude(&points, 0.5), Some(0.5));
+    }
+
+    #[test]
+    fn test_frequency_grid_includes_end() {
+        let sweep = FrequencySweepConfig::default();
+        let frequencies = sweep.frequencies();
+        assert_eq!(frequencies.first(), Some(&18000.0));
+        assert_eq!(frequencies.last(), Some(&30000.0));
+        assert_eq!(frequencies.len(), 49);
+    }
+
+    #[tokio::test]
+    async fn test_frequency_sweep_writes_csv() {
+        let temp_dir = TempDir::new().unwrap();
+        let clean_path = temp_dir.path().join("tone.wav");
+        generate_sine_wav(&clean_path, 440.0, 0.2, 0.5, 24000).unwrap();
+
+        let sweep = FrequencySweepConfig {
+            start_hz: 20000.0,
+            end_hz: 30000.0,
+            step_hz: 5000.0,
+            ..Default::default()
+        };
+        let points = run_frequency_sweep(&clean_path, "hello", &sweep, &SpectralTranscriber)
+            .await
+            .unwrap();
+
+        let frequencies: Vec<f32> = points.iter().map(|p| p.frequency).collect();
+        assert_eq!(frequencies, vec![20000.0, 25000.0, 30000.0]);
+        assert!(points.iter().all(|p| p.jam_score == 1.0));
+
+        let csv_path = temp_dir.path().join("frequency.csv");
+        write_frequency_csv(&points, &csv_path).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv.lines().nth(1).unwrap().starts_with("20000,0,,1.0000,"));
+    }
+
+    #[tokio::test]
+    async fn test_frequency_sweep_covers_the_default_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let clean_path = temp_dir.path().join("tone.wav");
+        generate_sine_wav(&clean_path, 440.0, 0.2, 0.1, 96000).unwrap();
+
+        // Starts in the audible band, below what the jammer would accept
+        let sweep = FrequencySweepConfig::default();
+        let points = run_frequency_sweep(&clean_path, "hello", &sweep, &SpectralTranscriber)
+            .await
+            .unwrap();
+        assert_eq!(points.len(), sweep.frequencies().len());
+        assert_eq!(points[0].frequency, 18000.0);
+    }
+
+    #[tokio::test]
+    async fn test_frequency_sweep_rejects_tone_above_nyquist() {
+        let temp_dir = TempDir::new().unwrap();
+        let clean_path = temp_dir.path().join("tone.wav");
+        generate_sine_wav(&clean_path, 440.0, 0.1, 0.5, 48000).unwrap();
+
+        let sweep = FrequencySweepConfig {
+            start_hz: 30000.0,
+            signal: SignalConfig::default(),
+            ..Default::default()
+        };
+        let err = run_frequency_sweep(&clean_path, "hello", &sweep, &SpectralTranscriber)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("30000 Hz"), "{:#}", err);
+    }
+}
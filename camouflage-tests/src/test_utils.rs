@@ -1,30 +1,90 @@
 use anyhow::{Context, Result};
-use camouflage_core::{SignalConfig, SignalGenerator};
+use camouflage_core::analysis::analyze_wav;
+use camouflage_core::{wav, MixCurve, SignalConfig, SignalGenerator};
 use hound::{WavSpec, WavWriter};
 use std::path::Path;
+use std::time::Duration;
 
-/// Generate a pure ultrasonic audio file for testing
+pub use camouflage_core::analysis::{SpectrumReport, AUDIBLE_LIMIT_HZ};
+
+/// Output format for generated WAV files
+#[derive(Debug, Clone, Copy)]
+pub struct WavOptions {
+    /// Number of interleaved channels, each offset and steered as the
+    /// config's `channel_phase_offsets` and `beamform` say
+    pub channels: u16,
+    /// Integer bit depth: 8, 16, 24 or 32
+    pub bits_per_sample: u16,
+    /// Add TPDF dither before quantizing, decorrelating the rounding error
+    /// from the signal so it cannot form audible spurs
+    pub dither: bool,
+}
+
+impl Default for WavOptions {
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            bits_per_sample: 16,
+            dither: false,
+        }
+    }
+}
+
+/// Generate a pure ultrasonic audio file for testing, optionally dithered
+///
+/// Thin wrapper over [`camouflage_core::wav::write_ultrasonic`].
 pub fn generate_pure_ultrasonic(
     output_path: &Path,
     duration_secs: f32,
     config: &SignalConfig,
+    dither: bool,
+) -> Result<()> {
+    wav::write_ultrasonic(
+        output_path,
+        config,
+        Duration::from_secs_f32(duration_secs),
+        dither,
+    )?;
+    Ok(())
+}
+
+/// Generate a pure ultrasonic audio file with the given channel count and bit depth
+pub fn generate_ultrasonic_wav(
+    output_path: &Path,
+    duration_secs: f32,
+    config: &SignalConfig,
+    options: &WavOptions,
 ) -> Result<()> {
+    if !matches!(options.bits_per_sample, 8 | 16 | 24 | 32) {
+        anyhow::bail!("Unsupported bit depth: {}", options.bits_per_sample);
+    }
+    if options.channels == 0 {
+        anyhow::bail!("WAV output needs at least one channel");
+    }
+
     let spec = WavSpec {
-        channels: 1,
+        channels: options.channels,
         sample_rate: config.sample_rate,
-        bits_per_sample: 16,
+        bits_per_sample: options.bits_per_sample,
         sample_format: hound::SampleFormat::Int,
     };
 
     let mut writer = WavWriter::create(output_path, spec).context("Failed to create WAV writer")?;
 
     let mut generator = SignalGenerator::new(config.clone());
-    let num_samples = (config.sample_rate as f32 * duration_secs) as usize;
+    let num_frames = (config.sample_rate as f32 * duration_secs) as usize;
+    let mut samples = vec![0.0; num_frames * options.channels as usize];
+    generator.generate_interleaved(&mut samples, options.channels as usize);
+    let mut dither = options
+        .dither
+        .then(|| wav::Dither::new(generator.config().seed.unwrap_or(0)));
 
-    for _ in 0..num_samples {
-        let sample = generator.next_sample();
-        let sample_i16 = (sample * i16::MAX as f32) as i16;
-        writer.write_sample(sample_i16)?;
+    for sample in samples {
+        let scaled = match &mut dither {
+            Some(dither) => dither.quantize(sample, options.bits_per_sample),
+            None => wav::f32_to_int_sat(sample, options.bits_per_sample),
+        };
+        writer.write_sample(scaled)?;
     }
 
     writer.finalize()?;
@@ -32,28 +92,490 @@ pub fn generate_pure_ultrasonic(
 }
 
 /// Mix audio with ultrasonic signal
+///
+/// The generator advances once per frame, so every channel of a multi-channel
+/// input carries the same ultrasonic component, shifted by its entry in
+/// `config.channel_phase_offsets` and any `beamform` delay. The signal is generated
+/// at the input file's sample rate, overriding `config.sample_rate`.
+/// Integer and float inputs of any bit depth are read, and the output keeps
+/// the input's format. Thin wrapper over [`camouflage_core::wav::mix_ultrasonic`].
 pub fn mix_audio_with_ultrasonic(
     input_path: &Path,
     output_path: &Path,
     config: &SignalConfig,
     mix_ratio: f32,
+    curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
 ) -> Result<()> {
-    let mut reader = hound::WavReader::open(input_path)?;
-    let spec = reader.spec();
+    wav::mix_ultrasonic(
+        input_path,
+        output_path,
+        config,
+        mix_ratio,
+        curve,
+        normalize_input,
+        dither,
+    )?;
+    Ok(())
+}
 
-    let mut writer = WavWriter::create(output_path, spec)?;
-    let mut generator = SignalGenerator::new(config.clone());
+/// Generate a mono 16-bit sine tone, e.g. as a stand-in for clean speech
+pub fn generate_sine_wav(
+    output_path: &Path,
+    frequency: f32,
+    amplitude: f32,
+    duration_secs: f32,
+    sample_rate: u32,
+) -> Result<()> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
 
-    let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+    let mut writer = WavWriter::create(output_path, spec).context("Failed to create WAV writer")?;
+    let num_samples = (sample_rate as f32 * duration_secs) as usize;
 
-    for sample in samples {
-        let original = sample as f32 / i16::MAX as f32;
-        let ultrasonic = generator.next_sample();
-        let mixed = original * (1.0 - mix_ratio) + ultrasonic * mix_ratio;
-        let mixed_i16 = (mixed * i16::MAX as f32) as i16;
-        writer.write_sample(mixed_i16)?;
+    for i in 0..num_samples {
+        let t = i as f32 / sample_rate as f32;
+        let sample = amplitude * (2.0 * std::f32::consts::PI * frequency * t).sin();
+        writer.write_sample(wav::f32_to_i16_sat(sample))?;
     }
 
     writer.finalize()?;
     Ok(())
 }
+
+/// Resample a WAV file to `target_rate` using linear interpolation
+///
+/// Intended for lifting low-rate speech (e.g. 24 kHz TTS output) to a rate
+/// that can carry the ultrasonic band before mixing. The output keeps the
+/// input's channel count, bit depth and sample format.
+pub fn resample_wav(input_path: &Path, output_path: &Path, target_rate: u32) -> Result<()> {
+    let (spec, samples) = wav::read_wav(input_path)?;
+    let channels = spec.channels.max(1) as usize;
+    let frames: Vec<&[f32]> = samples.chunks(channels).collect();
+
+    let mut resampled = Vec::new();
+    if !frames.is_empty() {
+        let step = spec.sample_rate as f64 / target_rate as f64;
+        let out_frames = (frames.len() as f64 / step).floor() as usize;
+
+        for n in 0..out_frames {
+            let position = n as f64 * step;
+            let index = position as usize;
+            let frac = (position - index as f64) as f32;
+            let next = (index + 1).min(frames.len() - 1);
+
+            for (&a, &b) in frames[index].iter().zip(frames[next]) {
+                resampled.push(a + (b - a) * frac);
+            }
+        }
+    }
+
+    wav::write_audio(
+        output_path,
+        WavSpec {
+            sample_rate: target_rate,
+            ..spec
+        },
+        &resampled,
+        None,
+    )
+}
+
+/// Analyze the spectrum of a WAV file
+///
+/// Thin wrapper over [`camouflage_core::analysis::analyze_wav`].
+pub fn analyze_spectrum(path: &Path) -> Result<SpectrumReport> {
+    analyze_wav(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_stereo_mix_has_identical_ultrasonic_per_channel() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("stereo_silence.wav");
+        let output_path = temp_dir.path().join("stereo_mixed.wav");
+
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(&input_path, spec).unwrap();
+        for _ in 0..4800 * 2 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+        mix_audio_with_ultrasonic(
+            &input_path,
+            &output_path,
+            &config,
+            0.5,
+            MixCurve::Linear,
+            false,
+            false,
+        )
+        .unwrap();
+
+        let mut reader = hound::WavReader::open(&output_path).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let mixed: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(mixed.len(), 4800 * 2);
+
+        // Silent input means the output is the ultrasonic component alone;
+        // it must match a mono generator advanced once per frame
+        let mut generator = SignalGenerator::new(config);
+        for frame in mixed.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+            let expected = wav::f32_to_i16_sat(generator.next_sample() * 0.5);
+            assert_eq!(frame[0], expected);
+        }
+    }
+
+    #[test]
+    fn test_full_scale_mix_does_not_wrap() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("loud_sine.wav");
+        let output_path = temp_dir.path().join("loud_mixed.wav");
+        generate_sine_wav(&input_path, 440.0, 1.0, 1.0, 48000).unwrap();
+
+        // Full-scale input and signal under equal-power gains of about 0.71
+        // each, so the naive sum peaks near 1.4
+        let config = SignalConfig {
+            amplitude: 1.0,
+            seed: Some(1),
+            ..Default::default()
+        };
+        let (original_gain, signal_gain) = MixCurve::EqualPower.gains(0.5);
+        assert!(original_gain + signal_gain > 1.0);
+        mix_audio_with_ultrasonic(
+            &input_path,
+            &output_path,
+            &config,
+            0.5,
+            MixCurve::EqualPower,
+            false,
+            false,
+        )
+        .unwrap();
+
+        let (_, original) = wav::read_wav(&input_path).unwrap();
+        let mut reader = hound::WavReader::open(&output_path).unwrap();
+        let mixed: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        assert_eq!(mixed.len(), original.len());
+
+        // Wherever the sum is clearly positive or negative, the output must
+        // be too; a wrapped sample would flip sign
+        let mut generator = SignalGenerator::new(config);
+        let mut over_full_scale = 0;
+        for (&out, &input) in mixed.iter().zip(&original) {
+            let sum = input * original_gain + generator.next_sample() * signal_gain;
+            if sum.abs() > 1.0 {
+                over_full_scale += 1;
+            }
+            if sum.abs() > 0.1 {
+                assert_eq!(
+                    out.signum(),
+                    sum.signum() as i16,
+                    "sample wrapped: sum {} written as {}",
+                    sum,
+                    out
+                );
+            }
+        }
+        assert!(over_full_scale > 0, "the sum never exceeded full scale");
+    }
+
+    #[test]
+    fn test_mix_reads_any_input_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        for (bits_per_sample, sample_format) in [
+            (16, hound::SampleFormat::Int),
+            (24, hound::SampleFormat::Int),
+            (32, hound::SampleFormat::Float),
+        ] {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate: 48000,
+                bits_per_sample,
+                sample_format,
+            };
+            let voice: Vec<f32> = (0..48000)
+                .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 48000.0).sin())
+                .collect();
+            let input_path = temp_dir
+                .path()
+                .join(format!("voice_{}.wav", bits_per_sample));
+            let output_path = temp_dir
+                .path()
+                .join(format!("mixed_{}.wav", bits_per_sample));
+            wav::write_audio(&input_path, spec, &voice, None).unwrap();
+
+            mix_audio_with_ultrasonic(
+                &input_path,
+                &output_path,
+                &config,
+                0.5,
+                MixCurve::Linear,
+                false,
+                false,
+            )
+            .unwrap();
+
+            let (mixed_spec, mixed) = wav::read_wav(&output_path).unwrap();
+            assert_eq!(mixed_spec, spec);
+            assert_eq!(mixed.len(), voice.len());
+            let peaks = analyze_spectrum(&output_path).unwrap().peak_frequencies;
+            assert!(peaks.iter().any(|f| (f - 440.0).abs() < 2.0), "{:?}", peaks);
+            assert!(
+                peaks.iter().any(|f| (f - config.frequency).abs() < 400.0),
+                "{:?}",
+                peaks
+            );
+        }
+    }
+
+    #[test]
+    fn test_mix_reports_truncated_input() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("truncated.wav");
+        generate_sine_wav(&input_path, 440.0, 0.5, 0.1, 48000).unwrap();
+
+        // Cut the data chunk off mid-sample
+        let bytes = std::fs::read(&input_path).unwrap();
+        std::fs::write(&input_path, &bytes[..bytes.len() - 1001]).unwrap();
+
+        let result = mix_audio_with_ultrasonic(
+            &input_path,
+            &temp_dir.path().join("mixed.wav"),
+            &SignalConfig::default(),
+            0.5,
+            MixCurve::Linear,
+            false,
+            false,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_resample_keeps_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("tts.wav");
+        let output_path = temp_dir.path().join("tts_48k.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 24000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let voice: Vec<f32> = (0..24000)
+            .flat_map(|i| {
+                let s = 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 24000.0).sin();
+                [s, s]
+            })
+            .collect();
+        wav::write_audio(&input_path, spec, &voice, None).unwrap();
+
+        resample_wav(&input_path, &output_path, 48000).unwrap();
+
+        let (resampled_spec, resampled) = wav::read_wav(&output_path).unwrap();
+        assert_eq!(
+            resampled_spec,
+            WavSpec {
+                sample_rate: 48000,
+                ..spec
+            }
+        );
+        assert_eq!(resampled.len(), 48000 * 2);
+        let peaks = analyze_spectrum(&output_path).unwrap().peak_frequencies;
+        assert!((peaks[0] - 440.0).abs() < 2.0, "{:?}", peaks);
+    }
+
+    #[test]
+    fn test_normalized_mix_evens_out_input_levels() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = SignalConfig {
+            seed: Some(1),
+            ..Default::default()
+        };
+
+        // RMS of the audible part of a mixed file, from its spectrum
+        let audible_rms = |amplitude: f32, normalize: bool| {
+            let input_path = temp_dir.path().join(format!("voice_{}.wav", amplitude));
+            let output_path = temp_dir.path().join("mixed.wav");
+            generate_sine_wav(&input_path, 440.0, amplitude, 1.0, 48000).unwrap();
+            mix_audio_with_ultrasonic(
+                &input_path,
+                &output_path,
+                &config,
+                0.5,
+                MixCurve::Linear,
+                normalize,
+                false,
+            )
+            .unwrap();
+
+            let (_, samples) = wav::read_wav(&output_path).unwrap();
+            let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+            let report = analyze_spectrum(&output_path).unwrap();
+            rms as f64 * report.audible_energy_fraction.sqrt()
+        };
+
+        let level_db = |quiet: f64, loud: f64| 20.0 * (loud / quiet).log10();
+        let raw = level_db(audible_rms(0.05, false), audible_rms(0.6, false));
+        let normalized = level_db(audible_rms(0.05, true), audible_rms(0.6, true));
+        assert!(raw > 20.0, "{}", raw);
+        assert!(normalized.abs() < 1.0, "{}", normalized);
+    }
+
+    #[test]
+    fn test_pure_ultrasonic_has_no_audible_energy() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("pure_ultrasonic.wav");
+
+        let config = SignalConfig::default();
+        generate_pure_ultrasonic(&path, 1.0, &config, false).unwrap();
+
+        let report = analyze_spectrum(&path).unwrap();
+
+        assert!(
+            report.audible_energy_fraction < 0.001,
+            "audible energy fraction too high: {}",
+            report.audible_energy_fraction
+        );
+        assert!(report.spectral_centroid > AUDIBLE_LIMIT_HZ);
+
+        // The three tones (22700, 23000, 23300 Hz) are the strongest peaks
+        let mut top: Vec<f32> = report.peak_frequencies[..3].to_vec();
+        top.sort_by(f32::total_cmp);
+        for (peak, expected) in top.iter().zip([22700.0, 23000.0, 23300.0]) {
+            assert!(
+                (peak - expected).abs() < 5.0,
+                "peak {} != {}",
+                peak,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_stereo_24_bit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("stereo_24.wav");
+
+        let config = SignalConfig::default();
+        let options = WavOptions {
+            channels: 2,
+            bits_per_sample: 24,
+            ..Default::default()
+        };
+        generate_ultrasonic_wav(&path, 0.5, &config, &options).unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.bits_per_sample, 24);
+
+        let samples: Vec<i32> = reader.samples::<i32>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), 24000 * 2);
+
+        // Peaks use the 24-bit range rather than being stuck at 16-bit scale
+        let peak = samples.iter().map(|s| s.abs()).max().unwrap();
+        assert!(peak > i16::MAX as i32);
+        assert!(peak < (1 << 23));
+
+        for frame in samples.chunks(2) {
+            assert_eq!(frame[0], frame[1]);
+        }
+
+        let report = analyze_spectrum(&path).unwrap();
+        assert!(report.audible_energy_fraction < 0.001);
+    }
+
+    #[test]
+    fn test_generate_applies_channel_phase_offsets() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("inverted.wav");
+
+        let config = SignalConfig {
+            channel_phase_offsets: vec![0.0, 180.0],
+            ..Default::default()
+        };
+        let options = WavOptions {
+            channels: 2,
+            ..Default::default()
+        };
+        generate_ultrasonic_wav(&path, 0.1, &config, &options).unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let samples: Vec<i16> = reader.samples::<i16>().map(|s| s.unwrap()).collect();
+        for frame in samples.chunks(2) {
+            assert!(
+                (frame[0] as i32 + frame[1] as i32).abs() <= 2,
+                "{:?}",
+                frame
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_rejects_unsupported_bit_depth() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad.wav");
+        let options = WavOptions {
+            channels: 1,
+            bits_per_sample: 12,
+            ..Default::default()
+        };
+
+        assert!(generate_ultrasonic_wav(&path, 0.1, &SignalConfig::default(), &options).is_err());
+    }
+
+    #[test]
+    fn test_dither_lowers_audible_spurs() {
+        let temp_dir = TempDir::new().unwrap();
+        // A few LSBs of signal: undithered rounding error is periodic with the tones
+        let config = SignalConfig {
+            amplitude: 0.0005,
+            ..Default::default()
+        };
+
+        let mut spur_db = Vec::new();
+        for dither in [false, true] {
+            let path = temp_dir.path().join(format!("quiet_{}.wav", dither));
+            let options = WavOptions {
+                dither,
+                ..Default::default()
+            };
+            generate_ultrasonic_wav(&path, 1.0, &config, &options).unwrap();
+            spur_db.push(analyze_spectrum(&path).unwrap().audible_peak_db);
+        }
+
+        // Dither trades discrete spurs for a flat, lower noise floor
+        assert!(
+            spur_db[1] < spur_db[0] - 6.0,
+            "dithered spur {:.1} dB vs undithered {:.1} dB",
+            spur_db[1],
+            spur_db[0]
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Common interface over the speech-to-text clients
+
+use crate::deepgram::{DeepgramClient, DeepgramResult};
+use crate::deepgram_stream::DeepgramStreamClient;
+use crate::google_stt::{GoogleSttClient, GoogleSttResult};
+use crate::whisper::{WhisperClient, WhisperResult};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use std::path::Path;
+
+/// Thresholds at which a transcription counts as jammed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JammingCriteria {
+    /// Most words a jammed transcript may still contain
+    pub max_words: usize,
+    /// Confidence below which a transcript counts as jammed at any length;
+    /// ignored for engines that report no confidence
+    pub min_confidence: f64,
+    /// No-speech probability above which a transcript counts as jammed at
+    /// any length; ignored for engines that report none
+    pub max_no_speech_prob: f64,
+}
+
+impl Default for JammingCriteria {
+    fn default() -> Self {
+        Self {
+            max_words: 0,
+            min_confidence: 0.1,
+            // Whisper's no-speech threshold. Whisper itself only treats a
+            // segment as silence when its average log probability is also
+            // below -1.0, so this is the looser of the two
+            max_no_speech_prob: 0.6,
+        }
+    }
+}
+
+impl JammingCriteria {
+    /// Defaults for Whisper, which tends to hallucinate a word or two from
+    /// noise and whose confidence is not comparable to other engines'
+    pub fn whisper() -> Self {
+        Self {
+            max_words: 2,
+            min_confidence: 0.0,
+            ..Self::default()
+        }
+    }
+
+    /// Whether a transcript with these statistics counts as jammed
+    pub fn is_jammed(
+        &self,
+        word_count: usize,
+        confidence: Option<f64>,
+        no_speech_prob: Option<f64>,
+    ) -> bool {
+        word_count <= self.max_words
+            || confidence.is_some_and(|c| c < self.min_confidence)
+            || no_speech_prob.is_some_and(|p| p > self.max_no_speech_prob)
+    }
+}
+
+/// Engine-independent transcription result
+#[derive(Debug, Clone)]
+pub struct TranscriptionResult {
+    /// Name of the engine that produced the result
+    pub engine: String,
+    pub transcript: String,
+    /// Confidence (0.0-1.0), if the engine reports one
+    pub confidence: Option<f64>,
+    /// Probability that the audio holds no speech, if the engine reports one
+    pub no_speech_prob: Option<f64>,
+    pub word_count: usize,
+    /// Verdict from the engine-specific result type
+    pub jammed: bool,
+}
+
+impl TranscriptionResult {
+    /// Determine if audio is effectively jammed
+    pub fn is_effectively_jammed(&self) -> bool {
+        self.jammed
+    }
+
+    /// Get a quality score (0.0 = completely jammed, 1.0 = transcribed)
+    pub fn quality_score(&self) -> f64 {
+        if self.word_count == 0 {
+            0.0
+        } else {
+            self.confidence.unwrap_or(1.0)
+        }
+    }
+
+    /// Re-judge the transcript against custom thresholds
+    pub fn is_jammed_with(&self, criteria: &JammingCriteria) -> bool {
+        criteria.is_jammed(self.word_count, self.confidence, self.no_speech_prob)
+    }
+
+    /// Word error rate against the clean phrase, capped at 1.0; see [`crate::wer::jam_score`]
+    pub fn jam_score(&self, clean_reference: &str) -> f64 {
+        crate::wer::jam_score(clean_reference, &self.transcript)
+    }
+}
+
+impl From<WhisperResult> for TranscriptionResult {
+    fn from(result: WhisperResult) -> Self {
+        Self {
+            engine: "Whisper".to_string(),
+            jammed: result.is_effectively_jammed(),
+            confidence: result.confidence(),
+            no_speech_prob: result.no_speech_prob(),
+            transcript: result.transcript,
+            word_count: result.word_count,
+        }
+    }
+}
+
+impl From<DeepgramResult> for TranscriptionResult {
+    fn from(result: DeepgramResult) -> Self {
+        Self {
+            engine: "Deepgram".to_string(),
+            jammed: result.is_effectively_jammed(),
+            transcript: result.transcript,
+            confidence: Some(result.confidence),
+            no_speech_prob: None,
+            word_count: result.word_count,
+        }
+    }
+}
+
+impl From<GoogleSttResult> for TranscriptionResult {
+    fn from(result: GoogleSttResult) -> Self {
+        Self {
+            engine: "Google Speech-to-Text".to_string(),
+            jammed: result.is_effectively_jammed(),
+            transcript: result.transcript,
+            confidence: Some(result.confidence),
+            no_speech_prob: None,
+            word_count: result.word_count,
+        }
+    }
+}
+
+/// A speech-to-text engine that can transcribe an audio file
+#[async_trait]
+pub trait Transcriber: Send + Sync {
+    /// Human-readable engine name
+    fn name(&self) -> &str;
+
+    /// Transcribe an audio file
+    async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult>;
+}
+
+/// Transcribe every file in `paths`, with up to `concurrency` requests in
+/// flight at once
+///
+/// Results come back in the order of `paths`, whatever order the requests
+/// finish in; a failed file does not stop the others. A `concurrency` of 0
+/// is treated as 1. Keep it within the engine's rate limit.
+pub async fn transcribe_batch<P: AsRef<Path>>(
+    transcriber: &dyn Transcriber,
+    paths: &[P],
+    concurrency: usize,
+) -> Vec<Result<TranscriptionResult>> {
+    let mut results: Vec<(usize, Result<TranscriptionResult>)> =
+        stream::iter(paths.iter().enumerate())
+            .map(|(index, path)| async move {
+                (index, transcriber.transcribe_file(path.as_ref()).await)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[async_trait]
+impl Transcriber for WhisperClient {
+    fn name(&self) -> &str {
+        "Whisper"
+    }
+
+    async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+        Ok(WhisperClient::transcribe_file(self, audio_path)
+            .await?
+            .into())
+    }
+}
+
+#[async_trait]
+impl Transcriber for DeepgramClient {
+    fn name(&self) -> &str {
+        "Deepgram"
+    }
+
+    async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+        Ok(DeepgramClient::transcribe_file(self, audio_path)
+            .await?
+            .into())
+    }
+}
+
+#[async_trait]
+impl Transcriber for DeepgramStreamClient {
+    fn name(&self) -> &str {
+        "Deepgram (streaming)"
+    }
+
+    async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+        let mut result: TranscriptionResult = self.transcribe_wav_file(audio_path).await?.into();
+        result.engine = self.name().to_string();
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Transcriber for GoogleSttClient {
+    fn name(&self) -> &str {
+        "Google Speech-to-Text"
+    }
+
+    async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+        Ok(GoogleSttClient::transcribe_file(self, audio_path)
+            .await?
+            .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Answers with the file name after a delay, counting requests in flight
+    #[derive(Default)]
+    struct SlowTranscriber {
+        in_flight: AtomicUsize,
+        most_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Transcriber for SlowTranscriber {
+        fn name(&self) -> &str {
+            "Slow stand-in"
+        }
+
+        async fn transcribe_file(&self, audio_path: &Path) -> Result<TranscriptionResult> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_in_flight.fetch_max(now, Ordering::SeqCst);
+
+            // Earlier files take longer, so they finish out of order
+            let name = audio_path.file_stem().unwrap().to_string_lossy();
+            let index: u64 = name.parse()?;
+            tokio::time::sleep(Duration::from_millis(5 * (10 - index))).await;
+
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            if index == 3 {
+                anyhow::bail!("request {} failed", index);
+            }
+            Ok(TranscriptionResult {
+                engine: self.name().to_string(),
+                transcript: name.into_owned(),
+                confidence: None,
+                no_speech_prob: None,
+                word_count: 1,
+                jammed: false,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_batch_keeps_order_and_limit() {
+        let paths: Vec<String> = (0..10).map(|i| format!("{}.wav", i)).collect();
+        let transcriber = SlowTranscriber::default();
+
+        let results = transcribe_batch(&transcriber, &paths, 3).await;
+        assert_eq!(results.len(), 10);
+        for (i, result) in results.iter().enumerate() {
+            match result {
+                Ok(result) => assert_eq!(result.transcript, i.to_string()),
+                Err(e) => assert_eq!((i, e.to_string()), (3, "request 3 failed".into())),
+            }
+        }
+        assert_eq!(transcriber.most_in_flight.load(Ordering::SeqCst), 3);
+
+        let serial = SlowTranscriber::default();
+        transcribe_batch(&serial, &paths[..4], 0).await;
+        assert_eq!(serial.most_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_conversions_keep_engine_verdicts() {
+        // Whisper tolerates a couple of hallucinated words
+        let whisper: TranscriptionResult = WhisperResult {
+            transcript: "you you".to_string(),
+            word_count: 2,
+            words: Vec::new(),
+            segments: Vec::new(),
+        }
+        .into();
+        assert!(whisper.is_effectively_jammed());
+        assert_eq!(whisper.confidence, None);
+        assert_eq!(whisper.quality_score(), 1.0);
+
+        // Deepgram does not, unless confidence is negligible
+        let deepgram: TranscriptionResult = DeepgramResult {
+            transcript: "you you".to_string(),
+            confidence: 0.6,
+            word_count: 2,
+        }
+        .into();
+        assert!(!deepgram.is_effectively_jammed());
+        assert_eq!(deepgram.quality_score(), 0.6);
+    }
+
+    #[test]
+    fn test_custom_criteria() {
+        let result = DeepgramResult {
+            transcript: "quick fox".to_string(),
+            confidence: 0.3,
+            word_count: 2,
+        };
+        assert!(!result.is_effectively_jammed());
+
+        let lenient = JammingCriteria {
+            max_words: 2,
+            ..Default::default()
+        };
+        assert!(result.is_jammed_with(&lenient));
+
+        let strict_confidence = JammingCriteria {
+            min_confidence: 0.5,
+            ..Default::default()
+        };
+        assert!(result.is_jammed_with(&strict_confidence));
+
+        // Without segment scores Whisper has no confidence, so only the
+        // word limit applies
+        let whisper = WhisperResult {
+            transcript: "quick brown fox".to_string(),
+            word_count: 3,
+            words: Vec::new(),
+            segments: Vec::new(),
+        };
+        assert!(!whisper.is_effectively_jammed());
+        assert!(!whisper.is_jammed_with(&strict_confidence));
+        assert!(whisper.is_jammed_with(&JammingCriteria {
+            max_words: 3,
+            min_confidence: 1.0,
+            max_no_speech_prob: 0.0,
+        }));
+
+        let result: TranscriptionResult = result.into();
+        assert!(result.is_jammed_with(&lenient));
+    }
+}
@@ -0,0 +1,82 @@
+//! Word error rate between a transcript and the phrase that was spoken
+
+/// Lowercase words with surrounding punctuation removed
+fn tokenize(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Levenshtein distance over tokens (substitutions, insertions, deletions)
+fn edit_distance(reference: &[String], hypothesis: &[String]) -> usize {
+    let mut previous: Vec<usize> = (0..=hypothesis.len()).collect();
+    let mut current = vec![0; hypothesis.len() + 1];
+
+    for (i, ref_word) in reference.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, hyp_word) in hypothesis.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ref_word != hyp_word);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[hypothesis.len()]
+}
+
+/// Word error rate of `hypothesis` against `reference`
+///
+/// Case and punctuation are ignored. The rate can exceed 1.0 when the
+/// hypothesis inserts extra words.
+pub fn word_error_rate(reference: &str, hypothesis: &str) -> f64 {
+    let reference = tokenize(reference);
+    let hypothesis = tokenize(hypothesis);
+
+    if reference.is_empty() {
+        return if hypothesis.is_empty() { 0.0 } else { 1.0 };
+    }
+    edit_distance(&reference, &hypothesis) as f64 / reference.len() as f64
+}
+
+/// How thoroughly a transcript was disrupted, from 0.0 (exact) to 1.0
+///
+/// The word error rate against the clean phrase, capped at 1.0 so that
+/// hallucinated extra words do not score above a fully lost transcript.
+pub fn jam_score(clean_reference: &str, transcript: &str) -> f64 {
+    word_error_rate(clean_reference, transcript).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+
+    #[test]
+    fn test_word_error_rate() {
+        assert_eq!(
+            word_error_rate(PHRASE, "the quick brown fox jumps over the lazy dog"),
+            0.0
+        );
+        assert_eq!(word_error_rate(PHRASE, ""), 1.0);
+
+        // One substitution and one deletion out of nine words
+        let wer = word_error_rate(PHRASE, "The quick brown box jumps over lazy dog");
+        assert!((wer - 2.0 / 9.0).abs() < 1e-9);
+
+        // Insertions can push the rate past 1.0; the score caps it
+        let noisy = "you you you you you you you you you you";
+        assert!(word_error_rate("hello", noisy) > 1.0);
+        assert_eq!(jam_score("hello", noisy), 1.0);
+    }
+
+    #[test]
+    fn test_partial_disruption_scores_between() {
+        let score = jam_score(PHRASE, "the fox over dog");
+        assert!(score > 0.4 && score < 0.8, "{}", score);
+    }
+}
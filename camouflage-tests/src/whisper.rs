@@ -1,7 +1,10 @@
+use crate::retry::RetryPolicy;
+use crate::transcriber::JammingCriteria;
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde::Deserialize;
 use std::path::Path;
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tracing::info;
@@ -11,6 +14,33 @@ const WHISPER_API_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
 #[derive(Debug, Deserialize)]
 pub struct WhisperResponse {
     pub text: String,
+    #[serde(default)]
+    pub words: Vec<WordTiming>,
+    #[serde(default)]
+    pub segments: Vec<SegmentTiming>,
+}
+
+/// One transcribed word, with its times in seconds from the start of the file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WordTiming {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// One transcribed phrase, with its times in seconds from the start of the
+/// file and Whisper's scores for it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SegmentTiming {
+    pub text: String,
+    pub start: f64,
+    pub end: f64,
+    /// Mean log probability of the segment's tokens
+    #[serde(default)]
+    pub avg_logprob: Option<f64>,
+    /// Probability that the segment holds no speech at all
+    #[serde(default)]
+    pub no_speech_prob: Option<f64>,
 }
 
 /// Transcription result from Whisper
@@ -18,12 +48,42 @@ pub struct WhisperResponse {
 pub struct WhisperResult {
     pub transcript: String,
     pub word_count: usize,
+    /// Timing of each word that survived, in order
+    pub words: Vec<WordTiming>,
+    /// Timing of each segment, in order
+    pub segments: Vec<SegmentTiming>,
 }
 
 impl WhisperResult {
-    /// Determine if audio is effectively jammed
+    /// Determine if audio is effectively jammed, allowing 1-2 hallucinated words
     pub fn is_effectively_jammed(&self) -> bool {
-        self.word_count <= 2 // Allow 1-2 hallucinated words
+        self.is_jammed_with(&JammingCriteria::whisper())
+    }
+
+    /// Determine if audio is jammed under custom thresholds
+    ///
+    /// A high [`no_speech_prob`](Self::no_speech_prob) counts as jammed
+    /// even when hallucinated words push the transcript past `max_words`.
+    pub fn is_jammed_with(&self, criteria: &JammingCriteria) -> bool {
+        criteria.is_jammed(self.word_count, self.confidence(), self.no_speech_prob())
+    }
+
+    /// Mean token probability across the segments (0.0-1.0), or `None`
+    /// if the response had no segment scores
+    ///
+    /// Runs lower than other engines' confidence for the same audio.
+    pub fn confidence(&self) -> Option<f64> {
+        mean(
+            self.segments
+                .iter()
+                .filter_map(|s| s.avg_logprob.map(f64::exp)),
+        )
+    }
+
+    /// Mean probability across the segments that they hold no speech, or
+    /// `None` if the response had no segment scores
+    pub fn no_speech_prob(&self) -> Option<f64> {
+        mean(self.segments.iter().filter_map(|s| s.no_speech_prob))
     }
 
     /// Get a quality score (0.0 = completely jammed, 1.0 = transcribed)
@@ -31,14 +91,27 @@ impl WhisperResult {
         if self.word_count == 0 {
             0.0
         } else {
-            1.0
+            self.confidence().unwrap_or(1.0)
         }
     }
+
+    /// Word error rate against the clean phrase, capped at 1.0; see [`crate::wer::jam_score`]
+    pub fn jam_score(&self, clean_reference: &str) -> f64 {
+        crate::wer::jam_score(clean_reference, &self.transcript)
+    }
+}
+
+/// Mean of `values`, or `None` if there are none
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
 }
 
 pub struct WhisperClient {
     client: Client,
     api_key: String,
+    api_url: String,
+    retry: RetryPolicy,
 }
 
 impl WhisperClient {
@@ -46,9 +119,29 @@ impl WhisperClient {
         Self {
             client: Client::new(),
             api_key,
+            api_url: WHISPER_API_URL.to_string(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// Set how many times a transient (429/5xx or network) failure is retried
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    /// Set the time limit for each request attempt (default 60s)
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.retry.timeout = timeout;
+        self
+    }
+
+    /// Override the API endpoint (e.g. for a proxy or mock server)
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
     /// Transcribe audio file with Whisper
     pub async fn transcribe_file(&self, audio_path: &Path) -> Result<WhisperResult> {
         info!(
@@ -72,22 +165,27 @@ impl WhisperClient {
             .and_then(|s| s.to_str())
             .unwrap_or("audio.wav");
 
-        // Create multipart form
-        let part = reqwest::multipart::Part::bytes(audio_data)
-            .file_name(filename.to_string())
-            .mime_str("audio/wav")?;
+        // Make API request, rebuilding the multipart form for each attempt
+        let response = self
+            .retry
+            .send(|| {
+                let part = reqwest::multipart::Part::bytes(audio_data.clone())
+                    .file_name(filename.to_string())
+                    .mime_str("audio/wav")?;
 
-        let form = reqwest::multipart::Form::new()
-            .part("file", part)
-            .text("model", "whisper-1");
+                let form = reqwest::multipart::Form::new()
+                    .part("file", part)
+                    .text("model", "whisper-1")
+                    .text("response_format", "verbose_json")
+                    .text("timestamp_granularities[]", "word")
+                    .text("timestamp_granularities[]", "segment");
 
-        // Make API request
-        let response = self
-            .client
-            .post(WHISPER_API_URL)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .multipart(form)
-            .send()
+                Ok(self
+                    .client
+                    .post(&self.api_url)
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .multipart(form))
+            })
             .await
             .context("Failed to send transcription request")?;
 
@@ -111,13 +209,136 @@ impl WhisperClient {
         let result = WhisperResult {
             transcript: transcript.clone(),
             word_count,
+            words: whisper_response.words,
+            segments: whisper_response
+                .segments
+                .into_iter()
+                .map(|segment| SegmentTiming {
+                    text: segment.text.trim().to_string(),
+                    ..segment
+                })
+                .collect(),
         };
 
         info!("Whisper transcription result:");
         info!("  Transcript: '{}'", transcript);
         info!("  Word count: {}", word_count);
+        if let (Some(confidence), Some(no_speech)) = (result.confidence(), result.no_speech_prob())
+        {
+            info!("  Confidence: {:.3}", confidence);
+            info!("  No-speech probability: {:.3}", no_speech);
+        }
         info!("  Effectively jammed: {}", result.is_effectively_jammed());
 
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, method};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_transcribe_parses_timings() {
+        let server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(body_string_contains("verbose_json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "task": "transcribe",
+                "language": "english",
+                "duration": 2.4,
+                "text": " Hello world. Again.",
+                "words": [
+                    { "word": "Hello", "start": 0.1, "end": 0.42 },
+                    { "word": "world", "start": 0.42, "end": 0.9 },
+                    { "word": "Again", "start": 1.8, "end": 2.2 }
+                ],
+                "segments": [
+                    { "id": 0, "seek": 0, "start": 0.0, "end": 1.0, "text": " Hello world.",
+                      "tokens": [50364, 2425], "temperature": 0.0, "avg_logprob": -0.3,
+                      "compression_ratio": 0.8, "no_speech_prob": 0.01 },
+                    { "id": 1, "seek": 0, "start": 1.6, "end": 2.4, "text": " Again.",
+                      "tokens": [50444], "temperature": 0.0, "avg_logprob": -0.5,
+                      "compression_ratio": 0.8, "no_speech_prob": 0.02 }
+                ]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audio_path = temp_dir.path().join("audio.wav");
+        std::fs::write(&audio_path, b"RIFF").unwrap();
+
+        let client = WhisperClient::new("test".to_string()).with_api_url(server.uri());
+        let result = client.transcribe_file(&audio_path).await.unwrap();
+
+        assert_eq!(result.transcript, "Hello world. Again.");
+        assert_eq!(result.word_count, 3);
+        assert_eq!(result.words.len(), 3);
+        assert_eq!(
+            result.words[2],
+            WordTiming {
+                word: "Again".to_string(),
+                start: 1.8,
+                end: 2.2,
+            }
+        );
+        assert_eq!(
+            result.segments,
+            [
+                SegmentTiming {
+                    text: "Hello world.".to_string(),
+                    start: 0.0,
+                    end: 1.0,
+                    avg_logprob: Some(-0.3),
+                    no_speech_prob: Some(0.01),
+                },
+                SegmentTiming {
+                    text: "Again.".to_string(),
+                    start: 1.6,
+                    end: 2.4,
+                    avg_logprob: Some(-0.5),
+                    no_speech_prob: Some(0.02),
+                },
+            ]
+        );
+        let confidence = ((-0.3f64).exp() + (-0.5f64).exp()) / 2.0;
+        assert!((result.confidence().unwrap() - confidence).abs() < 1e-12);
+        assert!((result.no_speech_prob().unwrap() - 0.015).abs() < 1e-12);
+        assert!(!result.is_effectively_jammed());
+    }
+
+    #[test]
+    fn test_no_speech_outweighs_hallucinated_words() {
+        let segment = |text: &str, no_speech_prob| SegmentTiming {
+            text: text.to_string(),
+            start: 0.0,
+            end: 3.0,
+            avg_logprob: Some(-1.2),
+            no_speech_prob: Some(no_speech_prob),
+        };
+        let mut result = WhisperResult {
+            transcript: "Thank you for watching.".to_string(),
+            word_count: 4,
+            words: Vec::new(),
+            segments: vec![segment("Thank you for watching.", 0.85)],
+        };
+        // Past the word limit, but Whisper itself doubts there was speech
+        assert!(result.is_effectively_jammed());
+
+        result.segments[0].no_speech_prob = Some(0.1);
+        assert!(!result.is_effectively_jammed());
+    }
+
+    #[test]
+    fn test_plain_response_has_no_timings() {
+        let response: WhisperResponse =
+            serde_json::from_str(r#"{ "text": "hello world" }"#).unwrap();
+        assert!(response.words.is_empty());
+        assert!(response.segments.is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+use camouflage_core::analysis::SignalStats;
+use camouflage_core::{wav, SignalConfig};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use tempfile::TempDir;
+use tracing::info;
+
+/// Encode a WAV file with ffmpeg, then decode it back to WAV
+fn transcode(input: &Path, encoded: &Path, decoded: &Path) {
+    for (from, to) in [(input, encoded), (encoded, decoded)] {
+        let status = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(from)
+            .arg(to)
+            .status()
+            .expect("ffmpeg not found on PATH");
+        assert!(status.success(), "ffmpeg failed on {}", from.display());
+    }
+}
+
+fn rms(path: &Path) -> f32 {
+    let (_, samples) = wav::read_wav(path).expect("Failed to read WAV");
+    SignalStats::measure(&samples).rms
+}
+
+#[test]
+#[ignore] // Run with: cargo test --test e2e_codecs -- --ignored (needs ffmpeg)
+fn test_lossy_codecs_strip_ultrasonic() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    info!("=== E2E Lossy Codec Test ===");
+
+    let temp_dir = TempDir::new().unwrap();
+    let source = temp_dir.path().join("jam.wav");
+    wav::write_ultrasonic(
+        &source,
+        &SignalConfig::default(),
+        Duration::from_secs(2),
+        false,
+    )
+    .expect("Failed to write signal");
+    let original = rms(&source);
+
+    for extension in ["mp3", "ogg"] {
+        let encoded = temp_dir.path().join(format!("jam.{}", extension));
+        let decoded = temp_dir.path().join(format!("jam_{}.wav", extension));
+        transcode(&source, &encoded, &decoded);
+
+        let loss_db = 20.0 * (original / rms(&decoded).max(1e-9)).log10();
+        info!("{}: signal down {:.1} dB", extension, loss_db);
+
+        // Why lossy output is refused: the signal does not survive encoding
+        assert!(
+            loss_db > 20.0,
+            "{} kept the signal ({:.1} dB)",
+            extension,
+            loss_db
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use camouflage_core::SignalConfig;
-use camouflage_tests::{DeepgramClient, OpenAITTS};
+use camouflage_tests::{
+    record_result, speech_source_from_env, DeepgramClient, DeepgramStreamClient,
+};
 use std::env;
 use tempfile::TempDir;
 use tracing::info;
@@ -13,19 +15,18 @@ async fn test_deepgram_transcribes_clean_audio() {
 
     info!("=== E2E Deepgram Clean Audio Test ===");
 
-    let openai_key = env::var("OPENAI_API_KEY")
-        .expect("OPENAI_API_KEY not set. Please set it to run E2E tests.");
     let deepgram_key = env::var("DEEPGRAM_API_KEY")
         .expect("DEEPGRAM_API_KEY not set. Please set it to run E2E tests.");
 
-    let tts = OpenAITTS::new(openai_key);
+    let speech = speech_source_from_env().expect("Set PIPER_MODEL or OPENAI_API_KEY");
     let deepgram = DeepgramClient::new(deepgram_key);
 
     let temp_dir = TempDir::new().unwrap();
-    let audio_path = temp_dir.path().join("clean_voice.flac");
+    let audio_path = temp_dir.path().join("clean_voice.wav");
 
-    info!("Step 1: Generate clean voice sample with OpenAI TTS");
-    tts.generate_speech(TEST_PHRASE, &audio_path, None)
+    info!("Step 1: Generate clean voice sample with {}", speech.name());
+    speech
+        .speak_to_wav(TEST_PHRASE, &audio_path)
         .await
         .expect("Failed to generate TTS");
 
@@ -53,6 +54,14 @@ async fn test_deepgram_transcribes_clean_audio() {
         result.word_count
     );
 
+    let wer = result.jam_score(TEST_PHRASE);
+    info!("  Word error rate: {:.2}", wer);
+    assert!(
+        wer < 0.2,
+        "Clean audio should transcribe nearly verbatim, got WER {:.2}",
+        wer
+    );
+
     info!("✓ Clean audio transcribes successfully with Deepgram");
 }
 
@@ -74,6 +83,7 @@ async fn test_deepgram_pure_ultrasonic_not_transcribable() {
         &ultrasonic_path,
         3.0,
         &SignalConfig::default(),
+        false,
     )
     .expect("Failed to generate ultrasonic audio");
 
@@ -88,6 +98,13 @@ async fn test_deepgram_pure_ultrasonic_not_transcribable() {
     info!("  Confidence: {:.2}", result.confidence);
     info!("  Word count: {}", result.word_count);
     info!("  Effectively jammed: {}", result.is_effectively_jammed());
+    let jam_score = result.jam_score(TEST_PHRASE);
+    info!("  Jam score: {:.2}", jam_score);
+    assert!(
+        jam_score > 0.8,
+        "Transcript should share almost nothing with the phrase, got WER {:.2}",
+        jam_score
+    );
 
     // Pure ultrasonic should not produce meaningful transcription
     assert!(
@@ -122,6 +139,7 @@ async fn test_deepgram_multiple_configurations() {
                 amplitude: 0.3,
                 num_tones: 1,
                 frequency_spread: 0.0,
+                ..Default::default()
             },
         ),
         ("multi_tone_3", SignalConfig::default()),
@@ -133,6 +151,7 @@ async fn test_deepgram_multiple_configurations() {
                 amplitude: 0.3,
                 num_tones: 5,
                 frequency_spread: 400.0,
+                ..Default::default()
             },
         ),
     ];
@@ -146,7 +165,7 @@ async fn test_deepgram_multiple_configurations() {
 
         let audio_path = temp_dir.path().join(format!("{}.wav", name));
 
-        camouflage_tests::test_utils::generate_pure_ultrasonic(&audio_path, 2.0, config)
+        camouflage_tests::test_utils::generate_pure_ultrasonic(&audio_path, 2.0, config, false)
             .expect("Failed to generate signal");
 
         let result = deepgram
@@ -159,6 +178,9 @@ async fn test_deepgram_multiple_configurations() {
         info!("  Word count: {}", result.word_count);
         info!("  Jammed: {}\n", result.is_effectively_jammed());
 
+        record_result(name, config, &result.clone().into(), TEST_PHRASE)
+            .expect("Failed to record result");
+
         assert!(
             result.is_effectively_jammed(),
             "Configuration {} should produce untranscribable audio",
@@ -168,3 +190,43 @@ async fn test_deepgram_multiple_configurations() {
 
     info!("✓ All ultrasonic configurations disrupt Deepgram");
 }
+
+#[tokio::test]
+#[ignore]
+async fn test_deepgram_stream_pure_ultrasonic_not_transcribable() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    info!("=== Pure Ultrasonic Deepgram Streaming Test ===");
+
+    let deepgram_key = env::var("DEEPGRAM_API_KEY").expect("DEEPGRAM_API_KEY not set");
+    let deepgram = DeepgramStreamClient::new(deepgram_key);
+
+    info!("Streaming pure ultrasonic signal (23kHz, 3 seconds) in real time");
+    let result = deepgram
+        .transcribe_signal(&SignalConfig::default(), 3.0)
+        .await
+        .expect("Failed to stream transcription");
+
+    info!("Streaming transcription result:");
+    info!("  Transcript: '{}'", result.transcript);
+    info!("  Confidence: {:.2}", result.confidence);
+    info!("  Word count: {}", result.word_count);
+    info!("  Effectively jammed: {}", result.is_effectively_jammed());
+    let jam_score = result.jam_score(TEST_PHRASE);
+    info!("  Jam score: {:.2}", jam_score);
+    assert!(
+        jam_score > 0.8,
+        "Transcript should share almost nothing with the phrase, got WER {:.2}",
+        jam_score
+    );
+
+    assert!(
+        result.is_effectively_jammed(),
+        "Pure ultrasonic should not transcribe when streamed. Got {} words with {:.2} confidence: '{}'",
+        result.word_count,
+        result.confidence,
+        result.transcript
+    );
+
+    info!("✓ Camouflage disrupts Deepgram streaming recognition");
+}
@@ -0,0 +1,115 @@
+use camouflage_core::SignalConfig;
+use camouflage_tests::{speech_source_from_env, GoogleSttClient};
+use std::env;
+use tempfile::TempDir;
+use tracing::info;
+
+const TEST_PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+
+#[tokio::test]
+#[ignore] // Run with: cargo test --test e2e_google -- --ignored
+async fn test_google_transcribes_clean_audio() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    info!("=== E2E Google Speech-to-Text Clean Audio Test ===");
+
+    let google_key = env::var("GOOGLE_API_KEY")
+        .expect("GOOGLE_API_KEY not set. Please set it to run E2E tests.");
+
+    let speech = speech_source_from_env().expect("Set PIPER_MODEL or OPENAI_API_KEY");
+    let google = GoogleSttClient::new(google_key);
+
+    let temp_dir = TempDir::new().unwrap();
+    let audio_path = temp_dir.path().join("clean_voice.wav");
+
+    info!("Step 1: Generate clean voice sample with {}", speech.name());
+    speech
+        .speak_to_wav(TEST_PHRASE, &audio_path)
+        .await
+        .expect("Failed to generate TTS");
+
+    info!("Step 2: Transcribe with Google Speech-to-Text");
+    let result = google
+        .transcribe_file(&audio_path)
+        .await
+        .expect("Failed to transcribe");
+
+    info!("Transcription result:");
+    info!("  Original: '{}'", TEST_PHRASE);
+    info!("  Transcribed: '{}'", result.transcript);
+    info!("  Confidence: {:.2}", result.confidence);
+    info!("  Word count: {}", result.word_count);
+
+    assert!(
+        result.confidence > 0.7,
+        "Clean audio should transcribe with high confidence, got: {}",
+        result.confidence
+    );
+    assert!(
+        result.word_count >= 9,
+        "Should transcribe at least 9 words, got: {}",
+        result.word_count
+    );
+
+    let wer = result.jam_score(TEST_PHRASE);
+    info!("  Word error rate: {:.2}", wer);
+    assert!(
+        wer < 0.2,
+        "Clean audio should transcribe nearly verbatim, got WER {:.2}",
+        wer
+    );
+
+    info!("✓ Clean audio transcribes successfully with Google Speech-to-Text");
+}
+
+#[tokio::test]
+#[ignore]
+async fn test_google_pure_ultrasonic_not_transcribable() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    info!("=== Pure Ultrasonic Google Speech-to-Text Test ===");
+
+    let google_key = env::var("GOOGLE_API_KEY").expect("GOOGLE_API_KEY not set");
+    let google = GoogleSttClient::new(google_key);
+
+    let temp_dir = TempDir::new().unwrap();
+    let ultrasonic_path = temp_dir.path().join("pure_ultrasonic.wav");
+
+    info!("Step 1: Generating pure ultrasonic audio (23kHz, 3 seconds)");
+    camouflage_tests::test_utils::generate_pure_ultrasonic(
+        &ultrasonic_path,
+        3.0,
+        &SignalConfig::default(),
+        false,
+    )
+    .expect("Failed to generate ultrasonic audio");
+
+    info!("Step 2: Attempting to transcribe with Google Speech-to-Text");
+    let result = google
+        .transcribe_file(&ultrasonic_path)
+        .await
+        .expect("Failed to transcribe");
+
+    info!("Pure ultrasonic transcription result:");
+    info!("  Transcript: '{}'", result.transcript);
+    info!("  Confidence: {:.2}", result.confidence);
+    info!("  Word count: {}", result.word_count);
+    info!("  Effectively jammed: {}", result.is_effectively_jammed());
+    let jam_score = result.jam_score(TEST_PHRASE);
+    info!("  Jam score: {:.2}", jam_score);
+    assert!(
+        jam_score > 0.8,
+        "Transcript should share almost nothing with the phrase, got WER {:.2}",
+        jam_score
+    );
+
+    assert!(
+        result.is_effectively_jammed(),
+        "Pure ultrasonic should not transcribe. Got {} words with {:.2} confidence: '{}'",
+        result.word_count,
+        result.confidence,
+        result.transcript
+    );
+
+    info!("✓ Camouflage disrupts Google Speech-to-Text recognition");
+}
@@ -0,0 +1,75 @@
+use camouflage_core::SignalConfig;
+use camouflage_tests::quality_gate::{check_engine, transcribers_from_env, MAX_CLEAN_WER};
+use camouflage_tests::{record_result, speech_source_from_env};
+use tempfile::TempDir;
+use tracing::info;
+
+const TEST_PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+
+#[tokio::test]
+#[ignore] // Run with: cargo test --test e2e_quality_gate -- --ignored
+async fn test_every_engine_passes_sanity_before_jamming() {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    info!("=== Quality Gate: engine sanity, then jamming ===");
+
+    let engines = transcribers_from_env();
+    assert!(
+        !engines.is_empty(),
+        "Set OPENAI_API_KEY, DEEPGRAM_API_KEY or GOOGLE_API_KEY"
+    );
+    let speech = speech_source_from_env().expect("Set PIPER_MODEL or OPENAI_API_KEY");
+
+    let temp_dir = TempDir::new().unwrap();
+    let clean_path = temp_dir.path().join("clean_voice.wav");
+    let ultrasonic_path = temp_dir.path().join("ultrasonic.wav");
+
+    info!("Generating clean voice sample with {}", speech.name());
+    speech
+        .speak_to_wav(TEST_PHRASE, &clean_path)
+        .await
+        .expect("Failed to generate TTS");
+    camouflage_tests::test_utils::generate_pure_ultrasonic(
+        &ultrasonic_path,
+        3.0,
+        &SignalConfig::default(),
+        false,
+    )
+    .expect("Failed to generate ultrasonic audio");
+
+    for engine in &engines {
+        // A broken engine or key fails here, not as a jamming result
+        if let Err(e) = check_engine(engine.as_ref(), &clean_path, TEST_PHRASE, MAX_CLEAN_WER).await
+        {
+            panic!("{}", e);
+        }
+
+        let result = engine
+            .transcribe_file(&ultrasonic_path)
+            .await
+            .unwrap_or_else(|e| panic!("{} failed: {}", engine.name(), e));
+        info!(
+            "{}: '{}' ({} words, jammed: {})",
+            engine.name(),
+            result.transcript,
+            result.word_count,
+            result.is_effectively_jammed()
+        );
+        record_result(
+            "quality_gate",
+            &SignalConfig::default(),
+            &result,
+            TEST_PHRASE,
+        )
+        .expect("Failed to record result");
+
+        assert!(
+            result.is_effectively_jammed(),
+            "Jamming regression: {} transcribed ultrasonic audio as '{}'",
+            engine.name(),
+            result.transcript
+        );
+    }
+
+    info!("✓ Every engine transcribes clean audio and is jammed by the signal");
+}
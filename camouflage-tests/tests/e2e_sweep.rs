@@ -0,0 +1,89 @@
+use camouflage_tests::sweep::{
+    frequency_sweep_phrase, minimum_jamming_amplitude, sweep_phrase, write_frequency_csv,
+    write_sweep_csv,
+};
+use camouflage_tests::{speech_source_from_env, FrequencySweepConfig, SweepConfig, WhisperClient};
+use std::env;
+use tempfile::TempDir;
+use tracing::info;
+
+const TEST_PHRASE: &str = "The quick brown fox jumps over the lazy dog.";
+
+#[tokio::test]
+#[ignore] // Run with: cargo test --test e2e_sweep -- --ignored --nocapture
+async fn test_whisper_amplitude_sweep() {
+    let _ = tracing_subscriber::fmt().with_env_filter("info").try_init();
+
+    info!("=== Whisper Amplitude Sweep ===");
+
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+    let speech = speech_source_from_env().expect("Set PIPER_MODEL or OPENAI_API_KEY");
+    let whisper = WhisperClient::new(api_key);
+
+    let sweep = SweepConfig::default();
+    let points = sweep_phrase(&*speech, TEST_PHRASE, &sweep, &whisper)
+        .await
+        .expect("Sweep failed");
+
+    assert_eq!(
+        points.len(),
+        sweep.amplitudes.len() * sweep.mix_ratios.len()
+    );
+
+    for &mix_ratio in &sweep.mix_ratios {
+        match minimum_jamming_amplitude(&points, mix_ratio) {
+            Some(amplitude) => info!(
+                "Mix ratio {:.2}: jamming holds from amplitude {:.2}",
+                mix_ratio, amplitude
+            ),
+            None => info!("Mix ratio {:.2}: not jammed at any amplitude", mix_ratio),
+        }
+    }
+
+    let output_dir = env::var("SWEEP_OUTPUT_DIR").ok();
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = match &output_dir {
+        Some(dir) => std::path::PathBuf::from(dir).join("whisper_sweep.csv"),
+        None => temp_dir.path().join("whisper_sweep.csv"),
+    };
+    write_sweep_csv(&points, &csv_path).expect("Failed to write CSV");
+    info!("Sweep results written to {}", csv_path.display());
+}
+
+#[tokio::test]
+#[ignore] // Run with: cargo test --test e2e_sweep -- --ignored --nocapture
+async fn test_whisper_frequency_sweep() {
+    let _ = tracing_subscriber::fmt().with_env_filter("info").try_init();
+
+    info!("=== Whisper Frequency Sweep ===");
+
+    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
+    let speech = speech_source_from_env().expect("Set PIPER_MODEL or OPENAI_API_KEY");
+    let whisper = WhisperClient::new(api_key);
+
+    let sweep = FrequencySweepConfig::default();
+    let points = frequency_sweep_phrase(&*speech, TEST_PHRASE, &sweep, &whisper)
+        .await
+        .expect("Frequency sweep failed");
+
+    assert_eq!(points.len(), sweep.frequencies().len());
+
+    if let Some(best) = points
+        .iter()
+        .max_by(|a, b| a.jam_score.total_cmp(&b.jam_score))
+    {
+        info!(
+            "Most disruptive frequency: {:.0} Hz (jam score {:.2})",
+            best.frequency, best.jam_score
+        );
+    }
+
+    let output_dir = env::var("SWEEP_OUTPUT_DIR").ok();
+    let temp_dir = TempDir::new().unwrap();
+    let csv_path = match &output_dir {
+        Some(dir) => std::path::PathBuf::from(dir).join("whisper_frequency_sweep.csv"),
+        None => temp_dir.path().join("whisper_frequency_sweep.csv"),
+    };
+    write_frequency_csv(&points, &csv_path).expect("Failed to write CSV");
+    info!("Frequency sweep results written to {}", csv_path.display());
+}
@@ -1,5 +1,7 @@
 use camouflage_core::SignalConfig;
-use camouflage_tests::{OpenAITTS, WhisperClient};
+use camouflage_tests::{
+    record_result, DeepgramClient, OpenAITTS, Transcriber, TtsOptions, WhisperClient,
+};
 use std::env;
 use tempfile::TempDir;
 use tracing::info;
@@ -23,7 +25,7 @@ async fn test_whisper_transcribes_clean_audio() {
     let audio_path = temp_dir.path().join("clean_voice.flac");
 
     info!("Step 1: Generate clean voice sample with OpenAI TTS");
-    tts.generate_speech(TEST_PHRASE, &audio_path, None)
+    tts.generate_speech(TEST_PHRASE, &audio_path, &TtsOptions::default())
         .await
         .expect("Failed to generate TTS");
 
@@ -49,6 +51,14 @@ async fn test_whisper_transcribes_clean_audio() {
         "Clean audio should not be jammed"
     );
 
+    let wer = result.jam_score(TEST_PHRASE);
+    info!("  Word error rate: {:.2}", wer);
+    assert!(
+        wer < 0.2,
+        "Clean audio should transcribe nearly verbatim, got WER {:.2}",
+        wer
+    );
+
     info!("✓ Clean audio transcribes successfully with Whisper");
 }
 
@@ -70,6 +80,7 @@ async fn test_whisper_pure_ultrasonic_not_transcribable() {
         &ultrasonic_path,
         3.0,
         &SignalConfig::default(),
+        false,
     )
     .expect("Failed to generate ultrasonic audio");
 
@@ -83,6 +94,13 @@ async fn test_whisper_pure_ultrasonic_not_transcribable() {
     info!("  Transcript: '{}'", result.transcript);
     info!("  Word count: {}", result.word_count);
     info!("  Effectively jammed: {}", result.is_effectively_jammed());
+    let jam_score = result.jam_score(TEST_PHRASE);
+    info!("  Jam score: {:.2}", jam_score);
+    assert!(
+        jam_score > 0.8,
+        "Transcript should share almost nothing with the phrase, got WER {:.2}",
+        jam_score
+    );
 
     // Whisper sometimes hallucinates short words from silence/noise
     // Consider it jammed if: empty, or just 1-2 hallucinated words
@@ -128,6 +146,7 @@ async fn test_whisper_multiple_configurations() {
                 amplitude: 0.3,
                 num_tones: 1,
                 frequency_spread: 0.0,
+                ..Default::default()
             },
         ),
         ("multi_tone_3", SignalConfig::default()),
@@ -139,6 +158,7 @@ async fn test_whisper_multiple_configurations() {
                 amplitude: 0.3,
                 num_tones: 5,
                 frequency_spread: 400.0,
+                ..Default::default()
             },
         ),
     ];
@@ -152,7 +172,7 @@ async fn test_whisper_multiple_configurations() {
 
         let audio_path = temp_dir.path().join(format!("{}.wav", name));
 
-        camouflage_tests::test_utils::generate_pure_ultrasonic(&audio_path, 2.0, config)
+        camouflage_tests::test_utils::generate_pure_ultrasonic(&audio_path, 2.0, config, false)
             .expect("Failed to generate signal");
 
         let result = whisper
@@ -164,6 +184,9 @@ async fn test_whisper_multiple_configurations() {
         info!("  Word count: {}", result.word_count);
         info!("  Jammed: {}\n", result.is_effectively_jammed());
 
+        record_result(name, config, &result.clone().into(), TEST_PHRASE)
+            .expect("Failed to record result");
+
         assert!(
             result.is_effectively_jammed(),
             "Configuration {} should produce untranscribable audio",
@@ -184,8 +207,10 @@ async fn test_whisper_vs_deepgram_comparison() {
     let openai_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
     let deepgram_key = env::var("DEEPGRAM_API_KEY").expect("DEEPGRAM_API_KEY not set");
 
-    let whisper = WhisperClient::new(openai_key);
-    let deepgram = camouflage_tests::DeepgramClient::new(deepgram_key);
+    let engines: Vec<Box<dyn Transcriber>> = vec![
+        Box::new(WhisperClient::new(openai_key)),
+        Box::new(DeepgramClient::new(deepgram_key)),
+    ];
 
     let temp_dir = TempDir::new().unwrap();
     let ultrasonic_path = temp_dir.path().join("ultrasonic.wav");
@@ -195,42 +220,40 @@ async fn test_whisper_vs_deepgram_comparison() {
         &ultrasonic_path,
         3.0,
         &SignalConfig::default(),
+        false,
     )
     .expect("Failed to generate");
 
-    info!("\nTranscribing with both Whisper and Deepgram...");
+    info!("\nTranscribing with all engines...");
+    info!("\n=== Results ===");
 
-    let whisper_result = whisper
-        .transcribe_file(&ultrasonic_path)
-        .await
-        .expect("Whisper failed");
+    for engine in &engines {
+        let result = engine
+            .transcribe_file(&ultrasonic_path)
+            .await
+            .unwrap_or_else(|e| panic!("{} failed: {}", engine.name(), e));
 
-    let deepgram_result = deepgram
-        .transcribe_file(&ultrasonic_path)
-        .await
-        .expect("Deepgram failed");
+        info!("{}:", engine.name());
+        info!("  Transcript: '{}'", result.transcript);
+        if let Some(confidence) = result.confidence {
+            info!("  Confidence: {:.2}", confidence);
+        }
+        info!("  Word count: {}", result.word_count);
+        info!("  Jammed: {}", result.is_effectively_jammed());
+        record_result(
+            "engine_comparison",
+            &SignalConfig::default(),
+            &result,
+            TEST_PHRASE,
+        )
+        .expect("Failed to record result");
 
-    info!("\n=== Results ===");
-    info!("Whisper:");
-    info!("  Transcript: '{}'", whisper_result.transcript);
-    info!("  Word count: {}", whisper_result.word_count);
-    info!("  Jammed: {}", whisper_result.is_effectively_jammed());
-
-    info!("\nDeepgram:");
-    info!("  Transcript: '{}'", deepgram_result.transcript);
-    info!("  Confidence: {:.2}", deepgram_result.confidence);
-    info!("  Word count: {}", deepgram_result.word_count);
-    info!("  Jammed: {}", deepgram_result.is_effectively_jammed());
-
-    // Both should be jammed
-    assert!(
-        whisper_result.is_effectively_jammed(),
-        "Whisper should be jammed"
-    );
-    assert!(
-        deepgram_result.is_effectively_jammed(),
-        "Deepgram should be jammed"
-    );
+        assert!(
+            result.is_effectively_jammed(),
+            "{} should be jammed",
+            engine.name()
+        );
+    }
 
     info!("\n✓ Camouflage defeats both Whisper and Deepgram");
 }
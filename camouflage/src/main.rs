@@ -1,9 +1,36 @@
-use camouflage_core::{get_status, is_running, save_pid, stop_daemon};
-use camouflage_core::{SignalConfig, SpeakerJammer, SystemJammer};
-use clap::{Parser, Subcommand};
-use std::io::{self, Write};
-use std::path::PathBuf;
-use tracing::info;
+use anyhow::Context;
+use camouflage_core::{
+    get_daemon_status, get_state_file, is_running, lock_daemon, remove_pid, save_pid, stop_daemon,
+    DaemonConfig,
+};
+use camouflage_core::{
+    wav, CamouflageError, MixCurve, SignalConfig, SignalGenerator, SpeakerJammer, SystemJammer,
+    ToneDistribution, WaveformType, MIN_ULTRASONIC_FREQUENCY,
+};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How often the daemon saves its signal state for a seamless restart
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Environment variable holding the fleet's shared token
+#[cfg(feature = "fleet")]
+const FLEET_TOKEN_ENV: &str = "CAMOUFLAGE_FLEET_TOKEN";
+
+/// How long `daemon verify` listens for the signal
+const VERIFY_DURATION: Duration = Duration::from_secs(1);
+
+/// How much signal is rendered to check its peak before playing or writing it
+const PEAK_CHECK_DURATION: Duration = Duration::from_millis(500);
+
+/// How often `--profile` logs the generator's CPU use
+const PROFILE_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Parser)]
 #[command(name = "camouflage")]
@@ -20,6 +47,11 @@ struct Cli {
     #[arg(short, long, default_value = "0.25")]
     amplitude: f32,
 
+    /// Scale the summed output (0.0-1.0) while keeping the balance between
+    /// tones; the signal peaks at up to amplitude × master gain
+    #[arg(long, default_value = "1.0", value_name = "GAIN")]
+    master_gain: f32,
+
     /// Number of tones for multi-tone jamming
     #[arg(short, long, default_value = "3")]
     num_tones: usize,
@@ -27,18 +59,232 @@ struct Cli {
     /// Frequency spread for multi-tone in Hz
     #[arg(short, long, default_value = "300")]
     spread: f32,
+
+    /// Let the tone spacing wander by up to this fraction of --spread, e.g.
+    /// 0.05 for ±5%, so a static comb filter cannot stay matched
+    #[arg(long, default_value = "0", value_name = "FRACTION")]
+    spread_dither: f32,
+
+    /// How often the spacing dither picks a new target, in Hz
+    #[arg(long, default_value = "0.5", value_name = "HZ")]
+    spread_dither_rate: f32,
+
+    /// How the tones are spaced within the spread
+    #[arg(long, value_enum, default_value = "even")]
+    tone_distribution: Distribution,
+
+    /// Place each tone at these offsets in Hz from the frequency instead,
+    /// e.g. "-600,-400,0,800"; sets the number of tones
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "HZ,...",
+        allow_negative_numbers = true,
+        conflicts_with = "tone_distribution"
+    )]
+    tone_offsets: Vec<f32>,
+
+    /// Waveform of each tone
+    #[arg(short, long, value_enum, default_value = "sine")]
+    waveform: Waveform,
+
+    /// Boost higher tones by this many dB per octave above 20 kHz, to make
+    /// up for speaker roll-off
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "DB",
+        allow_negative_numbers = true
+    )]
+    tilt: f32,
+
+    /// Start from a named preset (see `camouflage presets`); --config and
+    /// explicit flags override it
+    #[arg(long, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Load signal settings from a TOML or JSON file; explicit flags override it
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Hop the carrier among these frequencies in Hz, e.g. "22000,23000,24000"
+    #[arg(long, value_delimiter = ',', value_name = "HZ,...")]
+    hop: Vec<f32>,
+
+    /// Time between carrier hops in milliseconds
+    #[arg(long, default_value = "500", value_name = "MS")]
+    hop_interval: f32,
+
+    /// Phase offset of each output channel in degrees, e.g. "0,90" for stereo
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "DEG,...",
+        allow_negative_numbers = true
+    )]
+    channel_phase: Vec<f32>,
+
+    /// Seed every random part of the signal, for runs that can be repeated
+    /// exactly (default: a new seed each run, which the jammer modes log)
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    /// Add a quiet 21 kHz pilot tone sending this ID (0-65535), so other
+    /// devices can detect and identify the jammer
+    #[arg(long, value_name = "ID")]
+    pilot_id: Option<u16>,
+
+    /// Fade the signal in from silence over this many seconds after starting
+    #[arg(long, value_name = "SECS")]
+    amplitude_ramp: Option<f32>,
+
+    /// Apply a steep 19 kHz high-pass to the output to guarantee no audible leakage
+    #[arg(long)]
+    safety_highpass: bool,
+
+    /// Reject tones within this many Hz of the output's Nyquist limit
+    /// (e.g. 2000); 0 allows anything below Nyquist
+    #[arg(long, default_value = "0", value_name = "HZ")]
+    nyquist_guard: f32,
+
+    /// Fail on tones below 20 kHz instead of adjusting the frequency, and
+    /// on signals peaking above 0.95 of full scale instead of warning
+    #[arg(long)]
+    strict: bool,
+
+    /// Log output format
+    #[arg(long, value_enum, default_value = "pretty")]
+    log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    /// Human-readable text
+    Pretty,
+    /// One JSON object per line
+    Json,
+}
+
+/// CLI names for [`WaveformType`]
+#[derive(Clone, Copy, ValueEnum)]
+enum Waveform {
+    Sine,
+    Square,
+    Sawtooth,
+    Triangle,
+    /// Noise band-passed around the frequency, spanning all tones
+    BandNoise,
+}
+
+impl From<Waveform> for WaveformType {
+    fn from(waveform: Waveform) -> Self {
+        match waveform {
+            Waveform::Sine => WaveformType::Sine,
+            Waveform::Square => WaveformType::Square,
+            Waveform::Sawtooth => WaveformType::Sawtooth,
+            Waveform::Triangle => WaveformType::Triangle,
+            Waveform::BandNoise => WaveformType::BandNoise,
+        }
+    }
+}
+
+/// CLI names for the built-in [`ToneDistribution`]s
+#[derive(Clone, Copy, ValueEnum)]
+enum Distribution {
+    /// Evenly spaced around the frequency
+    Even,
+    /// Densest at the low end of the spread
+    Geometric,
+}
+
+impl From<Distribution> for ToneDistribution {
+    fn from(distribution: Distribution) -> Self {
+        match distribution {
+            Distribution::Even => ToneDistribution::Even,
+            Distribution::Geometric => ToneDistribution::Geometric,
+        }
+    }
+}
+
+/// CLI names for [`MixCurve`]
+#[derive(Clone, Copy, ValueEnum)]
+enum Curve {
+    /// Gains proportional to the ratio; quieter around 0.5
+    Linear,
+    /// Constant loudness across ratios
+    EqualPower,
+}
+
+impl From<Curve> for MixCurve {
+    fn from(curve: Curve) -> Self {
+        match curve {
+            Curve::Linear => MixCurve::Linear,
+            Curve::EqualPower => MixCurve::EqualPower,
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Mode {
     /// Output ultrasonic signal through speakers to jam nearby microphones
-    Speaker,
+    Speaker {
+        /// Stop automatically after this many seconds instead of waiting for Enter
+        #[arg(long, value_name = "SECS")]
+        duration: Option<u64>,
+
+        /// Play only on these output channels, counting from 1, e.g. "1,2"
+        /// for the front pair of a surround device; the rest stay silent
+        #[arg(long, value_delimiter = ',', value_name = "N,...")]
+        channels: Vec<usize>,
+
+        /// Log how much of each audio callback's time budget the generator uses
+        #[arg(long)]
+        profile: bool,
+
+        /// Run the audio callback at realtime priority (on Linux this needs
+        /// CAP_SYS_NICE or an rtprio limit)
+        #[arg(long)]
+        realtime: bool,
+
+        /// Open the output in WASAPI exclusive mode for a higher sample rate
+        /// than the Windows mixer's, falling back to shared mode if the
+        /// device refuses (Windows)
+        #[arg(long)]
+        exclusive: bool,
+    },
 
     /// Create virtual audio device to prevent remote call recording
     System {
         /// Mix ratio of ultrasonic signal (0.0-1.0)
         #[arg(short, long, default_value = "0.5")]
         mix_ratio: f32,
+
+        /// How the mix ratio is turned into gains for the audio and the signal
+        #[arg(long, value_enum, default_value = "linear")]
+        mix_curve: Curve,
+
+        /// Bring the audio to a fixed loudness before mixing, so quiet and
+        /// loud sources get the same balance at a given ratio
+        #[arg(long)]
+        normalize_input: bool,
+
+        /// Stop automatically after this many seconds instead of waiting for Enter
+        #[arg(long, value_name = "SECS")]
+        duration: Option<u64>,
+
+        /// Input carrying system audio, e.g. "camouflage_sink.monitor" on
+        /// Linux (default: the platform's virtual device); see `devices`
+        #[arg(long, value_name = "SOURCE")]
+        capture_source: Option<String>,
+
+        /// Log how much of each audio callback's time budget the generator uses
+        #[arg(long)]
+        profile: bool,
+
+        /// Run the audio callback at realtime priority (on Linux this needs
+        /// CAP_SYS_NICE or an rtprio limit)
+        #[arg(long)]
+        realtime: bool,
     },
 
     /// Run in daemon mode (background process)
@@ -47,51 +293,336 @@ enum Mode {
         command: DaemonCommand,
     },
 
-    /// Install system mode audio device for your platform
-    Install,
+    /// Install system mode audio device for your platform and make it the
+    /// default output
+    Install {
+        /// Remove camouflage audio modules left behind by crashed runs instead (Linux)
+        #[arg(long)]
+        clean: bool,
+
+        /// Leave the default output alone
+        #[arg(long)]
+        keep_default: bool,
+
+        /// Set everything up without manual steps, or fail; reports each step
+        /// and exits non-zero if any failed
+        #[arg(long, conflicts_with = "clean")]
+        non_interactive: bool,
+    },
+
+    /// Restore the default output saved by `install` and remove the audio device
+    Uninstall,
+
+    /// Inspect a WAV file's spectrum and check that it is inaudible
+    Analyze {
+        /// WAV file to analyze
+        file: PathBuf,
+
+        /// Also draw the spectrum to this SVG file
+        #[cfg(feature = "plot")]
+        #[arg(long, value_name = "SVG")]
+        plot: Option<PathBuf>,
+    },
+
+    /// Write the signal to a WAV or FLAC file, or mix it into an existing recording
+    Generate {
+        /// Output file (.wav or .flac)
+        #[arg(short, long)]
+        out: PathBuf,
+
+        /// Length of the generated file in seconds (ignored with --mix)
+        #[arg(long, default_value = "10", value_name = "SECS")]
+        duration: f32,
+
+        /// Sample rate of the generated file in Hz (ignored with --mix)
+        #[arg(long, default_value = "48000", value_name = "HZ")]
+        sample_rate: u32,
+
+        /// Mix the signal into this WAV or FLAC file instead of writing it alone
+        #[arg(long, value_name = "FILE")]
+        mix: Option<PathBuf>,
+
+        /// Mix ratio of ultrasonic signal (0.0-1.0), used with --mix
+        #[arg(short, long, default_value = "0.5")]
+        mix_ratio: f32,
+
+        /// How the mix ratio is turned into gains for the audio and the signal
+        #[arg(long, value_enum, default_value = "linear")]
+        mix_curve: Curve,
+
+        /// Bring the audio to a fixed loudness before mixing, so quiet and
+        /// loud sources get the same balance at a given ratio
+        #[arg(long)]
+        normalize_input: bool,
+
+        /// Add TPDF dither before quantizing to integer samples, so a quiet
+        /// signal leaves no quantization spurs in the audible band
+        #[arg(long)]
+        dither: bool,
+    },
+
+    /// Mix the signal into every WAV/FLAC recording in a directory
+    Mix {
+        /// Directory of recordings to protect
+        #[arg(long, value_name = "DIR")]
+        in_dir: PathBuf,
+
+        /// Directory for the mixed files
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+
+        /// Mix ratio of ultrasonic signal (0.0-1.0)
+        #[arg(short, long, default_value = "0.5")]
+        mix_ratio: f32,
+
+        /// How the mix ratio is turned into gains for the audio and the signal
+        #[arg(long, value_enum, default_value = "linear")]
+        mix_curve: Curve,
+
+        /// Bring the audio to a fixed loudness before mixing, so quiet and
+        /// loud sources get the same balance at a given ratio
+        #[arg(long)]
+        normalize_input: bool,
+
+        /// Add TPDF dither before quantizing to integer samples, so a quiet
+        /// signal leaves no quantization spurs in the audible band
+        #[arg(long)]
+        dither: bool,
+    },
+
+    /// Find an amplitude for your speakers, measured by a microphone or by ear
+    Calibrate {
+        /// Output device to calibrate (default: system default)
+        #[arg(long, value_name = "DEVICE")]
+        output: Option<String>,
+
+        /// Input device that measures the emitted level (default: system default)
+        #[arg(long, value_name = "DEVICE")]
+        input: Option<String>,
+
+        /// Tone level to reach at the microphone in dBFS
+        #[arg(
+            long,
+            default_value = "-40",
+            value_name = "DB",
+            allow_negative_numbers = true
+        )]
+        target_db: f32,
+
+        /// Skip the microphone and ask whether each step is audible instead
+        #[arg(long)]
+        manual: bool,
+    },
+
+    /// Measure how far above 15 kHz your speakers play, using a microphone
+    ///
+    /// Plays a quiet sweep from 15 to 25 kHz and reports where it rolls off.
+    Response {
+        /// Output device to measure (default: system default)
+        #[arg(long, value_name = "DEVICE")]
+        output: Option<String>,
+
+        /// Input device that records the sweep (default: system default)
+        #[arg(long, value_name = "DEVICE")]
+        input: Option<String>,
+    },
+
+    /// List audio output devices and their capabilities
+    Devices {
+        /// Play a short, quiet test sweep on the named device
+        #[arg(long, value_name = "DEVICE")]
+        test: Option<String>,
+    },
+
+    /// List the built-in signal presets
+    Presets {
+        /// Print the presets and their full settings as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Check offline that every preset renders cleanly; no audio device needed
+    Selftest,
+
+    /// Find daemons on the local network and control them together
+    #[cfg(feature = "fleet")]
+    Fleet {
+        #[command(subcommand)]
+        command: FleetCommand,
+
+        /// Token shared by the fleet (default: $CAMOUFLAGE_FLEET_TOKEN)
+        #[arg(long, global = true)]
+        token: Option<String>,
+
+        /// How long to look for daemons in seconds
+        #[arg(long, global = true, default_value = "2", value_name = "SECS")]
+        wait: f32,
+    },
+}
+
+#[cfg(feature = "fleet")]
+#[derive(Subcommand)]
+enum FleetCommand {
+    /// List the daemons on the network and what they are playing
+    Status {
+        /// Print each daemon's status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Retune every daemon to the signal flags (or --config)
+    Set,
+
+    /// Stop every daemon
+    Stop,
+}
+
+/// Options for starting the daemon, shared by `start` and `restart`
+#[derive(Args)]
+struct StartArgs {
+    /// Daemon mode (speaker or system)
+    #[arg(short, long, default_value = "speaker")]
+    mode: String,
+
+    /// Rebuild the output stream after this many seconds of silence
+    #[arg(long, value_name = "SECS")]
+    watchdog: Option<u64>,
+
+    /// Stay attached to the terminal instead of detaching; used by
+    /// launchd and systemd, which manage the process themselves
+    #[arg(long)]
+    foreground: bool,
+
+    /// Write the log here (default: daemon.log in the config directory);
+    /// with --foreground the log stays on the terminal unless this is set
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Run the audio callback at realtime priority (on Linux this needs
+    /// CAP_SYS_NICE or an rtprio limit)
+    #[arg(long)]
+    realtime: bool,
+
+    /// Accept fleet commands on this TCP port and advertise the daemon
+    /// on the local network; needs $CAMOUFLAGE_FLEET_TOKEN or --fleet-token
+    #[cfg(feature = "fleet")]
+    #[arg(long, value_name = "PORT")]
+    fleet_port: Option<u16>,
+
+    /// Token fleet commands must carry
+    #[cfg(feature = "fleet")]
+    #[arg(long, value_name = "TOKEN", requires = "fleet_port")]
+    fleet_token: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum DaemonCommand {
     /// Start daemon in speaker mode
-    Start {
-        /// Daemon mode (speaker or system)
-        #[arg(short, long, default_value = "speaker")]
-        mode: String,
-    },
+    Start(StartArgs),
 
-    /// Stop running daemon
+    /// Stop running daemon, waiting until it has exited
     Stop,
 
+    /// Stop the running daemon, wait until it has exited, and start afresh
+    Restart(StartArgs),
+
     /// Check daemon status
-    Status,
+    Status {
+        /// Print status as JSON
+        #[arg(long)]
+        json: bool,
+    },
 
     /// Enable auto-start on boot
-    Enable,
+    Enable {
+        /// Daemon log file (default: daemon.log in the config directory)
+        #[arg(long, value_name = "PATH")]
+        log_file: Option<PathBuf>,
+    },
 
     /// Disable auto-start on boot
     Disable,
+
+    /// Confirm with a microphone that the signal is reaching the air
+    ///
+    /// Uses the signal flags (or --config) to know which band to listen for.
+    Verify {
+        /// Input device to listen with (default: system default)
+        #[arg(long, value_name = "DEVICE")]
+        input: Option<String>,
+    },
+
+    /// Show the daemon's log
+    Logs {
+        /// Keep printing new lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+
+        /// Daemon log file (default: daemon.log in the config directory)
+        #[arg(long, value_name = "PATH")]
+        log_file: Option<PathBuf>,
+    },
+}
+
+impl DaemonCommand {
+    /// Options of a command that starts the daemon
+    fn start_args(&self) -> Option<&StartArgs> {
+        match self {
+            Self::Start(args) | Self::Restart(args) => Some(args),
+            _ => None,
+        }
+    }
 }
 
 fn main() -> anyhow::Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
     // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::INFO.into()),
-        )
-        .init();
-
-    let cli = Cli::parse();
-
-    // Build signal configuration
-    let mut config = SignalConfig {
-        frequency: cli.frequency,
-        sample_rate: 48000, // Will be updated by audio device
-        amplitude: cli.amplitude,
-        num_tones: cli.num_tones,
-        frequency_spread: cli.spread,
+    let env_filter = tracing_subscriber::EnvFilter::from_default_env()
+        .add_directive(tracing::Level::INFO.into());
+    // Escape codes would end up in the daemon's log file
+    let to_log_file = match &cli.mode {
+        Mode::Daemon { command } => command
+            .start_args()
+            .is_some_and(|args| !args.foreground || args.log_file.is_some()),
+        _ => false,
     };
+    let ansi = io::stdout().is_terminal() && !to_log_file;
+    match cli.log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
+            .with_ansi(ansi)
+            .init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(env_filter)
+            .init(),
+    }
+
+    if let Mode::Presets { json } = cli.mode {
+        return run_presets(json);
+    }
+    if let Mode::Selftest = cli.mode {
+        return run_selftest();
+    }
+
+    // Build signal configuration: preset, then config file, then explicit flags
+    let mut config = build_signal_config(&cli, &matches)?;
+
+    let opens_output = match &cli.mode {
+        Mode::Speaker { .. } | Mode::System { .. } => true,
+        Mode::Daemon { command } => command.start_args().is_some(),
+        _ => false,
+    };
+    if let Mode::Generate {
+        sample_rate,
+        mix: None,
+        ..
+    } = cli.mode
+    {
+        config.sample_rate = sample_rate;
+    }
 
     // Warn if amplitude is too high (can cause audible distortion)
     if config.amplitude > 0.4 {
@@ -105,88 +636,415 @@ fn main() -> anyhow::Result<()> {
         eprintln!("   The signal remains highly effective at lower amplitudes.\n");
     }
 
-    // Validate that all tones will be in ultrasonic range
-    let min_freq = config.frequency - (config.num_tones as f32 / 2.0) * config.frequency_spread;
-    let max_freq = config.frequency + (config.num_tones as f32 / 2.0) * config.frequency_spread;
+    // Keep all tones in the ultrasonic range unless --strict asks to fail instead
+    let (min_freq, _) = config.tone_range();
+    if !cli.strict {
+        if let Some(original) = config.adjust_to_ultrasonic() {
+            eprintln!("⚠️  Warning: Some tones below 20kHz (audible range)!");
+            eprintln!("   Lowest tone: {:.0} Hz", min_freq);
+            eprintln!(
+                "   Adjusting frequency from {:.0} Hz to {:.0} Hz to keep all tones above 20kHz...",
+                original, config.frequency
+            );
+            eprintln!("   Use --strict to make this an error instead.\n");
+        }
+    }
+    // The jammer asks the device for a rate high enough for the signal and
+    // validates against the one it gets; check everything else here
+    if opens_output {
+        config.sample_rate = config.sample_rate.max(config.required_sample_rate());
+    }
+    config.validate().context("Invalid signal configuration")?;
+    for warning in config.warnings() {
+        eprintln!("⚠️  Warning: {}\n", warning);
+    }
+    if opens_output || matches!(cli.mode, Mode::Generate { .. } | Mode::Mix { .. }) {
+        check_peak_headroom(&config, cli.strict)?;
+    }
+    if opens_output {
+        report_audibility(&config);
+    }
 
-    if min_freq < 20000.0 {
-        eprintln!("⚠️  Warning: Some tones below 20kHz (audible range)!");
-        eprintln!("   Lowest tone: {:.0} Hz", min_freq);
-        eprintln!("   Adjusting to keep all tones above 20kHz...\n");
+    let ramp = cli
+        .amplitude_ramp
+        .map(Duration::try_from_secs_f32)
+        .transpose()
+        .context("Invalid --amplitude-ramp")?;
 
-        // Adjust configuration to keep all tones ultrasonic
-        let needed_frequency =
-            20000.0 + (config.num_tones as f32 / 2.0) * config.frequency_spread + 500.0;
-        config.frequency = needed_frequency;
+    match cli.mode {
+        Mode::Speaker {
+            duration,
+            channels,
+            profile,
+            realtime,
+            exclusive,
+        } => run_speaker_jammer(
+            config,
+            JamOptions {
+                duration: duration.map(Duration::from_secs),
+                ramp,
+                profile,
+                realtime,
+            },
+            &channels,
+            exclusive,
+        )?,
+        Mode::System {
+            mix_ratio,
+            mix_curve,
+            normalize_input,
+            duration,
+            capture_source,
+            profile,
+            realtime,
+        } => run_system_jammer(
+            config,
+            mix_ratio,
+            mix_curve.into(),
+            normalize_input,
+            JamOptions {
+                duration: duration.map(Duration::from_secs),
+                ramp,
+                profile,
+                realtime,
+            },
+            capture_source.as_deref(),
+        )?,
+        Mode::Daemon { command } => run_daemon_command(command, config, ramp)?,
+        Mode::Install {
+            keep_default,
+            non_interactive: true,
+            ..
+        } => run_install_unattended(!keep_default),
+        Mode::Install {
+            clean,
+            keep_default,
+            non_interactive: false,
+        } => run_install(clean, keep_default)?,
+        Mode::Uninstall => run_uninstall()?,
+        Mode::Analyze {
+            file,
+            #[cfg(feature = "plot")]
+            plot,
+        } => {
+            // Drawn first, as a failing analysis exits
+            #[cfg(feature = "plot")]
+            if let Some(out) = plot {
+                let (sample_rate, samples) = camouflage_core::analysis::read_mono_wav(&file)?;
+                camouflage_core::plot::plot_spectrum(&samples, sample_rate, &out)?;
+                println!("🖼  Spectrum plot written to {}\n", out.display());
+            }
+            run_analyze(&file)?
+        }
+        Mode::Generate {
+            out,
+            duration,
+            mix,
+            mix_ratio,
+            mix_curve,
+            normalize_input,
+            dither,
+            ..
+        } => run_generate(
+            &config,
+            &out,
+            duration,
+            mix.as_deref(),
+            mix_ratio,
+            mix_curve.into(),
+            normalize_input,
+            dither,
+        )?,
+        Mode::Mix {
+            in_dir,
+            out_dir,
+            mix_ratio,
+            mix_curve,
+            normalize_input,
+            dither,
+        } => run_mix(
+            &config,
+            &in_dir,
+            &out_dir,
+            mix_ratio,
+            mix_curve.into(),
+            normalize_input,
+            dither,
+        )?,
+        Mode::Calibrate {
+            output,
+            input,
+            target_db,
+            manual,
+        } => run_calibrate(&config, output, input, target_db, manual)?,
+        Mode::Response { output, input } => run_response(output, input)?,
+        Mode::Devices { test } => run_devices(test)?,
+        Mode::Presets { .. } | Mode::Selftest => {
+            unreachable!("handled before building the config")
+        }
+        #[cfg(feature = "fleet")]
+        Mode::Fleet {
+            command,
+            token,
+            wait,
+        } => run_fleet(command, &config, token, wait)?,
     }
 
-    if max_freq > 24000.0 && config.sample_rate == 48000 {
-        eprintln!("⚠️  Warning: Some tones near Nyquist limit (may alias)!");
-        eprintln!("   Highest tone: {:.0} Hz", max_freq);
-        eprintln!("   Consider reducing frequency spread.\n");
+    Ok(())
+}
+
+/// Build the signal configuration from the optional preset, config file and CLI flags
+///
+/// Without `--preset` or `--config` every flag (including its default) is
+/// used. Otherwise the preset is the base, the config file overrides the
+/// fields it lists, and only flags given explicitly on the command line
+/// override the result.
+fn build_signal_config(cli: &Cli, matches: &ArgMatches) -> anyhow::Result<SignalConfig> {
+    if cli.preset.is_none() && cli.config.is_none() {
+        let mut config = SignalConfig {
+            frequency: cli.frequency,
+            sample_rate: 48000, // Replaced by the output device's rate
+            amplitude: cli.amplitude,
+            master_gain: cli.master_gain,
+            num_tones: cli.num_tones,
+            frequency_spread: cli.spread,
+            spread_dither_depth: cli.spread_dither,
+            spread_dither_rate_hz: cli.spread_dither_rate,
+            tone_distribution: cli.tone_distribution.into(),
+            waveform: cli.waveform.into(),
+            frequency_tilt_db_per_octave: cli.tilt,
+            nyquist_guard_hz: cli.nyquist_guard,
+            safety_highpass: cli.safety_highpass,
+            hop_set: cli.hop.clone(),
+            hop_interval_ms: cli.hop_interval,
+            channel_phase_offsets: cli.channel_phase.clone(),
+            seed: cli.seed,
+            pilot_id: cli.pilot_id,
+            ..Default::default()
+        };
+        apply_tone_offsets(&mut config, &cli.tone_offsets);
+        return Ok(config);
     }
 
-    match cli.mode {
-        Mode::Speaker => run_speaker_jammer(config)?,
-        Mode::System { mix_ratio } => run_system_jammer(config, mix_ratio)?,
-        Mode::Daemon { command } => run_daemon_command(command, config)?,
-        Mode::Install => run_install()?,
+    let mut config = match &cli.preset {
+        Some(name) => SignalConfig::preset(name).with_context(|| {
+            format!(
+                "Unknown preset '{}'; list them with 'camouflage presets'",
+                name
+            )
+        })?,
+        None => SignalConfig::default(),
+    };
+    if let Some(path) = &cli.config {
+        config = load_config_file(path, &config)?;
+        info!("Loaded signal config from {}", path.display());
     }
+    let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
 
+    if explicit("frequency") {
+        config.frequency = cli.frequency;
+    }
+    if explicit("amplitude") {
+        config.amplitude = cli.amplitude;
+    }
+    if explicit("master_gain") {
+        config.master_gain = cli.master_gain;
+    }
+    if explicit("num_tones") {
+        config.num_tones = cli.num_tones;
+    }
+    if explicit("spread") {
+        config.frequency_spread = cli.spread;
+    }
+    if explicit("spread_dither") {
+        config.spread_dither_depth = cli.spread_dither;
+    }
+    if explicit("spread_dither_rate") {
+        config.spread_dither_rate_hz = cli.spread_dither_rate;
+    }
+    if explicit("tone_distribution") {
+        config.tone_distribution = cli.tone_distribution.into();
+    }
+    apply_tone_offsets(&mut config, &cli.tone_offsets);
+    if explicit("waveform") {
+        config.waveform = cli.waveform.into();
+    }
+    if explicit("tilt") {
+        config.frequency_tilt_db_per_octave = cli.tilt;
+    }
+    if explicit("nyquist_guard") {
+        config.nyquist_guard_hz = cli.nyquist_guard;
+    }
+    if explicit("safety_highpass") {
+        config.safety_highpass = true;
+    }
+    if explicit("hop") {
+        config.hop_set = cli.hop.clone();
+    }
+    if explicit("hop_interval") {
+        config.hop_interval_ms = cli.hop_interval;
+    }
+    if explicit("channel_phase") {
+        config.channel_phase_offsets = cli.channel_phase.clone();
+    }
+    if cli.seed.is_some() {
+        config.seed = cli.seed;
+    }
+    if cli.pilot_id.is_some() {
+        config.pilot_id = cli.pilot_id;
+    }
+
+    Ok(config)
+}
+
+/// Switch to a custom distribution with one tone per offset, if any were given
+fn apply_tone_offsets(config: &mut SignalConfig, offsets: &[f32]) {
+    if !offsets.is_empty() {
+        config.num_tones = offsets.len();
+        config.tone_distribution = ToneDistribution::Custom(offsets.to_vec());
+    }
+}
+
+/// Load a `SignalConfig` from a `.json` file, or TOML for any other extension
+///
+/// Fields the file leaves out keep their values from `base`.
+fn load_config_file(path: &Path, base: &SignalConfig) -> anyhow::Result<SignalConfig> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file {}", path.display()))?;
+
+    let is_json = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+
+    // Parse strictly first so type errors point at the file
+    let file: serde_json::Value = if is_json {
+        serde_json::from_str::<SignalConfig>(&contents)
+            .with_context(|| format!("Malformed JSON config file {}", path.display()))?;
+        serde_json::from_str(&contents)?
+    } else {
+        toml::from_str::<SignalConfig>(&contents)
+            .with_context(|| format!("Malformed TOML config file {}", path.display()))?;
+        toml::from_str(&contents)?
+    };
+
+    let mut merged = serde_json::to_value(base)?;
+    if let (Some(merged), Some(file)) = (merged.as_object_mut(), file.as_object()) {
+        merged.extend(file.clone());
+    }
+    Ok(serde_json::from_value(merged)?)
+}
+
+/// Open the daemon log for appending, readable only by the user
+#[cfg(unix)]
+fn open_log_file(path: &Path) -> anyhow::Result<std::fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {}", path.display()))
+}
+
+/// Send stdout and stderr to `log` and read stdin from `/dev/null`
+#[cfg(unix)]
+fn redirect_output(log: &std::fs::File) -> anyhow::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let null = std::fs::File::open("/dev/null").context("Failed to open /dev/null")?;
+    for (fd, target) in [
+        (null.as_raw_fd(), libc::STDIN_FILENO),
+        (log.as_raw_fd(), libc::STDOUT_FILENO),
+        (log.as_raw_fd(), libc::STDERR_FILENO),
+    ] {
+        if unsafe { libc::dup2(fd, target) } < 0 {
+            return Err(io::Error::last_os_error()).context("Failed to redirect output");
+        }
+    }
     Ok(())
 }
 
-fn run_daemon_command(command: DaemonCommand, config: SignalConfig) -> anyhow::Result<()> {
-    match command {
-        DaemonCommand::Start { mode } => {
-            if is_running() {
-                println!("❌ Daemon is already running");
-                println!("   Use 'camouflage daemon stop' to stop it first");
-                return Ok(());
+/// Detach from the terminal as a classic Unix daemon
+///
+/// Forks twice with a `setsid` in between, so the daemon has no controlling
+/// terminal and can never acquire one, moves to `/` and sends its output to
+/// `log`. Returns the daemon's PID in the original process, which should then
+/// exit, and `None` in the daemon.
+#[cfg(unix)]
+fn daemonize(log: &std::fs::File) -> anyhow::Result<Option<u32>> {
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    // The daemon reports its PID back through the pipe once it is set up
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error()).context("Failed to create pipe");
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()).context("Failed to fork process"),
+        0 => unsafe {
+            libc::close(read_fd);
+            if libc::setsid() < 0 {
+                libc::_exit(1);
             }
-
-            println!("🚀 Starting daemon in {} mode...", mode);
-
-            // Daemonize the process
-            #[cfg(unix)]
-            {
-                unsafe {
-                    let pid = libc::fork();
-                    if pid < 0 {
-                        anyhow::bail!("Failed to fork process");
-                    }
-                    if pid > 0 {
-                        // Parent process
-                        println!("✓ Daemon started (PID: {})", pid);
-                        return Ok(());
-                    }
-                    // Child process continues
-                    libc::setsid();
-                }
+            // The session leader exits, so the daemon cannot reacquire a terminal
+            match libc::fork() {
+                -1 => libc::_exit(1),
+                0 => {}
+                _ => libc::_exit(0),
             }
 
-            // Save PID
-            save_pid()?;
-
-            // Run the jammer
-            if mode == "speaker" {
-                let mut jammer = SpeakerJammer::new(config)?;
-                jammer.start()?;
+            if std::env::set_current_dir("/").is_err() || redirect_output(log).is_err() {
+                libc::_exit(1);
+            }
 
-                // Run forever
-                loop {
-                    std::thread::sleep(std::time::Duration::from_secs(60));
-                }
-            } else {
-                let mut jammer = SystemJammer::new(config, 0.5)?;
-                jammer.start()?;
+            let mut pipe = File::from_raw_fd(write_fd);
+            pipe.write_all(&std::process::id().to_ne_bytes())?;
+            Ok(None)
+        },
+        child => {
+            let mut pipe = unsafe {
+                libc::close(write_fd);
+                File::from_raw_fd(read_fd)
+            };
+            unsafe {
+                libc::waitpid(child, std::ptr::null_mut(), 0);
+            }
 
-                // Run forever
-                loop {
-                    std::thread::sleep(std::time::Duration::from_secs(60));
-                }
+            let mut pid = [0; 4];
+            match pipe.read_exact(&mut pid) {
+                Ok(()) => Ok(Some(u32::from_ne_bytes(pid))),
+                Err(_) => anyhow::bail!("Daemon exited during startup"),
             }
         }
+    }
+}
+
+/// Log the outcome of restoring the daemon's saved signal state
+fn resume_signal(result: Result<bool, CamouflageError>, state_file: &Path) {
+    match result {
+        Ok(true) => info!("Resumed signal state from {}", state_file.display()),
+        Ok(false) => {}
+        Err(e) => warn!("Ignoring unreadable signal state: {}", e),
+    }
+}
+
+fn run_daemon_command(
+    command: DaemonCommand,
+    config: SignalConfig,
+    ramp: Option<Duration>,
+) -> anyhow::Result<()> {
+    match command {
+        DaemonCommand::Start(args) => run_daemon_start(args, config, ramp)?,
 
         DaemonCommand::Stop => {
             println!("🛑 Stopping daemon...");
@@ -194,14 +1052,28 @@ fn run_daemon_command(command: DaemonCommand, config: SignalConfig) -> anyhow::R
             println!("✓ Daemon stopped");
         }
 
-        DaemonCommand::Status => {
-            let status = get_status();
-            println!("Daemon status: {}", status);
+        DaemonCommand::Restart(args) => {
+            println!("🔄 Restarting daemon...");
+            stop_daemon()?;
+            run_daemon_start(args, config, ramp)?
+        }
+
+        DaemonCommand::Status { json } => {
+            let status = get_daemon_status();
+            if json {
+                println!("{}", serde_json::to_string(&status)?);
+            } else {
+                println!("Daemon status: {}", status);
+            }
         }
 
-        DaemonCommand::Enable => {
+        DaemonCommand::Enable { log_file } => {
             println!("⚙️  Enabling auto-start...");
-            install_autostart()?;
+            let mut daemon = DaemonConfig::default();
+            if let Some(log_file) = log_file {
+                daemon.log_file = std::path::absolute(log_file)?;
+            }
+            install_autostart(&daemon)?;
             println!("✓ Auto-start enabled");
         }
 
@@ -210,40 +1082,871 @@ fn run_daemon_command(command: DaemonCommand, config: SignalConfig) -> anyhow::R
             uninstall_autostart()?;
             println!("✓ Auto-start disabled");
         }
+
+        DaemonCommand::Verify { input } => run_verify(input)?,
+
+        DaemonCommand::Logs { follow, log_file } => {
+            let log_file = log_file.unwrap_or_else(|| DaemonConfig::default().log_file);
+            show_daemon_logs(&log_file, follow)?
+        }
     }
 
     Ok(())
 }
 
-fn run_install() -> anyhow::Result<()> {
-    println!("🔧 Installing system audio device...\n");
+/// Start the daemon, detaching unless `args.foreground` is set
+fn run_daemon_start(
+    args: StartArgs,
+    config: SignalConfig,
+    ramp: Option<Duration>,
+) -> anyhow::Result<()> {
+    let StartArgs {
+        mode,
+        watchdog,
+        foreground,
+        log_file,
+        realtime,
+        #[cfg(feature = "fleet")]
+        fleet_port,
+        #[cfg(feature = "fleet")]
+        fleet_token,
+    } = args;
+
+    // Another start may be racing this one; whoever locks first wins
+    let Some(_lock) = lock_daemon()? else {
+        println!("❌ Daemon is already running");
+        println!("   Use 'camouflage daemon stop' to stop it first");
+        std::process::exit(1);
+    };
 
-    #[cfg(target_os = "macos")]
-    {
-        use camouflage_core::platform;
-        let audio = platform::get_system_audio();
-        audio.create_virtual_device()?;
+    println!("🚀 Starting daemon in {} mode...", mode);
+
+    #[cfg(unix)]
+    if !foreground || log_file.is_some() {
+        let log_path = log_file.unwrap_or_else(|| DaemonConfig::default().log_file);
+        let log = open_log_file(&log_path)?;
+        if foreground {
+            redirect_output(&log)?;
+        } else if let Some(pid) = daemonize(&log)? {
+            println!("✓ Daemon started (PID: {})", pid);
+            println!("   Logging to {}", log_path.display());
+            return Ok(());
+        }
     }
+    #[cfg(not(unix))]
+    let _ = (foreground, log_file);
 
-    #[cfg(target_os = "linux")]
-    {
-        use camouflage_core::platform;
-        let audio = platform::get_system_audio();
-        audio.create_virtual_device()?;
+    // Save PID
+    save_pid()?;
+
+    // Threads do not survive the fork, so the listener starts here
+    #[cfg(feature = "fleet")]
+    let fleet = match fleet_port {
+        Some(port) => Some(start_fleet_server(port, fleet_token, &mode)?),
+        None => None,
+    };
+    #[cfg(not(feature = "fleet"))]
+    let fleet = ();
+
+    // Run the jammer, resuming the signal where a previous daemon left off
+    let state_file = get_state_file()?;
+    let watchdog = watchdog.map(Duration::from_secs);
+    // Without an output device the daemon keeps running on the null output
+    if mode == "speaker" {
+        let mut jammer = SpeakerJammer::new_or_null(config)?;
+        if realtime {
+            jammer = jammer.with_realtime_priority();
+        }
+        resume_signal(jammer.restore_state(&state_file), &state_file);
+        if let Some(timeout) = watchdog {
+            jammer.enable_watchdog(timeout);
+        }
+        if let Some(ramp) = ramp {
+            jammer.set_amplitude_ramp(ramp);
+        }
+        jammer.start()?;
+
+        while wait_for_next_save(&fleet, &mode, &|| jammer.signal_config(), &|c| {
+            jammer.update_config(c)
+        }) {
+            if let Err(e) = jammer.check_watchdog() {
+                warn!("Watchdog failed to rebuild the stream: {}", e);
+            }
+            if let Err(e) = jammer.save_state(&state_file) {
+                warn!("Failed to save signal state: {}", e);
+            }
+        }
+        jammer.stop();
+    } else {
+        let mut jammer = SystemJammer::new_or_null(config, 0.5)?;
+        if realtime {
+            jammer = jammer.with_realtime_priority();
+        }
+        resume_signal(jammer.restore_state(&state_file), &state_file);
+        if let Some(timeout) = watchdog {
+            jammer.enable_watchdog(timeout);
+        }
+        if let Some(ramp) = ramp {
+            jammer.set_amplitude_ramp(ramp);
+        }
+        jammer.start()?;
+
+        while wait_for_next_save(&fleet, &mode, &|| jammer.signal_config(), &|c| {
+            jammer.update_config(c)
+        }) {
+            if let Err(e) = jammer.check_watchdog() {
+                warn!("Watchdog failed to rebuild the stream: {}", e);
+            }
+            if let Err(e) = jammer.save_state(&state_file) {
+                warn!("Failed to save signal state: {}", e);
+            }
+        }
+        jammer.stop();
+    }
+    info!("Stopped by a fleet command");
+    remove_pid()?;
+    Ok(())
+}
+
+/// Wait until the signal state is due to be saved again
+///
+/// Fleet commands that arrive meanwhile are answered right away. Returns
+/// false once the fleet has asked the daemon to stop.
+#[cfg(not(feature = "fleet"))]
+fn wait_for_next_save(
+    _fleet: &(),
+    _mode: &str,
+    _config: &dyn Fn() -> SignalConfig,
+    _update: &dyn Fn(SignalConfig) -> Result<(), CamouflageError>,
+) -> bool {
+    std::thread::sleep(STATE_SAVE_INTERVAL);
+    true
+}
+
+/// Wait until the signal state is due to be saved again
+///
+/// Fleet commands that arrive meanwhile are answered right away. Returns
+/// false once the fleet has asked the daemon to stop.
+#[cfg(feature = "fleet")]
+fn wait_for_next_save(
+    fleet: &Option<camouflage_core::fleet::FleetServer>,
+    mode: &str,
+    config: &dyn Fn() -> SignalConfig,
+    update: &dyn Fn(SignalConfig) -> Result<(), CamouflageError>,
+) -> bool {
+    use camouflage_core::fleet::{Command, MemberStatus, Response};
+    use std::time::Instant;
+
+    let Some(fleet) = fleet else {
+        std::thread::sleep(STATE_SAVE_INTERVAL);
+        return true;
+    };
+    let status = || MemberStatus {
+        name: host_name(),
+        mode: mode.to_string(),
+        config: config(),
+    };
+
+    let deadline = Instant::now() + STATE_SAVE_INTERVAL;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Some(incoming) = fleet.recv_timeout(remaining) else {
+            break;
+        };
+        match incoming.command.clone() {
+            Command::Status => incoming.reply(Response::ok(status())),
+            Command::Set { config } => {
+                info!("Retuning for a fleet command");
+                match update(config) {
+                    Ok(()) => incoming.reply(Response::ok(status())),
+                    Err(e) => incoming.reply(Response::error(e.to_string())),
+                }
+            }
+            Command::Stop => {
+                incoming.reply(Response::ok(status()));
+                return false;
+            }
+        }
     }
+    true
+}
 
-    #[cfg(target_os = "windows")]
+#[cfg(feature = "fleet")]
+fn start_fleet_server(
+    port: u16,
+    token: Option<String>,
+    mode: &str,
+) -> anyhow::Result<camouflage_core::fleet::FleetServer> {
+    let token = fleet_token(token, "--fleet-token")?;
+    let server = camouflage_core::fleet::FleetServer::start(&host_name(), token, port)?;
+    info!(
+        "Accepting fleet commands on port {} in {} mode",
+        server.port(),
+        mode
+    );
+    Ok(server)
+}
+
+/// Token from `flag`, else from the environment
+#[cfg(feature = "fleet")]
+fn fleet_token(token: Option<String>, flag: &str) -> anyhow::Result<String> {
+    token
+        .or_else(|| std::env::var(FLEET_TOKEN_ENV).ok())
+        .filter(|token| !token.is_empty())
+        .with_context(|| format!("Set a fleet token with {} or ${}", flag, FLEET_TOKEN_ENV))
+}
+
+/// Name this machine is known by on the network
+#[cfg(feature = "fleet")]
+fn host_name() -> String {
+    #[cfg(unix)]
     {
-        use camouflage_core::platform;
-        let audio = platform::get_system_audio();
-        audio.create_virtual_device()?;
+        let mut buf = [0u8; 256];
+        // SAFETY: the buffer outlives the call and its length is passed along
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
     }
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "camouflage".to_string())
+}
 
+#[cfg(feature = "fleet")]
+fn run_fleet(
+    command: FleetCommand,
+    config: &SignalConfig,
+    token: Option<String>,
+    wait: f32,
+) -> anyhow::Result<()> {
+    use camouflage_core::fleet::{self, Command};
+
+    let token = fleet_token(token, "--token")?;
+    let wait = Duration::try_from_secs_f32(wait).context("Invalid --wait")?;
+    let (command, json) = match command {
+        FleetCommand::Status { json } => (Command::Status, json),
+        FleetCommand::Set => (
+            Command::Set {
+                config: config.clone(),
+            },
+            false,
+        ),
+        FleetCommand::Stop => (Command::Stop, false),
+    };
+
+    if !json {
+        println!("🔍 Looking for daemons...");
+    }
+    let members = fleet::discover(wait)?;
+    if members.is_empty() {
+        if json {
+            println!("[]");
+        } else {
+            println!("❌ No daemons found on the network");
+        }
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    let mut statuses = Vec::new();
+    for member in &members {
+        let result = fleet::send(member.addr, &token, command.clone()).and_then(|response| {
+            match response.error {
+                Some(error) if !response.ok => Err(anyhow::anyhow!(error)),
+                _ => Ok(response.status),
+            }
+        });
+        match result {
+            Ok(status) if json => statuses.push(status),
+            Ok(Some(status)) => {
+                let (lowest, highest) = status.config.tone_range();
+                println!(
+                    "✓ {} ({}): {} mode, {} tones at {:.0}-{:.0} Hz, amplitude {}",
+                    member.name,
+                    member.addr,
+                    status.mode,
+                    status.config.num_tones,
+                    lowest,
+                    highest,
+                    status.config.amplitude
+                );
+            }
+            Ok(None) => println!("✓ {} ({})", member.name, member.addr),
+            Err(e) => {
+                failed += 1;
+                eprintln!("❌ {} ({}): {:#}", member.name, member.addr, e);
+            }
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string(&statuses)?);
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} daemons did not respond", failed, members.len());
+    }
+    Ok(())
+}
+
+fn run_verify(input: Option<String>) -> anyhow::Result<()> {
+    use camouflage_core::platform::{self, MIN_EMISSION_SNR_DB};
+
+    if !is_running() {
+        println!("❌ FAIL: daemon is not running");
+        println!("   Start it with: camouflage daemon start");
+        std::process::exit(1);
+    }
+    // Listen for what the daemon is playing, not what these flags say
+    let Some(config) = SignalGenerator::saved_config(&get_state_file()?)? else {
+        anyhow::bail!(
+            "The daemon has not saved its signal yet; try again in {} s",
+            STATE_SAVE_INTERVAL.as_secs()
+        );
+    };
+    let Some(device) = platform::input_device(input.as_deref())? else {
+        anyhow::bail!("No input device available to listen with; pass --input <DEVICE>");
+    };
+
+    let (lowest, highest) = config.occupied_band();
+    println!(
+        "🎤 Listening for {:.1} s for the signal at {:.0}-{:.0} Hz...",
+        VERIFY_DURATION.as_secs_f32(),
+        lowest,
+        highest
+    );
+    let emission = platform::verify_emission(&device, &config, VERIFY_DURATION)?;
+    println!(
+        "  Signal band: {:.1} dBFS, noise floor: {:.1} dBFS ({:.1} dB above)",
+        emission.level.band_dbfs,
+        emission.level.floor_dbfs,
+        emission.level.snr_db()
+    );
+    println!();
+
+    if emission.passed() {
+        println!("✓ PASS: the jamming signal is reaching the microphone");
+        Ok(())
+    } else {
+        println!(
+            "❌ FAIL: the signal band is less than {:.0} dB above the noise floor",
+            MIN_EMISSION_SNR_DB
+        );
+        println!("   The speakers may roll off before the signal frequency, or the volume is");
+        println!("   too low. Try a lower --frequency or run 'camouflage calibrate'.");
+        std::process::exit(1);
+    }
+}
+
+fn run_presets(json: bool) -> anyhow::Result<()> {
+    let presets = SignalConfig::presets();
+    if json {
+        let entries: Vec<serde_json::Value> = presets
+            .iter()
+            .map(|(name, config, description)| {
+                serde_json::json!({
+                    "name": name,
+                    "description": description,
+                    "config": config,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    println!("Signal presets:\n");
+    for (name, config, description) in presets {
+        let (lowest, highest) = config.occupied_band();
+        let waveform = serde_json::to_value(config.waveform)?;
+        println!("▶ {}", name);
+        println!("    {}", description);
+        println!(
+            "    {} {} tone(s), {:.0}-{:.0} Hz, amplitude {}",
+            config.num_tones,
+            waveform.as_str().unwrap_or_default(),
+            lowest,
+            highest,
+            config.amplitude
+        );
+        if !config.hop_set.is_empty() {
+            println!(
+                "    Hops every {:.0} ms among {:?} Hz",
+                config.hop_interval_ms, config.hop_set
+            );
+        }
+        if config.safety_highpass {
+            println!("    Safety high-pass at 19 kHz");
+        }
+    }
+
+    println!("\nUse one with: camouflage --preset <NAME> speaker");
+    Ok(())
+}
+
+fn run_selftest() -> anyhow::Result<()> {
+    use camouflage_core::selftest::run_self_test;
+
+    println!(
+        "🔬 Camouflage {} self-test (offline, no audio device)\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    let checks = run_self_test();
+    println!("{:<12} {:<24} {:<8} Detail", "Preset", "Check", "Result");
+    for check in &checks {
+        println!(
+            "{:<12} {:<24} {:<8} {}",
+            check.preset,
+            check.check,
+            if check.passed { "✓ pass" } else { "❌ FAIL" },
+            check.detail
+        );
+    }
+
+    let failed = checks.iter().filter(|c| !c.passed).count();
+    if failed > 0 {
+        println!("\n❌ {} of {} checks failed", failed, checks.len());
+        std::process::exit(1);
+    }
+    println!("\n✓ All {} checks passed", checks.len());
+    Ok(())
+}
+
+fn run_install(clean: bool, keep_default: bool) -> anyhow::Result<()> {
+    if clean {
+        return run_install_clean();
+    }
+
+    println!("🔧 Installing system audio device...\n");
+
+    let audio = camouflage_core::platform::get_system_audio();
+    audio.create_virtual_device()?.keep();
+    if keep_default {
+        return Ok(());
+    }
+
+    // A failed switch leaves a working device, so it only warns
+    match camouflage_core::platform::switch_default_output(audio.as_ref()) {
+        Ok(Some(previous)) => {
+            println!(
+                "✓ Default output set to {} (was {})",
+                audio.virtual_output_name(),
+                previous
+            );
+            println!("   `camouflage uninstall` switches back");
+        }
+        Ok(None) => println!(
+            "✓ {} is already the default output",
+            audio.virtual_output_name()
+        ),
+        Err(e) => eprintln!("⚠️  Could not change the default output: {:#}", e),
+    }
+
+    Ok(())
+}
+
+fn run_install_unattended(switch_default: bool) {
+    use camouflage_core::platform;
+
+    println!("🔧 Installing system audio device (non-interactive)...\n");
+
+    let audio = platform::get_system_audio();
+    let steps = platform::install_unattended(audio.as_ref(), switch_default);
+    for step in &steps {
+        match &step.result {
+            Ok(detail) => println!("✓ {}: {}", step.name, detail),
+            Err(e) => println!("❌ {}: {:#}", step.name, e),
+        }
+    }
+
+    if steps.iter().any(|step| step.result.is_err()) {
+        std::process::exit(1);
+    }
+    println!("\n✓ System mode is set up");
+}
+
+fn run_uninstall() -> anyhow::Result<()> {
+    use camouflage_core::platform;
+
+    let audio = platform::get_system_audio();
+    match platform::restore_default_output(audio.as_ref())? {
+        Some(previous) => println!("✓ Default output restored to {}", previous),
+        None => println!("No default output was saved by install; leaving it alone"),
+    }
+    audio.remove_virtual_device()
+}
+
+#[cfg(target_os = "linux")]
+fn run_install_clean() -> anyhow::Result<()> {
+    use camouflage_core::platform::linux::LinuxSystemAudio;
+
+    println!("🧹 Removing stray camouflage audio modules...");
+    match LinuxSystemAudio::new().remove_stray_modules()? {
+        0 => println!("✓ No stray modules found"),
+        removed => println!("✓ Removed {} module(s)", removed),
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn run_install_clean() -> anyhow::Result<()> {
+    println!("Nothing to clean: only Linux loads audio modules for system mode");
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_generate(
+    config: &SignalConfig,
+    out: &Path,
+    duration: f32,
+    mix: Option<&Path>,
+    mix_ratio: f32,
+    mix_curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+) -> anyhow::Result<()> {
+    let log = match mix {
+        Some(input) => {
+            println!(
+                "🎛️  Mixing ultrasonic signal into {} (mix ratio {})...",
+                input.display(),
+                mix_ratio
+            );
+            wav::mix_ultrasonic(
+                input,
+                out,
+                config,
+                mix_ratio,
+                mix_curve,
+                normalize_input,
+                dither,
+            )?
+        }
+        None => {
+            if !duration.is_finite() || duration <= 0.0 {
+                anyhow::bail!("Duration must be positive, got {}", duration);
+            }
+            println!(
+                "🎛️  Generating {} s of ultrasonic signal at {} Hz...",
+                duration, config.sample_rate
+            );
+            wav::write_ultrasonic(out, config, Duration::from_secs_f32(duration), dither)?
+        }
+    };
+
+    println!("✓ Wrote {}", out.display());
+    let sidecar = log.write_sidecar(out)?;
+    println!(
+        "🗒  {} signal events written to {}",
+        log.events.len(),
+        sidecar.display()
+    );
+    Ok(())
+}
+
+fn run_mix(
+    config: &SignalConfig,
+    in_dir: &Path,
+    out_dir: &Path,
+    mix_ratio: f32,
+    mix_curve: MixCurve,
+    normalize_input: bool,
+    dither: bool,
+) -> anyhow::Result<()> {
+    println!(
+        "🎛️  Mixing ultrasonic signal into recordings in {} (mix ratio {})...\n",
+        in_dir.display(),
+        mix_ratio
+    );
+
+    let outcomes = wav::mix_directory(
+        in_dir,
+        out_dir,
+        config,
+        mix_ratio,
+        mix_curve,
+        normalize_input,
+        dither,
+    )?;
+    if outcomes.is_empty() {
+        println!("No WAV or FLAC files found in {}", in_dir.display());
+        return Ok(());
+    }
+
+    let mut mixed = 0;
+    for outcome in &outcomes {
+        match &outcome.result {
+            Ok(()) => {
+                mixed += 1;
+                println!(
+                    "  ✓ {} -> {}",
+                    outcome.input.display(),
+                    outcome.output.display()
+                );
+            }
+            Err(e) => eprintln!("  ⚠️  Skipped {}: {:#}", outcome.input.display(), e),
+        }
+    }
+
+    println!("\n✓ Mixed {} of {} files", mixed, outcomes.len());
+    if mixed == 0 {
+        anyhow::bail!("No files could be mixed");
+    }
+    Ok(())
+}
+
+fn run_analyze(file: &Path) -> anyhow::Result<()> {
+    use camouflage_core::analysis::{self, AUDIBLE_LIMIT_HZ, MAX_AUDIBLE_ENERGY_FRACTION};
+
+    let report = analysis::analyze_wav(file)?;
+
+    println!("📊 Spectral analysis: {}\n", file.display());
+    println!("  Sample rate: {} Hz", report.sample_rate);
+    println!(
+        "  Audible-band energy (< {:.0} Hz): {:.4}%",
+        AUDIBLE_LIMIT_HZ,
+        report.audible_energy_fraction * 100.0
+    );
+    println!("  Spectral centroid: {:.0} Hz", report.spectral_centroid);
+    println!(
+        "  Strongest audible component: {:.1} dB below the peak",
+        -report.audible_peak_db
+    );
+    let peaks: Vec<String> = report
+        .peak_frequencies
+        .iter()
+        .map(|f| format!("{:.0} Hz", f))
+        .collect();
+    println!("  Dominant frequencies: {}", peaks.join(", "));
+    println!();
+
+    if report.is_ultrasonic() {
+        println!("✓ PASS: all significant energy is above 20 kHz");
+        Ok(())
+    } else {
+        println!(
+            "❌ FAIL: more than {}% of the energy is in the audible range",
+            MAX_AUDIBLE_ENERGY_FRACTION * 100.0
+        );
+        println!("   The file may contain audible content or aliasing from the output chain.");
+        std::process::exit(1);
+    }
+}
+
+fn run_calibrate(
+    config: &SignalConfig,
+    output: Option<String>,
+    input: Option<String>,
+    target_db: f32,
+    manual: bool,
+) -> anyhow::Result<()> {
+    use camouflage_core::platform::{self, CalibrationTarget};
+
+    let device_out = platform::output_device(output.as_deref())?;
+    let device_in = if manual {
+        None
+    } else {
+        platform::input_device(input.as_deref())?
+    };
+
+    let target = CalibrationTarget {
+        frequency: config.frequency,
+        level_dbfs: target_db,
+        ..Default::default()
+    };
+
+    let Some(device_in) = device_in else {
+        if !manual {
+            println!("🎤 No input device available; calibrating by ear instead.\n");
+        }
+        return calibrate_by_ear(&device_out, &target);
+    };
+
+    println!(
+        "🎚️  Calibrating with a {:.0} Hz tone until the microphone measures {:.1} dBFS...",
+        target.frequency, target.level_dbfs
+    );
+    println!("   Place the microphone where the jamming needs to reach.\n");
+
+    let calibration = platform::calibrate_amplitude(&device_in, &device_out, &target)?;
+    if calibration.reached_target {
+        println!(
+            "✓ Recommended amplitude: {:.3} (measured {:.1} dBFS)",
+            calibration.amplitude, calibration.level_dbfs
+        );
+        println!(
+            "   Use: camouflage --amplitude {:.3} speaker",
+            calibration.amplitude
+        );
+    } else {
+        println!(
+            "⚠️  Target not reached: {:.1} dBFS at the maximum amplitude {:.2}",
+            calibration.level_dbfs, calibration.amplitude
+        );
+        println!("   Move the microphone closer or use speakers with better ultrasonic response.");
+    }
+    Ok(())
+}
+
+/// Step the tone up until the user reports hearing it, then back off
+fn calibrate_by_ear(
+    device: &camouflage_core::platform::Device,
+    target: &camouflage_core::platform::CalibrationTarget,
+) -> anyhow::Result<()> {
+    use camouflage_core::platform::{self, CalibrationTarget};
+
+    println!(
+        "👂 Playing a {:.0} Hz tone at rising amplitudes. It should stay silent;",
+        target.frequency
+    );
+    println!("   answer 'y' as soon as you hear anything (hiss, whine or clicks).\n");
+
+    let step = CalibrationTarget {
+        step_db: 3.0,
+        ..target.clone()
+    };
+    let mut inaudible = 0.0;
+    for amplitude in step.amplitude_steps() {
+        print!("   Amplitude {:.3}... ", amplitude);
+        io::stdout().flush()?;
+        platform::play_tone(device, target.frequency, amplitude, Duration::from_secs(2))?;
+
+        print!("heard anything? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if answer.trim().eq_ignore_ascii_case("y") {
+            break;
+        }
+        inaudible = amplitude;
+    }
+
+    if inaudible == 0.0 {
+        println!("\n⚠️  Audible even at the lowest amplitude; check the speakers for distortion.");
+        return Ok(());
+    }
+
+    // Back off 6 dB from the loudest inaudible step for headroom
+    let recommended = inaudible / 2.0;
+    println!(
+        "\n✓ Inaudible up to {:.3}. Recommended amplitude: {:.3}",
+        inaudible, recommended
+    );
+    println!("   Use: camouflage --amplitude {:.3} speaker", recommended);
+    Ok(())
+}
+
+fn run_response(output: Option<String>, input: Option<String>) -> anyhow::Result<()> {
+    use camouflage_core::platform::{self, response::ROLLOFF_DB};
+
+    let device_out = platform::output_device(output.as_deref())?;
+    let Some(device_in) = platform::input_device(input.as_deref())? else {
+        anyhow::bail!("No input device available to record the sweep; pass --input <DEVICE>");
+    };
+
+    println!("📈 Playing a quiet sweep from 15 to 25 kHz...");
+    println!("   Point the speaker at the microphone from 30-50 cm and keep the room quiet.\n");
+    let response = platform::measure_hf_response(&device_out, &device_in)?;
+    for point in &response.points {
+        println!("  {:>7.0} Hz  {:>6.1} dB", point.frequency, point.level_db);
+    }
+    println!();
+
+    match response.rolloff_hz {
+        Some(rolloff) => {
+            println!(
+                "⚠️  Response drops {:.0} dB below its 15 kHz level by {:.0} Hz",
+                ROLLOFF_DB, rolloff
+            );
+            if rolloff < MIN_ULTRASONIC_FREQUENCY {
+                println!(
+                    "   This speaker cannot reproduce the signal above {:.0} Hz; use a tweeter \
+                     that reaches higher, or check the microphone does.",
+                    MIN_ULTRASONIC_FREQUENCY
+                );
+            } else {
+                println!(
+                    "   Keep every tone below this, or check the microphone reaches this high."
+                );
+            }
+        }
+        None => {
+            let top = response.points.last().map_or(0.0, |p| p.frequency);
+            println!("✓ No roll-off up to {:.0} Hz", top);
+        }
+    }
+    Ok(())
+}
+
+fn run_devices(test: Option<String>) -> anyhow::Result<()> {
+    use camouflage_core::platform;
+
+    if let Some(name) = test {
+        println!("🔊 Playing 2-second test sweep on '{}'...", name);
+        println!("   You should hear a rising tone that fades out above ~18 kHz.");
+        platform::play_test_sweep(&name, std::time::Duration::from_secs(2), 0.05)?;
+        println!("✓ Test sweep finished");
+        return Ok(());
+    }
+
+    let devices = platform::list_output_devices()?;
+    if devices.is_empty() {
+        println!("No audio output devices found");
+        return Ok(());
+    }
+
+    println!("Audio output devices:\n");
+    for device in &devices {
+        let marker = if device.is_default { " (default)" } else { "" };
+        println!(
+            "{} {}{}",
+            if device.is_default { "▶" } else { " " },
+            device.name,
+            marker
+        );
+
+        if device.configs.is_empty() {
+            println!("    Supported configs unavailable");
+            continue;
+        }
+
+        let channels: Vec<String> = device
+            .channel_counts()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        println!("    Channels: {}", channels.join(", "));
+        for config in &device.configs {
+            println!(
+                "    {} ch: {}-{} Hz",
+                config.channels, config.min_sample_rate, config.max_sample_rate
+            );
+        }
+        println!(
+            "    Ultrasonic capable: {}",
+            if device.supports_ultrasonic() {
+                "✓ yes"
+            } else {
+                "❌ no (needs 48 kHz or higher)"
+            }
+        );
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+    match platform::get_system_audio().list_capture_sources() {
+        Ok(sources) if !sources.is_empty() => {
+            println!("\nCapture sources for system mode (--capture-source):\n");
+            for source in sources {
+                println!("  {}", source);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => println!("\nCapture sources unavailable: {:#}", e),
+    }
+
+    println!("\nTest a device with: camouflage devices --test \"<DEVICE>\"");
     Ok(())
 }
 
 #[cfg(target_os = "macos")]
-fn install_autostart() -> anyhow::Result<()> {
+fn install_autostart(daemon: &DaemonConfig) -> anyhow::Result<()> {
     use std::fs;
     use std::path::PathBuf;
 
@@ -266,18 +1969,22 @@ fn install_autostart() -> anyhow::Result<()> {
         <string>{}</string>
         <string>daemon</string>
         <string>start</string>
+        <string>--foreground</string>
+        <string>--log-file</string>
+        <string>{log}</string>
     </array>
     <key>RunAtLoad</key>
     <true/>
     <key>KeepAlive</key>
     <true/>
     <key>StandardOutPath</key>
-    <string>/tmp/camouflage.log</string>
+    <string>{log}</string>
     <key>StandardErrorPath</key>
-    <string>/tmp/camouflage.err</string>
+    <string>{log}</string>
 </dict>
 </plist>"#,
-        exe_path.display()
+        exe_path.display(),
+        log = daemon.log_file.display()
     );
 
     fs::write(&plist_path, plist_content)?;
@@ -292,7 +1999,7 @@ fn install_autostart() -> anyhow::Result<()> {
 }
 
 #[cfg(target_os = "linux")]
-fn install_autostart() -> anyhow::Result<()> {
+fn install_autostart(daemon: &DaemonConfig) -> anyhow::Result<()> {
     use std::fs;
     use std::path::PathBuf;
 
@@ -310,13 +2017,16 @@ After=sound.target
 
 [Service]
 Type=simple
-ExecStart={} daemon start
+ExecStart={exe} daemon start --foreground --log-file "{log}"
+StandardOutput=append:{log}
+StandardError=append:{log}
 Restart=always
 RestartSec=5
 
 [Install]
 WantedBy=default.target"#,
-        exe_path.display()
+        exe = exe_path.display(),
+        log = daemon.log_file.display()
     );
 
     fs::write(&service_path, service_content)?;
@@ -335,7 +2045,7 @@ WantedBy=default.target"#,
 }
 
 #[cfg(target_os = "windows")]
-fn install_autostart() -> anyhow::Result<()> {
+fn install_autostart(_daemon: &DaemonConfig) -> anyhow::Result<()> {
     println!("⚠️  Windows auto-start:");
     println!("   1. Press Win+R");
     println!("   2. Type: shell:startup");
@@ -344,6 +2054,40 @@ fn install_autostart() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Print the daemon's log, or keep following it
+#[cfg(unix)]
+fn show_daemon_logs(log_file: &Path, follow: bool) -> anyhow::Result<()> {
+    if follow {
+        // -F waits for a log that has not been created yet
+        let status = std::process::Command::new("tail")
+            .args(["-n", "50", "-F"])
+            .arg(log_file)
+            .status()
+            .context("Failed to run tail")?;
+        if !status.success() {
+            anyhow::bail!("tail exited with {}", status);
+        }
+        return Ok(());
+    }
+
+    match std::fs::read_to_string(log_file) {
+        Ok(contents) => print!("{}", contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            println!("No daemon log yet at {}", log_file.display());
+            println!("   The log is created when the daemon is first started.");
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", log_file.display())),
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn show_daemon_logs(_log_file: &Path, _follow: bool) -> anyhow::Result<()> {
+    println!("⚠️  The daemon logs to the console window that started it.");
+    println!("   Windows auto-start does not keep a log file.");
+    Ok(())
+}
+
 #[cfg(target_os = "macos")]
 fn uninstall_autostart() -> anyhow::Result<()> {
     let home = std::env::var("HOME")?;
@@ -390,7 +2134,23 @@ fn uninstall_autostart() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn run_speaker_jammer(config: SignalConfig) -> anyhow::Result<()> {
+/// How the speaker and system modes run, apart from the signal
+struct JamOptions {
+    /// Stop after this long instead of waiting for Enter
+    duration: Option<Duration>,
+    ramp: Option<Duration>,
+    /// Log the generator's CPU use every [`PROFILE_INTERVAL`]
+    profile: bool,
+    /// Run the audio callback at realtime priority
+    realtime: bool,
+}
+
+fn run_speaker_jammer(
+    config: SignalConfig,
+    options: JamOptions,
+    channels: &[usize],
+    exclusive: bool,
+) -> anyhow::Result<()> {
     info!("=== Speaker Jammer Mode ===");
     info!("Frequency: {} Hz", config.frequency);
     info!(
@@ -399,50 +2159,210 @@ fn run_speaker_jammer(config: SignalConfig) -> anyhow::Result<()> {
     );
     info!("Number of tones: {}", config.num_tones);
 
-    let mut jammer = SpeakerJammer::new(config)?;
-    jammer.start()?;
+    let stop = stop_on_ctrlc()?;
+    let mut jammer = if exclusive {
+        SpeakerJammer::new_exclusive(config)?
+    } else {
+        SpeakerJammer::new(config)?
+    };
+    if options.realtime {
+        jammer = jammer.with_realtime_priority();
+    }
+    info!("Output stream: {}", jammer.stream_info());
+    if let Some(seed) = jammer.signal_config().seed {
+        info!("Seed: {} (repeat this run with --seed {})", seed, seed);
+    }
+    if let Some(ramp) = options.ramp {
+        jammer.set_amplitude_ramp(ramp);
+    }
+    if options.profile {
+        jammer.enable_profiling(PROFILE_INTERVAL)?;
+    }
+    if !channels.is_empty() {
+        if channels.contains(&0) {
+            anyhow::bail!("Channels are numbered from 1");
+        }
+        let zero_based: Vec<usize> = channels.iter().map(|c| c - 1).collect();
+        jammer.set_output_channels(&zero_based)?;
+        info!("Playing on channels {:?}", channels);
+    }
 
-    println!("\n✓ Speaker jammer is now active!");
-    println!("  Ultrasonic signal is being transmitted through your speakers.");
-    println!("  This will interfere with nearby microphones.");
-    println!("\nPress Enter to stop...");
+    if let Some(duration) = options.duration {
+        println!("\n✓ Speaker jammer is now active!");
+        print_timed_hint(duration);
+        jammer.start_for(duration, &stop)?;
+    } else {
+        jammer.start()?;
 
-    wait_for_enter();
+        println!("\n✓ Speaker jammer is now active!");
+        println!("  Ultrasonic signal is being transmitted through your speakers.");
+        println!("  This will interfere with nearby microphones.");
+        println!("\nPress Enter or Ctrl-C to stop...");
+
+        wait_for_enter(&stop);
+        jammer.stop();
+    }
 
-    jammer.stop();
     println!("Jammer stopped.");
+    if options.profile {
+        println!("  Generator used {}", jammer.cpu_stats());
+    }
 
     Ok(())
 }
 
-fn run_system_jammer(config: SignalConfig, mix_ratio: f32) -> anyhow::Result<()> {
+fn run_system_jammer(
+    config: SignalConfig,
+    mix_ratio: f32,
+    mix_curve: MixCurve,
+    normalize_input: bool,
+    options: JamOptions,
+    capture_source: Option<&str>,
+) -> anyhow::Result<()> {
     info!("=== System Jammer Mode ===");
     info!("Frequency: {} Hz", config.frequency);
     info!("Amplitude: {}", config.amplitude);
     info!("Number of tones: {}", config.num_tones);
     info!("Mix ratio: {}", mix_ratio);
 
+    let stop = stop_on_ctrlc()?;
     let mut jammer = SystemJammer::new(config, mix_ratio)?;
-    jammer.start()?;
+    if options.realtime {
+        jammer = jammer.with_realtime_priority();
+    }
+    jammer.set_mix_curve(mix_curve);
+    jammer.set_normalize_input(normalize_input);
+    info!("Output stream: {}", jammer.stream_info());
+    if let Some(seed) = jammer.signal_config().seed {
+        info!("Seed: {} (repeat this run with --seed {})", seed, seed);
+    }
+    if let Some(source) = capture_source {
+        jammer.set_capture_source(source)?;
+    }
+    if let Some(ramp) = options.ramp {
+        jammer.set_amplitude_ramp(ramp);
+    }
+    if options.profile {
+        jammer.enable_profiling(PROFILE_INTERVAL)?;
+    }
 
-    println!("\n✓ System jammer is now active!");
-    println!("  Ultrasonic signal is being mixed with system audio.");
-    println!("  This will interfere with remote call recording.");
-    println!(
-        "  Note: Full virtual audio device support requires platform-specific implementation."
-    );
-    println!("\nPress Enter to stop...");
+    if let Some(duration) = options.duration {
+        println!("\n✓ System jammer is now active!");
+        print_timed_hint(duration);
+        jammer.start_for(duration, &stop)?;
+    } else {
+        jammer.start()?;
+
+        println!("\n✓ System jammer is now active!");
+        println!("  Ultrasonic signal is being mixed with system audio.");
+        println!("  This will interfere with remote call recording.");
+        println!(
+            "  Note: Full virtual audio device support requires platform-specific implementation."
+        );
+        println!("\nPress Enter or Ctrl-C to stop...");
 
-    wait_for_enter();
+        wait_for_enter(&stop);
+        jammer.stop();
+    }
 
-    jammer.stop();
     println!("Jammer stopped.");
+    if options.profile {
+        println!("  Generator used {}", jammer.cpu_stats());
+    }
 
     Ok(())
 }
 
-fn wait_for_enter() {
-    let mut input = String::new();
+/// Log the config's audible-band leakage and warn unless it is rated safe
+fn report_audibility(config: &SignalConfig) {
+    use camouflage_core::analysis::{AudibilityRisk, HEARING_THRESHOLD_DBFS};
+
+    let report = config.audibility_report();
+    match report.risk {
+        AudibilityRisk::Safe => info!(
+            "Audibility: safe ({:.0} dB below the hearing threshold)",
+            report.headroom_db
+        ),
+        risk => {
+            eprintln!(
+                "⚠️  Warning: Audibility risk is {}: audible-band leakage peaks at {:.0} dBFS",
+                risk, report.audible_peak_dbfs
+            );
+            eprintln!(
+                "   ({:.0} dB from the assumed {:.0} dBFS hearing threshold).",
+                report.headroom_db, HEARING_THRESHOLD_DBFS
+            );
+            eprintln!("   Try --safety-highpass, a longer crossfade or a higher --frequency.\n");
+        }
+    }
+}
+
+/// Render a moment of the signal and warn if its true peak nears 0 dBFS
+///
+/// With `strict`, a peak above the safe limit is an error instead.
+fn check_peak_headroom(config: &SignalConfig, strict: bool) -> anyhow::Result<()> {
+    use camouflage_core::analysis::{MAX_SAFE_PEAK, TRUE_PEAK_WARN_DBTP};
+
+    let stats = SignalGenerator::new(config.clone()).measure(PEAK_CHECK_DURATION);
+    if strict && stats.exceeds_safe_peak() {
+        anyhow::bail!(
+            "Signal peaks at {:.2} of full scale, above the {} limit ({} tones at amplitude {}). \
+             Lower --amplitude, --master-gain or --tilt",
+            stats.true_peak,
+            MAX_SAFE_PEAK,
+            config.num_tones,
+            config.amplitude
+        );
+    }
+    if stats.near_full_scale() {
+        eprintln!(
+            "⚠️  Warning: True peak is {:.1} dBTP (above {:.0} dBTP); the DAC may clip between samples.",
+            stats.true_peak_dbtp(),
+            TRUE_PEAK_WARN_DBTP
+        );
+        eprintln!(
+            "   Sample peak: {:.2}, reconstructed (4x oversampled) peak: {:.2}",
+            stats.sample_peak, stats.true_peak
+        );
+        eprintln!("   Lower --amplitude, --master-gain or --tilt to leave headroom.\n");
+    }
+    Ok(())
+}
+
+fn print_timed_hint(duration: Duration) {
+    println!(
+        "  Running for {} seconds (Ctrl-C to stop early)...",
+        duration.as_secs()
+    );
+}
+
+/// Install a Ctrl-C handler that sets the returned stop flag
+///
+/// The first Ctrl-C requests the same clean shutdown as pressing Enter; a
+/// second one exits immediately in case teardown hangs.
+fn stop_on_ctrlc() -> anyhow::Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&stop);
+    ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("\nStopping jammer...");
+    })
+    .context("Failed to install Ctrl-C handler")?;
+    Ok(stop)
+}
+
+/// Block until Enter is pressed or `stop` is set
+fn wait_for_enter(stop: &Arc<AtomicBool>) {
     io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut input).unwrap();
+
+    let flag = Arc::clone(stop);
+    std::thread::spawn(move || {
+        let mut input = String::new();
+        let _ = io::stdin().read_line(&mut input);
+        flag.store(true, Ordering::SeqCst);
+    });
+
+    camouflage_core::wait_until_stopped(None, stop);
 }